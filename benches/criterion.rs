@@ -0,0 +1,248 @@
+#[macro_use]
+extern crate criterion;
+extern crate wabt;
+extern crate wasvm;
+
+use criterion::Criterion;
+use wasvm::prelude::*;
+
+const FIB_RECURSIVE: &str = r#"
+(module
+  (func $fib (export "fib") (param i32) (result i32)
+    (if (result i32) (i32.lt_u (get_local 0) (i32.const 2))
+      (then (get_local 0))
+      (else
+        (i32.add
+          (call $fib (i32.sub (get_local 0) (i32.const 1)))
+          (call $fib (i32.sub (get_local 0) (i32.const 2)))))))
+)
+"#;
+
+// NOTE: Hot path consists of sequences which are fused into superinstructions.
+const COUNT: &str = r#"
+(module
+  (func (export "count") (param $n i32) (result i32)
+    (local $i i32)
+    (local $acc i32)
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (get_local $i) (get_local $n)))
+        (set_local $acc (i32.add (get_local $acc) (get_local $i)))
+        (set_local $i (i32.add (get_local $i) (i32.const 1)))
+        (br $next)))
+    (get_local $acc))
+)
+"#;
+
+const MEMORY_COPY: &str = r#"
+(module
+  (memory 1)
+  (func (export "copy") (param $len i32) (result i32)
+    (local $i i32)
+    (block $done
+      (loop $fill
+        (br_if $done (i32.ge_u (get_local $i) (get_local $len)))
+        (i32.store8 (get_local $i) (get_local $i))
+        (set_local $i (i32.add (get_local $i) (i32.const 1)))
+        (br $fill)))
+    (set_local $i (i32.const 0))
+    (block $done
+      (loop $copy
+        (br_if $done (i32.ge_u (get_local $i) (get_local $len)))
+        (i32.store8
+          (i32.add (get_local $i) (i32.const 32768))
+          (i32.load8_u (get_local $i)))
+        (set_local $i (i32.add (get_local $i) (i32.const 1)))
+        (br $copy)))
+    (i32.load8_u (i32.add (get_local $len) (i32.const 32767))))
+)
+"#;
+
+const FLOAT_KERNEL: &str = r#"
+(module
+  (func (export "sum_sqrt") (param $n i32) (result f64)
+    (local $i i32)
+    (local $acc f64)
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (get_local $i) (get_local $n)))
+        (set_local $acc
+          (f64.add
+            (get_local $acc)
+            (f64.sqrt (f64.mul (f64.convert_u/i32 (get_local $i)) (f64.const 1.5)))))
+        (set_local $i (i32.add (get_local $i) (i32.const 1)))
+        (br $next)))
+    (get_local $acc))
+)
+"#;
+
+// NOTE: Both functions count how many numbers below $n are odd.
+// `if_flattened` goes through If/Else which is encoded as flat bytecode with jump offsets,
+// `if_as_blocks` expresses the same control flow as nested Block and BrIf like a tree-walking interpreter.
+const IF_BRANCHES: &str = r#"
+(module
+  (func (export "if_flattened") (param $n i32) (result i32)
+    (local $i i32)
+    (local $odd i32)
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (get_local $i) (get_local $n)))
+        (if (i32.and (get_local $i) (i32.const 1))
+          (then (set_local $odd (i32.add (get_local $odd) (i32.const 1))))
+          (else (nop)))
+        (set_local $i (i32.add (get_local $i) (i32.const 1)))
+        (br $next)))
+    (get_local $odd))
+
+  (func (export "if_as_blocks") (param $n i32) (result i32)
+    (local $i i32)
+    (local $odd i32)
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (get_local $i) (get_local $n)))
+        (block $else
+          (block $then
+            (br_if $then (i32.and (get_local $i) (i32.const 1)))
+            (br $else))
+          (set_local $odd (i32.add (get_local $odd) (i32.const 1))))
+        (set_local $i (i32.add (get_local $i) (i32.const 1)))
+        (br $next)))
+    (get_local $odd))
+)
+"#;
+
+fn compile(source: &str) -> Vec<u8> {
+  wabt::wat2wasm(source).unwrap()
+}
+
+fn instantiate(bytes: &[u8]) -> ModuleInstance {
+  let store = init_store();
+  let module = decode_module(bytes);
+  instantiate_module(store, module, Default::default(), 65536).unwrap()
+}
+
+fn bench_fib_recursive(c: &mut Criterion) {
+  let bytes = compile(FIB_RECURSIVE);
+  c.bench_function("fib_recursive 20", move |b| {
+    let mut vm = instantiate(&bytes);
+    b.iter(|| {
+      assert_eq!(
+        vm.run("fib", vec![Values::I32(20)]).unwrap(),
+        vec![Values::I32(6765)]
+      )
+    })
+  });
+}
+
+fn bench_count(c: &mut Criterion) {
+  let bytes = compile(COUNT);
+  c.bench_function("count 10000", move |b| {
+    let mut vm = instantiate(&bytes);
+    b.iter(|| {
+      assert_eq!(
+        vm.run("count", vec![Values::I32(10000)]).unwrap(),
+        vec![Values::I32(49_995_000)]
+      )
+    })
+  });
+}
+
+fn bench_memory_copy(c: &mut Criterion) {
+  let bytes = compile(MEMORY_COPY);
+  c.bench_function("memory_copy 32768", move |b| {
+    let mut vm = instantiate(&bytes);
+    b.iter(|| {
+      assert_eq!(
+        vm.run("copy", vec![Values::I32(32768)]).unwrap(),
+        vec![Values::I32(255)]
+      )
+    })
+  });
+}
+
+fn bench_float_kernel(c: &mut Criterion) {
+  let bytes = compile(FLOAT_KERNEL);
+  c.bench_function("float_kernel 10000", move |b| {
+    let mut vm = instantiate(&bytes);
+    b.iter(|| vm.run("sum_sqrt", vec![Values::I32(10000)]).unwrap())
+  });
+}
+
+fn bench_if_branches(c: &mut Criterion) {
+  let bytes = compile(IF_BRANCHES);
+  c.bench_function_over_inputs(
+    "if_branches 10000",
+    move |b, invoke| {
+      let mut vm = instantiate(&bytes);
+      b.iter(|| {
+        assert_eq!(
+          vm.run(invoke, vec![Values::I32(10000)]).unwrap(),
+          vec![Values::I32(5000)]
+        )
+      })
+    },
+    vec!["if_flattened", "if_as_blocks"],
+  );
+}
+
+// NOTE: Generates a function which consists of about 50k instructions with nested blocks.
+fn large_function() -> String {
+  let mut body = String::new();
+  for _ in 0..5000 {
+    body.push_str(
+      "(block (set_local 0 (i32.add (get_local 0) (i32.const 1))) \
+       (if (i32.eqz (get_local 0)) (then (nop)) (else (drop (i32.const 0)))))\n",
+    );
+  }
+  format!(
+    "(module (func (export \"large\") (param i32) (result i32) {} (get_local 0)))",
+    body
+  )
+}
+
+fn bench_decode_large_function(c: &mut Criterion) {
+  let bytes = compile(&large_function());
+  c.bench_function("decode_large_function", move |b| {
+    b.iter(|| decode_module(&bytes).unwrap())
+  });
+}
+
+fn bench_instantiation(c: &mut Criterion) {
+  let bytes = compile(MEMORY_COPY);
+  c.bench_function("instantiation", move |b| b.iter(|| instantiate(&bytes)));
+}
+
+// NOTE: Same workloads as above named by the representation of values on operand stacks,
+// so that results of `cargo bench` and `cargo bench --features nan-boxing` are compared side by side.
+fn bench_value_representations(c: &mut Criterion) {
+  let representation = Engine::new(EngineLimits::default()).value_representation();
+  let bytes = compile(FIB_RECURSIVE);
+  c.bench_function(
+    &format!("fib_recursive 20 by {:?}", representation),
+    move |b| {
+      let mut vm = instantiate(&bytes);
+      b.iter(|| vm.run("fib", vec![Values::I32(20)]).unwrap())
+    },
+  );
+  let bytes = compile(FLOAT_KERNEL);
+  c.bench_function(
+    &format!("float_kernel 10000 by {:?}", representation),
+    move |b| {
+      let mut vm = instantiate(&bytes);
+      b.iter(|| vm.run("sum_sqrt", vec![Values::I32(10000)]).unwrap())
+    },
+  );
+}
+
+criterion_group!(
+  benches,
+  bench_fib_recursive,
+  bench_count,
+  bench_memory_copy,
+  bench_float_kernel,
+  bench_if_branches,
+  bench_decode_large_function,
+  bench_instantiation,
+  bench_value_representations
+);
+criterion_main!(benches);
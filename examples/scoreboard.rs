@@ -0,0 +1,416 @@
+// Run every script of testsuite and write the result of each file as a scoreboard.
+//
+// $ cargo run --example scoreboard -- [testsuite directory] [output directory]
+//
+// Unlike tests/run.rs, a failed command doesn't stop the script,
+// so that progress of conformance can be tracked by counts of passed commands.
+extern crate wabt;
+extern crate wasvm;
+
+use std::cell::RefCell;
+use std::env::args;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::rc::Rc;
+use wabt::script::{Action, CommandKind, ModuleBinary, ScriptParser, Value};
+use wasvm::prelude::*;
+
+const MAX_STACK_HEIGHT: usize = 65536;
+
+enum Outcome {
+  Pass,
+  Fail(String),
+  Skip,
+}
+
+#[derive(Default)]
+struct FileScore {
+  name: String,
+  passed: usize,
+  failed: usize,
+  skipped: usize,
+  // NOTE: Pairs of line and reason.
+  failures: Vec<(u64, String)>,
+}
+
+impl FileScore {
+  fn record(&mut self, line: u64, outcome: Outcome) {
+    match outcome {
+      Outcome::Pass => self.passed += 1,
+      Outcome::Skip => self.skipped += 1,
+      Outcome::Fail(reason) => {
+        self.failed += 1;
+        self.failures.push((line, reason));
+      }
+    }
+  }
+}
+
+fn get_args(args: &[Value<f32, f64>]) -> Vec<Values> {
+  args
+    .iter()
+    .map(|v| match v {
+      Value::I32(value) => Values::I32(*value),
+      Value::I64(value) => Values::I64(*value),
+      Value::F32(value) => Values::F32(*value),
+      Value::F64(value) => Values::F64(*value),
+    })
+    .collect()
+}
+
+// NOTE: Modules of `module quote` remain in text format, so they are assembled by wabt.
+fn into_binary(module: &ModuleBinary) -> Option<Vec<u8>> {
+  let bytes = module.clone().into_vec();
+  if bytes.starts_with(&[0, 97, 115, 109]) {
+    Some(bytes)
+  } else {
+    wabt::wat2wasm(bytes).ok()
+  }
+}
+
+fn is_same_value(actual: &Values, expected: &Values) -> bool {
+  match (actual, expected) {
+    (Values::F32(n), Values::F32(m)) if n.is_nan() => m.is_nan(),
+    (Values::F64(n), Values::F64(m)) if n.is_nan() => m.is_nan(),
+    _ => actual == expected,
+  }
+}
+
+fn is_nan(values: &[Values]) -> bool {
+  match values.first() {
+    Some(Values::F32(n)) => n.is_nan(),
+    Some(Values::F64(n)) => n.is_nan(),
+    _ => false,
+  }
+}
+
+struct Script {
+  environment: ScriptEnvironment,
+}
+
+impl Script {
+  fn new() -> Self {
+    let mut external_modules = ExternalModules::default();
+    external_modules
+      .register_module(Some("spectest".to_owned()), create_spectest())
+      .unwrap();
+    Script {
+      environment: ScriptEnvironment::new(external_modules),
+    }
+  }
+
+  fn module_of(&self, name: &Option<String>) -> Result<Rc<RefCell<ModuleInstance>>, String> {
+    self
+      .environment
+      .get(name.as_ref().map(|name| name.as_str()))
+      .map_err(|_| format!("Module {:?} is not instantiated.", name))
+  }
+
+  fn perform(&mut self, action: &Action<f32, f64>) -> Result<RunOutcome, String> {
+    let (field, arguments, module) = match action {
+      Action::Invoke {
+        field,
+        args,
+        module,
+      } => (field, get_args(args), module),
+      Action::Get { field, module } => (field, vec![], module),
+    };
+    let vm = self.module_of(module)?;
+    let outcome = vm.borrow_mut().invoke(field, arguments);
+    outcome.map_err(|err| format!("{:?}", err))
+  }
+
+  fn perform_values(&mut self, action: &Action<f32, f64>) -> Result<Vec<Values>, String> {
+    match self.perform(action)? {
+      RunOutcome::Values(values) => Ok(values),
+      RunOutcome::Trap(details) => Err(format!("Unexpected trap {:?}.", details.trap)),
+    }
+  }
+
+  fn expect_error<T>(result: Result<T, WasmError>) -> Outcome {
+    match result {
+      Ok(_) => Outcome::Fail("Expected an error, but succeeded.".to_owned()),
+      Err(_) => Outcome::Pass,
+    }
+  }
+
+  fn run_command(&mut self, kind: CommandKind<f32, f64>) -> Outcome {
+    use self::CommandKind::*;
+    let result = match kind {
+      Module { module, name } => self
+        .environment
+        .instantiate(
+          name.as_ref().map(|name| name.as_str()),
+          &module.into_vec(),
+          MAX_STACK_HEIGHT,
+        )
+        .map(|_| ())
+        .map_err(|err| format!("{:?}", err)),
+      Register { name, as_name } => self
+        .environment
+        .register(name.as_ref().map(|name| name.as_str()), &as_name)
+        .map_err(|err| format!("{:?}", err)),
+      PerformAction(action) => self.perform(&action).map(|_| ()),
+      AssertReturn { action, expected } => {
+        let expected = get_args(&expected);
+        self.perform_values(&action).and_then(|actual| {
+          let is_same = actual.len() == expected.len()
+            && actual
+              .iter()
+              .zip(expected.iter())
+              .all(|(actual, expected)| is_same_value(actual, expected));
+          if is_same {
+            Ok(())
+          } else {
+            Err(format!("Expected {:?}, got {:?}.", expected, actual))
+          }
+        })
+      }
+      AssertReturnCanonicalNan { action } | AssertReturnArithmeticNan { action } => {
+        self.perform_values(&action).and_then(|actual| {
+          if is_nan(&actual) {
+            Ok(())
+          } else {
+            Err(format!("Expected NaN, got {:?}.", actual))
+          }
+        })
+      }
+      AssertTrap { action, message } => self.perform(&action).and_then(|outcome| match outcome {
+        RunOutcome::Trap(ref details) if message.starts_with(details.message) => Ok(()),
+        RunOutcome::Trap(details) => Err(format!(
+          "Expected trap '{}', got {:?}.",
+          message, details.trap
+        )),
+        RunOutcome::Values(values) => Err(format!("Expected trap, got {:?}.", values)),
+      }),
+      AssertInvalid { module, .. } => {
+        let bytes = module.into_vec();
+        return Script::expect_error(validate_module(&decode_module(&bytes)));
+      }
+      AssertMalformed { module, .. } => {
+        let bytes = match into_binary(&module) {
+          Some(bytes) => bytes,
+          // NOTE: Malformed text form is rejected by wabt rather than this crate.
+          None => return Outcome::Skip,
+        };
+        return Script::expect_error(instantiate_module(
+          init_store(),
+          decode_module(&bytes),
+          Default::default(),
+          MAX_STACK_HEIGHT,
+        ));
+      }
+      AssertUninstantiable { module, .. } | AssertUnlinkable { module, .. } => {
+        let bytes = match into_binary(&module) {
+          Some(bytes) => bytes,
+          None => return Outcome::Fail("Failed to assemble the module.".to_owned()),
+        };
+        let external_modules = match self.environment.external_modules() {
+          Ok(external_modules) => external_modules,
+          Err(err) => return Outcome::Fail(format!("{:?}", err)),
+        };
+        return Script::expect_error(instantiate_module(
+          init_store(),
+          decode_module(&bytes),
+          external_modules,
+          MAX_STACK_HEIGHT,
+        ));
+      }
+      // NOTE: Same as tests/run.rs, exhaustion of call stack is not checked yet.
+      _ => return Outcome::Skip,
+    };
+    match result {
+      Ok(()) => Outcome::Pass,
+      Err(reason) => Outcome::Fail(reason),
+    }
+  }
+}
+
+fn run_file(path: &Path) -> FileScore {
+  let mut score = FileScore {
+    name: path
+      .file_stem()
+      .map(|stem| stem.to_string_lossy().into_owned())
+      .unwrap_or_default(),
+    ..Default::default()
+  };
+  let source = match fs::read_to_string(path) {
+    Ok(source) => source,
+    Err(err) => {
+      score.record(0, Outcome::Fail(format!("{}", err)));
+      return score;
+    }
+  };
+  let mut parser = match ScriptParser::<f32, f64>::from_str(&source) {
+    Ok(parser) => parser,
+    Err(err) => {
+      score.record(0, Outcome::Fail(format!("{:?}", err)));
+      return score;
+    }
+  };
+  let mut script = Script::new();
+  loop {
+    let command = match parser.next() {
+      Ok(Some(command)) => command,
+      Ok(None) => break,
+      Err(err) => {
+        score.record(0, Outcome::Fail(format!("{:?}", err)));
+        break;
+      }
+    };
+    let line = command.line;
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| script.run_command(command.kind)))
+      .unwrap_or_else(|payload| {
+        let message = if let Some(message) = payload.downcast_ref::<&str>() {
+          message.to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+          message.to_owned()
+        } else {
+          "Panicked".to_owned()
+        };
+        Outcome::Fail(format!("Panicked: {}", message))
+      });
+    score.record(line, outcome);
+  }
+  score
+}
+
+fn escape_json(s: &str) -> String {
+  let mut escaped = String::new();
+  for c in s.chars() {
+    match c {
+      '"' => escaped.push_str("\\\""),
+      '\\' => escaped.push_str("\\\\"),
+      '\n' => escaped.push_str("\\n"),
+      c if (c as u32) < 0x20 => {
+        let _ = write!(escaped, "\\u{:04x}", c as u32);
+      }
+      c => escaped.push(c),
+    }
+  }
+  escaped
+}
+
+fn total_of(scores: &[FileScore]) -> (usize, usize, usize) {
+  scores
+    .iter()
+    .fold((0, 0, 0), |(passed, failed, skipped), score| {
+      (
+        passed + score.passed,
+        failed + score.failed,
+        skipped + score.skipped,
+      )
+    })
+}
+
+fn to_json(scores: &[FileScore]) -> String {
+  let (passed, failed, skipped) = total_of(scores);
+  let mut json = String::new();
+  let _ = writeln!(json, "{{");
+  let _ = writeln!(
+    json,
+    "  \"total\": {{ \"passed\": {}, \"failed\": {}, \"skipped\": {} }},",
+    passed, failed, skipped
+  );
+  let _ = writeln!(json, "  \"files\": [");
+  for (i, score) in scores.iter().enumerate() {
+    let failures = score
+      .failures
+      .iter()
+      .map(|(line, reason)| {
+        format!(
+          "{{ \"line\": {}, \"reason\": \"{}\" }}",
+          line,
+          escape_json(reason)
+        )
+      })
+      .collect::<Vec<_>>()
+      .join(", ");
+    let _ = writeln!(
+      json,
+      "    {{ \"name\": \"{}\", \"passed\": {}, \"failed\": {}, \"skipped\": {}, \"failures\": [{}] }}{}",
+      escape_json(&score.name),
+      score.passed,
+      score.failed,
+      score.skipped,
+      failures,
+      if i + 1 < scores.len() { "," } else { "" }
+    );
+  }
+  let _ = writeln!(json, "  ]");
+  let _ = writeln!(json, "}}");
+  json
+}
+
+fn rate_of(passed: usize, failed: usize) -> f64 {
+  if passed + failed == 0 {
+    100.0
+  } else {
+    passed as f64 * 100.0 / (passed + failed) as f64
+  }
+}
+
+fn to_markdown(scores: &[FileScore]) -> String {
+  let (passed, failed, skipped) = total_of(scores);
+  let mut markdown = String::new();
+  let _ = writeln!(markdown, "# Conformance scoreboard\n");
+  let _ = writeln!(markdown, "| File | Passed | Failed | Skipped | Rate |");
+  let _ = writeln!(markdown, "| --- | ---: | ---: | ---: | ---: |");
+  for score in scores.iter() {
+    let _ = writeln!(
+      markdown,
+      "| {} | {} | {} | {} | {:.1}% |",
+      score.name,
+      score.passed,
+      score.failed,
+      score.skipped,
+      rate_of(score.passed, score.failed)
+    );
+  }
+  let _ = writeln!(
+    markdown,
+    "| **Total** | {} | {} | {} | {:.1}% |",
+    passed,
+    failed,
+    skipped,
+    rate_of(passed, failed)
+  );
+  markdown
+}
+
+fn main() -> io::Result<()> {
+  let arguments = args().collect::<Vec<String>>();
+  let testsuite = arguments.get(1).map_or("./testsuite", |dir| dir.as_str());
+  let output = arguments.get(2).map_or(".", |dir| dir.as_str());
+
+  let mut paths = fs::read_dir(testsuite)?
+    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+    .filter(|path| path.extension().map_or(false, |ext| ext == "wast"))
+    .collect::<Vec<_>>();
+  paths.sort();
+
+  // NOTE: Panics are reported in the scoreboard, so default messages are suppressed.
+  panic::set_hook(Box::new(|_| {}));
+  let scores = paths
+    .iter()
+    .map(|path| {
+      let score = run_file(path);
+      println!(
+        "{}: {} passed, {} failed, {} skipped",
+        score.name, score.passed, score.failed, score.skipped
+      );
+      score
+    })
+    .collect::<Vec<_>>();
+  let _ = panic::take_hook();
+
+  fs::write(Path::new(output).join("scoreboard.json"), to_json(&scores))?;
+  fs::write(
+    Path::new(output).join("scoreboard.md"),
+    to_markdown(&scores),
+  )?;
+  Ok(())
+}
@@ -0,0 +1,32 @@
+use alloc::string::String;
+use core::fmt;
+use decode::SourceLocation;
+
+/// A frame of call stack at the time of trap.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BacktraceFrame {
+  /// Export name of the function if it has.
+  pub function_name: Option<String>,
+  /// Offset of the instruction from the beginning of code section, which host functions don't have.
+  pub offset: Option<u32>,
+  /// Location in source code resolved by `.debug_line` section.
+  pub location: Option<SourceLocation>,
+}
+
+impl fmt::Display for BacktraceFrame {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "{}",
+      self
+        .function_name
+        .as_ref()
+        .map_or("<anonymous>", |n| n.as_str())
+    )?;
+    match (&self.location, self.offset) {
+      (Some(location), _) => write!(f, " at {}", location),
+      (None, Some(offset)) => write!(f, " at offset 0x{:x}", offset),
+      (None, None) => Ok(()),
+    }
+  }
+}
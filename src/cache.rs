@@ -0,0 +1,145 @@
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::hash::{BuildHasher, Hash, Hasher};
+use decode::Module;
+use embedder::{decode_module, validate_module};
+use error::Result;
+
+struct Entry {
+  hash: u64,
+  // NOTE: Bytes are kept to tell collisions of hashes apart.
+  bytes: Vec<u8>,
+  module: Rc<Module>,
+}
+
+/// Cache of decoded and validated modules keyed by hash of their bytes,
+/// which evicts the least recently used module when it exceeds its capacity.
+/// Hash is computed by `BuildHasher` of embedder, since `core` provides no hasher.
+/// Modules decoded by an `Engine` go through the cache once it's set by `Engine::cache_modules`.
+pub struct ModuleCache<B: BuildHasher> {
+  capacity: usize,
+  build_hasher: B,
+  // NOTE: Ordered from the least recently used, lookup is linear expecting small capacity.
+  entries: Vec<Entry>,
+}
+
+impl<B: BuildHasher> ModuleCache<B> {
+  pub fn new(capacity: usize, build_hasher: B) -> Self {
+    ModuleCache {
+      capacity,
+      build_hasher,
+      entries: Vec::with_capacity(capacity),
+    }
+  }
+
+  fn hash_of(&self, bytes: &[u8]) -> u64 {
+    let mut hasher = self.build_hasher.build_hasher();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+  }
+
+  /// Module decoded from `bytes` and validated, which is shared from the cache if it has been done before.
+  /// Modules failed to decode or validate are not cached.
+  pub fn get_or_decode(&mut self, bytes: &[u8]) -> Result<Rc<Module>> {
+    let hash = self.hash_of(bytes);
+    let position = self
+      .entries
+      .iter()
+      .position(|entry| entry.hash == hash && entry.bytes.as_slice() == bytes);
+    if let Some(position) = position {
+      let entry = self.entries.remove(position);
+      let module = entry.module.clone();
+      self.entries.push(entry);
+      return Ok(module);
+    }
+
+    let module = decode_module(bytes);
+    validate_module(&module)?;
+    let module = Rc::new(module?);
+    if self.capacity == 0 {
+      return Ok(module);
+    }
+    if self.entries.len() >= self.capacity {
+      self.entries.remove(0);
+    }
+    self.entries.push(Entry {
+      hash,
+      bytes: bytes.to_vec(),
+      module: module.clone(),
+    });
+    Ok(module)
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  pub fn clear(&mut self) {
+    self.entries.clear();
+  }
+}
+
+impl<B: BuildHasher> fmt::Debug for ModuleCache<B> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("ModuleCache")
+      .field("capacity", &self.capacity)
+      .field("len", &self.entries.len())
+      .finish()
+  }
+}
+
+// NOTE: Erases hasher of a cache, so that an engine can hold it.
+pub(crate) trait ModuleDecoder: fmt::Debug {
+  fn get_or_decode(&mut self, bytes: &[u8]) -> Result<Rc<Module>>;
+}
+
+impl<B: BuildHasher> ModuleDecoder for ModuleCache<B> {
+  fn get_or_decode(&mut self, bytes: &[u8]) -> Result<Rc<Module>> {
+    ModuleCache::get_or_decode(self, bytes)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::collections::hash_map::RandomState;
+
+  fn module_with_custom_section(name: u8) -> Vec<u8> {
+    vec![
+      0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+      0x00, 0x02, 0x01, name, // custom
+    ]
+  }
+
+  #[test]
+  fn evict_least_recently_used_module() {
+    let mut cache = ModuleCache::new(2, RandomState::new());
+    let (a, b, c) = (
+      module_with_custom_section(b'a'),
+      module_with_custom_section(b'b'),
+      module_with_custom_section(b'c'),
+    );
+    let decoded = cache.get_or_decode(&a).unwrap();
+    cache.get_or_decode(&b).unwrap();
+    assert!(Rc::ptr_eq(&decoded, &cache.get_or_decode(&a).unwrap()));
+    assert_eq!(cache.len(), 2);
+
+    cache.get_or_decode(&c).unwrap();
+    assert_eq!(
+      cache
+        .entries
+        .iter()
+        .map(|entry| entry.bytes.clone())
+        .collect::<Vec<_>>(),
+      vec![a, c]
+    );
+
+    assert!(cache.get_or_decode(&[0x00, 0x61]).is_err());
+    assert_eq!(cache.len(), 2);
+  }
+}
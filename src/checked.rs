@@ -0,0 +1,134 @@
+use alloc::vec::Vec;
+use frame::Frame;
+use opcode::OpcodeInfo;
+use stack::Stack;
+use value_type::ValueTypes;
+
+/// Types of operands which validator modeled each instruction to pop, ordered by offsets of instructions.
+/// Operands are ordered from bottom to top.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct TypeStream(Vec<(usize, Vec<ValueTypes>)>);
+
+impl TypeStream {
+  pub(crate) fn push(&mut self, ptr: usize, operands: Vec<ValueTypes>) {
+    self.0.push((ptr, operands));
+  }
+
+  pub(crate) fn operands_at(&self, ptr: usize) -> Option<&[ValueTypes]> {
+    self
+      .0
+      .binary_search_by_key(&ptr, |(p, _)| *p)
+      .ok()
+      .map(|idx| self.0[idx].1.as_slice())
+  }
+}
+
+/// Compare values on top of the stack with operands of the instruction which is about to be executed,
+/// and panic with the state of interpreter if they differ.
+pub(crate) fn check_operands(frame: &Frame, code: u8, stack: &Stack) {
+  let ptr = frame.ptr() as usize - 1;
+  let function_instance = &frame.function_instance;
+  let expected = match function_instance.expected_operands(ptr) {
+    Some(expected) => expected,
+    None => return,
+  };
+  let actual = stack.types_on_top(expected.len());
+  if actual.len() == expected.len()
+    && actual
+      .iter()
+      .zip(expected.iter())
+      .all(|(actual, expected)| actual.as_ref() == Some(expected))
+  {
+    return;
+  }
+  // NOTE: Superinstructions are not in opcode table.
+  let mnemonic = OpcodeInfo::of(code).map_or("superinstruction", |info| info.mnemonic);
+  panic!(
+    "Interpreter is inconsistent with validator: {} (0x{:02x}) at {} of function {} expects operands {:?}, but stack has {:?}.\nStack: {:?}",
+    mnemonic,
+    code,
+    ptr,
+    function_instance.export_name().map_or("_", |name| name.as_str()),
+    expected,
+    actual,
+    stack
+  );
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use embedder::{decode_module, init_store, instantiate_module};
+  use features::Features;
+  use validate::Context;
+  use value::Values;
+
+  // (module (func (export "add") (param i32 i32) (result i32) get_local 0 get_local 1 i32.add))
+  const BYTES: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+    0x01, 0x07, 0x01, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, // type
+    0x03, 0x02, 0x01, 0x00, // function
+    0x07, 0x07, 0x01, 0x03, 0x61, 0x64, 0x64, 0x00, 0x00, // export
+    0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x6a, 0x0b, // code
+  ];
+
+  #[test]
+  fn record_operands_of_instructions() {
+    let module = decode_module(BYTES).unwrap();
+    let type_streams = Context::new(&module, module.features.to_owned())
+      .and_then(|context| context.type_streams())
+      .unwrap();
+    let mut expected = TypeStream::default();
+    expected.push(0, vec![]);
+    expected.push(5, vec![]);
+    expected.push(10, vec![ValueTypes::I32, ValueTypes::I32]);
+    assert_eq!(type_streams, vec![expected]);
+
+    let mut vm = instantiate_module(
+      init_store(),
+      decode_module(BYTES),
+      Default::default(),
+      65536,
+    )
+    .unwrap();
+    assert_eq!(
+      vm.run("add", vec![Values::I32(1), Values::I32(2)]),
+      Ok(vec![Values::I32(3)])
+    );
+  }
+
+  // (module (func (export "pair") (result i32 i32) i32.const 1 i32.const 2))
+  const MULTI_VALUE_BYTES: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+    0x01, 0x06, 0x01, 0x60, 0x00, 0x02, 0x7f, 0x7f, // type
+    0x03, 0x02, 0x01, 0x00, // function
+    0x07, 0x08, 0x01, 0x04, 0x70, 0x61, 0x69, 0x72, 0x00, 0x00, // export
+    0x0a, 0x08, 0x01, 0x06, 0x00, 0x41, 0x01, 0x41, 0x02, 0x0b, // code
+  ];
+
+  #[test]
+  fn record_operands_with_features_of_module() {
+    let mut module = decode_module(MULTI_VALUE_BYTES).unwrap();
+    assert!(Context::new(&module, module.features.to_owned())
+      .and_then(|context| context.type_streams())
+      .is_err());
+
+    module.features(Features {
+      multi_value: true,
+      ..Features::default()
+    });
+    let type_streams = Context::new(&module, module.features.to_owned())
+      .and_then(|context| context.type_streams())
+      .unwrap();
+    let mut expected = TypeStream::default();
+    expected.push(0, vec![]);
+    expected.push(5, vec![]);
+    assert_eq!(type_streams, vec![expected]);
+
+    let mut vm = instantiate_module(init_store(), Ok(module), Default::default(), 65536).unwrap();
+    assert_eq!(
+      vm.run("pair", vec![]),
+      Ok(vec![Values::I32(1), Values::I32(2)])
+    );
+  }
+}
@@ -0,0 +1,73 @@
+use alloc::vec::Vec;
+use decode::{Component, CoreInstance};
+use embedder::{decode_module, init_store, instantiate_module};
+use error::{Result, Trap};
+use module::{unknown_export, ExportSuggestion, ExternalModules};
+use value::Values;
+use vm::ModuleInstance;
+
+/// Instance of component, which holds core instances in order of its definitions.
+#[derive(Debug)]
+pub struct ComponentInstance {
+  instances: Vec<ModuleInstance>,
+}
+
+impl ComponentInstance {
+  pub(crate) fn new(component: Component, max_stack_height: usize) -> Result<Self> {
+    let mut instances: Vec<ModuleInstance> = vec![];
+    for core_instance in component.instances.into_iter() {
+      match core_instance {
+        CoreInstance::Instantiate {
+          module_idx,
+          arguments,
+        } => {
+          let bytes = component
+            .modules
+            .get(module_idx as usize)
+            .ok_or(Trap::Notfound)?;
+          let mut external_modules = ExternalModules::default();
+          for (name, instance_idx) in arguments.into_iter() {
+            let instance = instances.get(instance_idx as usize).ok_or(Trap::Notfound)?;
+            external_modules.register_module(Some(name), instance.export_module())?;
+          }
+          let module = decode_module(bytes);
+          instances.push(instantiate_module(
+            init_store(),
+            module,
+            external_modules,
+            max_stack_height,
+          )?);
+        }
+      }
+    }
+    Ok(ComponentInstance { instances })
+  }
+
+  /// Invoke a function exported from core instances.
+  /// Latter instances take precedence, since the main module is usually instantiated at last.
+  pub fn run(&mut self, invoke: &str, arguments: Vec<Values>) -> Result<Vec<Values>> {
+    if let Some(instance) = self
+      .instances
+      .iter_mut()
+      .rev()
+      .find(|instance| instance.has_export(invoke))
+    {
+      return instance.run(invoke, arguments);
+    }
+    // NOTE: Instances may export the same name, which is suggested once.
+    let mut export_names: Vec<&str> = vec![];
+    for instance in self.instances.iter() {
+      for name in instance.export_names().into_iter() {
+        if !export_names.contains(&name) {
+          export_names.push(name);
+        }
+      }
+    }
+    // NOTE: Core instances are instantiated by stores of default configurations.
+    Err(unknown_export(
+      invoke,
+      &export_names,
+      &ExportSuggestion::default(),
+    ))
+  }
+}
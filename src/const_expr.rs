@@ -0,0 +1,70 @@
+use error::{Result, Trap, WasmError};
+use global::GlobalInstance;
+use isa::Isa;
+use value::Values;
+
+fn read_u32(expression: &[u8]) -> Result<u32> {
+  let mut buf = [0; 4];
+  buf.clone_from_slice(expression.get(1..5)?);
+  Ok(unsafe { core::mem::transmute::<_, u32>(buf) })
+}
+
+fn read_u64(expression: &[u8]) -> Result<u64> {
+  let mut buf = [0; 8];
+  buf.clone_from_slice(expression.get(1..9)?);
+  Ok(unsafe { core::mem::transmute::<_, u64>(buf) })
+}
+
+/// Evaluate a flattened constant expression, which initializes a global or gives offset of a segment.
+/// `globals` are ones initialized so far in index order, so that global.get of itself or later one,
+/// which might form a cycle, traps by `UninitializedGlobal`.
+pub(crate) fn evaluate(expression: &[u8], globals: &[GlobalInstance]) -> Result<Values> {
+  match Isa::from(*expression.first()?) {
+    Isa::I32Const => Ok(Values::I32(read_u32(expression)? as i32)),
+    Isa::I64Const => Ok(Values::I64(read_u64(expression)? as i64)),
+    Isa::F32Const => Ok(Values::F32(f32::from_bits(read_u32(expression)?))),
+    Isa::F64Const => Ok(Values::F64(f64::from_bits(read_u64(expression)?))),
+    Isa::GetGlobal => {
+      let idx = read_u32(expression)?;
+      globals
+        .get(idx as usize)
+        .map(|global| global.get_value())
+        .ok_or(WasmError::Trap(Trap::UninitializedGlobal(idx)))
+    }
+    x => unreachable!("Expected constant expression, got {:?}", x),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use global::GlobalType;
+  use isa::into_vec_u8;
+  use isa::ComposedCode::*;
+  use value_type::ValueTypes;
+
+  #[test]
+  fn evaluate_global_get_in_index_order() {
+    let mut globals = vec![];
+    let expressions = vec![
+      into_vec_u8(&[Code(Isa::I32Const), Byte(5), Byte(0), Byte(0), Byte(0)]),
+      into_vec_u8(&[Code(Isa::GetGlobal), Byte(0), Byte(0), Byte(0), Byte(0)]),
+      into_vec_u8(&[Code(Isa::GetGlobal), Byte(1), Byte(0), Byte(0), Byte(0)]),
+    ];
+    for expression in expressions.iter() {
+      let value = evaluate(expression, &globals).unwrap();
+      assert_eq!(value, Values::I32(5));
+      globals.push(GlobalInstance::new(
+        GlobalType::Const(ValueTypes::I32),
+        value,
+        None,
+      ));
+    }
+
+    let expression = into_vec_u8(&[Code(Isa::GetGlobal), Byte(3), Byte(0), Byte(0), Byte(0)]);
+    assert_eq!(
+      evaluate(&expression, &globals),
+      Err(WasmError::Trap(Trap::UninitializedGlobal(3)))
+    );
+  }
+}
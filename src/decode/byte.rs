@@ -1,11 +1,17 @@
 use super::decodable::{Decodable, Leb128Decodable, U32Decodable};
+use super::limits::DecodeLimits;
 use super::section::{Module, SectionCode};
+use super::verifier::{AcceptAll, Verifier};
 use super::*;
 use alloc::vec::Vec;
 use core::convert::TryFrom;
 use core::default::Default;
 use error::{Result, WasmError, Trap};
 
+// NOTE: Magic words and version precede sections.
+const SIZE_OF_HEADER: usize = 8;
+const HEADER: [u8; SIZE_OF_HEADER] = [0, 97, 115, 109, 1, 0, 0, 0];
+
 impl_decodable!(Byte);
 impl Leb128Decodable for Byte {}
 impl U32Decodable for Byte {}
@@ -19,15 +25,15 @@ impl Byte {
     if magic_words.starts_with(&[40]) {
       return Err(WasmError::Trap(Trap::UnsupportedTextform));
     }
-    if magic_words != [0, 97, 115, 109] {
+    if magic_words != &HEADER[..4] {
       return Err(WasmError::Trap(Trap::MagicHeaderNotDetected));
     }
     if 4 > bytes.len() {
       return Err(WasmError::Trap(Trap::UnexpectedEnd));
     }
     let (wasm_versions, bytes) = bytes.split_at(4);
-    if wasm_versions != [1, 0, 0, 0] {
-      return Err(WasmError::Trap(Trap::UnsupportedTextform));
+    if wasm_versions != &HEADER[4..] {
+      return Err(WasmError::Trap(Trap::UnknownBinaryVersion));
     }
     Ok(Byte::new(bytes.to_vec()))
   }
@@ -48,27 +54,80 @@ impl Byte {
     Ok(bytes)
   }
 
+  // Decoders of sections report offsets from the head of the section, which are rebased onto the module.
+  fn rebase_offset(error: WasmError, offset_of_section: usize) -> WasmError {
+    match error {
+      WasmError::Trap(Trap::InvalidUTF8Name { offset }) => WasmError::Trap(Trap::InvalidUTF8Name {
+        offset: offset + offset_of_section,
+      }),
+      error => error,
+    }
+  }
+
   pub fn decode(&mut self) -> Result<Module> {
+    self.decode_with_limits(&DecodeLimits::default())
+  }
+
+  /// Same as `decode`, but rejects function bodies which exceed `limits`.
+  pub fn decode_with_limits(&mut self, limits: &DecodeLimits) -> Result<Module> {
+    self.decode_with_verifier(limits, &AcceptAll)
+  }
+
+  /// Same as `decode_with_limits`, but `verifier` checks each section before it's decoded.
+  pub fn decode_with_verifier(
+    &mut self,
+    limits: &DecodeLimits,
+    verifier: &Verifier,
+  ) -> Result<Module> {
     use self::SectionCode::*;
     let mut section = Module::default();
+    let mut last_order = 0;
+    let mut size_of_decoded_sections = 0;
     while self.has_next() {
-      let code = SectionCode::try_from(self.next())?;
+      let head_of_section = self.byte_ptr;
+      let id = self.next();
+      let code = SectionCode::try_from(id)?;
+      if let Some(order) = code.order() {
+        if order <= last_order {
+          return Err(WasmError::Trap(Trap::UnexpectedSection));
+        }
+        last_order = order;
+      }
       let bytes = self.decode_section()?;
+      verifier
+        .verify_section(id?, &bytes)
+        .map_err(WasmError::Rejected)?;
+      // NOTE: Bytes of decoded sections are drained, so pointer is at the head of current section.
+      let start_of_section = SIZE_OF_HEADER + size_of_decoded_sections + head_of_section;
+      let offset_of_section = SIZE_OF_HEADER + size_of_decoded_sections + self.byte_ptr;
+      size_of_decoded_sections += bytes.len();
+      section.boundaries.push((
+        code.clone(),
+        start_of_section..offset_of_section + bytes.len(),
+        offset_of_section,
+      ));
+      let rebase = |error: WasmError| Byte::rebase_offset(error, offset_of_section);
       match code {
         Type => section.function_types(&mut sec_type::Section::new(bytes).decode()?),
         Function => section.functions(&mut sec_function::Section::new(bytes).decode()?),
-        Code => section.codes(&mut sec_code::Section::new(bytes).decode()?),
+        Code => section.codes(&mut sec_code::Section::new(bytes).decode_with_limits(limits)?),
         Data => section.datas(&mut sec_data::Section::new(bytes).decode()?),
         Memory => section.limits(&mut sec_memory::Section::new(bytes).decode()?),
         Table => section.tables(&mut sec_table::Section::new(bytes).decode()?),
         Global => section.globals(&mut sec_global::Section::new(bytes).decode()?),
         Element => section.elements(&mut sec_element::Section::new(bytes).decode()?),
-        Custom => section.customs(&mut sec_custom::Section::new(bytes).decode()?),
-        Export => section.exports(sec_export::Section::new(bytes).decode()?),
-        Import => section.imports(sec_import::Section::new(bytes).decode()?),
+        Custom => section.customs(&mut sec_custom::Section::new(bytes).decode().map_err(rebase)?),
+        Export => section.exports(sec_export::Section::new(bytes).decode().map_err(rebase)?),
+        Import => section.imports(sec_import::Section::new(bytes).decode().map_err(rebase)?),
         Start => section.start(sec_start::Section::new(bytes).decode()?),
+        DataCount => section.data_count(sec_data_count::Section::new(bytes).decode()?),
       };
     }
+    if let Some(data_count) = section.data_count {
+      if data_count as usize != section.datas.len() {
+        return Err(WasmError::Trap(Trap::DataCountAndDataInconsistent));
+      }
+    }
     Ok(section)
   }
 }
@@ -77,7 +136,7 @@ impl Byte {
 mod tests {
   use super::*;
   use embedder::{decode_module, init_store};
-  use function::{FunctionInstance, FunctionType};
+  use function::{FunctionInstance, FunctionType, InstructionFusion};
   use isa::{into_vec_u8, ComposedCode as Cc};
   use module::ExternalModules;
   use std::fs::File;
@@ -86,6 +145,9 @@ mod tests {
 
   macro_rules! test_decode {
     ($fn_name:ident, $file_name:expr, $fn_insts: expr) => {
+      test_decode!($fn_name, $file_name, InstructionFusion::Unfused, $fn_insts);
+    };
+    ($fn_name:ident, $file_name:expr, $fusion:expr, $fn_insts: expr) => {
       #[test]
       fn $fn_name() {
         use isa::Isa::*;
@@ -93,12 +155,13 @@ mod tests {
         let mut buffer = vec![];
         let _ = file.read_to_end(&mut buffer);
         let mut store = init_store();
+        store.instruction_fusion = $fusion;
         decode_module(&buffer)
           .unwrap()
           .complete(&ExternalModules::default(), &mut store)
           .unwrap();
         assert_eq!(
-          store.get_function_instance(&From::from(0u32)).unwrap(),
+          *store.get_function_instance(&From::from(0u32)).unwrap(),
           $fn_insts
         );
       }
@@ -233,6 +296,62 @@ mod tests {
     )
   );
 
+  test_decode!(
+    decode_add_fused,
+    "dist/add",
+    InstructionFusion::Fused,
+    FunctionInstance::new(
+      Some("_subject".to_owned()),
+      FunctionType::new(vec![TYPE_I32, TYPE_I32], vec![TYPE_I32]),
+      vec![],
+      into_vec_u8(&[
+        Cc::Code(GetLocalGetLocalI32Add),
+        Cc::Byte(1),
+        Cc::Byte(0),
+        Cc::Byte(0),
+        Cc::Byte(0),
+        Cc::Code(GetLocal),
+        Cc::Byte(0),
+        Cc::Byte(0),
+        Cc::Byte(0),
+        Cc::Byte(0),
+        Cc::Code(I32Add),
+        Cc::Code(End),
+      ]),
+    )
+  );
+
+  test_decode!(
+    decode_add_five_fused,
+    "dist/add_five",
+    InstructionFusion::Fused,
+    FunctionInstance::new(
+      Some("_subject".to_owned()),
+      FunctionType::new(vec![TYPE_I32, TYPE_I32], vec![TYPE_I32]),
+      vec![],
+      into_vec_u8(&[
+        Cc::Code(GetLocal),
+        Cc::Byte(0),
+        Cc::Byte(0),
+        Cc::Byte(0),
+        Cc::Byte(0),
+        Cc::Code(I32ConstI32Add),
+        Cc::Byte(10),
+        Cc::Byte(0),
+        Cc::Byte(0),
+        Cc::Byte(0),
+        Cc::Code(I32Add),
+        Cc::Code(GetLocal),
+        Cc::Byte(1),
+        Cc::Byte(0),
+        Cc::Byte(0),
+        Cc::Byte(0),
+        Cc::Code(I32Add),
+        Cc::Code(End),
+      ]),
+    )
+  );
+
   test_decode!(
     decode_if_lt,
     "dist/if_lt",
@@ -590,4 +709,103 @@ mod tests {
       ]),
     )
   );
+
+  #[test]
+  fn decode_sections_out_of_order() {
+    let bytes = [
+      0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+      0x03, 0x01, 0x00, // function
+      0x01, 0x01, 0x00, // type
+    ];
+    assert_eq!(
+      decode_module(&bytes).unwrap_err(),
+      WasmError::Trap(Trap::UnexpectedSection)
+    );
+  }
+
+  #[test]
+  fn decode_duplicated_sections() {
+    let bytes = [
+      0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+      0x01, 0x01, 0x00, // type
+      0x00, 0x01, 0x00, // custom
+      0x01, 0x01, 0x00, // type
+    ];
+    assert_eq!(
+      decode_module(&bytes).unwrap_err(),
+      WasmError::Trap(Trap::UnexpectedSection)
+    );
+  }
+
+  #[test]
+  fn record_offsets_of_instructions() {
+    // (module (func (result i32) (block (result i32) i32.const 300)))
+    let bytes = [
+      0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+      0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+      0x03, 0x02, 0x01, 0x00, // function
+      0x0a, 0x0a, 0x01, 0x08, 0x00, 0x02, 0x7f, 0x41, 0xac, 0x02, 0x0b, 0x0b, // code
+    ];
+    let module = decode_module(&bytes).unwrap();
+    let (body, _) = module.codes[0].as_ref().unwrap();
+    let (_, offsets) = body.flatten().unwrap();
+    assert_eq!(
+      offsets.iter().cloned().collect::<Vec<_>>(),
+      vec![(0, 3), (6, 5), (11, 8), (12, 9)]
+    );
+    assert_eq!(offsets.offset_of(6), Some(5));
+    assert_eq!(offsets.offset_of(7), None);
+  }
+
+  #[test]
+  fn decode_data_count() {
+    let bytes = [
+      0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+      0x05, 0x03, 0x01, 0x00, 0x01, // memory
+      0x0c, 0x01, 0x01, // data count
+      0x0b, 0x07, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x01, 0x2a, // data
+    ];
+    assert_eq!(decode_module(&bytes).unwrap().data_count, Some(1));
+
+    let bytes = [
+      0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+      0x05, 0x03, 0x01, 0x00, 0x01, // memory
+      0x0c, 0x01, 0x02, // data count
+      0x0b, 0x07, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x01, 0x2a, // data
+    ];
+    assert_eq!(
+      decode_module(&bytes).unwrap_err(),
+      WasmError::Trap(Trap::DataCountAndDataInconsistent)
+    );
+  }
+
+  #[test]
+  fn decode_boundaries_of_sections() {
+    let bytes = [
+      0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+      0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type
+      0x00, 0x04, 0x01, 0x78, 0x2a, 0x2b, // custom "x"
+      0x03, 0x02, 0x01, 0x00, // function
+      0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b, // code
+    ];
+    let module = decode_module(&bytes).unwrap();
+    let sections = module.sections(&bytes);
+    assert_eq!(
+      sections
+        .iter()
+        .map(|(code, range, _)| (code.clone(), range.clone()))
+        .collect::<Vec<_>>(),
+      vec![
+        (SectionCode::Type, 8..14),
+        (SectionCode::Custom, 14..20),
+        (SectionCode::Function, 20..24),
+        (SectionCode::Code, 24..30),
+      ]
+    );
+    let (_, _, payload) = sections[1];
+    assert_eq!(payload, &[0x01, 0x78, 0x2a, 0x2b]);
+    for (_, range, payload) in sections.iter() {
+      assert!(bytes[range.clone()].ends_with(payload));
+    }
+  }
 }
@@ -0,0 +1,110 @@
+use super::decodable::{Leb128Decodable, NameDecodable, U32Decodable, U8Iterator};
+use alloc::string::String;
+use alloc::vec::Vec;
+use error::{Result, Trap, WasmError};
+
+impl_decodable!(ComponentByte);
+impl Leb128Decodable for ComponentByte {}
+impl U32Decodable for ComponentByte {}
+impl NameDecodable for ComponentByte {}
+
+// NOTE: Component binary shares magic words with module, and distinguished by version and layer.
+const COMPONENT_VERSION_AND_LAYER: [u8; 4] = [0x0d, 0x00, 0x01, 0x00];
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum CoreInstance {
+  // Instantiate module by index with instances which satisfy its imports by module name.
+  Instantiate {
+    module_idx: u32,
+    arguments: Vec<(String, u32)>,
+  },
+}
+
+/// Component which consists of core modules and core instances.
+/// Other definitions of component model are not supported yet and skipped.
+#[derive(Debug, Default)]
+pub struct Component {
+  // NOTE: Modules are kept as binary, because a module may be instantiated multiple times.
+  pub(crate) modules: Vec<Vec<u8>>,
+  pub(crate) instances: Vec<CoreInstance>,
+}
+
+impl ComponentByte {
+  pub fn new_with_drop(bytes: &[u8]) -> Result<Self> {
+    if 8 > bytes.len() {
+      return Err(WasmError::Trap(Trap::UnexpectedEnd));
+    }
+    let (preamble, bytes) = bytes.split_at(8);
+    if preamble[0..4] != [0, 97, 115, 109] {
+      return Err(WasmError::Trap(Trap::MagicHeaderNotDetected));
+    }
+    if preamble[4..8] != COMPONENT_VERSION_AND_LAYER {
+      return Err(WasmError::Trap(Trap::UnknownComponentVersion));
+    }
+    Ok(ComponentByte::new(bytes.to_vec()))
+  }
+
+  fn has_next(&self) -> bool {
+    self.byte_ptr < self.bytes.len()
+  }
+
+  fn decode_section(&mut self) -> Result<Vec<u8>> {
+    let bin_size_of_section = self.decode_leb128_u32()?;
+    let start = self.byte_ptr;
+    let end = start + bin_size_of_section as usize;
+    if end > self.bytes.len() {
+      return Err(WasmError::Trap(Trap::LengthOutofBounds));
+    }
+    self.byte_ptr = end;
+    Ok(self.bytes[start..end].to_vec())
+  }
+
+  fn decode_core_instances(bytes: Vec<u8>) -> Result<Vec<CoreInstance>> {
+    let mut section = ComponentByte::new(bytes);
+    let count_of_instances = section.decode_leb128_u32()?;
+    let mut instances = vec![];
+    for _ in 0..count_of_instances {
+      match section.next() {
+        Some(0x00) => {
+          let module_idx = section.decode_leb128_u32()?;
+          let count_of_arguments = section.decode_leb128_u32()?;
+          let mut arguments = vec![];
+          for _ in 0..count_of_arguments {
+            let name = section.decode_name()?;
+            // NOTE: Only core instance can be passed as an argument.
+            if section.next() != Some(0x12) {
+              return Err(WasmError::Trap(Trap::UnsupportedComponentDefinition));
+            }
+            arguments.push((name, section.decode_leb128_u32()?));
+          }
+          instances.push(CoreInstance::Instantiate {
+            module_idx,
+            arguments,
+          });
+        }
+        // NOTE: Instance from inline exports requires core aliases.
+        Some(_) => return Err(WasmError::Trap(Trap::UnsupportedComponentDefinition)),
+        None => return Err(WasmError::Trap(Trap::UnexpectedEnd)),
+      }
+    }
+    Ok(instances)
+  }
+
+  pub fn decode(&mut self) -> Result<Component> {
+    let mut component = Component::default();
+    while self.has_next() {
+      let code = self.next();
+      let bytes = self.decode_section()?;
+      match code {
+        Some(0x01) => component.modules.push(bytes),
+        Some(0x02) => component
+          .instances
+          .append(&mut ComponentByte::decode_core_instances(bytes)?),
+        // Custom, core type, component, instance, alias, type, canon, start, import and export sections.
+        Some(0x00) | Some(0x03..=0x0b) => {}
+        _ => return Err(WasmError::Trap(Trap::InvalidSectionId)),
+      }
+    }
+    Ok(component)
+  }
+}
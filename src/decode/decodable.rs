@@ -83,9 +83,16 @@ pub trait Leb128Decodable: U8Iterator {
   impl_decode_leb128!(u64, u64::from, decode_leb128_u64_internal);
 }
 
+// NOTE: Last byte of 5 bytes encoding of u32 has only 4 bits which fit in it.
+const UNUSED_BITS_OF_LAST_BYTE_OF_U32: u8 = 0b0111_0000;
+
 pub trait U32Decodable: Leb128Decodable {
   fn decode_leb128_u32(&mut self) -> Result<u32> {
-    let (buf, _) = self.decode_leb128_u32_internal()?;
+    let (buf, shift) = self.decode_leb128_u32_internal()?;
+    let last_byte = self.bytes()[self.byte_ptr() - 1];
+    if shift == 35 && last_byte & UNUSED_BITS_OF_LAST_BYTE_OF_U32 != 0 {
+      return Err(Trap::BitshiftOverflow.into());
+    }
     Ok(buf)
   }
 }
@@ -108,19 +115,27 @@ pub trait LimitDecodable: U32Decodable {
         let max = self.decode_leb128_u32()?;
         Ok(HasUpperLimit(min, max))
       }
-      x => unreachable!("Expected limit code, got {:?}", x),
+      // NOTE: Reference interpreter decodes flags of limits as an integer, so other flags are too large.
+      Some(_) => Err(Trap::BitshiftOverflow.into()),
+      None => Err(Trap::UnexpectedEnd.into()),
     }
   }
 }
 
 pub trait NameDecodable: U32Decodable {
+  // NOTE: Offset of invalid byte is from the head of bytes of decoder, see `Trap::InvalidUTF8Name`.
   fn decode_name(&mut self) -> Result<String> {
     let size_of_name = self.decode_leb128_u32()?;
+    let start = self.byte_ptr();
     let mut buf = vec![];
     for _ in 0..size_of_name {
       buf.push(self.next()?);
     }
-    String::from_utf8(buf).map_err(|_| WasmError::Trap(Trap::InvalidUTF8Encoding))
+    String::from_utf8(buf).map_err(|err| {
+      WasmError::Trap(Trap::InvalidUTF8Name {
+        offset: start + err.utf8_error().valid_up_to(),
+      })
+    })
   }
 }
 
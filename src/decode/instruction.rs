@@ -19,13 +19,51 @@ macro_rules! impl_push_raw_bytes {
   ($name: ident, $ty: ty, $width: expr) => {
     fn $name(&self, raw: $ty, expressions: &mut Vec<u8>) {
       let bytes: [u8; $width] = unsafe { core::mem::transmute(raw) };
-      for byte in bytes.iter() {
-        expressions.push(*byte);
-      }
+      expressions.extend_from_slice(&bytes);
     }
   };
 }
 
+/// Side table of flattened instructions, which maps position of each instruction
+/// to its offset from the beginning of code section in binary format.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InstructionOffsets(Vec<(u32, u32)>);
+
+impl InstructionOffsets {
+  fn push(&mut self, ptr: usize, offset: usize) {
+    self.0.push((ptr as u32, offset as u32));
+  }
+
+  // NOTE: Offsets of a body flattened alone start from 0, so they're moved to the position of body.
+  pub(crate) fn rebase(&mut self, base: usize) {
+    for (_, offset) in self.0.iter_mut() {
+      *offset += base as u32;
+    }
+  }
+
+  /// Offset in binary format of an instruction which starts at `ptr` of flattened instructions.
+  pub fn offset_of(&self, ptr: u32) -> Option<u32> {
+    self
+      .0
+      .binary_search_by_key(&ptr, |(p, _)| *p)
+      .ok()
+      .map(|idx| self.0[idx].1)
+  }
+
+  /// Offset in binary format of the instruction which lies just before `ptr` of flattened instructions.
+  pub fn offset_before(&self, ptr: u32) -> Option<u32> {
+    match self.0.binary_search_by_key(&ptr, |(p, _)| *p) {
+      Ok(0) | Err(0) => None,
+      Ok(idx) | Err(idx) => Some(self.0[idx - 1].1),
+    }
+  }
+
+  /// Pairs of position in flattened instructions and offset in binary format, ordered by position.
+  pub fn iter(&self) -> impl Iterator<Item = &(u32, u32)> {
+    self.0.iter()
+  }
+}
+
 pub trait InstructionDecodable: U32Decodable + Peekable + SignedIntegerDecodable {
   impl_decode_float!(u32, decode_f32, 4);
   impl_decode_float!(u64, decode_f64, 8);
@@ -54,101 +92,147 @@ pub trait InstructionDecodable: U32Decodable + Peekable + SignedIntegerDecodable
   }
 
   fn decode_instructions(&mut self) -> Result<Vec<u8>> {
-    use self::Isa::*;
     let mut expressions = vec![];
-    while !Isa::is_else_or_end(self.peek()) {
+    let terminator =
+      self.decode_instructions_into(&mut expressions, &mut InstructionOffsets::default())?;
+    expect_end(terminator)?;
+    Ok(expressions)
+  }
+
+  // NOTE: Flattened instructions are slightly larger than its binary representation,
+  // so pre-sizing by declared body size avoids most of reallocations.
+  fn decode_function_body(
+    &mut self,
+    size_of_function: usize,
+  ) -> Result<(Vec<u8>, InstructionOffsets)> {
+    let mut expressions = Vec::with_capacity(size_of_function);
+    let mut offsets = InstructionOffsets::default();
+    // NOTE: Bytes of a body are sliced by its size, so every byte which runs out belongs to the function.
+    let terminator = self
+      .decode_instructions_into(&mut expressions, &mut offsets)
+      .map_err(|error| match error {
+        WasmError::Trap(Trap::Notfound) => WasmError::Trap(Trap::UnexpectedEnd),
+        error => error,
+      })?;
+    expect_end(terminator)?;
+    Ok((expressions, offsets))
+  }
+
+  /// Decode instructions until Else or End appears outside of nested blocks, then returns the terminator.
+  /// Nested blocks are written into the same buffer and their sizes are patched when they are closed,
+  /// so that deeply nested blocks consume heap rather than stack of host.
+  /// Bytes which run out before the terminator trap by `UnexpectedEnd`.
+  fn decode_instructions_into(
+    &mut self,
+    expressions: &mut Vec<u8>,
+    offsets: &mut InstructionOffsets,
+  ) -> Result<Isa> {
+    use self::Isa::*;
+    let mut controls: Vec<OpenControl> = vec![];
+    loop {
+      if self.peek().is_none() {
+        return Err(WasmError::Trap(Trap::UnexpectedEnd));
+      }
+      offsets.push(expressions.len(), self.byte_ptr());
       let code = self.next()?;
+      if let Some(proposal) = Isa::proposal_of(code, self.peek()) {
+        return Err(WasmError::Trap(Trap::UnsupportedFeature {
+          proposal,
+          opcode: code,
+        }));
+      }
+      if Isa::is_illegal(code) {
+        return Err(WasmError::Trap(Trap::IllegalOpcode { opcode: code }));
+      }
+      if !cfg!(feature = "float") && Isa::is_float(code) {
+        return Err(WasmError::Trap(Trap::UnsupportedFloat { opcode: code }));
+      }
       match Isa::from(code) {
-        // NOTE: Else and End are already consumed at decoding "If" instructions.
-        Reserved | End | Else => unreachable!("{:?}", code),
+        // NOTE: Rejected by `is_illegal` above.
+        Reserved | GetLocalGetLocalI32Add | I32ConstI32Add | GetLocalI32Load => {
+          unreachable!("{:?}", code)
+        }
+        terminator @ Else | terminator @ End => {
+          expressions.push(code);
+          match controls.pop() {
+            Some(control) => close_control(control, terminator, expressions, &mut controls)?,
+            None => return Ok(terminator),
+          }
+        }
         Unreachable | Nop | Return | DropInst => expressions.push(code),
 
         Block => {
           let block_type = self.next()?;
-          let mut instructions = self.decode_instructions()?;
-          let size =
-            (2 /* Block inst + Type of block */ + 4 /* size of size */ + instructions.len()) as u32;
+          controls.push(OpenControl::Block(expressions.len()));
           expressions.push(code);
-          self.push_u32_as_bytes(size, &mut expressions);
+          self.push_u32_as_bytes(0, expressions);
           expressions.push(block_type);
-          expressions.append(&mut instructions);
         }
         Loop => {
           let block_type = self.next()?;
-          let mut instructions = self.decode_instructions()?;
+          controls.push(OpenControl::Loop);
           expressions.push(code);
           expressions.push(block_type);
-          expressions.append(&mut instructions);
         }
         If => {
           let block_type = self.next()?;
-          let mut if_insts = self.decode_instructions()?;
-          let last = *if_insts.last()?;
-          let mut else_insts = match Isa::from(last) {
-            Else => self.decode_instructions()?,
-            End => vec![],
-            x => unreachable!("{:?}", x),
-          };
-          let size_of_if = (2 /* If inst + Type of block */ + 8 + if_insts.len()) as u32;
-          let size_of_else = else_insts.len() as u32;
+          controls.push(OpenControl::If(expressions.len()));
           expressions.push(code);
-          self.push_u32_as_bytes(size_of_if, &mut expressions);
-          self.push_u32_as_bytes(size_of_else, &mut expressions);
+          self.push_u32_as_bytes(0, expressions);
+          self.push_u32_as_bytes(0, expressions);
           expressions.push(block_type);
-          expressions.append(&mut if_insts);
-          expressions.append(&mut else_insts);
         }
 
         GetLocal | SetLocal | TeeLocal | GetGlobal | SetGlobal | Br | BrIf | Call => {
           expressions.push(code);
           let idx = self.decode_leb128_u32()?;
-          self.push_u32_as_bytes(idx, &mut expressions);
+          self.push_u32_as_bytes(idx, expressions);
         }
 
         BrTable => {
           expressions.push(code);
           let len = self.decode_leb128_u32()?;
-          self.push_u32_as_bytes(len, &mut expressions);
+          self.push_u32_as_bytes(len, expressions);
           for _ in 0..len {
             let idx = self.decode_leb128_u32()?;
-            self.push_u32_as_bytes(idx, &mut expressions);
+            self.push_u32_as_bytes(idx, expressions);
           }
           let idx = self.decode_leb128_u32()?;
-          self.push_u32_as_bytes(idx, &mut expressions);
+          self.push_u32_as_bytes(idx, expressions);
         }
         CallIndirect => {
           expressions.push(code);
           let idx = self.decode_leb128_u32()?;
-          self.push_u32_as_bytes(idx, &mut expressions);
+          self.push_u32_as_bytes(idx, expressions);
           self.next(); // Drop code 0x00.
         }
 
         I32Const => {
           expressions.push(code);
           let value = self.decode_leb128_i32()?;
-          self.push_u32_as_bytes(value, &mut expressions);
+          self.push_u32_as_bytes(value, expressions);
         }
         I64Const => {
           expressions.push(code);
           let value = self.decode_leb128_i64()?;
-          self.push_u64_as_bytes(value, &mut expressions);
+          self.push_u64_as_bytes(value, expressions);
         }
         F32Const => {
           expressions.push(code);
           let value = self.decode_f32()?;
-          self.push_u32_as_bytes(value, &mut expressions);
+          self.push_u32_as_bytes(value, expressions);
         }
         F64Const => {
           expressions.push(code);
           let value = self.decode_f64()?;
-          self.push_u64_as_bytes(value, &mut expressions);
+          self.push_u64_as_bytes(value, expressions);
         }
 
         I32Load | I64Load | F32Load | F64Load | I32Load8Sign | I32Load8Unsign | I32Load16Sign
         | I32Load16Unsign | I64Load8Sign | I64Load8Unsign | I64Load16Sign | I64Load16Unsign
         | I64Load32Sign | I64Load32Unsign | I32Store | I64Store | F32Store | F64Store
         | I32Store8 | I32Store16 | I64Store8 | I64Store16 | I64Store32 => {
-          self.decode_memory(code, &mut expressions)?
+          self.decode_memory(code, expressions)?
         }
 
         MemorySize | MemoryGrow => {
@@ -282,11 +366,60 @@ pub trait InstructionDecodable: U32Decodable + Peekable + SignedIntegerDecodable
         | Select => expressions.push(code),
       };
     }
-    let end_code = self.next()?;
-    match Isa::from(end_code) {
-      Else | End => expressions.push(end_code),
-      x => unreachable!("{:?}", x),
+  }
+}
+
+fn patch_u32_as_bytes(raw: u32, at: usize, expressions: &mut Vec<u8>) {
+  let bytes: [u8; 4] = unsafe { core::mem::transmute(raw) };
+  expressions[at..(at + 4)].copy_from_slice(&bytes);
+}
+
+// Block, Loop or If whose End isn't decoded yet, with position of its opcode in flattened instructions.
+// Else holds size of then-branch of the If, too.
+enum OpenControl {
+  Block(usize),
+  Loop,
+  If(usize),
+  Else(usize, u32),
+}
+
+// Patch sizes of a block closed by `terminator`, which is already written.
+// Size of then-branch of If includes its terminator, and size of else-branch is 0 without Else.
+fn close_control(
+  control: OpenControl,
+  terminator: Isa,
+  expressions: &mut Vec<u8>,
+  controls: &mut Vec<OpenControl>,
+) -> Result<()> {
+  let end_of_block = expressions.len() as u32;
+  match (control, terminator) {
+    (OpenControl::Block(start_of_block), Isa::End) => {
+      let size = end_of_block - start_of_block as u32;
+      patch_u32_as_bytes(size, start_of_block + 1, expressions);
+    }
+    (OpenControl::Loop, Isa::End) => {}
+    (OpenControl::If(start_of_if), Isa::Else) => {
+      let size_of_if = end_of_block - start_of_if as u32;
+      controls.push(OpenControl::Else(start_of_if, size_of_if));
     }
-    Ok(expressions)
+    (OpenControl::If(start_of_if), Isa::End) => {
+      let size_of_if = end_of_block - start_of_if as u32;
+      patch_u32_as_bytes(size_of_if, start_of_if + 1, expressions);
+    }
+    (OpenControl::Else(start_of_if, size_of_if), Isa::End) => {
+      let size_of_else = end_of_block - start_of_if as u32 - size_of_if;
+      patch_u32_as_bytes(size_of_if, start_of_if + 1, expressions);
+      patch_u32_as_bytes(size_of_else, start_of_if + 5, expressions);
+    }
+    _ => return Err(WasmError::Trap(Trap::MisplacedElse)),
+  }
+  Ok(())
+}
+
+// NOTE: Else only terminates then-branch of If, so other blocks and function bodies must end by End.
+fn expect_end(terminator: Isa) -> Result<()> {
+  match terminator {
+    Isa::End => Ok(()),
+    _ => Err(WasmError::Trap(Trap::MisplacedElse)),
   }
 }
@@ -0,0 +1,48 @@
+use error::{Result, Trap, WasmError};
+
+/// Limits of function bodies checked while decoding, so that pathological modules are rejected
+/// before their bodies are allocated or flattened. None means unlimited.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DecodeLimits {
+  /// Size of a function body in bytes, which includes declarations of its locals.
+  pub max_body_size: Option<usize>,
+  /// Count of declared locals per function, parameters are excluded.
+  pub max_locals: Option<usize>,
+  /// Depth of nested blocks, loops and ifs in a function.
+  pub max_nesting_depth: Option<usize>,
+  /// Count of labels in a br_table, which includes its default label.
+  pub max_br_table_targets: Option<usize>,
+}
+
+fn check(max: Option<usize>, count: usize, trap: Trap) -> Result<()> {
+  match max {
+    Some(max) if count > max => Err(WasmError::Trap(trap)),
+    _ => Ok(()),
+  }
+}
+
+impl DecodeLimits {
+  pub(crate) fn check_body_size(&self, size: usize) -> Result<()> {
+    check(self.max_body_size, size, Trap::FunctionBodyTooLarge)
+  }
+
+  pub(crate) fn check_locals(&self, count: usize) -> Result<()> {
+    check(self.max_locals, count, Trap::TooManyLocals)
+  }
+
+  pub(crate) fn check_nesting_depth(&self, depth: usize) -> Result<()> {
+    check(self.max_nesting_depth, depth, Trap::NestingTooDeep)
+  }
+
+  pub(crate) fn check_br_table_targets(&self, count: usize) -> Result<()> {
+    check(
+      self.max_br_table_targets,
+      count,
+      Trap::TooManyBrTableTargets,
+    )
+  }
+
+  pub(crate) fn checks_instructions(&self) -> bool {
+    self.max_nesting_depth.is_some() || self.max_br_table_targets.is_some()
+  }
+}
@@ -2,24 +2,39 @@
 mod decodable;
 mod byte;
 mod code;
+mod component;
 mod instruction;
+mod limits;
 mod sec_code;
 mod sec_custom;
 mod sec_data;
+mod sec_data_count;
+mod sec_debug_line;
+mod sec_dylink;
 mod sec_element;
 mod sec_export;
 mod sec_function;
 mod sec_global;
 mod sec_import;
 mod sec_memory;
+mod sec_producers;
 mod sec_start;
 mod sec_table;
 mod sec_type;
 mod section;
+mod verifier;
 
 pub use self::byte::Byte;
+pub use self::component::{Component, ComponentByte, CoreInstance};
 pub use self::decodable::{AbstractDecodable, U8Iterator};
+pub use self::instruction::InstructionOffsets;
+pub use self::limits::DecodeLimits;
+pub use self::sec_code::RawBody;
 pub use self::sec_data::Data;
+pub use self::sec_debug_line::{DebugLines, SourceLocation};
+pub use self::sec_dylink::Dylink;
 pub use self::sec_element::{Element, ElementType};
-pub use self::sec_table::TableType;
-pub use self::section::Module;
+pub use self::sec_producers::{ProducerVersion, Producers};
+pub use self::sec_table::{TableIndexType, TableType};
+pub use self::section::{Module, SectionCode};
+pub use self::verifier::Verifier;
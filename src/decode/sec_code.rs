@@ -1,10 +1,13 @@
 use super::decodable::{
   Decodable, Leb128Decodable, Peekable, SignedIntegerDecodable, U32Decodable, U8Iterator,
 };
-use super::instruction::InstructionDecodable;
+use super::instruction::{InstructionDecodable, InstructionOffsets};
+use super::limits::DecodeLimits;
+use super::section::Code;
 use alloc::vec::Vec;
 use core::convert::From;
-use error::Result;
+use error::{Result, Trap, WasmError};
+use opcode::{Immediate, OpcodeInfo};
 use value_type::ValueTypes;
 
 impl_decodable!(Section);
@@ -15,32 +18,140 @@ impl U32Decodable for Section {}
 impl SignedIntegerDecodable for Section {}
 impl InstructionDecodable for Section {}
 
-impl Decodable for Section {
-  // FIXME:
-  type Item = Vec<Result<(Vec<u8>, Vec<ValueTypes>)>>;
-  fn decode(&mut self) -> Result<Self::Item> {
+/// Instructions of a function body in binary format, which are flattened on demand.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawBody {
+  bytes: Vec<u8>,
+  // Offset of the first instruction from the beginning of code section.
+  offset: usize,
+}
+
+impl RawBody {
+  pub(crate) fn flatten(&self) -> Result<(Vec<u8>, InstructionOffsets)> {
+    self.clone().flatten_in_place()
+  }
+
+  /// Same as `flatten`, but decodes its own bytes instead of a copy of them.
+  pub(crate) fn flatten_in_place(&mut self) -> Result<(Vec<u8>, InstructionOffsets)> {
+    let bytes = core::mem::replace(&mut self.bytes, vec![]);
+    let size_of_body = bytes.len();
+    let mut section = Section::new(bytes);
+    let flattened = section.decode_function_body(size_of_body);
+    self.bytes = section.bytes;
+    let (expressions, mut offsets) = flattened?;
+    offsets.rebase(self.offset);
+    Ok((expressions, offsets))
+  }
+}
+
+impl Section {
+  // NOTE: Scan instructions only for their structure without flattening them,
+  // an opcode which isn't in the table stops scanning and is reported at flattening.
+  fn check_instructions(&mut self, end_of_function: usize, limits: &DecodeLimits) -> Result<()> {
+    use self::Immediate::*;
+    let start = self.byte_ptr;
+    let mut depth = 0;
+    while self.byte_ptr < end_of_function {
+      let info = match OpcodeInfo::of(self.next()?) {
+        Some(info) => info,
+        None => break,
+      };
+      match info.code {
+        // block, loop and if
+        0x02 | 0x03 | 0x04 => {
+          depth += 1;
+          limits.check_nesting_depth(depth)?;
+        }
+        // end
+        0x0b if depth > 0 => depth -= 1,
+        _ => {}
+      }
+      for immediate in info.immediates.iter() {
+        match immediate {
+          BlockType | MemoryIndex => {
+            self.next()?;
+          }
+          LabelIndex | FunctionIndex | LocalIndex | GlobalIndex => {
+            self.decode_leb128_u32()?;
+          }
+          LabelTable => {
+            let count_of_labels = self.decode_leb128_u32()? as usize;
+            limits.check_br_table_targets(count_of_labels + 1)?;
+            for _ in 0..=count_of_labels {
+              self.decode_leb128_u32()?;
+            }
+          }
+          TypeIndex => {
+            self.decode_leb128_u32()?;
+            self.next()?;
+          }
+          MemoryArgument => {
+            self.decode_leb128_u32()?;
+            self.decode_leb128_u32()?;
+          }
+          Int32 => {
+            self.decode_leb128_i32()?;
+          }
+          Int64 => {
+            self.decode_leb128_i64()?;
+          }
+          Float32 => {
+            self.decode_f32()?;
+          }
+          Float64 => {
+            self.decode_f64()?;
+          }
+        }
+      }
+    }
+    self.byte_ptr = start;
+    Ok(())
+  }
+
+  /// Same as `decode`, but rejects a function body which exceeds `limits`.
+  pub fn decode_with_limits(&mut self, limits: &DecodeLimits) -> Result<Vec<Result<Code>>> {
     let count_of_section = self.decode_leb128_u32()?;
     (0..count_of_section)
       .map(|_| {
         let size_of_function = self.decode_leb128_u32()?;
+        limits.check_body_size(size_of_function as usize)?;
         let end_of_function = self.byte_ptr + (size_of_function as usize);
         let count_of_locals = self.decode_leb128_u32()? as usize;
         let mut locals: Vec<ValueTypes> = Vec::with_capacity(count_of_locals);
+        let mut count_of_declared = 0usize;
         for _ in 0..count_of_locals {
           let count_of_type = self.decode_leb128_u32()?;
+          count_of_declared = count_of_declared.saturating_add(count_of_type as usize);
+          limits.check_locals(count_of_declared)?;
           let value_type = ValueTypes::from(self.next()?);
           for _ in 0..count_of_type {
             locals.push(value_type.clone());
           }
         }
-        Ok(match self.decode_instructions() {
-          Ok(expressions) => Ok((expressions, locals)),
-          Err(err) => {
-            self.byte_ptr = end_of_function;
-            Err(err)
-          }
-        })
+        // NOTE: Instructions are flattened at instantiation or on the first call,
+        // so an error of them is reported by each function.
+        if end_of_function > self.bytes.len() || end_of_function < self.byte_ptr {
+          self.byte_ptr = self.bytes.len();
+          return Ok(Err(WasmError::Trap(Trap::UnexpectedEnd)));
+        }
+        if limits.checks_instructions() {
+          self.check_instructions(end_of_function, limits)?;
+        }
+        let body = RawBody {
+          bytes: self.bytes[self.byte_ptr..end_of_function].to_vec(),
+          offset: self.byte_ptr,
+        };
+        self.byte_ptr = end_of_function;
+        Ok(Ok((body, locals)))
       })
       .collect::<Result<Vec<_>>>()
   }
 }
+
+impl Decodable for Section {
+  // FIXME:
+  type Item = Vec<Result<Code>>;
+  fn decode(&mut self) -> Result<Self::Item> {
+    self.decode_with_limits(&DecodeLimits::default())
+  }
+}
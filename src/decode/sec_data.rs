@@ -5,7 +5,7 @@ use super::instruction::InstructionDecodable;
 use alloc::vec::Vec;
 use error::Result;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Data {
   pub memidx: u32,
   pub offset: Vec<u8>,
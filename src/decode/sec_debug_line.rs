@@ -0,0 +1,338 @@
+use super::decodable::{
+  Decodable, Leb128Decodable, SignedIntegerDecodable, U32Decodable, U8Iterator,
+};
+#[cfg(not(any(test, feature = "std")))]
+use alloc::prelude::*;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use error::{Result, Trap, WasmError};
+
+impl_decodable!(Section);
+impl Leb128Decodable for Section {}
+impl U32Decodable for Section {}
+impl SignedIntegerDecodable for Section {}
+
+// Standard opcodes of line number program.
+const DW_LNS_COPY: u8 = 0x01;
+const DW_LNS_ADVANCE_PC: u8 = 0x02;
+const DW_LNS_ADVANCE_LINE: u8 = 0x03;
+const DW_LNS_SET_FILE: u8 = 0x04;
+const DW_LNS_SET_COLUMN: u8 = 0x05;
+const DW_LNS_CONST_ADD_PC: u8 = 0x08;
+const DW_LNS_FIXED_ADVANCE_PC: u8 = 0x09;
+
+// Extended opcodes of line number program.
+const DW_LNE_END_SEQUENCE: u8 = 0x01;
+const DW_LNE_SET_ADDRESS: u8 = 0x02;
+const DW_LNE_DEFINE_FILE: u8 = 0x03;
+
+/// Location in source code which an instruction is compiled from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLocation {
+  pub file: String,
+  pub line: u32,
+  pub column: u32,
+}
+
+impl fmt::Display for SourceLocation {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}:{}", self.file, self.line)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Row {
+  address: u32,
+  file: usize,
+  line: u32,
+  column: u32,
+  end_sequence: bool,
+}
+
+/// Line number information of `.debug_line` section,
+/// which maps offset of instructions in code section to locations in source code.
+/// Only DWARF version 2 to 4 in 32-bit format is supported, other units are skipped.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DebugLines {
+  // NOTE: File tables of every units are concatenated.
+  files: Vec<String>,
+  rows: Vec<Row>,
+}
+
+impl DebugLines {
+  /// Location of an instruction which starts at `offset` from the beginning of code section.
+  pub fn location_of(&self, offset: u32) -> Option<SourceLocation> {
+    let idx = match self.rows.binary_search_by_key(&offset, |row| row.address) {
+      Ok(mut idx) => {
+        // NOTE: Rows can share an address, the last one is the most specific.
+        while self.rows.get(idx + 1).map(|row| row.address) == Some(offset) {
+          idx += 1;
+        }
+        idx
+      }
+      Err(0) => return None,
+      Err(idx) => idx - 1,
+    };
+    let row = &self.rows[idx];
+    if row.end_sequence {
+      return None;
+    }
+    Some(SourceLocation {
+      file: self.files.get(row.file)?.to_owned(),
+      line: row.line,
+      column: row.column,
+    })
+  }
+}
+
+struct Registers {
+  address: u32,
+  file: u32,
+  line: i64,
+  column: u32,
+}
+
+impl Registers {
+  fn new() -> Self {
+    Registers {
+      address: 0,
+      file: 1,
+      line: 1,
+      column: 0,
+    }
+  }
+}
+
+impl Section {
+  fn has_next(&self) -> bool {
+    self.byte_ptr < self.bytes.len()
+  }
+
+  fn decode_u8(&mut self) -> Result<u8> {
+    Ok(self.next()?)
+  }
+
+  fn decode_fixed(&mut self, width: usize) -> Result<u64> {
+    if width > 8 {
+      return Err(WasmError::Trap(Trap::UnsupportedDebugInfo));
+    }
+    let mut buf = 0u64;
+    for i in 0..width {
+      buf |= u64::from(self.next()?) << (8 * i);
+    }
+    Ok(buf)
+  }
+
+  fn decode_string(&mut self) -> Result<String> {
+    let mut buf = vec![];
+    loop {
+      match self.next()? {
+        0 => break,
+        byte => buf.push(byte),
+      }
+    }
+    String::from_utf8(buf).map_err(|_| WasmError::Trap(Trap::InvalidUTF8Encoding))
+  }
+
+  fn decode_file_entry(&mut self, name: String, directories: &[String]) -> Result<String> {
+    let directory = self.decode_leb128_u32()? as usize;
+    let _modification_time = self.decode_leb128_u32()?;
+    let _length = self.decode_leb128_u32()?;
+    // NOTE: Index 0 points compilation directory, which is only known by .debug_info.
+    Ok(match directories.get(directory.wrapping_sub(1)) {
+      Some(dir) if !name.starts_with('/') => format!("{}/{}", dir, name),
+      _ => name,
+    })
+  }
+
+  fn decode_unit(&mut self, lines: &mut DebugLines) -> Result<()> {
+    let unit_length = self.decode_fixed(4)? as u32;
+    if unit_length == 0xffff_ffff {
+      return Err(WasmError::Trap(Trap::UnsupportedDebugInfo));
+    }
+    let end_of_unit = self.byte_ptr + unit_length as usize;
+    if end_of_unit > self.bytes.len() {
+      return Err(WasmError::Trap(Trap::UnexpectedEnd));
+    }
+    let version = self.decode_fixed(2)?;
+    if version < 2 || version > 4 {
+      self.byte_ptr = end_of_unit;
+      return Ok(());
+    }
+    let header_length = self.decode_fixed(4)? as usize;
+    let start_of_program = self.byte_ptr + header_length;
+    let minimum_instruction_length = u32::from(self.decode_u8()?);
+    if version >= 4 {
+      let _maximum_operations_per_instruction = self.decode_u8()?;
+    }
+    let _default_is_stmt = self.decode_u8()?;
+    let line_base = i64::from(self.decode_u8()? as i8);
+    let line_range = self.decode_u8()?;
+    let opcode_base = self.decode_u8()?;
+    if line_range == 0 {
+      return Err(WasmError::Trap(Trap::UnsupportedDebugInfo));
+    }
+    let mut standard_opcode_lengths = vec![];
+    for _ in 1..opcode_base {
+      standard_opcode_lengths.push(self.decode_u8()?);
+    }
+    let mut directories = vec![];
+    loop {
+      match self.decode_string()? {
+        ref dir if dir.is_empty() => break,
+        dir => directories.push(dir),
+      }
+    }
+    let base_of_files = lines.files.len();
+    loop {
+      let name = self.decode_string()?;
+      if name.is_empty() {
+        break;
+      }
+      let file = self.decode_file_entry(name, &directories)?;
+      lines.files.push(file);
+    }
+
+    self.byte_ptr = start_of_program;
+    let mut registers = Registers::new();
+    while self.byte_ptr < end_of_unit {
+      let mut emit_row = false;
+      let mut end_sequence = false;
+      match self.decode_u8()? {
+        0 => {
+          let size = self.decode_leb128_u32()? as usize;
+          let end_of_instruction = self.byte_ptr + size;
+          match self.decode_u8()? {
+            DW_LNE_END_SEQUENCE => {
+              emit_row = true;
+              end_sequence = true;
+            }
+            DW_LNE_SET_ADDRESS => {
+              registers.address = self.decode_fixed(size.saturating_sub(1))? as u32
+            }
+            DW_LNE_DEFINE_FILE => {
+              let name = self.decode_string()?;
+              let file = self.decode_file_entry(name, &directories)?;
+              lines.files.push(file);
+            }
+            _ => {}
+          }
+          self.byte_ptr = end_of_instruction;
+        }
+        DW_LNS_COPY => emit_row = true,
+        DW_LNS_ADVANCE_PC => {
+          let advance = self
+            .decode_leb128_u32()?
+            .wrapping_mul(minimum_instruction_length);
+          registers.address = registers.address.wrapping_add(advance);
+        }
+        DW_LNS_ADVANCE_LINE => registers.line += i64::from(self.decode_leb128_i32()? as i32),
+        DW_LNS_SET_FILE => registers.file = self.decode_leb128_u32()?,
+        DW_LNS_SET_COLUMN => registers.column = self.decode_leb128_u32()?,
+        DW_LNS_CONST_ADD_PC => {
+          let adjusted_opcode = u32::from(255 - opcode_base);
+          let advance = adjusted_opcode / u32::from(line_range) * minimum_instruction_length;
+          registers.address = registers.address.wrapping_add(advance);
+        }
+        DW_LNS_FIXED_ADVANCE_PC => {
+          registers.address = registers.address.wrapping_add(self.decode_fixed(2)? as u32)
+        }
+        opcode if opcode < opcode_base => {
+          // NOTE: Arguments of other standard opcodes don't affect locations.
+          for _ in 0..standard_opcode_lengths[opcode as usize - 1] {
+            self.decode_leb128_u32()?;
+          }
+        }
+        opcode => {
+          let adjusted_opcode = opcode - opcode_base;
+          let advance = u32::from(adjusted_opcode / line_range) * minimum_instruction_length;
+          registers.address = registers.address.wrapping_add(advance);
+          registers.line += line_base + i64::from(adjusted_opcode % line_range);
+          emit_row = true;
+        }
+      }
+      if emit_row {
+        lines.rows.push(Row {
+          address: registers.address,
+          file: base_of_files.wrapping_add((registers.file as usize).wrapping_sub(1)),
+          line: registers.line as u32,
+          column: registers.column,
+          end_sequence,
+        });
+      }
+      if end_sequence {
+        registers = Registers::new();
+      }
+    }
+    self.byte_ptr = end_of_unit;
+    Ok(())
+  }
+}
+
+impl Decodable for Section {
+  type Item = DebugLines;
+
+  fn decode(&mut self) -> Result<Self::Item> {
+    let mut lines = DebugLines::default();
+    while self.has_next() {
+      self.decode_unit(&mut lines)?;
+    }
+    // NOTE: Sort stably to keep order of rows which share an address.
+    lines.rows.sort_by_key(|row| row.address);
+    Ok(lines)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn decode_line_number_program() {
+    let bytes = vec![
+      0x3a, 0x00, 0x00, 0x00, // unit_length
+      0x04, 0x00, // version
+      0x22, 0x00, 0x00, 0x00, // header_length
+      0x01, // minimum_instruction_length
+      0x01, // maximum_operations_per_instruction
+      0x01, // default_is_stmt
+      0xfb, // line_base = -5
+      0x0e, // line_range = 14
+      0x0d, // opcode_base = 13
+      // standard_opcode_lengths
+      0x00, 0x01, 0x01, 0x01, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x01, 0x73, 0x72, 0x63,
+      0x00, 0x00, // include_directories = ["src"]
+      0x6c, 0x69, 0x62, 0x2e, 0x72, 0x73, 0x00, 0x01, 0x00, 0x00, // file_names = ["lib.rs"]
+      0x00, // end of file_names
+      0x00, 0x05, 0x02, 0x05, 0x00, 0x00, 0x00, // DW_LNE_set_address 0x05
+      0x03, 0x29, // DW_LNS_advance_line 41
+      0x01, // DW_LNS_copy
+      0x05, 0x09, // DW_LNS_set_column 9
+      0x9f, // special opcode: address += 10, line += 1
+      0x02, 0x04, // DW_LNS_advance_pc 4
+      0x00, 0x01, 0x01, // DW_LNE_end_sequence
+    ];
+    let lines = Section::new(bytes).decode().unwrap();
+    assert_eq!(lines.location_of(0x04), None);
+    assert_eq!(
+      lines.location_of(0x05),
+      Some(SourceLocation {
+        file: "src/lib.rs".to_owned(),
+        line: 42,
+        column: 0,
+      })
+    );
+    assert_eq!(
+      lines
+        .location_of(0x10)
+        .map(|location| format!("{}", location)),
+      Some("src/lib.rs:43".to_owned())
+    );
+    assert_eq!(
+      lines.location_of(0x12).map(|location| location.column),
+      Some(9)
+    );
+    assert_eq!(lines.location_of(0x13), None);
+  }
+}
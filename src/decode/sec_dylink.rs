@@ -0,0 +1,71 @@
+use super::decodable::{Decodable, Leb128Decodable, NameDecodable, U32Decodable};
+use alloc::string::String;
+use alloc::vec::Vec;
+use error::Result;
+
+impl_decodable!(Section);
+impl Leb128Decodable for Section {}
+impl U32Decodable for Section {}
+impl NameDecodable for Section {}
+
+/// Fields of `dylink` section, which a side module of dynamic linking declares
+/// to let a loader allocate regions of memory and table for it.
+/// Alignments are exponents of 2 as they're encoded.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Dylink {
+  pub memory_size: u32,
+  pub memory_alignment: u32,
+  pub table_size: u32,
+  pub table_alignment: u32,
+  /// Names of shared libraries which the module depends on.
+  pub needed: Vec<String>,
+}
+
+impl Decodable for Section {
+  type Item = Dylink;
+
+  fn decode(&mut self) -> Result<Self::Item> {
+    let memory_size = self.decode_leb128_u32()?;
+    let memory_alignment = self.decode_leb128_u32()?;
+    let table_size = self.decode_leb128_u32()?;
+    let table_alignment = self.decode_leb128_u32()?;
+    let count_of_needed = self.decode_leb128_u32()?;
+    let mut needed = vec![];
+    for _ in 0..count_of_needed {
+      needed.push(self.decode_name()?);
+    }
+    Ok(Dylink {
+      memory_size,
+      memory_alignment,
+      table_size,
+      table_alignment,
+      needed,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn decode_dylink() {
+    let bytes = vec![
+      0x80, 0x01, // memory size
+      0x03, // memory alignment
+      0x02, // table size
+      0x00, // table alignment
+      0x01, 0x07, 0x6c, 0x69, 0x62, 0x63, 0x2e, 0x73, 0x6f, // "libc.so"
+    ];
+    assert_eq!(
+      Section::new(bytes).decode(),
+      Ok(Dylink {
+        memory_size: 128,
+        memory_alignment: 3,
+        table_size: 2,
+        table_alignment: 0,
+        needed: vec!["libc.so".to_owned()],
+      })
+    );
+  }
+}
@@ -0,0 +1,92 @@
+use super::decodable::{Decodable, Leb128Decodable, NameDecodable, U32Decodable};
+use alloc::string::String;
+use alloc::vec::Vec;
+use error::Result;
+
+impl_decodable!(Section);
+impl Leb128Decodable for Section {}
+impl U32Decodable for Section {}
+impl NameDecodable for Section {}
+
+/// Name and version of a tool which took part in producing a module.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProducerVersion {
+  pub name: String,
+  pub version: String,
+}
+
+/// Fields of `producers` section, which records languages, tools and SDKs used to produce a module.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Producers {
+  pub language: Vec<ProducerVersion>,
+  pub processed_by: Vec<ProducerVersion>,
+  pub sdk: Vec<ProducerVersion>,
+}
+
+impl Section {
+  fn decode_versions(&mut self) -> Result<Vec<ProducerVersion>> {
+    let count_of_versions = self.decode_leb128_u32()?;
+    let mut versions = vec![];
+    for _ in 0..count_of_versions {
+      let name = self.decode_name()?;
+      let version = self.decode_name()?;
+      versions.push(ProducerVersion { name, version });
+    }
+    Ok(versions)
+  }
+}
+
+impl Decodable for Section {
+  type Item = Producers;
+
+  fn decode(&mut self) -> Result<Self::Item> {
+    let mut producers = Producers::default();
+    let count_of_fields = self.decode_leb128_u32()?;
+    for _ in 0..count_of_fields {
+      let field_name = self.decode_name()?;
+      let mut versions = self.decode_versions()?;
+      // NOTE: Fields may be added to the convention later, so unknown ones are skipped.
+      match field_name.as_str() {
+        "language" => producers.language.append(&mut versions),
+        "processed-by" => producers.processed_by.append(&mut versions),
+        "sdk" => producers.sdk.append(&mut versions),
+        _ => {}
+      }
+    }
+    Ok(producers)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn decode_producers() {
+    let bytes = vec![
+      0x03, // count of fields
+      0x08, 0x6c, 0x61, 0x6e, 0x67, 0x75, 0x61, 0x67, 0x65, // "language"
+      0x01, 0x04, 0x52, 0x75, 0x73, 0x74, 0x00, // "Rust" ""
+      0x0c, 0x70, 0x72, 0x6f, 0x63, 0x65, 0x73, 0x73, 0x65, 0x64, 0x2d, 0x62,
+      0x79, // processed-by
+      0x01, 0x05, 0x72, 0x75, 0x73, 0x74, 0x63, // "rustc"
+      0x04, 0x31, 0x2e, 0x33, 0x31, // "1.31"
+      0x03, 0x66, 0x6f, 0x6f, // "foo"
+      0x01, 0x01, 0x61, 0x01, 0x62, // "a" "b"
+    ];
+    assert_eq!(
+      Section::new(bytes).decode(),
+      Ok(Producers {
+        language: vec![ProducerVersion {
+          name: "Rust".to_owned(),
+          version: "".to_owned(),
+        }],
+        processed_by: vec![ProducerVersion {
+          name: "rustc".to_owned(),
+          version: "1.31".to_owned(),
+        }],
+        sdk: vec![],
+      })
+    );
+  }
+}
@@ -4,19 +4,88 @@ use alloc::vec::Vec;
 use error::Result;
 use memory::Limit;
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
+/// Type of operands which index a table. Tables are indexed by u64 internally regardless of it,
+/// and it bounds their sizes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableIndexType {
+  I32,
+  /// Not decoded until table64 of memory64 proposal is supported.
+  I64,
+}
+
+impl TableIndexType {
+  /// Maximum count of elements which indices of the type can address.
+  pub fn max_size(&self) -> u64 {
+    match self {
+      TableIndexType::I32 => u64::from(u32::max_value()) + 1,
+      TableIndexType::I64 => u64::max_value(),
+    }
+  }
+}
+
+impl Default for TableIndexType {
+  fn default() -> Self {
+    TableIndexType::I32
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct TableType {
   element_type: ElementType,
   pub(crate) limit: Limit,
+  index_type: TableIndexType,
 }
 
 impl TableType {
   pub fn new(element_type: ElementType, limit: Limit) -> Self {
+    TableType::new_with_index_type(element_type, limit, TableIndexType::default())
+  }
+
+  pub fn new_with_index_type(
+    element_type: ElementType,
+    limit: Limit,
+    index_type: TableIndexType,
+  ) -> Self {
     TableType {
       element_type,
       limit,
+      index_type,
     }
   }
+
+  pub(crate) fn with_min(&self, min: u32) -> TableType {
+    TableType::new_with_index_type(
+      self.element_type.clone(),
+      self.limit.with_min(min),
+      self.index_type.clone(),
+    )
+  }
+
+  pub fn index_type(&self) -> &TableIndexType {
+    &self.index_type
+  }
+
+  /// Count of elements which the table has initially.
+  pub fn min(&self) -> u64 {
+    match self.limit {
+      Limit::NoUpperLimit(min) | Limit::HasUpperLimit(min, _) => u64::from(min),
+    }
+  }
+
+  /// Count of elements which the table can grow up to, bounded by its index type.
+  pub fn max(&self) -> u64 {
+    match self.limit {
+      Limit::HasUpperLimit(_, max) => u64::from(max),
+      Limit::NoUpperLimit(_) => self.index_type.max_size(),
+    }
+  }
+
+  /// Whether a table of `self` can be imported as `expected`, see `Limit::matches`.
+  pub fn matches(&self, expected: &TableType) -> bool {
+    self.element_type == expected.element_type
+      && self.index_type == expected.index_type
+      && self.limit.matches(&expected.limit)
+  }
 }
 
 impl_decodable!(Section);
@@ -1,22 +1,38 @@
+use super::decodable::Decodable;
+use super::instruction::InstructionOffsets;
+use super::sec_code::RawBody;
+use super::sec_debug_line;
+use super::sec_dylink::{self, Dylink};
 use super::sec_element::Element;
+use super::sec_producers;
 use super::sec_table::TableType;
 use super::Data;
-#[cfg(not(test))]
+#[cfg(not(any(test, feature = "std")))]
 use alloc::prelude::*;
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::convert::TryFrom;
 use core::default::Default;
-use error::{Result, Trap, WasmError};
-use function::{FunctionInstance, FunctionType};
+use core::ops::Range;
+use error::{Result, Trap, TypeError, WasmError};
+use features::Features;
+use function::{
+  BodyDecoding, FunctionInstance, FunctionType, InstructionBuffer, InstructionFusion,
+};
 use global::{GlobalInstances, GlobalType};
 use memory::{Limit, MemoryInstance, MemoryInstances};
+use memory_backend::MemoryStrategy;
 use module::{
-  ExternalInterface, ExternalInterfaces, ExternalModules, InternalModule, FUNCTION_DESCRIPTOR,
-  GLOBAL_DESCRIPTOR, MEMORY_DESCRIPTOR, TABLE_DESCRIPTOR,
+  ExternalInterface, ExternalInterfaces, ExternalModules, ImportDescriptor, InternalModule,
+  ModuleDescriptor, FUNCTION_DESCRIPTOR, GLOBAL_DESCRIPTOR, MEMORY_DESCRIPTOR, TABLE_DESCRIPTOR,
 };
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use stack_usage::StackUsage;
 use store::Store;
-use table::{TableInstance, TableInstances};
+use table::{SegmentInitialization, TableInstance, TableInstances};
+#[cfg(feature = "checked-interp")]
+use validate::Context;
 use value_type::ValueTypes;
 
 #[derive(Debug, PartialEq, Clone)]
@@ -33,6 +49,7 @@ pub enum SectionCode {
   Element,
   Code,
   Data,
+  DataCount,
 }
 
 impl TryFrom<Option<u8>> for SectionCode {
@@ -52,17 +69,44 @@ impl TryFrom<Option<u8>> for SectionCode {
       Some(0x9) => Ok(Element),
       Some(0xa) => Ok(Code),
       Some(0xb) => Ok(Data),
+      Some(0xc) => Ok(DataCount),
       _ => Err(Trap::InvalidSectionId),
     }
   }
 }
 
-#[derive(Debug)]
+impl SectionCode {
+  // NOTE: Sections except custom section must appear in this order at most once.
+  // DataCount section is placed between Element and Code section.
+  pub(crate) fn order(&self) -> Option<u8> {
+    use self::SectionCode::*;
+    match self {
+      Custom => None,
+      Type => Some(1),
+      Import => Some(2),
+      Function => Some(3),
+      Table => Some(4),
+      Memory => Some(5),
+      Global => Some(6),
+      Export => Some(7),
+      Start => Some(8),
+      Element => Some(9),
+      DataCount => Some(10),
+      Code => Some(11),
+      Data => Some(12),
+    }
+  }
+}
+
+// Instructions in binary format and local variables of a function.
+pub(crate) type Code = (RawBody, Vec<ValueTypes>);
+
+#[derive(Debug, Clone)]
 pub struct Module {
   pub(crate) function_types: Vec<FunctionType>,
   pub(crate) functions: Vec<u32>,
   pub(crate) exports: ExternalInterfaces,
-  pub(crate) codes: Vec<Result<(Vec<u8>, Vec<ValueTypes>)>>,
+  pub(crate) codes: Vec<Result<Code>>,
   pub(crate) datas: Vec<Data>,
   pub(crate) limits: Vec<Limit>,
   pub(crate) tables: Vec<TableType>,
@@ -71,6 +115,14 @@ pub struct Module {
   pub(crate) customs: Vec<(String, Vec<u8>)>,
   pub(crate) imports: ExternalInterfaces,
   pub(crate) start: Option<u32>,
+  pub(crate) data_count: Option<u32>,
+  // NOTE: Recorded only when the module is validated by `validate_module_with_limits`.
+  pub(crate) stack_usages: Option<Vec<StackUsage>>,
+  // NOTE: Proposals which the module is validated with, all of them are disabled unless it's told by
+  // `Module::features` or `validate_module_with_limits`.
+  pub(crate) features: Features,
+  // NOTE: Ranges of sections in the binary which the module is decoded from, paired with offsets of their payloads.
+  pub(crate) boundaries: Vec<(SectionCode, Range<usize>, usize)>,
 }
 
 impl Default for Module {
@@ -88,6 +140,10 @@ impl Default for Module {
       customs: vec![],
       imports: ExternalInterfaces::default(),
       start: None,
+      data_count: None,
+      stack_usages: None,
+      features: Features::default(),
+      boundaries: vec![],
     }
   }
 }
@@ -104,7 +160,7 @@ macro_rules! impl_builder {
 impl Module {
   impl_builder!(function_types, function_types, FunctionType);
   impl_builder!(functions, functions, u32);
-  impl_builder!(codes, codes, Result<(Vec<u8>, Vec<ValueTypes>)>);
+  impl_builder!(codes, codes, Result<Code>);
   impl_builder!(datas, datas, Data);
   impl_builder!(limits, limits, Limit);
   impl_builder!(tables, tables, TableType);
@@ -127,6 +183,64 @@ impl Module {
     self
   }
 
+  pub fn data_count(&mut self, x: u32) -> &mut Self {
+    self.data_count = Some(x);
+    self
+  }
+
+  /// Proposals which the module uses, static checks at instantiation accept them as validation does.
+  pub fn features(&mut self, x: Features) -> &mut Self {
+    self.features = x;
+    self
+  }
+
+  /// Names of exports in the order declared in the binary.
+  pub fn export_names(&self) -> Vec<&str> {
+    self.exports.names()
+  }
+
+  /// Pairs of module name and name of imports in the order declared in the binary.
+  pub fn import_names(&self) -> Vec<(&str, &str)> {
+    self
+      .imports
+      .iter()
+      .map(|import| {
+        let module_name = import.module_name.as_ref().map_or("", |name| name.as_str());
+        (module_name, import.name.as_str())
+      })
+      .collect()
+  }
+
+  /// Fields of `dylink` section, which only side modules of dynamic linking have.
+  /// Malformed one is ignored as same as other custom sections.
+  pub fn dylink(&self) -> Option<Dylink> {
+    self
+      .customs
+      .iter()
+      .find(|(name, _)| name == "dylink")
+      .and_then(|(_, bytes)| sec_dylink::Section::new(bytes.clone()).decode().ok())
+  }
+
+  /// Sections in the order of the binary including custom ones, each of which is paired with its range
+  /// in the binary from the section id to the end, and its payload.
+  /// Sections of unknown ids aren't listed, since decoding rejects them by `InvalidSectionId`.
+  ///
+  /// `bytes` is the binary which the module is decoded from, so that the module doesn't keep a copy of it.
+  /// Panics if it's shorter than the decoded one.
+  pub fn sections<'a>(&self, bytes: &'a [u8]) -> Vec<(SectionCode, Range<usize>, &'a [u8])> {
+    self
+      .boundaries
+      .iter()
+      .map(|(code, range, payload_start)| {
+        (
+          code.clone(),
+          range.clone(),
+          &bytes[*payload_start..range.end],
+        )
+      })
+      .collect()
+  }
+
   fn validate_memory(
     datas: &[Data],
     limits: &[Limit],
@@ -185,6 +299,23 @@ impl Module {
     }
   }
 
+  // NOTE: Every import is matched against its type before any segment is written to shared instances.
+  fn match_imports(
+    imports_table: &[ExternalInterface],
+    imports_memory: &[ExternalInterface],
+    external_modules: &ExternalModules,
+  ) -> Result<()> {
+    for import in imports_table.iter() {
+      external_modules.find_table_instances(import)?;
+    }
+    for import in imports_memory.iter() {
+      external_modules
+        .find_memory_instances(import)?
+        .match_import(import)?;
+    }
+    Ok(())
+  }
+
   fn memory_instances(
     datas: Vec<Data>,
     limits: &[Limit],
@@ -192,6 +323,7 @@ impl Module {
     imports: &[ExternalInterface],
     external_modules: &ExternalModules,
     global_instances: &GlobalInstances,
+    strategy: &MemoryStrategy,
   ) -> Result<MemoryInstances> {
     // NOTE: Currently WASM specification assumed only one memory instance;
     let memory_idx = 0;
@@ -210,6 +342,7 @@ impl Module {
           limit.to_owned(),
           export_name,
           global_instances,
+          strategy,
         )?]));
       }
     }
@@ -267,38 +400,153 @@ impl Module {
     }
   }
 
-  fn function_type(idx: usize, function_types: &[FunctionType]) -> FunctionType {
+  /// Count of imports whose descriptors match, e.g. to know where defined ones start in an index space.
+  pub(crate) fn count_of_imports(
+    imports: &ExternalInterfaces,
+    matches: fn(&ImportDescriptor) -> bool,
+  ) -> u32 {
+    imports
+      .iter()
+      .filter(|import| match &import.descriptor {
+        ModuleDescriptor::ImportDescriptor(descriptor) => matches(descriptor),
+        _ => false,
+      })
+      .count() as u32
+  }
+
+  /// Type which is defined at `type_idx` of type section.
+  pub(crate) fn type_of(function_types: &[FunctionType], type_idx: u32) -> Result<&FunctionType> {
     function_types
-      .get(idx)
-      .expect("Function type can't found.")
-      .to_owned()
+      .get(type_idx as usize)
+      .ok_or(WasmError::TypeError(TypeError::UnknownFunctionType(
+        type_idx,
+      )))
   }
 
+  /// Type of function which is defined at `func_idx` of function section.
+  /// Note that index of function section doesn't count imported functions.
+  pub(crate) fn function_type_of<'a>(
+    function_types: &'a [FunctionType],
+    functions: &[u32],
+    func_idx: u32,
+  ) -> Result<&'a FunctionType> {
+    let type_idx = functions
+      .get(func_idx as usize)
+      .ok_or(WasmError::TypeError(TypeError::UnknownFunction(func_idx)))?;
+    Module::type_of(function_types, *type_idx)
+  }
+
+  fn flatten_code(
+    code: &mut Result<Code>,
+    fusion: &InstructionFusion,
+  ) -> Result<(Vec<u8>, InstructionOffsets)> {
+    let (mut expressions, offsets) = match code {
+      Ok((body, _)) => body.flatten_in_place()?,
+      Err(err) => return Err(err.to_owned()),
+    };
+    fusion.apply(&mut expressions);
+    Ok((expressions, offsets))
+  }
+
+  #[cfg(not(feature = "parallel"))]
+  fn flatten_codes(
+    codes: &mut [Result<Code>],
+    fusion: &InstructionFusion,
+  ) -> Vec<Result<(Vec<u8>, InstructionOffsets)>> {
+    codes
+      .iter_mut()
+      .map(|code| Module::flatten_code(code, fusion))
+      .collect()
+  }
+
+  // NOTE: Bodies are already split by their declared sizes, so each of them is flattened independently.
+  #[cfg(feature = "parallel")]
+  fn flatten_codes(
+    codes: &mut [Result<Code>],
+    fusion: &InstructionFusion,
+  ) -> Vec<Result<(Vec<u8>, InstructionOffsets)>> {
+    codes
+      .par_iter_mut()
+      .map(|code| Module::flatten_code(code, fusion))
+      .collect()
+  }
+
+  fn pack_codes(
+    flattened_codes: Vec<Result<(Vec<u8>, InstructionOffsets)>>,
+  ) -> Vec<Result<(InstructionBuffer, InstructionOffsets)>> {
+    let mut bodies = vec![];
+    let offsets = flattened_codes
+      .into_iter()
+      .map(|code| match code {
+        Ok((expressions, offsets)) => {
+          bodies.push(expressions);
+          Ok(offsets)
+        }
+        Err(err) => {
+          bodies.push(vec![]);
+          Err(err)
+        }
+      })
+      .collect::<Vec<_>>();
+    offsets
+      .into_iter()
+      .zip(InstructionBuffer::pack(bodies).into_iter())
+      .map(|(offsets, buffer)| offsets.map(|offsets| (buffer, offsets)))
+      .collect()
+  }
+
+  // NOTE: Imported functions occupy lower indices of function index space,
+  // so index of defined function starts from count of imports.
   fn function_instances(
     function_types: &[FunctionType],
     functions: &[u32],
     exports: &ExternalInterfaces,
-    codes: Vec<Result<(Vec<u8>, Vec<ValueTypes>)>>,
+    mut codes: Vec<Result<Code>>,
+    count_of_imports: usize,
+    body_decoding: &BodyDecoding,
+    fusion: &InstructionFusion,
+    stack_usages: &Option<Vec<StackUsage>>,
   ) -> Result<Vec<FunctionInstance>> {
+    let flattened_codes = match body_decoding {
+      BodyDecoding::Eager => Module::pack_codes(Module::flatten_codes(&mut codes, fusion))
+        .into_iter()
+        .map(Some)
+        .collect::<Vec<_>>(),
+      BodyDecoding::Lazy => codes.iter().map(|_| None).collect::<Vec<_>>(),
+    };
     codes
       .into_iter()
+      .zip(flattened_codes.into_iter())
       .enumerate()
-      .map(|(idx, code)| {
+      .map(|(idx, (code, flattened_code))| {
         let export_name = exports
-          .find_kind_by_idx(idx as u32, &FUNCTION_DESCRIPTOR)
+          .find_kind_by_idx((count_of_imports + idx) as u32, &FUNCTION_DESCRIPTOR)
           .map(|x| x.name.to_owned());
-        let index_of_type = match functions.get(idx) {
-          Some(n) => *n,
-          None => return Err(WasmError::Trap(Trap::FunctionAndCodeInconsitent)),
+        if functions.len() <= idx {
+          return Err(WasmError::Trap(Trap::FunctionAndCodeInconsitent));
+        }
+        let function_type =
+          Module::function_type_of(function_types, functions, idx as u32)?.to_owned();
+        let (body, locals) = code?;
+        let function_instance = match flattened_code {
+          Some(flattened_code) => {
+            let (instructions, offsets) = flattened_code?;
+            FunctionInstance::new_with_offsets(
+              export_name,
+              function_type,
+              locals,
+              instructions,
+              offsets,
+            )
+          }
+          None => {
+            FunctionInstance::new_lazy(export_name, function_type, locals, body, fusion.to_owned())
+          }
         };
-        let function_type = Module::function_type(index_of_type as usize, function_types);
-        let (expressions, locals) = code?;
-        Ok(FunctionInstance::new(
-          export_name,
-          function_type,
-          locals,
-          expressions,
-        ))
+        if let Some(stack_usage) = stack_usages.as_ref().and_then(|usages| usages.get(idx)) {
+          function_instance.set_stack_usage(*stack_usage);
+        }
+        Ok(function_instance)
       })
       .collect::<Result<Vec<_>>>()
   }
@@ -319,6 +567,11 @@ impl Module {
     external_modules: &ExternalModules,
     store: &mut Store,
   ) -> Result<InternalModule> {
+    // NOTE: Modules failed to validate are just not checked.
+    #[cfg(feature = "checked-interp")]
+    let type_streams = Context::new(&self, self.features.to_owned())
+      .and_then(|context| context.type_streams())
+      .ok();
     match self {
       Module {
         function_types,
@@ -332,6 +585,8 @@ impl Module {
         globals,
         imports,
         start,
+        customs,
+        stack_usages,
         ..
       } => {
         let grouped_imports = imports.group_by_kind()?;
@@ -340,8 +595,27 @@ impl Module {
         let imports_memory = grouped_imports.get(&MEMORY_DESCRIPTOR)?;
         let imports_global = grouped_imports.get(&GLOBAL_DESCRIPTOR)?;
 
-        let mut internal_function_instances =
-          Module::function_instances(&function_types, &functions, &exports, codes)?;
+        let mut internal_function_instances = Module::function_instances(
+          &function_types,
+          &functions,
+          &exports,
+          codes,
+          imports_function.len(),
+          &store.body_decoding,
+          &store.instruction_fusion,
+          &stack_usages,
+        )?;
+        #[cfg(feature = "checked-interp")]
+        {
+          if let Some(type_streams) = type_streams {
+            for (function_instance, type_stream) in internal_function_instances
+              .iter()
+              .zip(type_streams.into_iter())
+            {
+              function_instance.set_type_stream(type_stream);
+            }
+          }
+        }
 
         let mut function_instances = Module::external_function_instances(
           &function_types,
@@ -358,15 +632,18 @@ impl Module {
           &external_modules,
         )?;
 
+        Module::match_imports(&imports_table, &imports_memory, &external_modules)?;
+
         // TODO: Move to context mod.
-        let (validate_memory, validate_table) = (
+        let is_atomic = store.segment_initialization == SegmentInitialization::Atomic;
+        if is_atomic {
           Module::validate_memory(
             &datas,
             &limits,
             &imports_memory,
             &external_modules,
             &global_instances,
-          ),
+          )?;
           Module::validate_table(
             &elements,
             &tables,
@@ -374,10 +651,28 @@ impl Module {
             &external_modules,
             &global_instances,
             &function_instances,
-          ),
-        );
-        validate_memory?;
-        validate_table?;
+          )?;
+        }
+
+        let mut table_instances = None;
+        if !is_atomic {
+          table_instances = Some(Module::table_instances(
+            &elements,
+            tables.clone(),
+            &exports,
+            &imports_table,
+            &external_modules,
+            &global_instances,
+            &function_instances,
+          )?);
+          Module::validate_memory(
+            &datas,
+            &limits,
+            &imports_memory,
+            &external_modules,
+            &global_instances,
+          )?;
+        }
 
         let memory_instances = Module::memory_instances(
           datas,
@@ -386,24 +681,42 @@ impl Module {
           &imports_memory,
           &external_modules,
           &global_instances,
+          &store.memory_strategy,
         )?;
 
-        let mut table_instances = Module::table_instances(
-          &elements,
-          tables,
-          &exports,
-          &imports_table,
-          &external_modules,
-          &global_instances,
-          &function_instances,
-        )?;
+        let table_instances = match table_instances {
+          Some(table_instances) => table_instances,
+          None => Module::table_instances(
+            &elements,
+            tables,
+            &exports,
+            &imports_table,
+            &external_modules,
+            &global_instances,
+            &function_instances,
+          )?,
+        };
 
         store.function_instances = function_instances;
         store.function_types = function_types;
         store.memory_instances = memory_instances;
         store.table_instances = table_instances;
         store.global_instances = global_instances;
-        let internal_module = InternalModule::new(exports, start);
+        // NOTE: Debug information is optional, so malformed one is just ignored.
+        let mut debug_lines = None;
+        let mut producers = None;
+        for (name, bytes) in customs.into_iter() {
+          match name.as_str() {
+            ".debug_line" if debug_lines.is_none() => {
+              debug_lines = sec_debug_line::Section::new(bytes).decode().ok()
+            }
+            "producers" if producers.is_none() => {
+              producers = sec_producers::Section::new(bytes).decode().ok()
+            }
+            _ => {}
+          }
+        }
+        let internal_module = InternalModule::new(exports, start, debug_lines, producers);
         Ok(internal_module)
       }
     }
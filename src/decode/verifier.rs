@@ -0,0 +1,20 @@
+use alloc::string::String;
+
+/// Checks of a host which veto modules before they're decoded, e.g. by signatures, checksums of data segments
+/// or allowlists of imports. A veto is returned as `WasmError::Rejected` with its reason.
+pub trait Verifier {
+  /// Called with the whole binary, before any section is verified.
+  fn verify_module(&self, _bytes: &[u8]) -> core::result::Result<(), String> {
+    Ok(())
+  }
+
+  /// Called with id and payload of each section in order of the binary, before the section is decoded.
+  fn verify_section(&self, _id: u8, _payload: &[u8]) -> core::result::Result<(), String> {
+    Ok(())
+  }
+}
+
+// NOTE: Decoders without a verifier pass this one.
+pub(crate) struct AcceptAll;
+
+impl Verifier for AcceptAll {}
@@ -0,0 +1,188 @@
+use alloc::vec::Vec;
+use decode::Element;
+use instructions::Instructions;
+use isa::Isa;
+use module::{ExportDescriptor, ExternalInterfaces, ModuleDescriptor};
+
+// NOTE: Larger than compilers emit for a switch of reasonable size.
+const HUGE_BR_TABLE_TARGETS: usize = 1024;
+
+/// Suspicious but legal construct found while validating a module.
+/// Functions are referred by indices in function index space, which imported functions occupy first.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+  /// Alignment hint of a memory access is less than natural alignment of the access,
+  /// both of them are exponents of 2 as they're encoded.
+  UnderalignedAccess {
+    function: u32,
+    mnemonic: &'static str,
+    align: u32,
+    natural: u32,
+  },
+  /// A defined function is neither exported, called, referred by element segments nor the start function.
+  UnusedFunction(u32),
+  /// A br_table has more than 1024 labels, which includes its default label.
+  HugeBrTable {
+    function: u32,
+    count_of_targets: usize,
+  },
+}
+
+fn read_u32(body: &[u8], ptr: usize) -> u32 {
+  let mut buf = [0; 4];
+  buf.clone_from_slice(&body[ptr..(ptr + 4)]);
+  unsafe { core::mem::transmute::<_, u32>(buf) }
+}
+
+fn natural_alignment(code: u8) -> Option<u32> {
+  use self::Isa::*;
+  match Isa::from(code) {
+    I32Load8Sign | I32Load8Unsign | I64Load8Sign | I64Load8Unsign | I32Store8 | I64Store8 => {
+      Some(0)
+    }
+    I32Load16Sign | I32Load16Unsign | I64Load16Sign | I64Load16Unsign | I32Store16 | I64Store16 => {
+      Some(1)
+    }
+    I32Load | F32Load | I64Load32Sign | I64Load32Unsign | I32Store | F32Store | I64Store32 => {
+      Some(2)
+    }
+    I64Load | F64Load | I64Store | F64Store => Some(3),
+    _ => None,
+  }
+}
+
+/// Functions, globals and memory which a function uses.
+#[derive(Debug, Default)]
+pub(crate) struct BodyUses {
+  pub callees: Vec<u32>,
+  pub written_globals: Vec<u32>,
+  pub accesses_memory: bool,
+}
+
+/// Warnings of a flattened body of a valid function, and what it uses.
+pub(crate) fn diagnose_body(function: u32, body: &[u8], warnings: &mut Vec<Warning>) -> BodyUses {
+  let mut uses = BodyUses::default();
+  for (offset, info, _) in Instructions::new(body) {
+    if let Some(natural) = natural_alignment(info.code) {
+      uses.accesses_memory = true;
+      let align = read_u32(body, offset + 1);
+      if align < natural {
+        warnings.push(Warning::UnderalignedAccess {
+          function,
+          mnemonic: info.mnemonic,
+          align,
+          natural,
+        });
+      }
+      continue;
+    }
+    match Isa::from(info.code) {
+      Isa::Call => uses.callees.push(read_u32(body, offset + 1)),
+      Isa::SetGlobal => uses.written_globals.push(read_u32(body, offset + 1)),
+      Isa::MemorySize | Isa::MemoryGrow => uses.accesses_memory = true,
+      Isa::BrTable => {
+        let count_of_targets = read_u32(body, offset + 1) as usize + 1;
+        if count_of_targets > HUGE_BR_TABLE_TARGETS {
+          warnings.push(Warning::HugeBrTable {
+            function,
+            count_of_targets,
+          });
+        }
+      }
+      _ => {}
+    }
+  }
+  uses
+}
+
+/// Calls between defined functions, and functions which exports, the start function and element segments refer.
+/// Functions are indexed in function index space, and calls of imported functions are ignored.
+pub(crate) struct CallGraph {
+  count_of_imports: u32,
+  callees: Vec<Vec<u32>>,
+  roots: Vec<u32>,
+}
+
+impl CallGraph {
+  pub(crate) fn new(
+    count_of_imports: u32,
+    uses: &[BodyUses],
+    exports: &ExternalInterfaces,
+    start: &Option<u32>,
+    elements: &[Element],
+  ) -> Self {
+    let roots = exports
+      .iter()
+      .filter_map(|export| match &export.descriptor {
+        ModuleDescriptor::ExportDescriptor(ExportDescriptor::Function(idx)) => Some(idx.to_u32()),
+        _ => None,
+      })
+      .chain(start.iter().cloned())
+      .chain(
+        elements
+          .iter()
+          .flat_map(|element| element.init.iter().map(|idx| idx.to_u32())),
+      )
+      .collect();
+    CallGraph {
+      count_of_imports,
+      callees: uses.iter().map(|uses| uses.callees.clone()).collect(),
+      roots,
+    }
+  }
+
+  fn defined(&self, function: u32) -> Option<usize> {
+    let idx = function.checked_sub(self.count_of_imports)? as usize;
+    if idx < self.callees.len() {
+      Some(idx)
+    } else {
+      None
+    }
+  }
+
+  // Indices of defined functions which aren't marked in `referred`.
+  fn unreferred(&self, referred: Vec<bool>) -> Vec<u32> {
+    referred
+      .into_iter()
+      .enumerate()
+      .filter(|(_, referred)| !referred)
+      .map(|(idx, _)| self.count_of_imports + idx as u32)
+      .collect()
+  }
+
+  /// Defined functions which neither roots nor other functions refer, so recursive calls don't make them used.
+  pub(crate) fn unused(&self) -> Vec<u32> {
+    let mut used = vec![false; self.callees.len()];
+    for function in self.roots.iter() {
+      if let Some(idx) = self.defined(*function) {
+        used[idx] = true;
+      }
+    }
+    for (caller, callees) in self.callees.iter().enumerate() {
+      for idx in callees.iter().filter_map(|callee| self.defined(*callee)) {
+        if idx != caller {
+          used[idx] = true;
+        }
+      }
+    }
+    self.unreferred(used)
+  }
+
+  /// Defined functions which aren't reachable from roots even through calls.
+  pub(crate) fn unreachable(&self) -> Vec<u32> {
+    let mut reachable = vec![false; self.callees.len()];
+    let mut pending = self.roots.clone();
+    while let Some(function) = pending.pop() {
+      let idx = match self.defined(function) {
+        Some(idx) => idx,
+        None => continue,
+      };
+      if reachable[idx] {
+        continue;
+      }
+      reachable[idx] = true;
+      pending.extend(self.callees[idx].iter().cloned());
+    }
+    self.unreferred(reachable)
+  }
+}
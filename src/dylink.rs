@@ -0,0 +1,97 @@
+#[cfg(not(any(test, feature = "std")))]
+use alloc::prelude::*;
+use decode::Dylink;
+use error::{Result, Trap};
+use global::{GlobalInstance, GlobalType};
+use memory::PAGE_SIZE;
+use module::ExternalModule;
+use value::Values;
+use value_type::ValueTypes;
+
+fn align_to(value: u64, alignment: u32) -> Result<u64> {
+  if alignment >= 32 {
+    return Err(Trap::FailToGrow.into());
+  }
+  let mask = (1 << alignment) - 1;
+  Ok((value + mask) & !mask)
+}
+
+fn to_u32(value: u64) -> Result<u32> {
+  if value > u64::from(u32::max_value()) {
+    return Err(Trap::FailToGrow.into());
+  }
+  Ok(value as u32)
+}
+
+/// Memory and table of a main module, which side modules are loaded into by the dynamic linking convention
+/// of emscripten, e.g. to load MAIN_MODULE and SIDE_MODULE pairs.
+///
+/// A side module imports memory, table, `__memory_base` and `__table_base` from "env",
+/// and its data and element segments are offset by the bases, so that it occupies regions allocated for it.
+#[derive(Debug)]
+pub struct DynamicLinking {
+  main: ExternalModule,
+  memory_end: u32,
+}
+
+impl DynamicLinking {
+  /// `main` is exports of the main module, e.g. by `ModuleInstance::export_module`, which include its memory and table.
+  /// Memory is allocated from `heap_base`, which the main module doesn't use, e.g. value of its `__heap_base`.
+  pub fn new(main: ExternalModule, heap_base: u32) -> Self {
+    DynamicLinking {
+      main,
+      memory_end: heap_base,
+    }
+  }
+
+  /// Allocate `size` bytes aligned by 2^`alignment` after formerly allocated regions, and returns the address.
+  /// Memory grows when the region doesn't fit in it, and traps by `FailToGrow` if it can't.
+  pub fn allocate_memory(&mut self, size: u32, alignment: u32) -> Result<u32> {
+    let memory_instances = &self.main.memory_instances;
+    if memory_instances.is_empty() {
+      return Err(Trap::UnknownImport.into());
+    }
+    let base = align_to(u64::from(self.memory_end), alignment)?;
+    let end = base + u64::from(size);
+    let page_size = u64::from(PAGE_SIZE);
+    let current_size = u64::from(memory_instances.size_by_pages()) * page_size;
+    if end > current_size {
+      let increase_pages = (end - current_size + page_size - 1) / page_size;
+      memory_instances.memory_grow(to_u32(increase_pages)?)?;
+    }
+    self.memory_end = to_u32(end)?;
+    to_u32(base)
+  }
+
+  /// Append `size` uninitialized elements aligned by 2^`alignment` to the table,
+  /// and returns the index of the first one. Traps by `FailToGrow` if the table exceeds its maximum.
+  pub fn allocate_table(&mut self, size: u32, alignment: u32) -> Result<u32> {
+    let table_instances = &self.main.table_instances;
+    let len = table_instances.first_size().ok_or(Trap::UnknownImport)?;
+    let base = align_to(len, alignment)?;
+    let end = to_u32(base + u64::from(size))?;
+    table_instances.append_uninitialized(u64::from(end) - len)?;
+    to_u32(base)
+  }
+
+  /// Module which a side module imports from "env", which consists of exports of the main module,
+  /// `__memory_base` and `__table_base` of regions allocated as `dylink` of the side module declares.
+  /// Globals of the bases are added only to the returned module, not to the main module.
+  pub fn side_module_env(&mut self, dylink: &Dylink) -> Result<ExternalModule> {
+    let memory_base = self.allocate_memory(dylink.memory_size, dylink.memory_alignment)?;
+    let table_base = self.allocate_table(dylink.table_size, dylink.table_alignment)?;
+    let base = |name: &str, value: u32| {
+      GlobalInstance::new(
+        GlobalType::Const(ValueTypes::I32),
+        Values::I32(value as i32),
+        Some(name.to_owned()),
+      )
+    };
+    let mut env = self.main.clone();
+    env.global_instances = self.main.global_instances.appended(vec![
+      base("__memory_base", memory_base),
+      base("__table_base", table_base),
+    ]);
+    Ok(env)
+  }
+}
@@ -1,10 +1,15 @@
-#[cfg(not(test))]
+#[cfg(not(any(test, feature = "std")))]
 use alloc::prelude::*;
-use decode::{Byte, Module};
-use error::Result;
+use component::ComponentInstance;
+use decode::{Byte, Component, ComponentByte, DecodeLimits, Module, Verifier};
+use diagnostics::Warning;
+use error::{Result, Trap, WasmError};
+use features::Features;
 use frame::Frame;
+use lint::{self, LintDiagnostic};
 use module::ExternalModules;
 use stack::Stack;
+use stack_usage::StackLimits;
 use store::Store;
 use validate::Context;
 use vm::ModuleInstance;
@@ -17,38 +22,129 @@ pub fn decode_module(bytes: &[u8]) -> Result<Module> {
   Byte::new_with_drop(&bytes)?.decode()
 }
 
+/// Decode a module as same as `decode_module`, and reject it when any of its function bodies exceeds `limits`.
+pub fn decode_module_with_limits(bytes: &[u8], limits: &DecodeLimits) -> Result<Module> {
+  Byte::new_with_drop(&bytes)?.decode_with_limits(limits)
+}
+
+/// Decode a module as same as `decode_module_with_limits`, after `verifier` accepts the whole binary
+/// and each of its sections. Vetoed modules are returned as `WasmError::Rejected`.
+pub fn decode_module_with_verifier(
+  bytes: &[u8],
+  limits: &DecodeLimits,
+  verifier: &Verifier,
+) -> Result<Module> {
+  verifier.verify_module(bytes).map_err(WasmError::Rejected)?;
+  Byte::new_with_drop(&bytes)?.decode_with_verifier(limits, verifier)
+}
+
 pub fn validate_module(module: &Result<Module>) -> Result<()> {
+  validate_module_with_features(module, &Features::default())
+}
+
+/// Validate a module which may use proposals enabled by `features`.
+pub fn validate_module_with_features(module: &Result<Module>, features: &Features) -> Result<()> {
   match module {
-    Ok(module) => Context::new(module)?.validate(),
+    Ok(module) => Context::new(module, features.to_owned())?.validate(),
     Err(err) => Err(err.to_owned()),
   }
 }
 
+/// Validate a module as same as `validate_module_with_features`, and returns warnings of suspicious but legal
+/// constructs in it.
+pub fn validate_module_with_diagnostics(
+  module: &Result<Module>,
+  features: &Features,
+) -> Result<Vec<Warning>> {
+  match module {
+    Ok(module) => Context::new(module, features.to_owned())?.validate_with_diagnostics(),
+    Err(err) => Err(err.to_owned()),
+  }
+}
+
+/// Validate a module as same as `validate_module_with_features`, and returns findings of static checks,
+/// e.g. functions which never run.
+pub fn lint_module(module: &Result<Module>, features: &Features) -> Result<Vec<LintDiagnostic>> {
+  match module {
+    Ok(module) => {
+      let context = Context::new(module, features.to_owned())?;
+      context.validate()?;
+      Ok(lint::check_bodies(module, &context.bodies()))
+    }
+    Err(err) => Err(err.to_owned()),
+  }
+}
+
+/// Validate a module as same as `validate_module_with_features`, and reject it by `StackLimitExceeded`
+/// when any of its functions exceeds `limits`.
+/// Stack usages of functions are recorded on returned module, so that each call reserves its stack space upfront.
+pub fn validate_module_with_limits(
+  module: Result<Module>,
+  features: &Features,
+  limits: &StackLimits,
+) -> Result<Module> {
+  let mut module = module?;
+  let stack_usages = Context::new(&module, features.to_owned())?.validate_with_limits(limits)?;
+  module.stack_usages = Some(stack_usages);
+  module.features(features.to_owned());
+  Ok(module)
+}
+
 pub fn instantiate_module(
   mut store: Store,
   section: Result<Module>, // module: Module(PreVm)
   external_modules: ExternalModules,
   max_stack_height: usize,
 ) -> Result<ModuleInstance> {
+  let module = section?;
+  // NOTE: Memories declared by the module are checked before allocated,
+  // so that a module beyond the limit of engine doesn't make the host allocate them at all.
+  if let Some(engine) = &store.engine {
+    let pages = module
+      .limits
+      .iter()
+      .fold(0u32, |acc, limit| acc.saturating_add(limit.min_pages()));
+    if !engine.can_grow_memory(pages) {
+      return Err(WasmError::Trap(Trap::ResourceLimitExceeded));
+    }
+  }
   // TODO: Return pair of (Store, Vm) by using Rc<Store> type.
-  let internal_module = section?.complete(&external_modules, &mut store)?;
+  let internal_module = module.complete(&external_modules, &mut store)?;
+  let engine_slot = match &store.engine {
+    Some(engine) => Some(engine.admit_instance(&store.memory_instances)?),
+    None => None,
+  };
   let mut vm =
     ModuleInstance::new_from(store, internal_module, external_modules, max_stack_height)?;
+  vm.engine_slot = engine_slot;
   if let Some(idx) = vm.start_index().clone() {
-    let function_instance = vm.get_function_instance(&idx)?;
-    let frame = Frame::new(
-      vm.stack.stack_ptr(),
-      vm.stack.frame_ptr(),
-      function_instance,
-      &mut vec![],
-    );
+    let function_instance = vm.get_function_instance(&idx)?.clone();
+    let frame = Frame::new(vm.stack.activation_record(), function_instance, &mut vec![])?;
     vm.stack.push_frame(frame)?;
     vm.evaluate()?;
+    // NOTE: Start function is left unfinished only when fuel of engine runs out or it's interrupted.
+    if vm.is_interrupted() {
+      return Err(WasmError::Trap(Trap::Interrupted));
+    }
+    if vm.is_out_of_budget() {
+      return Err(WasmError::Trap(Trap::ResourceLimitExceeded));
+    }
     vm.stack = Stack::new(max_stack_height);
   };
   Ok(vm)
 }
 
+pub fn decode_component(bytes: &[u8]) -> Result<Component> {
+  ComponentByte::new_with_drop(&bytes)?.decode()
+}
+
+pub fn instantiate_component(
+  component: Result<Component>,
+  max_stack_height: usize,
+) -> Result<ComponentInstance> {
+  ComponentInstance::new(component?, max_stack_height)
+}
+
 // module_imports(module):(name,name,externtype)∗¶
 // module_exports(module):(name,externtype)∗¶
 // get_export(moduleinst,name):externval | error¶
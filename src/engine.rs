@@ -0,0 +1,404 @@
+#[cfg(not(any(test, feature = "std")))]
+use alloc::prelude::*;
+use alloc::rc::Rc;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use cache::{ModuleCache, ModuleDecoder};
+use core::cell::{Cell, RefCell};
+use core::hash::BuildHasher;
+use core::sync::atomic::{AtomicBool, Ordering};
+use decode::Module;
+use embedder::{decode_module, instantiate_module, validate_module};
+use error::{Result, Trap, WasmError};
+use memory::{MemoryInstances, WeakMemoryInstances};
+use module::ExternalModules;
+use nan_box::{ValueRepresentation, VALUE_REPRESENTATION};
+use store::Store;
+use symbol::SymbolTable;
+use vm::ModuleInstance;
+
+/// Limits shared by all instances of an `Engine`, None means unlimited.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EngineLimits {
+  /// Total pages of living linear memories, a memory imported by several instances is counted once.
+  pub max_memory_pages: Option<u32>,
+  /// Count of living instances.
+  pub max_instances: Option<usize>,
+  /// Count of instructions executed by all instances until `Engine::next_epoch`.
+  pub max_fuel_per_epoch: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct Usage {
+  instances: Cell<usize>,
+  fuel: Cell<u64>,
+  memories: RefCell<Vec<WeakMemoryInstances>>,
+}
+
+impl Usage {
+  fn living_memories(&self) -> Vec<MemoryInstances> {
+    let mut memories = self.memories.borrow_mut();
+    memories.retain(|memory| memory.upgrade().is_some());
+    memories
+      .iter()
+      .filter_map(|memory| memory.upgrade())
+      .collect()
+  }
+
+  fn count_of_memory_pages(&self) -> u32 {
+    self.living_memories().iter().fold(0u32, |acc, memory| {
+      acc.saturating_add(memory.count_of_pages())
+    })
+  }
+}
+
+/// Owner of limits across instances, e.g. when a host process runs modules of many tenants.
+/// Instances instantiated by `Engine::instantiate` share its limits until they are dropped.
+///
+/// - Instantiation traps by `ResourceLimitExceeded` when count of instances or pages of memories exceeds.
+///   Pages declared by the module are checked before its memory is allocated.
+/// - `memory.grow` returns -1 when pages of memories would exceed.
+/// - Invocation traps by `ResourceLimitExceeded` when fuel of current epoch runs out,
+///   or yields when it runs by `ModuleInstance::run_bounded`.
+/// - Names of modules registered to `Engine::external_modules` are interned once by the engine.
+/// - Invocation traps by `Interrupted` once `InterruptHandle::interrupt` is called.
+/// - Modules decoded from bytes by the engine are shared through `ModuleCache` set by `Engine::cache_modules`.
+#[derive(Debug, Clone)]
+pub struct Engine {
+  limits: Rc<EngineLimits>,
+  usage: Rc<Usage>,
+  symbols: SymbolTable,
+  interrupted: InterruptHandle,
+  // NOTE: Shared with groups of the engine.
+  cache: Rc<RefCell<Option<Box<ModuleDecoder>>>>,
+  // NOTE: Engine of a group, whose limits are also enforced.
+  parent: Option<Rc<Engine>>,
+}
+
+impl Engine {
+  pub fn new(limits: EngineLimits) -> Self {
+    Engine {
+      limits: Rc::new(limits),
+      usage: Rc::new(Usage::default()),
+      symbols: SymbolTable::default(),
+      interrupted: InterruptHandle::default(),
+      cache: Rc::new(RefCell::new(None)),
+      parent: None,
+    }
+  }
+
+  /// Group of instances whose collective limits are `limits`, and which are also under limits of the engine.
+  /// Names of modules are interned by the engine.
+  pub fn group(&self, limits: EngineLimits) -> InstanceGroup {
+    InstanceGroup(Engine {
+      limits: Rc::new(limits),
+      usage: Rc::new(Usage::default()),
+      symbols: self.symbols.clone(),
+      interrupted: InterruptHandle::default(),
+      cache: self.cache.clone(),
+      parent: Some(Rc::new(self.clone())),
+    })
+  }
+
+  /// Handle which cancels invocations of all instances of the engine, including ones of its groups.
+  pub fn interrupt_handle(&self) -> InterruptHandle {
+    self.interrupted.clone()
+  }
+
+  pub fn limits(&self) -> &EngineLimits {
+    &self.limits
+  }
+
+  pub fn count_of_instances(&self) -> usize {
+    self.usage.instances.get()
+  }
+
+  pub fn count_of_memory_pages(&self) -> u32 {
+    self.usage.count_of_memory_pages()
+  }
+
+  /// Count of instructions executed in current epoch.
+  pub fn fuel_consumed(&self) -> u64 {
+    self.usage.fuel.get()
+  }
+
+  /// Start next epoch by refilling fuel.
+  pub fn next_epoch(&self) {
+    self.usage.fuel.set(0);
+  }
+
+  /// Representation of values on operand stacks of instances, which "nan-boxing" feature switches.
+  pub fn value_representation(&self) -> ValueRepresentation {
+    VALUE_REPRESENTATION
+  }
+
+  /// Names of modules interned by registries of the engine.
+  pub fn symbols(&self) -> &SymbolTable {
+    &self.symbols
+  }
+
+  /// Registry of modules whose names are interned by the engine, so that they're shared among its instances.
+  pub fn external_modules(&self) -> ExternalModules {
+    ExternalModules::with_symbols(self.symbols.clone())
+  }
+
+  /// Instantiate a module as same as `instantiate_module`, but under limits of the engine.
+  pub fn instantiate(
+    &self,
+    mut store: Store,
+    module: Result<Module>,
+    external_modules: ExternalModules,
+    max_stack_height: usize,
+  ) -> Result<ModuleInstance> {
+    store.engine = Some(self.clone());
+    instantiate_module(store, module, external_modules, max_stack_height)
+  }
+
+  /// Share decoded modules through `cache` from now on, which replaces former one if any.
+  pub fn cache_modules<B: BuildHasher + 'static>(&self, cache: ModuleCache<B>) {
+    self.cache.replace(Some(Box::new(cache)));
+  }
+
+  /// Decode and validate a module, which is shared from the cache of the engine if it has been done before.
+  pub fn decode_module(&self, bytes: &[u8]) -> Result<Rc<Module>> {
+    if let Some(cache) = self.cache.borrow_mut().as_mut() {
+      return cache.get_or_decode(bytes);
+    }
+    let module = decode_module(bytes);
+    validate_module(&module)?;
+    module.map(Rc::new)
+  }
+
+  /// Decode a module by `Engine::decode_module`, and instantiate it by `Engine::instantiate`.
+  /// A cached module is copied for the instance, since instantiation takes over its bodies.
+  pub fn instantiate_bytes(
+    &self,
+    store: Store,
+    bytes: &[u8],
+    external_modules: ExternalModules,
+    max_stack_height: usize,
+  ) -> Result<ModuleInstance> {
+    let module = self
+      .decode_module(bytes)
+      .map(|module| Rc::try_unwrap(module).unwrap_or_else(|module| (*module).clone()));
+    self.instantiate(store, module, external_modules, max_stack_height)
+  }
+
+  // Engine itself followed by engines which it belongs to.
+  fn lineage(&self) -> Lineage {
+    Lineage(Some(self))
+  }
+
+  // NOTE: Memories are registered here, so that growing memory of other instances is also counted.
+  // Limits of all ancestors are checked before registering, so that a rejected instance is counted by none of them.
+  pub(crate) fn admit_instance(&self, memory_instances: &MemoryInstances) -> Result<InstanceSlot> {
+    for engine in self.lineage() {
+      engine.check_admission(memory_instances)?;
+    }
+    let mut usages = vec![];
+    for engine in self.lineage() {
+      engine.register_instance(memory_instances);
+      usages.push(engine.usage.clone());
+    }
+    Ok(InstanceSlot(usages))
+  }
+
+  fn check_admission(&self, memory_instances: &MemoryInstances) -> Result<()> {
+    if let Some(max_instances) = self.limits.max_instances {
+      if self.usage.instances.get() >= max_instances {
+        return Err(WasmError::Trap(Trap::ResourceLimitExceeded));
+      }
+    }
+    if !self.is_registered(memory_instances) {
+      let pages = self
+        .usage
+        .count_of_memory_pages()
+        .saturating_add(memory_instances.count_of_pages());
+      if self.exceeds_memory_pages(pages) {
+        return Err(WasmError::Trap(Trap::ResourceLimitExceeded));
+      }
+    }
+    Ok(())
+  }
+
+  fn register_instance(&self, memory_instances: &MemoryInstances) {
+    if !self.is_registered(memory_instances) {
+      self
+        .usage
+        .memories
+        .borrow_mut()
+        .push(memory_instances.downgrade());
+    }
+    self.usage.instances.set(self.usage.instances.get() + 1);
+  }
+
+  fn is_registered(&self, memory_instances: &MemoryInstances) -> bool {
+    self
+      .usage
+      .living_memories()
+      .iter()
+      .any(|memory| memory.ptr_eq(memory_instances))
+  }
+
+  pub(crate) fn can_grow_memory(&self, increase_pages: u32) -> bool {
+    self.lineage().all(|engine| {
+      let pages = engine
+        .usage
+        .count_of_memory_pages()
+        .saturating_add(increase_pages);
+      !engine.exceeds_memory_pages(pages)
+    })
+  }
+
+  fn exceeds_memory_pages(&self, pages: u32) -> bool {
+    match self.limits.max_memory_pages {
+      Some(max_pages) => pages > max_pages,
+      None => false,
+    }
+  }
+
+  // NOTE: It's checked between frames, so the lineage is walked once for both fuel and interruption.
+  pub(crate) fn is_out_of_fuel(&self) -> bool {
+    self.lineage().any(|engine| {
+      let is_out_of_fuel = match engine.limits.max_fuel_per_epoch {
+        Some(max_fuel) => engine.usage.fuel.get() >= max_fuel,
+        None => false,
+      };
+      is_out_of_fuel || engine.interrupted.is_interrupted()
+    })
+  }
+
+  pub(crate) fn is_interrupted(&self) -> bool {
+    self
+      .lineage()
+      .any(|engine| engine.interrupted.is_interrupted())
+  }
+
+  /// Fuel left in current epoch of the engine and its ancestors, None means unlimited.
+  pub(crate) fn remaining_fuel(&self) -> Option<u64> {
+    self
+      .lineage()
+      .filter_map(|engine| {
+        engine
+          .limits
+          .max_fuel_per_epoch
+          .map(|max_fuel| max_fuel.saturating_sub(engine.usage.fuel.get()))
+      })
+      .min()
+  }
+
+  pub(crate) fn consume_fuel(&self, fuel: u64) {
+    for engine in self.lineage() {
+      let consumed = &engine.usage.fuel;
+      consumed.set(consumed.get().saturating_add(fuel));
+    }
+  }
+}
+
+struct Lineage<'a>(Option<&'a Engine>);
+
+impl<'a> Iterator for Lineage<'a> {
+  type Item = &'a Engine;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let engine = self.0?;
+    self.0 = engine.parent.as_ref().map(|parent| &**parent);
+    Some(engine)
+  }
+}
+
+/// Held by an instance of an engine, and releases its count of the engine and its ancestors
+/// when the instance is dropped.
+#[derive(Debug)]
+pub(crate) struct InstanceSlot(Vec<Rc<Usage>>);
+
+impl Drop for InstanceSlot {
+  fn drop(&mut self) {
+    for usage in self.0.iter() {
+      usage.instances.set(usage.instances.get() - 1);
+    }
+  }
+}
+
+/// Handle to cancel invocations of instances of an engine or a group, e.g. by a watchdog thread,
+/// a host function or between `ModuleInstance::run_bounded`. Clones share the same flag.
+///
+/// Once interrupted, running and later invocations trap by `Interrupted` until `reset`.
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle(Arc<AtomicBool>);
+
+impl InterruptHandle {
+  pub fn interrupt(&self) {
+    self.0.store(true, Ordering::SeqCst);
+  }
+
+  pub fn reset(&self) {
+    self.0.store(false, Ordering::SeqCst);
+  }
+
+  pub fn is_interrupted(&self) -> bool {
+    self.0.load(Ordering::SeqCst)
+  }
+}
+
+/// Instances which are throttled or cancelled as a unit, e.g. cooperating modules of a tenant.
+/// Limits of the group are enforced in addition to ones of the engine which it belongs to,
+/// and its instances are counted by both of them.
+#[derive(Debug, Clone)]
+pub struct InstanceGroup(Engine);
+
+impl InstanceGroup {
+  /// Instantiate a module as same as `Engine::instantiate`, but under limits of the group.
+  pub fn instantiate(
+    &self,
+    store: Store,
+    module: Result<Module>,
+    external_modules: ExternalModules,
+    max_stack_height: usize,
+  ) -> Result<ModuleInstance> {
+    self
+      .0
+      .instantiate(store, module, external_modules, max_stack_height)
+  }
+
+  /// Instantiate a module as same as `Engine::instantiate_bytes`, but under limits of the group.
+  /// The group shares the cache of its engine.
+  pub fn instantiate_bytes(
+    &self,
+    store: Store,
+    bytes: &[u8],
+    external_modules: ExternalModules,
+    max_stack_height: usize,
+  ) -> Result<ModuleInstance> {
+    self
+      .0
+      .instantiate_bytes(store, bytes, external_modules, max_stack_height)
+  }
+
+  pub fn limits(&self) -> &EngineLimits {
+    self.0.limits()
+  }
+
+  pub fn count_of_instances(&self) -> usize {
+    self.0.count_of_instances()
+  }
+
+  pub fn count_of_memory_pages(&self) -> u32 {
+    self.0.count_of_memory_pages()
+  }
+
+  /// Count of instructions executed by instances of the group in its current epoch.
+  pub fn fuel_consumed(&self) -> u64 {
+    self.0.fuel_consumed()
+  }
+
+  /// Refill fuel of the group, but not of the engine.
+  pub fn next_epoch(&self) {
+    self.0.next_epoch()
+  }
+
+  /// Handle which cancels invocations of the group, but not of other instances of the engine.
+  pub fn interrupt_handle(&self) -> InterruptHandle {
+    self.0.interrupt_handle()
+  }
+}
@@ -1,9 +1,13 @@
+use alloc::string::String;
+use alloc::vec::Vec;
 use core::convert::From;
 use core::option::NoneError;
+use function::FunctionType;
+use isa::Proposal;
+use value_type::ValueTypes;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Trap {
-  DivisionOverflow,
   DivisionByZero,
   DataSegmentDoesNotFit,
   ElementSegmentDoesNotFit,
@@ -23,6 +27,8 @@ pub enum Trap {
   FailToGrow,
   UnexpectedEnd,
   InvalidSectionId,
+  UnexpectedSection,
+  DataCountAndDataInconsistent,
   LengthOutofBounds,
   Unreachable,
   UnknownImport,
@@ -34,6 +40,78 @@ pub enum Trap {
   FunctionAndCodeInconsitent,
   InvalidUTF8Encoding,
   LinearMapOverflowed,
+  UnsupportedComponentDefinition,
+  UnsupportedDebugInfo,
+  UnsupportedFeature {
+    proposal: Proposal,
+    opcode: u8,
+  },
+  StaleResumeToken,
+  UnexpectedYield,
+  CircularImport(String),
+  ArgumentTypeMismatch {
+    expected: Vec<ValueTypes>,
+    actual: Vec<ValueTypes>,
+  },
+  HostError(String),
+  /// A limit of `Engine` shared across instances is exceeded.
+  ResourceLimitExceeded,
+  /// A job of `ExecutionService` took longer than its timeout.
+  Timeout,
+  MalformedFloatLiteral,
+  /// A literal is rounded to infinity, or a payload of NaN doesn't fit.
+  ConstantOutOfRange,
+  /// A name of import, export or custom section isn't encoded in UTF-8.
+  /// Offset of the first invalid byte is from the head of the module,
+  /// or of the section for names in components.
+  InvalidUTF8Name {
+    offset: usize,
+  },
+  /// A function body is larger than `DecodeLimits::max_body_size`.
+  FunctionBodyTooLarge,
+  /// A function declares more locals than `DecodeLimits::max_locals`.
+  TooManyLocals,
+  /// Blocks of a function are nested deeper than `DecodeLimits::max_nesting_depth`.
+  NestingTooDeep,
+  /// A br_table has more labels than `DecodeLimits::max_br_table_targets`.
+  TooManyBrTableTargets,
+  /// Else appears outside of If, or If has more than one Else.
+  MisplacedElse,
+  /// An index of local exceeds locals of the current frame, which validation prevents.
+  OutOfRangeLocal,
+  /// Constant expression refers to a global which isn't initialized yet, e.g. itself or a later one.
+  UninitializedGlobal(u32),
+  /// A float instruction appears while the crate is built without "float" feature.
+  UnsupportedFloat {
+    opcode: u8,
+  },
+  /// No module of a script is defined by the name, or no module is defined yet for None.
+  UnknownModule(Option<String>),
+  /// Count of arguments differs from parameters of the function, whose signature is `expected`.
+  ArityMismatch {
+    expected: FunctionType,
+    actual: usize,
+  },
+  /// An invocation passes or returns i64 under `I64Boundary::Reject`.
+  I64AtBoundary,
+  /// A global restored by `ModuleInstance::import_globals` is immutable, or its value has another type.
+  IncompatibleGlobal(String),
+  /// Memory is stored in chunks, e.g. by `MemoryStrategy::Lazy`, so it can't be viewed as a slice.
+  NonContiguousMemory,
+  /// An instruction exceeds a cap of `ExecutionLimits`, e.g. br_table with too many labels.
+  ExecutionLimitExceeded,
+  /// Invocation is cancelled by `InterruptHandle` of an engine or a group.
+  Interrupted,
+  /// Magic header is followed by a version other than 1.
+  UnknownBinaryVersion,
+  /// Guest calls proc_exit of WASI with the exit code, which terminates the invocation.
+  ProcessExit(u32),
+  /// Preamble of a component has a version or a layer other than ones this crate decodes.
+  UnknownComponentVersion,
+  /// A function body has a reserved or unassigned opcode of no proposal.
+  IllegalOpcode {
+    opcode: u8,
+  },
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -56,14 +134,173 @@ pub enum TypeError {
   UnknownTable(u32),
   UnknownGlobal(u32),
   ConstantExpressionRequired,
-  DuplicateExportName,
+  /// Exports of a module must have distinct names.
+  DuplicateExportName(String),
   GlobalIsImmutable,
+  DisabledFeature(Proposal),
+  StackLimitExceeded,
+  DisabledFloat,
+  /// Minimum of limits of a memory or a table is greater than its maximum.
+  InvalidLimits,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum WasmError {
   Trap(Trap),
   TypeError(TypeError),
+  /// A `Verifier` of the host vetoed the module by the reason.
+  Rejected(String),
+  /// Linking failed since no export has the name.
+  /// `similar` are names of exports which might be meant, as `ExportSuggestion` of the store configures.
+  UnknownExport {
+    name: String,
+    similar: Vec<String>,
+  },
+}
+
+// NOTE: Codes are part of public API, so a variant keeps its code once assigned.
+// New variants take the next unused code of its range, Trap from 1 and TypeError from 1001.
+// WasmError::Rejected takes 2001 and WasmError::UnknownExport takes 2002.
+macro_rules! impl_error_codes {
+  ($ty: ident, $table: ident, { $($code: expr => $variant: ident,)* }) => {
+    impl $ty {
+      /// Stable numeric identifier of the variant, e.g. for C API and RPC consumers.
+      pub fn code(&self) -> u32 {
+        match self {
+          $($ty::$variant { .. } => $code,)*
+        }
+      }
+    }
+
+    /// Pairs of stable code and name of variant.
+    pub const $table: &[(u32, &str)] = &[$(($code, stringify!($variant)),)*];
+  };
+}
+
+impl_error_codes!(Trap, TRAP_CODES, {
+  // NOTE: 1 is left unused, overflow of signed division traps with IntegerOverflow.
+  2 => DivisionByZero,
+  3 => DataSegmentDoesNotFit,
+  4 => ElementSegmentDoesNotFit,
+  5 => MemoryAccessOutOfBounds,
+  6 => BitshiftOverflow,
+  7 => IntegerOverflow,
+  8 => InvalidConversionToInt,
+  9 => InvalidMutability,
+  10 => Unknown,
+  11 => StackOverflow,
+  12 => StackUnderflow,
+  13 => Notfound,
+  14 => Undefined,
+  15 => UndefinedElement,
+  16 => TypeMismatch,
+  17 => IndirectCallTypeMismatch,
+  18 => FailToGrow,
+  19 => UnexpectedEnd,
+  20 => InvalidSectionId,
+  21 => UnexpectedSection,
+  22 => DataCountAndDataInconsistent,
+  23 => LengthOutofBounds,
+  24 => Unreachable,
+  25 => UnknownImport,
+  26 => UninitializedElement,
+  27 => IncompatibleImportType,
+  28 => MagicHeaderNotDetected,
+  29 => UnsupportedTextform,
+  30 => IntegerRepresentationTooLong,
+  31 => FunctionAndCodeInconsitent,
+  32 => InvalidUTF8Encoding,
+  33 => LinearMapOverflowed,
+  34 => UnsupportedComponentDefinition,
+  35 => UnsupportedDebugInfo,
+  36 => UnsupportedFeature,
+  37 => StaleResumeToken,
+  38 => UnexpectedYield,
+  39 => CircularImport,
+  40 => ArgumentTypeMismatch,
+  41 => HostError,
+  42 => ResourceLimitExceeded,
+  43 => Timeout,
+  44 => MalformedFloatLiteral,
+  45 => ConstantOutOfRange,
+  46 => InvalidUTF8Name,
+  // NOTE: 47 is left unused, missing exports are reported by WasmError::UnknownExport.
+  48 => FunctionBodyTooLarge,
+  49 => TooManyLocals,
+  50 => NestingTooDeep,
+  51 => TooManyBrTableTargets,
+  52 => MisplacedElse,
+  53 => OutOfRangeLocal,
+  54 => UninitializedGlobal,
+  55 => UnsupportedFloat,
+  56 => UnknownModule,
+  57 => ArityMismatch,
+  58 => I64AtBoundary,
+  59 => IncompatibleGlobal,
+  60 => NonContiguousMemory,
+  61 => ExecutionLimitExceeded,
+  62 => Interrupted,
+  63 => UnknownBinaryVersion,
+  64 => ProcessExit,
+  65 => UnknownComponentVersion,
+  66 => IllegalOpcode,
+});
+
+impl_error_codes!(TypeError, TYPE_ERROR_CODES, {
+  1001 => NotFound,
+  1002 => MultipleTables,
+  1003 => MultipleMemories,
+  1004 => TypeMismatch,
+  1005 => IndirectCallTypeMismatch,
+  1006 => IncompatibleImportType,
+  1007 => InvalidResultArity,
+  1008 => InvalidAlignment,
+  1009 => InvalidMemorySize,
+  1010 => InvalidStartFunction,
+  1011 => UnknownLabel,
+  1012 => UnknownLocal,
+  1013 => UnknownMemory,
+  1014 => UnknownFunctionType,
+  1015 => UnknownFunction,
+  1016 => UnknownTable,
+  1017 => UnknownGlobal,
+  1018 => ConstantExpressionRequired,
+  1019 => DuplicateExportName,
+  1020 => GlobalIsImmutable,
+  1021 => DisabledFeature,
+  1022 => StackLimitExceeded,
+  1023 => DisabledFloat,
+  1024 => InvalidLimits,
+});
+
+const REJECTED_CODE: u32 = 2001;
+const UNKNOWN_EXPORT_CODE: u32 = 2002;
+
+impl WasmError {
+  pub fn code(&self) -> u32 {
+    match self {
+      WasmError::Trap(trap) => trap.code(),
+      WasmError::TypeError(type_error) => type_error.code(),
+      WasmError::Rejected(_) => REJECTED_CODE,
+      WasmError::UnknownExport { .. } => UNKNOWN_EXPORT_CODE,
+    }
+  }
+
+  /// Name of the variant which has `code`.
+  pub fn name_of_code(code: u32) -> Option<&'static str> {
+    TRAP_CODES
+      .iter()
+      .chain(TYPE_ERROR_CODES.iter())
+      .chain(
+        [
+          (REJECTED_CODE, "Rejected"),
+          (UNKNOWN_EXPORT_CODE, "UnknownExport"),
+        ]
+        .iter(),
+      )
+      .find(|(c, _)| *c == code)
+      .map(|(_, name)| *name)
+  }
 }
 
 impl From<WasmError> for NoneError {
@@ -0,0 +1,288 @@
+use decode::Module;
+use embedder::{decode_module, init_store, instantiate_module};
+use error::{Result, Trap, WasmError};
+use module::ExternalModules;
+use resume::BoundedRun;
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use value::Values;
+
+// NOTE: Timeout is checked each time a job executes this count of instructions.
+const INSTRUCTIONS_PER_SLICE: u64 = 10_000;
+
+type ImportsFactory = Arc<Fn() -> ExternalModules + Send + Sync>;
+
+/// Handle of a module registered to `ExecutionService`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModuleHandle(usize);
+
+/// Invocation of an export of a module, run on a worker of `ExecutionService`.
+#[derive(Debug, Clone)]
+pub struct Job {
+  pub module: ModuleHandle,
+  pub export: String,
+  pub arguments: Vec<Values>,
+  /// Count of instructions the job may execute, it traps by `ResourceLimitExceeded` when runs out.
+  pub fuel: Option<u64>,
+  /// Time the job may take, it traps by `Timeout` when elapses.
+  pub timeout: Option<Duration>,
+}
+
+impl Job {
+  pub fn new(module: ModuleHandle, export: &str, arguments: Vec<Values>) -> Self {
+    Job {
+      module,
+      export: export.to_owned(),
+      arguments,
+      fuel: None,
+      timeout: None,
+    }
+  }
+}
+
+/// Receiver of the result of a job.
+#[derive(Debug)]
+pub struct JobHandle(Receiver<Result<Vec<Values>>>);
+
+impl JobHandle {
+  /// Block until the job finishes.
+  pub fn wait(self) -> Result<Vec<Values>> {
+    self
+      .0
+      .recv()
+      .unwrap_or_else(|_| Err(WasmError::Trap(Trap::Unknown)))
+  }
+
+  /// Result of the job if it has finished.
+  pub fn try_wait(&self) -> Option<Result<Vec<Values>>> {
+    match self.0.try_recv() {
+      Ok(result) => Some(result),
+      Err(TryRecvError::Empty) => None,
+      Err(TryRecvError::Disconnected) => Some(Err(WasmError::Trap(Trap::Unknown))),
+    }
+  }
+}
+
+struct Task {
+  job: Job,
+  bytes: Option<Arc<Vec<u8>>>,
+  sender: Sender<Result<Vec<Values>>>,
+}
+
+/// Pool of threads which run jobs concurrently.
+///
+/// Since instances are not shareable across threads, each job runs on a fresh instance of its module,
+/// which a worker instantiates from the module decoded once per worker.
+/// As same as `Linker`, modules are not validated, so embedders should validate them before registration.
+/// Imports of modules are resolved by `ExternalModules` created by each worker.
+pub struct ExecutionService {
+  modules: Vec<Arc<Vec<u8>>>,
+  sender: Option<Sender<Task>>,
+  workers: Vec<JoinHandle<()>>,
+}
+
+impl ExecutionService {
+  pub fn new(count_of_workers: usize, max_stack_height: usize) -> Self {
+    ExecutionService::new_with_imports(
+      count_of_workers,
+      max_stack_height,
+      Arc::new(ExternalModules::default),
+    )
+  }
+
+  /// `imports` is called once per worker to create modules which jobs import from.
+  pub fn new_with_imports(
+    count_of_workers: usize,
+    max_stack_height: usize,
+    imports: ImportsFactory,
+  ) -> Self {
+    let (sender, receiver) = channel::<Task>();
+    let receiver = Arc::new(Mutex::new(receiver));
+    let workers = (0..count_of_workers)
+      .map(|_| {
+        let receiver = receiver.clone();
+        let imports = imports.clone();
+        thread::spawn(move || Worker::new(max_stack_height, imports()).serve(&receiver))
+      })
+      .collect();
+    ExecutionService {
+      modules: vec![],
+      sender: Some(sender),
+      workers,
+    }
+  }
+
+  pub fn register_module(&mut self, bytes: Vec<u8>) -> ModuleHandle {
+    self.modules.push(Arc::new(bytes));
+    ModuleHandle(self.modules.len() - 1)
+  }
+
+  /// Queue a job, whose result is received by returned handle.
+  pub fn submit(&self, job: Job) -> JobHandle {
+    let (sender, receiver) = channel();
+    let task = Task {
+      bytes: self.modules.get(job.module.0).cloned(),
+      job,
+      sender,
+    };
+    // NOTE: Sender is taken only on drop, and workers never hang up before it.
+    if let Some(queue) = &self.sender {
+      let _ = queue.send(task);
+    }
+    JobHandle(receiver)
+  }
+
+  pub fn count_of_workers(&self) -> usize {
+    self.workers.len()
+  }
+}
+
+impl Drop for ExecutionService {
+  /// Wait for queued jobs to finish.
+  fn drop(&mut self) {
+    self.sender.take();
+    for worker in self.workers.drain(..) {
+      let _ = worker.join();
+    }
+  }
+}
+
+struct Worker {
+  max_stack_height: usize,
+  external_modules: ExternalModules,
+  // NOTE: Indexed by handle of module.
+  modules: Vec<Option<Module>>,
+}
+
+impl Worker {
+  fn new(max_stack_height: usize, external_modules: ExternalModules) -> Self {
+    Worker {
+      max_stack_height,
+      external_modules,
+      modules: vec![],
+    }
+  }
+
+  fn serve(&mut self, receiver: &Mutex<Receiver<Task>>) {
+    loop {
+      let task = match receiver.lock() {
+        Ok(receiver) => receiver.recv(),
+        Err(_) => return,
+      };
+      let Task { job, bytes, sender } = match task {
+        Ok(task) => task,
+        Err(_) => return,
+      };
+      let result = match bytes {
+        Some(bytes) => self.run(job, &bytes),
+        None => Err(WasmError::Trap(Trap::Notfound)),
+      };
+      let _ = sender.send(result);
+    }
+  }
+
+  fn module_of(&mut self, handle: ModuleHandle, bytes: &[u8]) -> Result<Module> {
+    if self.modules.len() <= handle.0 {
+      self.modules.resize(handle.0 + 1, None);
+    }
+    if let Some(module) = &self.modules[handle.0] {
+      return Ok(module.clone());
+    }
+    let module = decode_module(bytes)?;
+    self.modules[handle.0] = Some(module.clone());
+    Ok(module)
+  }
+
+  fn run(&mut self, job: Job, bytes: &[u8]) -> Result<Vec<Values>> {
+    let started = Instant::now();
+    let module = self.module_of(job.module, bytes)?;
+    let mut vm = instantiate_module(
+      init_store(),
+      Ok(module),
+      self.external_modules.clone(),
+      self.max_stack_height,
+    )?;
+    let mut remaining_fuel = job.fuel;
+    let slice_of = |fuel: Option<u64>| match fuel {
+      Some(fuel) if fuel < INSTRUCTIONS_PER_SLICE => fuel,
+      _ => INSTRUCTIONS_PER_SLICE,
+    };
+    let mut slice = slice_of(remaining_fuel);
+    let mut run = vm.run_bounded(&job.export, job.arguments, slice)?;
+    loop {
+      let token = match run {
+        BoundedRun::Finished(values) => return Ok(values),
+        BoundedRun::Suspended(_, _) => return Err(WasmError::Trap(Trap::UnexpectedYield)),
+        BoundedRun::Yielded(token) => token,
+      };
+      remaining_fuel = remaining_fuel.map(|fuel| fuel - slice);
+      if remaining_fuel == Some(0) {
+        return Err(WasmError::Trap(Trap::ResourceLimitExceeded));
+      }
+      if let Some(timeout) = job.timeout {
+        if started.elapsed() >= timeout {
+          return Err(WasmError::Trap(Trap::Timeout));
+        }
+      }
+      slice = slice_of(remaining_fuel);
+      run = vm.resume(token, slice)?;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // (module
+  //   (func (export "add") (param i32 i32) (result i32) get_local 0 get_local 1 i32.add)
+  //   (func (export "loop") loop i32.const 1 br_if 0 end))
+  const BYTES: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+    0x01, 0x0a, 0x02, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, 0x60, 0x00, 0x00, // type
+    0x03, 0x03, 0x02, 0x00, 0x01, // function
+    0x07, 0x0e, 0x02, 0x03, 0x61, 0x64, 0x64, 0x00, 0x00, 0x04, 0x6c, 0x6f, 0x6f, 0x70, 0x00,
+    0x01, // export
+    0x0a, 0x13, 0x02, // code
+    0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x6a, 0x0b, // body of add
+    0x09, 0x00, 0x03, 0x40, 0x41, 0x01, 0x0d, 0x00, 0x0b, 0x0b, // body of loop
+  ];
+
+  #[test]
+  fn run_jobs_on_workers_within_fuel_and_timeout() {
+    let mut service = ExecutionService::new(2, 65536);
+    let module = service.register_module(BYTES.to_vec());
+    let handles = (0..4)
+      .map(|i| {
+        service.submit(Job::new(
+          module,
+          "add",
+          vec![Values::I32(i), Values::I32(1)],
+        ))
+      })
+      .collect::<Vec<_>>();
+    for (i, handle) in handles.into_iter().enumerate() {
+      assert_eq!(handle.wait(), Ok(vec![Values::I32(i as i32 + 1)]));
+    }
+
+    let mut job = Job::new(module, "loop", vec![]);
+    job.fuel = Some(25_000);
+    assert_eq!(
+      service.submit(job).wait(),
+      Err(WasmError::Trap(Trap::ResourceLimitExceeded))
+    );
+    let mut job = Job::new(module, "loop", vec![]);
+    job.timeout = Some(Duration::from_millis(10));
+    assert_eq!(
+      service.submit(job).wait(),
+      Err(WasmError::Trap(Trap::Timeout))
+    );
+    let unknown = Job::new(ModuleHandle(1), "add", vec![]);
+    assert_eq!(
+      service.submit(unknown).wait(),
+      Err(WasmError::Trap(Trap::Notfound))
+    );
+  }
+}
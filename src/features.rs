@@ -0,0 +1,33 @@
+use isa::Proposal;
+
+/// Proposals which validation accepts beyond MVP, all of them are disabled by default.
+/// Opcodes of an enabled proposal which isn't implemented yet are still rejected by `Trap::UnsupportedFeature`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Features {
+  /// Functions returning multiple values, blocks of multiple values are not supported yet.
+  pub multi_value: bool,
+  pub sign_ext: bool,
+  pub sat_float_to_int: bool,
+  pub bulk_memory: bool,
+  pub reference_types: bool,
+  pub simd: bool,
+  pub threads: bool,
+  pub tail_call: bool,
+}
+
+impl Features {
+  pub fn accepts(&self, proposal: &Proposal) -> bool {
+    use self::Proposal::*;
+    match proposal {
+      MultiValue => self.multi_value,
+      SignExtension => self.sign_ext,
+      SaturatingFloatToInt => self.sat_float_to_int,
+      BulkMemory => self.bulk_memory,
+      ReferenceTypes => self.reference_types,
+      Simd => self.simd,
+      Threads => self.threads,
+      TailCall => self.tail_call,
+      ExceptionHandling | Gc => false,
+    }
+  }
+}
@@ -0,0 +1,323 @@
+#[cfg(not(any(test, feature = "std")))]
+use alloc::prelude::*;
+use alloc::string::String;
+use error::{Result, Trap, WasmError};
+
+// Parameters of IEEE 754 binary format.
+struct Format {
+  mantissa_bits: u32,
+  exponent_bits: u32,
+}
+
+const F32: Format = Format {
+  mantissa_bits: 23,
+  exponent_bits: 8,
+};
+
+const F64: Format = Format {
+  mantissa_bits: 52,
+  exponent_bits: 11,
+};
+
+// NOTE: Exponents beyond this never fit, so larger ones are clamped to avoid overflow.
+const MAX_EXPONENT: i64 = 1 << 20;
+
+impl Format {
+  fn bias(&self) -> i64 {
+    (1 << (self.exponent_bits - 1)) - 1
+  }
+
+  fn infinity(&self) -> u64 {
+    ((1 << self.exponent_bits) - 1) << self.mantissa_bits
+  }
+
+  fn sign_bit(&self) -> u64 {
+    1 << (self.mantissa_bits + self.exponent_bits)
+  }
+
+  fn canonical_nan(&self) -> u64 {
+    self.infinity() | (1 << (self.mantissa_bits - 1))
+  }
+
+  // Round `mantissa` * 2^`exponent` to nearest even, where `sticky` tells that truncated digits are not zero.
+  fn round(&self, mantissa: u64, exponent: i64, sticky: bool) -> Result<u64> {
+    if mantissa == 0 {
+      return Ok(0);
+    }
+    let leading_zeros = mantissa.leading_zeros();
+    let mantissa = u128::from(mantissa << leading_zeros) << 64;
+    // NOTE: Value is mantissa * 2^(exponent - 127) here, whose most significant bit is 2^exponent.
+    let exponent = exponent + 63 - i64::from(leading_zeros);
+    let min_exponent = 1 - self.bias();
+    let (biased_exponent, shift) = if exponent >= min_exponent {
+      (exponent + self.bias(), 127 - i64::from(self.mantissa_bits))
+    } else {
+      (
+        0,
+        127 - i64::from(self.mantissa_bits) + (min_exponent - exponent),
+      )
+    };
+    if biased_exponent >= (1 << self.exponent_bits) - 1 {
+      return Err(WasmError::Trap(Trap::ConstantOutOfRange));
+    }
+    if shift > 128 {
+      // NOTE: Value is less than half of the smallest subnormal.
+      return Ok(0);
+    }
+    let (kept, rest, half) = if shift == 128 {
+      (0, mantissa, 1u128 << 127)
+    } else {
+      let shift = shift as u32;
+      (
+        mantissa >> shift,
+        mantissa & ((1 << shift) - 1),
+        1 << (shift - 1),
+      )
+    };
+    let is_rounded_up = rest > half || (rest == half && (sticky || kept & 1 == 1));
+    let rounded = if is_rounded_up { kept + 1 } else { kept };
+    let rounded = rounded as u64;
+    // NOTE: Implicit leading bit of normal number is carried into exponent field,
+    // which also moves subnormal rounded up to the smallest normal.
+    let bits = if biased_exponent == 0 {
+      rounded
+    } else {
+      ((biased_exponent as u64) << self.mantissa_bits) + rounded - (1 << self.mantissa_bits)
+    };
+    if bits >= self.infinity() {
+      return Err(WasmError::Trap(Trap::ConstantOutOfRange));
+    }
+    Ok(bits)
+  }
+}
+
+fn malformed() -> WasmError {
+  WasmError::Trap(Trap::MalformedFloatLiteral)
+}
+
+// Digits separated by optional single underscores, e.g. "1_000".
+fn digits_of(literal: &str, radix: u32) -> Result<String> {
+  let mut digits = String::new();
+  let mut is_separated = true;
+  for c in literal.chars() {
+    if c == '_' && !is_separated {
+      is_separated = true;
+    } else if c.is_digit(radix) {
+      digits.push(c);
+      is_separated = false;
+    } else {
+      return Err(malformed());
+    }
+  }
+  if is_separated {
+    return Err(malformed());
+  }
+  Ok(digits)
+}
+
+fn split_at_first<'a>(literal: &'a str, delimiters: &[char]) -> (&'a str, Option<&'a str>) {
+  match literal.find(|c| delimiters.contains(&c)) {
+    Some(idx) => (&literal[..idx], Some(&literal[idx + 1..])),
+    None => (literal, None),
+  }
+}
+
+fn split_sign(literal: &str) -> (bool, &str) {
+  if literal.starts_with('-') {
+    (true, &literal[1..])
+  } else if literal.starts_with('+') {
+    (false, &literal[1..])
+  } else {
+    (false, literal)
+  }
+}
+
+fn exponent_of(literal: &str) -> Result<i64> {
+  let (is_negative, literal) = split_sign(literal);
+  let exponent = digits_of(literal, 10)?.chars().fold(0i64, |acc, c| {
+    let digit = i64::from(c.to_digit(10).unwrap_or(0));
+    core::cmp::min(acc * 10 + digit, MAX_EXPONENT)
+  });
+  Ok(if is_negative { -exponent } else { exponent })
+}
+
+fn parse_hex(literal: &str, format: &Format) -> Result<u64> {
+  let (significand, exponent) = split_at_first(literal, &['p', 'P']);
+  let (integer, fraction) = split_at_first(significand, &['.']);
+  let integer = digits_of(integer, 16)?;
+  let fraction = match fraction {
+    Some("") | None => String::new(),
+    Some(fraction) => digits_of(fraction, 16)?,
+  };
+  let mut exponent = match exponent {
+    Some(exponent) => exponent_of(exponent)?,
+    None => 0,
+  };
+
+  let mut mantissa: u64 = 0;
+  let mut sticky = false;
+  for (idx, c) in integer.chars().chain(fraction.chars()).enumerate() {
+    let digit = u64::from(c.to_digit(16).unwrap_or(0));
+    let is_fraction = idx >= integer.len();
+    if mantissa >> 60 == 0 {
+      mantissa = mantissa * 16 + digit;
+      if is_fraction {
+        exponent -= 4;
+      }
+    } else {
+      // NOTE: Digits beyond 64 bits only affect rounding.
+      sticky |= digit != 0;
+      if !is_fraction {
+        exponent += 4;
+      }
+    }
+  }
+  format.round(mantissa, exponent, sticky)
+}
+
+fn parse_decimal(literal: &str, format: &Format) -> Result<u64> {
+  let (significand, exponent) = split_at_first(literal, &['e', 'E']);
+  let (integer, fraction) = split_at_first(significand, &['.']);
+  let mut normalized = digits_of(integer, 10)?;
+  if let Some(fraction) = fraction {
+    if !fraction.is_empty() {
+      normalized.push('.');
+      normalized.push_str(&digits_of(fraction, 10)?);
+    }
+  }
+  if let Some(exponent) = exponent {
+    normalized.push('e');
+    normalized.push_str(&exponent_of(exponent)?.to_string());
+  }
+  // NOTE: Parsing decimal by core is rounded correctly.
+  let bits = if format.mantissa_bits == F32.mantissa_bits {
+    let value = normalized.parse::<f32>().map_err(|_| malformed())?;
+    if value.is_infinite() {
+      return Err(WasmError::Trap(Trap::ConstantOutOfRange));
+    }
+    u64::from(value.to_bits())
+  } else {
+    let value = normalized.parse::<f64>().map_err(|_| malformed())?;
+    if value.is_infinite() {
+      return Err(WasmError::Trap(Trap::ConstantOutOfRange));
+    }
+    value.to_bits()
+  };
+  Ok(bits)
+}
+
+fn parse_bits(literal: &str, format: &Format) -> Result<u64> {
+  let (is_negative, literal) = split_sign(literal);
+  let magnitude = if literal == "inf" {
+    format.infinity()
+  } else if literal == "nan" {
+    format.canonical_nan()
+  } else if literal.starts_with("nan:0x") {
+    let payload = digits_of(&literal[6..], 16)?;
+    let payload = u64::from_str_radix(&payload, 16).map_err(|_| malformed())?;
+    if payload == 0 || payload >= 1 << format.mantissa_bits {
+      return Err(WasmError::Trap(Trap::ConstantOutOfRange));
+    }
+    format.infinity() | payload
+  } else if literal.starts_with("0x") {
+    parse_hex(&literal[2..], format)?
+  } else {
+    parse_decimal(literal, format)?
+  };
+  Ok(if is_negative {
+    magnitude | format.sign_bit()
+  } else {
+    magnitude
+  })
+}
+
+/// Bit pattern of a float literal of text format, e.g. "-0x1.8p3", "nan:0x200000" or "1_000.5e-3".
+/// Literals rounded to infinity trap by `ConstantOutOfRange`, and malformed ones by `MalformedFloatLiteral`.
+pub fn parse_f32_bits(literal: &str) -> Result<u32> {
+  parse_bits(literal, &F32).map(|bits| bits as u32)
+}
+
+/// Bit pattern of a float literal of text format, see `parse_f32_bits`.
+pub fn parse_f64_bits(literal: &str) -> Result<u64> {
+  parse_bits(literal, &F64)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_special_values_of_f32() {
+    assert_eq!(parse_f32_bits("inf"), Ok(0x7f80_0000));
+    assert_eq!(parse_f32_bits("-inf"), Ok(0xff80_0000));
+    assert_eq!(parse_f32_bits("nan"), Ok(0x7fc0_0000));
+    assert_eq!(parse_f32_bits("-nan"), Ok(0xffc0_0000));
+    assert_eq!(parse_f32_bits("nan:0x1"), Ok(0x7f80_0001));
+    assert_eq!(parse_f32_bits("+nan:0x7f_ffff"), Ok(0x7fff_ffff));
+    assert_eq!(parse_f32_bits("-0"), Ok(0x8000_0000));
+    assert_eq!(parse_f32_bits("-0x0.0p0"), Ok(0x8000_0000));
+  }
+
+  #[test]
+  fn parse_hex_floats_exactly() {
+    assert_eq!(parse_f32_bits("0x1p127"), Ok(0x7f00_0000));
+    assert_eq!(parse_f32_bits("0x1.fffffep127"), Ok(0x7f7f_ffff));
+    assert_eq!(parse_f32_bits("0x1p-149"), Ok(0x0000_0001));
+    assert_eq!(parse_f32_bits("0x1p-126"), Ok(0x0080_0000));
+    assert_eq!(parse_f32_bits("0x1.8p-149"), Ok(0x0000_0002));
+    assert_eq!(parse_f32_bits("0x1p-150"), Ok(0x0000_0000));
+    assert_eq!(parse_f32_bits("0x1.000001p-150"), Ok(0x0000_0001));
+    assert_eq!(parse_f32_bits("0x1.00000100000000001p0"), Ok(0x3f80_0001));
+    assert_eq!(parse_f32_bits("0x1.000001p0"), Ok(0x3f80_0000));
+    assert_eq!(parse_f32_bits("0x1.000003p0"), Ok(0x3f80_0002));
+    assert_eq!(parse_f32_bits("0x1.fffffefffffffffffp127"), Ok(0x7f7f_ffff));
+    assert_eq!(parse_f32_bits("0xa_bc.d_ep1"), Ok(0x45ab_cde0));
+    assert_eq!(parse_f32_bits("0x1."), Ok(0x3f80_0000));
+    assert_eq!(parse_f64_bits("0x1p-1074"), Ok(0x0000_0000_0000_0001));
+    assert_eq!(
+      parse_f64_bits("0x1.fffffffffffffp1023"),
+      Ok(0x7fef_ffff_ffff_ffff)
+    );
+    assert_eq!(
+      parse_f64_bits("-0x1.0000000000000fffffffffffp-1022"),
+      Ok(0x8010_0000_0000_0001)
+    );
+  }
+
+  #[test]
+  fn parse_decimal_floats() {
+    assert_eq!(parse_f32_bits("1.5"), Ok(0x3fc0_0000));
+    assert_eq!(parse_f32_bits("1."), Ok(0x3f80_0000));
+    assert_eq!(parse_f32_bits("1_0e+1_0"), Ok(0x51ba_43b7));
+    assert_eq!(parse_f32_bits("-1e-45"), Ok(0x8000_0001));
+    assert_eq!(parse_f64_bits("0.1"), Ok(0x3fb9_9999_9999_999a));
+    assert_eq!(parse_f64_bits("1e-400"), Ok(0));
+  }
+
+  #[test]
+  fn reject_malformed_or_out_of_range_literals() {
+    let malformed = WasmError::Trap(Trap::MalformedFloatLiteral);
+    let out_of_range = WasmError::Trap(Trap::ConstantOutOfRange);
+    for literal in [
+      "", "0x", "1__0", "_1", "1_", "1.e", "0x1.p", "infinity", ".5", "nan:0x",
+    ]
+    .iter()
+    {
+      assert_eq!(
+        parse_f32_bits(literal),
+        Err(malformed.clone()),
+        "{}",
+        literal
+      );
+    }
+    assert_eq!(parse_f32_bits("0x1p128"), Err(out_of_range.clone()));
+    assert_eq!(parse_f32_bits("0x1.ffffffp127"), Err(out_of_range.clone()));
+    assert_eq!(parse_f32_bits("1e39"), Err(out_of_range.clone()));
+    assert_eq!(parse_f32_bits("nan:0x0"), Err(out_of_range.clone()));
+    assert_eq!(parse_f32_bits("nan:0x80_0000"), Err(out_of_range.clone()));
+    assert_eq!(
+      parse_f64_bits("0x1.fffffffffffff8p1023"),
+      Err(out_of_range.clone())
+    );
+  }
+}
@@ -25,15 +25,30 @@ macro_rules! impl_pop_bytes {
   };
 }
 
+/// Pointers of the operand stack saved on calling a function,
+/// which are restored when the function returns.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct ActivationRecord {
+  // Stack pointer at the call site after arguments were popped, locals of callee start from here.
+  pub(crate) base_ptr: usize,
+  // Frame pointer of caller.
+  pub(crate) caller_frame_ptr: usize,
+}
+
 #[derive(PartialEq)]
 pub struct Frame {
   // FIXME: No need to hold local_variables in frame.
   local_variables: RefCell<Vec<StackEntry>>,
   pub(crate) function_instance: FunctionInstance,
+  // NOTE: Cached on creation, since the loop of evaluation refers them on every call and return.
+  arity: u32,
+  count_of_locals: usize,
+  count_of_returns: u32,
   ptr: Cell<u32>,
+  // NOTE: Pointer can't tell it, since a frame may yield after jumping back to the loop at 0.
+  entered: Cell<bool>,
   pub last_ptr: u32,
-  pub return_ptr: usize,
-  pub prev_return_ptr: usize,
+  pub(crate) activation: ActivationRecord,
 }
 
 impl Frame {
@@ -41,32 +56,36 @@ impl Frame {
   impl_pop_bytes!(pop_raw_u64, u64, 8);
 
   pub fn new(
-    return_ptr: usize,
-    prev_return_ptr: usize,
+    activation: ActivationRecord,
     function_instance: FunctionInstance,
     arguments: &mut Vec<StackEntry>,
-  ) -> Self {
-    match function_instance {
+  ) -> Result<Self> {
+    let arity = arguments.len() as u32;
+    function_instance
+      .function_type_ref()
+      .check_arity(arguments.len())?;
+    let (local_variables, last_ptr) = match function_instance {
       FunctionInstance::LocalFn(ref f) => {
+        f.flatten()?;
         let last_ptr = f.get_expressions_count() as u32;
-        Frame {
-          local_variables: Frame::derive_local_variables(arguments, f.local_variables()),
-          function_instance: function_instance.clone(),
+        (
+          Frame::derive_local_variables(arguments, f.local_variables()),
           last_ptr,
-          return_ptr,
-          prev_return_ptr,
-          ptr: Cell::new(0),
-        }
+        )
       }
-      FunctionInstance::HostFn(_) => Frame {
-        local_variables: Frame::derive_local_variables(arguments, vec![]),
-        function_instance,
-        last_ptr: 0,
-        return_ptr,
-        prev_return_ptr,
-        ptr: Cell::new(0),
-      },
-    }
+      FunctionInstance::HostFn(_) => (Frame::derive_local_variables(arguments, &[]), 0),
+    };
+    Ok(Frame {
+      arity,
+      count_of_locals: local_variables.len(),
+      count_of_returns: function_instance.get_return_count(),
+      local_variables: RefCell::new(local_variables),
+      function_instance,
+      last_ptr,
+      activation,
+      ptr: Cell::new(0),
+      entered: Cell::new(false),
+    })
   }
 
   pub fn is_completed(&self) -> bool {
@@ -74,7 +93,11 @@ impl Frame {
   }
 
   pub fn is_fresh(&self) -> bool {
-    self.ptr.get().eq(&0)
+    !self.entered.get()
+  }
+
+  pub(crate) fn mark_entered(&self) {
+    self.entered.set(true);
   }
 
   pub fn get_local_variables(&self) -> RefMut<Vec<StackEntry>> {
@@ -85,10 +108,12 @@ impl Frame {
   // To [4,3,2,1]
   fn derive_local_variables(
     arguments: &mut Vec<StackEntry>,
-    mut local_variables: Vec<StackEntry>,
-  ) -> RefCell<Vec<StackEntry>> {
-    local_variables.append(arguments);
-    RefCell::new(local_variables)
+    local_variables: &[StackEntry],
+  ) -> Vec<StackEntry> {
+    let mut variables = Vec::with_capacity(local_variables.len() + arguments.len());
+    variables.extend_from_slice(local_variables);
+    variables.append(arguments);
+    variables
   }
 
   pub fn get_return_type(&self) -> &Vec<ValueTypes> {
@@ -96,14 +121,26 @@ impl Frame {
   }
 
   pub fn get_return_count(&self) -> u32 {
-    self.function_instance.get_return_count()
+    self.count_of_returns
+  }
+
+  pub(crate) fn arity(&self) -> u32 {
+    self.arity
+  }
+
+  pub(crate) fn count_of_locals(&self) -> usize {
+    self.count_of_locals
+  }
+
+  pub(crate) fn ptr(&self) -> u32 {
+    self.ptr.get()
   }
 
   pub fn get_start_of_label(&self) -> u32 {
     self.ptr.get().sub(1)
   }
 
-  fn peek(&self) -> Option<&u8> {
+  fn peek(&self) -> Option<u8> {
     let ptr = self.ptr.get();
     match self.function_instance {
       FunctionInstance::LocalFn(ref f) => f.get(ptr as usize),
@@ -111,7 +148,7 @@ impl Frame {
     }
   }
 
-  pub fn pop_ref(&self) -> Option<&u8> {
+  pub fn pop_ref(&self) -> Option<u8> {
     let head = self.peek();
     let ptr = self.ptr.get();
     self.ptr.set(ptr + 1);
@@ -120,7 +157,7 @@ impl Frame {
 
   pub fn pop_runtime_type(&self) -> Option<ValueTypes> {
     match self.pop_ref() {
-      Some(byte) => Some(ValueTypes::from(*byte)),
+      Some(byte) => Some(ValueTypes::from(byte)),
       None => None,
     }
   }
@@ -154,7 +191,7 @@ impl fmt::Debug for Frame {
         &format!("{:?}", self.function_instance.get_function_type()),
       )
       .field("ptr", &self.ptr)
-      .field("return_ptr", &self.return_ptr)
+      .field("activation", &self.activation)
       .finish()
   }
 }
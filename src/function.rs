@@ -1,13 +1,24 @@
-#[cfg(not(test))]
+#[cfg(not(any(test, feature = "std")))]
 use alloc::prelude::*;
-use alloc::rc::Rc;
+use alloc::rc::{Rc, Weak};
 use alloc::string::String;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
-use core::cell::RefCell;
+#[cfg(feature = "checked-interp")]
+use checked::TypeStream;
+use core::cell::{Cell, Ref, RefCell};
+use core::ffi::c_void;
 use core::fmt;
-use error::{Result, TypeError, WasmError};
-use module::ModuleName;
+use core::ptr;
+use decode::{InstructionOffsets, RawBody};
+use error::{Result, Trap, TypeError, WasmError};
+use fusion::fuse_superinstructions;
+use host_func::IntoHostFunc;
+use instructions::{walk_instructions, InstructionVisitor, Instructions};
+use module::{ExternalModule, ModuleName};
 use stack::StackEntry;
+use stack_usage::StackUsage;
+use symbol::Symbol;
 use value::Values;
 use value_type::ValueTypes;
 
@@ -17,12 +28,13 @@ struct FunctionTypeImpl {
   returns: Vec<ValueTypes>,
 }
 
+// NOTE: Shared with Arc, so that functions can be validated in parallel.
 #[derive(PartialEq, Clone)]
-pub struct FunctionType(Rc<FunctionTypeImpl>);
+pub struct FunctionType(Arc<FunctionTypeImpl>);
 
 impl FunctionType {
   pub fn new(parameters: Vec<ValueTypes>, returns: Vec<ValueTypes>) -> Self {
-    FunctionType(Rc::new(FunctionTypeImpl {
+    FunctionType(Arc::new(FunctionTypeImpl {
       parameters,
       returns,
     }))
@@ -39,6 +51,17 @@ impl FunctionType {
   pub fn get_arity(&self) -> u32 {
     self.0.parameters.len() as u32
   }
+
+  /// Arguments become locals of a frame by their positions, so their count must equal to parameters.
+  pub(crate) fn check_arity(&self, count_of_arguments: usize) -> Result<()> {
+    if count_of_arguments == self.0.parameters.len() {
+      return Ok(());
+    }
+    Err(WasmError::Trap(Trap::ArityMismatch {
+      expected: self.clone(),
+      actual: count_of_arguments,
+    }))
+  }
 }
 
 impl fmt::Debug for FunctionType {
@@ -64,45 +87,293 @@ impl fmt::Debug for FunctionType {
   }
 }
 
-#[derive(PartialEq)]
+/// When instructions of functions are flattened.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BodyDecoding {
+  /// Flatten all functions at instantiation, so that a malformed body fails instantiation.
+  Eager,
+  /// Flatten a function on its first call, which shortens startup of modules
+  /// whose functions are mostly never called. A malformed body traps at its first call.
+  Lazy,
+}
+
+impl Default for BodyDecoding {
+  fn default() -> Self {
+    BodyDecoding::Eager
+  }
+}
+
+/// Whether frequent sequences of instructions are fused into superinstructions when functions are flattened.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstructionFusion {
+  Fused,
+  /// Keep instructions as they're decoded, e.g. to compare them with ones of the binary.
+  Unfused,
+}
+
+impl Default for InstructionFusion {
+  fn default() -> Self {
+    InstructionFusion::Fused
+  }
+}
+
+impl InstructionFusion {
+  pub(crate) fn apply(&self, instructions: &mut [u8]) {
+    if let InstructionFusion::Fused = self {
+      fuse_superinstructions(instructions);
+    }
+  }
+}
+
+/// Flattened instructions of a function, which it owns by itself or shares an arena with other functions.
+pub(crate) enum InstructionBuffer {
+  // NOTE: Flattened body is kept as it is, so that it isn't copied to shrink.
+  Owned(Vec<u8>),
+  // NOTE: Arena is freed when the last function of the module is dropped.
+  Arena(Rc<Vec<u8>>, usize, usize),
+}
+
+impl InstructionBuffer {
+  /// Pack bodies into one arena, and returns buffers of them in the same order.
+  /// Functions flattened at instantiation share an arena, so that instantiating and dropping
+  /// many modules doesn't scatter small allocations over the heap.
+  ///
+  /// The arena is allocated at its final size and moved into `Rc` as it is,
+  /// and each body is freed as soon as it's copied into the arena.
+  pub(crate) fn pack(bodies: Vec<Vec<u8>>) -> Vec<InstructionBuffer> {
+    let size_of_arena = bodies.iter().map(|body| body.len()).sum();
+    let mut arena = Vec::with_capacity(size_of_arena);
+    let mut ranges = vec![];
+    for body in bodies.into_iter() {
+      let start = arena.len();
+      arena.extend_from_slice(&body);
+      ranges.push((start, arena.len()));
+    }
+    let arena = Rc::new(arena);
+    ranges
+      .into_iter()
+      .map(|(start, end)| InstructionBuffer::Arena(arena.clone(), start, end))
+      .collect()
+  }
+
+  fn as_slice(&self) -> &[u8] {
+    match self {
+      InstructionBuffer::Owned(instructions) => instructions,
+      InstructionBuffer::Arena(arena, start, end) => &arena[*start..*end],
+    }
+  }
+}
+
+struct FlattenedBody {
+  instructions: InstructionBuffer,
+  offsets: InstructionOffsets,
+}
+
 pub struct FunctionInstanceImpl {
   export_name: Option<String>,
   function_type: FunctionType,
   local_variables: Vec<StackEntry>,
-  body: Vec<u8>,
-  source_module_name: RefCell<Option<String>>,
+  // NOTE: Written only once by `flatten` while it's None.
+  body: RefCell<Option<FlattenedBody>>,
+  raw_body: RefCell<Option<(RawBody, InstructionFusion)>>,
+  source_module_name: RefCell<Option<Symbol>>,
+  // NOTE: Weak, since the instance refers to its functions in turn.
+  defining_instance: RefCell<Option<Weak<ExternalModule>>>,
+  stack_usage: Cell<Option<StackUsage>>,
+  #[cfg(feature = "checked-interp")]
+  type_stream: RefCell<Option<TypeStream>>,
+}
+
+// NOTE: Offsets are debug information, so functions decoded from binary
+// are equal to ones which are constructed by hand.
+impl PartialEq for FunctionInstanceImpl {
+  fn eq(&self, other: &FunctionInstanceImpl) -> bool {
+    self.export_name == other.export_name
+      && self.function_type == other.function_type
+      && self.local_variables == other.local_variables
+      && *self.body() == *other.body()
+      && self.raw_body == other.raw_body
+      && self.source_module_name == other.source_module_name
+  }
 }
 
 impl FunctionInstanceImpl {
+  fn is_flattened(&self) -> bool {
+    self.body.borrow().is_some()
+  }
+
+  /// Flatten instructions kept in binary format, which is done only once.
+  pub(crate) fn flatten(&self) -> Result<()> {
+    if self.is_flattened() {
+      return Ok(());
+    }
+    let (instructions, offsets) = match *self.raw_body.borrow_mut() {
+      Some((ref mut raw_body, ref fusion)) => {
+        let (mut instructions, offsets) = raw_body.flatten_in_place()?;
+        fusion.apply(&mut instructions);
+        (instructions, offsets)
+      }
+      None => unreachable!("Function has neither flattened nor raw body."),
+    };
+    self.body.replace(Some(FlattenedBody {
+      instructions: InstructionBuffer::Owned(instructions),
+      offsets,
+    }));
+    self.raw_body.replace(None);
+    Ok(())
+  }
+
   pub fn get_expressions_count(&self) -> usize {
-    self.body.len()
+    self.body().len()
   }
 
-  pub fn local_variables(&self) -> Vec<StackEntry> {
-    self.local_variables.clone()
+  pub fn local_variables(&self) -> &[StackEntry] {
+    &self.local_variables
   }
 
-  pub fn get(&self, idx: usize) -> Option<&u8> {
-    self.body.get(idx)
+  pub fn get(&self, idx: usize) -> Option<u8> {
+    self.body().get(idx).cloned()
   }
 
-  pub(crate) fn body(&self) -> &[u8] {
-    &self.body
+  // NOTE: A function which is not flattened yet has no instructions.
+  pub(crate) fn body(&self) -> Ref<[u8]> {
+    Ref::map(self.body.borrow(), |body| match body {
+      Some(flattened) => flattened.instructions.as_slice(),
+      None => &[],
+    })
+  }
+}
+
+/// Context which a raw host function receives, valid only while the call.
+#[repr(C)]
+pub struct VmContext {
+  /// Pointer given on registration, e.g. to state of the host.
+  pub data: *mut c_void,
+  /// Linear memory of the running instance, which is null when it has no memory or its memory isn't contiguous.
+  pub memory: *mut u8,
+  pub memory_size: usize,
+}
+
+/// Host function called without boxing, which reads arguments and writes results
+/// as bit patterns of `Values::to_bits` in order of its signature.
+pub type RawHostFn = extern "C" fn(*mut VmContext, *const u64, *mut u64);
+
+// NOTE: Arguments and results are passed by buffers on stack of the interpreter.
+pub(crate) const MAX_RAW_HOST_FN_VALUES: usize = 16;
+
+#[derive(Clone, Copy)]
+pub(crate) struct RawHostFunction {
+  trampoline: RawHostFn,
+  data: *mut c_void,
+}
+
+impl RawHostFunction {
+  pub(crate) fn call(
+    &self,
+    arguments: &[u64; MAX_RAW_HOST_FN_VALUES],
+    memory: Option<&mut [u8]>,
+  ) -> [u64; MAX_RAW_HOST_FN_VALUES] {
+    let (memory, memory_size) = memory.map_or((ptr::null_mut(), 0), |memory| {
+      (memory.as_mut_ptr(), memory.len())
+    });
+    let mut context = VmContext {
+      data: self.data,
+      memory,
+      memory_size,
+    };
+    let mut results = [0; MAX_RAW_HOST_FN_VALUES];
+    (self.trampoline)(&mut context, arguments.as_ptr(), results.as_mut_ptr());
+    results
   }
 }
 
 pub struct HostFunction {
   export_name: Option<String>,
   function_type: FunctionType,
-  source_module_name: RefCell<Option<String>>,
-  callable: &'static Fn(&[Values]) -> Vec<Values>,
+  source_module_name: RefCell<Option<Symbol>>,
+  callable: Rc<Fn(&[Values]) -> Result<Vec<Values>>>,
+  // NOTE: Calling yield function suspends the invocation instead of calling `callable`.
+  suspends: bool,
+  // NOTE: Interpreter calls it directly instead of `callable`, which is kept to call it by `Values`.
+  raw: Option<RawHostFunction>,
+}
+
+#[cfg(feature = "log")]
+const COUNT_OF_PREVIEW_VALUES: usize = 4;
+
+#[cfg(feature = "log")]
+fn preview_values(values: &[Values]) -> String {
+  let mut previews = values
+    .iter()
+    .take(COUNT_OF_PREVIEW_VALUES)
+    .map(|v| format!("{:?}", v))
+    .collect::<Vec<String>>();
+  if values.len() > COUNT_OF_PREVIEW_VALUES {
+    previews.push("..".to_owned());
+  }
+  previews.join(", ")
 }
 
 impl HostFunction {
-  pub(crate) fn call(&self, arguments: &[Values]) -> Vec<Values> {
-    let callable = self.callable;
+  pub(crate) fn raw(&self) -> Option<RawHostFunction> {
+    self.raw
+  }
+
+  #[cfg(not(feature = "log"))]
+  pub(crate) fn call(&self, arguments: &[Values]) -> Result<Vec<Values>> {
+    self.invoke(arguments)
+  }
+
+  // NOTE: Elapsed time can be measured only with std.
+  #[cfg(feature = "log")]
+  pub(crate) fn call(&self, arguments: &[Values]) -> Result<Vec<Values>> {
+    #[cfg(any(test, feature = "std"))]
+    let started_at = ::std::time::Instant::now();
+    let result = self.invoke(arguments);
+    #[cfg(any(test, feature = "std"))]
+    let duration = format!("{:?}", started_at.elapsed());
+    #[cfg(not(any(test, feature = "std")))]
+    let duration = "-".to_owned();
+    let outcome = match &result {
+      Ok(values) => format!("({})", preview_values(values)),
+      Err(err) => format!("{:?}", err),
+    };
+    debug!(
+      target: "wasvm::host_call",
+      "{}.{}({}) -> {} in {}",
+      self.source_module_name.borrow().as_ref().map_or("_", |n| n.as_str()),
+      self.export_name.as_ref().map_or("_", |n| n.as_str()),
+      preview_values(arguments),
+      outcome,
+      duration
+    );
+    result
+  }
+
+  #[cfg(not(any(test, feature = "std")))]
+  fn invoke(&self, arguments: &[Values]) -> Result<Vec<Values>> {
+    let callable = &self.callable;
     callable(arguments)
   }
+
+  // NOTE: Unwinding is only available with std,
+  // a panic inside of host function is converted to Trap::HostError instead of aborting embedder.
+  #[cfg(any(test, feature = "std"))]
+  fn invoke(&self, arguments: &[Values]) -> Result<Vec<Values>> {
+    use error::Trap;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    let callable = &self.callable;
+    catch_unwind(AssertUnwindSafe(|| callable(arguments))).map_err(|payload| {
+      let message = if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+      } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.to_owned()
+      } else {
+        "Host function panicked".to_owned()
+      };
+      WasmError::Trap(Trap::HostError(message))
+    })?
+  }
 }
 
 impl PartialEq for HostFunction {
@@ -110,6 +381,8 @@ impl PartialEq for HostFunction {
     self.export_name == other.export_name
       && self.function_type == other.function_type
       && self.source_module_name == other.source_module_name
+      && self.suspends == other.suspends
+      && self.raw.map(|raw| raw.trampoline as usize) == other.raw.map(|raw| raw.trampoline as usize)
   }
 }
 
@@ -123,8 +396,54 @@ impl FunctionInstance {
   pub fn new(
     export_name: Option<String>,
     function_type: FunctionType,
-    mut locals: Vec<ValueTypes>,
+    locals: Vec<ValueTypes>,
     body: Vec<u8>,
+  ) -> Self {
+    FunctionInstance::new_with_offsets(
+      export_name,
+      function_type,
+      locals,
+      InstructionBuffer::Owned(body),
+      InstructionOffsets::default(),
+    )
+  }
+
+  pub(crate) fn new_with_offsets(
+    export_name: Option<String>,
+    function_type: FunctionType,
+    mut locals: Vec<ValueTypes>,
+    instructions: InstructionBuffer,
+    offsets: InstructionOffsets,
+  ) -> Self {
+    locals.reverse();
+    let local_variables = locals
+      .iter()
+      .map(|local| StackEntry::new_value(Values::from(local)))
+      .collect::<Vec<_>>();
+    FunctionInstance::LocalFn(Rc::new(FunctionInstanceImpl {
+      export_name,
+      function_type,
+      local_variables,
+      body: RefCell::new(Some(FlattenedBody {
+        instructions,
+        offsets,
+      })),
+      raw_body: RefCell::new(None),
+      source_module_name: RefCell::new(None),
+      defining_instance: RefCell::new(None),
+      stack_usage: Cell::new(None),
+      #[cfg(feature = "checked-interp")]
+      type_stream: RefCell::new(None),
+    }))
+  }
+
+  /// Function whose instructions are flattened on its first call.
+  pub(crate) fn new_lazy(
+    export_name: Option<String>,
+    function_type: FunctionType,
+    mut locals: Vec<ValueTypes>,
+    raw_body: RawBody,
+    fusion: InstructionFusion,
   ) -> Self {
     locals.reverse();
     let local_variables = locals
@@ -135,8 +454,13 @@ impl FunctionInstance {
       export_name,
       function_type,
       local_variables,
-      body,
+      body: RefCell::new(None),
+      raw_body: RefCell::new(Some((raw_body, fusion))),
       source_module_name: RefCell::new(None),
+      defining_instance: RefCell::new(None),
+      stack_usage: Cell::new(None),
+      #[cfg(feature = "checked-interp")]
+      type_stream: RefCell::new(None),
     }))
   }
 
@@ -148,14 +472,123 @@ impl FunctionInstance {
   where
     F: Fn(&[Values]) -> Vec<Values>,
   {
+    FunctionInstance::new_host_closure(export_name, function_type, callable)
+  }
+
+  /// Same as `new_host_fn`, but accepts a closure which captures its environment.
+  pub fn new_host_closure<F>(
+    export_name: Option<String>,
+    function_type: FunctionType,
+    callable: F,
+  ) -> Self
+  where
+    F: Fn(&[Values]) -> Vec<Values> + 'static,
+  {
+    FunctionInstance::new_fallible_host_closure(export_name, function_type, move |arguments| {
+      Ok(callable(arguments))
+    })
+  }
+
+  /// Same as `new_host_closure`, but `callable` can trap, which unwinds the invocation calling it.
+  pub fn new_fallible_host_closure<F>(
+    export_name: Option<String>,
+    function_type: FunctionType,
+    callable: F,
+  ) -> Self
+  where
+    F: Fn(&[Values]) -> Result<Vec<Values>> + 'static,
+  {
+    FunctionInstance::HostFn(Rc::new(HostFunction {
+      export_name,
+      function_type,
+      source_module_name: RefCell::new(None),
+      callable: Rc::new(callable),
+      suspends: false,
+      raw: None,
+    }))
+  }
+
+  /// Same as `new_host_closure`, but the signature is derived from types of `callable`, see `IntoHostFunc`.
+  pub fn new_typed_host_fn<F, Params, Results>(export_name: Option<String>, callable: F) -> Self
+  where
+    F: IntoHostFunc<Params, Results>,
+  {
+    callable.into_host_func(export_name)
+  }
+
+  /// Host function which suspends the invocation calling it.
+  /// Its arguments are passed to the embedder, and results are supplied on resuming.
+  pub fn new_yield(export_name: Option<String>, function_type: FunctionType) -> Self {
+    FunctionInstance::HostFn(Rc::new(HostFunction {
+      export_name,
+      function_type,
+      source_module_name: RefCell::new(None),
+      callable: Rc::new(|_: &[Values]| Ok(vec![])),
+      suspends: true,
+      raw: None,
+    }))
+  }
+
+  /// Host function called through `trampoline` without boxing nor marshalling by `Values`,
+  /// for hosts whose functions are called so often that the overhead matters.
+  /// Panics when the signature has more than 16 parameters or results.
+  ///
+  /// # Safety
+  ///
+  /// `trampoline` must read and write no more values than the signature declares,
+  /// and `data` must be valid whenever the function is called.
+  pub unsafe fn new_raw_host_fn(
+    export_name: Option<String>,
+    function_type: FunctionType,
+    trampoline: RawHostFn,
+    data: *mut c_void,
+  ) -> Self {
+    assert!(
+      function_type.parameters().len() <= MAX_RAW_HOST_FN_VALUES
+        && function_type.returns().len() <= MAX_RAW_HOST_FN_VALUES,
+      "Raw host function accepts up to {} parameters and results",
+      MAX_RAW_HOST_FN_VALUES
+    );
+    let raw = RawHostFunction { trampoline, data };
+    let returns = function_type.returns().to_owned();
+    let callable = move |arguments: &[Values]| {
+      let mut buffer = [0; MAX_RAW_HOST_FN_VALUES];
+      for (bits, argument) in buffer.iter_mut().zip(arguments.iter()) {
+        *bits = argument.to_bits();
+      }
+      let results = raw.call(&buffer, None);
+      Ok(
+        returns
+          .iter()
+          .zip(results.iter())
+          .map(|(value_type, bits)| Values::from_bits(value_type, *bits))
+          .collect(),
+      )
+    };
     FunctionInstance::HostFn(Rc::new(HostFunction {
       export_name,
       function_type,
       source_module_name: RefCell::new(None),
-      callable,
+      callable: Rc::new(callable),
+      suspends: false,
+      raw: Some(raw),
     }))
   }
 
+  pub(crate) fn is_yield(&self) -> bool {
+    match self {
+      FunctionInstance::HostFn(f) => f.suspends,
+      FunctionInstance::LocalFn(_) => false,
+    }
+  }
+
+  pub(crate) fn raw(&self) -> Option<RawHostFunction> {
+    match self {
+      FunctionInstance::HostFn(f) => f.raw(),
+      FunctionInstance::LocalFn(_) => None,
+    }
+  }
+
   pub fn function_type_ref(&self) -> &FunctionType {
     match self {
       FunctionInstance::LocalFn(f) => &f.function_type,
@@ -165,21 +598,92 @@ impl FunctionInstance {
 
   pub fn set_source_module_name(&self, name: &ModuleName) {
     if let Some(name) = name {
-      let mut source_module_name = match self {
-        FunctionInstance::LocalFn(f) => f.source_module_name.borrow_mut(),
-        FunctionInstance::HostFn(f) => f.source_module_name.borrow_mut(),
-      };
-      source_module_name.replace(name.to_owned());
+      self.set_source_symbol(Symbol::from(name.as_str()));
+    };
+  }
+
+  /// Same as `set_source_module_name`, but by a symbol interned by the registry which the function is imported from.
+  pub(crate) fn set_source_symbol(&self, name: Symbol) {
+    let mut source_module_name = match self {
+      FunctionInstance::LocalFn(f) => f.source_module_name.borrow_mut(),
+      FunctionInstance::HostFn(f) => f.source_module_name.borrow_mut(),
     };
+    source_module_name.replace(name);
   }
 
   pub fn get_source_module_name(&self) -> Option<String> {
+    self
+      .get_source_symbol()
+      .map(|name| String::from(name.as_str()))
+  }
+
+  /// Same as `get_source_module_name`, but without allocation.
+  pub fn get_source_symbol(&self) -> Option<Symbol> {
     match self {
       FunctionInstance::LocalFn(f) => f.source_module_name.borrow().to_owned(),
       FunctionInstance::HostFn(f) => f.source_module_name.borrow().to_owned(),
     }
   }
 
+  /// Instance which defines the function, so that functions and memory which it refers to are ones of the instance
+  /// even when it's called through a table shared with other instances. Former one is kept if it's claimed already.
+  pub(crate) fn claim_defining_instance(&self, instance: &Rc<ExternalModule>) {
+    if let FunctionInstance::LocalFn(f) = self {
+      let mut defining_instance = f.defining_instance.borrow_mut();
+      if defining_instance.is_none() {
+        defining_instance.replace(Rc::downgrade(instance));
+      }
+    }
+  }
+
+  /// None for host functions, functions constructed by hand and ones whose instance is dropped already.
+  pub(crate) fn defining_instance(&self) -> Option<Rc<ExternalModule>> {
+    match self {
+      FunctionInstance::LocalFn(f) => f
+        .defining_instance
+        .borrow()
+        .as_ref()
+        .and_then(|instance| instance.upgrade()),
+      FunctionInstance::HostFn(_) => None,
+    }
+  }
+
+  /// Stack usage analyzed by validation, which is unknown for host functions
+  /// and functions of modules not validated by `validate_module_with_limits`.
+  pub fn stack_usage(&self) -> Option<StackUsage> {
+    match self {
+      FunctionInstance::LocalFn(f) => f.stack_usage.get(),
+      FunctionInstance::HostFn(_) => None,
+    }
+  }
+
+  pub(crate) fn set_stack_usage(&self, stack_usage: StackUsage) {
+    if let FunctionInstance::LocalFn(f) = self {
+      f.stack_usage.set(Some(stack_usage));
+    }
+  }
+
+  #[cfg(feature = "checked-interp")]
+  pub(crate) fn set_type_stream(&self, type_stream: TypeStream) {
+    if let FunctionInstance::LocalFn(f) = self {
+      f.type_stream.replace(Some(type_stream));
+    }
+  }
+
+  /// Types of operands which validator expects the instruction at `ptr` to pop.
+  #[cfg(feature = "checked-interp")]
+  pub(crate) fn expected_operands(&self, ptr: usize) -> Option<Vec<ValueTypes>> {
+    match self {
+      FunctionInstance::LocalFn(f) => f
+        .type_stream
+        .borrow()
+        .as_ref()
+        .and_then(|type_stream| type_stream.operands_at(ptr))
+        .map(|operands| operands.to_vec()),
+      FunctionInstance::HostFn(_) => None,
+    }
+  }
+
   pub fn get_arity(&self) -> u32 {
     match self {
       FunctionInstance::LocalFn(f) => f.function_type.parameters().len() as u32,
@@ -205,6 +709,49 @@ impl FunctionInstance {
     self.get_return_type().len() as u32
   }
 
+  pub(crate) fn export_name(&self) -> Option<&String> {
+    match self {
+      FunctionInstance::LocalFn(f) => f.export_name.as_ref(),
+      FunctionInstance::HostFn(f) => f.export_name.as_ref(),
+    }
+  }
+
+  pub(crate) fn is_same_instance(&self, other: &FunctionInstance) -> bool {
+    match (self, other) {
+      (FunctionInstance::LocalFn(l), FunctionInstance::LocalFn(r)) => Rc::ptr_eq(l, r),
+      (FunctionInstance::HostFn(l), FunctionInstance::HostFn(r)) => Rc::ptr_eq(l, r),
+      _ => false,
+    }
+  }
+
+  /// Offsets of instructions in binary format, which host functions don't have.
+  pub fn instruction_offsets(&self) -> Option<Ref<InstructionOffsets>> {
+    match self {
+      FunctionInstance::LocalFn(f) if f.is_flattened() => Some(Ref::map(f.body.borrow(), |body| {
+        &body.as_ref().expect("Function is flattened.").offsets
+      })),
+      _ => None,
+    }
+  }
+
+  /// Flattened instructions with their depth of labels, which host functions and functions
+  /// not flattened yet don't have.
+  /// Offsets are positions in flattened body, which `instruction_offsets` maps to ones in binary format.
+  pub fn instructions(&self) -> Instructions {
+    match self {
+      FunctionInstance::LocalFn(f) => Instructions::borrowed(f.body()),
+      FunctionInstance::HostFn(_) => Instructions::new(&[]),
+    }
+  }
+
+  /// Walk instructions as same as `instructions`, and notify `visitor` where blocks start and end.
+  pub fn walk_instructions<V: InstructionVisitor>(&self, visitor: &mut V) {
+    match self {
+      FunctionInstance::LocalFn(f) => walk_instructions(&f.body(), visitor),
+      FunctionInstance::HostFn(_) => {}
+    }
+  }
+
   pub fn validate_type(&self, other: &FunctionType) -> Result<()> {
     let my = match self {
       FunctionInstance::LocalFn(f) => &f.function_type,
@@ -228,7 +775,11 @@ impl FunctionInstance {
 
 impl fmt::Debug for FunctionInstance {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    let empty: Vec<u8> = vec![];
+    let empty: &[u8] = &[];
+    let body = match self {
+      FunctionInstance::LocalFn(f) => Some(f.body()),
+      FunctionInstance::HostFn(_) => None,
+    };
     f.debug_struct("FunctionInstance")
       .field(
         "export_name",
@@ -256,13 +807,69 @@ impl fmt::Debug for FunctionInstance {
           FunctionInstance::HostFn(f) => &f.function_type,
         },
       )
-      .field(
-        "instructions",
-        match self {
-          FunctionInstance::LocalFn(f) => &f.body,
-          FunctionInstance::HostFn(_) => &empty,
-        },
-      )
+      .field("instructions", &body.as_ref().map_or(empty, |body| &**body))
       .finish()
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use embedder::{decode_module, init_store, instantiate_module};
+  use indice::Indice;
+
+  // (module
+  //   (func (export "a") (result i32) i32.const 1)
+  //   (func (export "b") (result i32) i32.const 2))
+  const BYTES: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+    0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+    0x03, 0x03, 0x02, 0x00, 0x00, // function
+    0x07, 0x09, 0x02, 0x01, 0x61, 0x00, 0x00, 0x01, 0x62, 0x00, 0x01, // export
+    0x0a, 0x0b, 0x02, 0x04, 0x00, 0x41, 0x01, 0x0b, 0x04, 0x00, 0x41, 0x02, 0x0b, // code
+  ];
+
+  fn arena_of(function_instance: &FunctionInstance) -> Option<Rc<Vec<u8>>> {
+    match function_instance {
+      FunctionInstance::LocalFn(f) => {
+        let body = f.body.borrow();
+        let arena = match body.as_ref()?.instructions {
+          InstructionBuffer::Arena(ref arena, _, _) => Some(arena.clone()),
+          InstructionBuffer::Owned(_) => None,
+        };
+        arena
+      }
+      FunctionInstance::HostFn(_) => None,
+    }
+  }
+
+  #[test]
+  fn share_arena_between_functions_flattened_at_instantiation() {
+    let mut vm = instantiate_module(
+      init_store(),
+      decode_module(BYTES),
+      Default::default(),
+      65536,
+    )
+    .unwrap();
+    let a = vm
+      .get_function_instance(&Indice::from(0u32))
+      .unwrap()
+      .clone();
+    let b = vm
+      .get_function_instance(&Indice::from(1u32))
+      .unwrap()
+      .clone();
+    assert!(Rc::ptr_eq(&arena_of(&a).unwrap(), &arena_of(&b).unwrap()));
+    assert_eq!(vm.run("a", vec![]), Ok(vec![Values::I32(1)]));
+    assert_eq!(vm.run("b", vec![]), Ok(vec![Values::I32(2)]));
+
+    let mut store = init_store();
+    store.body_decoding = BodyDecoding::Lazy;
+    let mut vm =
+      instantiate_module(store, decode_module(BYTES), Default::default(), 65536).unwrap();
+    assert_eq!(vm.run("b", vec![]), Ok(vec![Values::I32(2)]));
+    let b = vm.get_function_instance(&Indice::from(1u32)).unwrap();
+    assert_eq!(arena_of(&b), None);
+  }
+}
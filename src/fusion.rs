@@ -0,0 +1,133 @@
+use isa::Isa;
+
+fn read_u32(body: &[u8], ptr: usize) -> u32 {
+  let mut buf = [0; 4];
+  buf.clone_from_slice(&body[ptr..(ptr + 4)]);
+  unsafe { core::mem::transmute::<_, u32>(buf) }
+}
+
+/// Size of flattened instruction which starts at `ptr` including its immediates.
+/// Superinstructions are regarded as their first instruction, whose immediates are kept.
+pub(crate) fn size_of_instruction(body: &[u8], ptr: usize) -> usize {
+  use self::Isa::*;
+  match Isa::from(body[ptr]) {
+    Block => 6,
+    Loop => 2,
+    If => 10,
+    GetLocal
+    | SetLocal
+    | TeeLocal
+    | GetGlobal
+    | SetGlobal
+    | Br
+    | BrIf
+    | Call
+    | CallIndirect
+    | I32Const
+    | F32Const
+    | GetLocalGetLocalI32Add
+    | I32ConstI32Add
+    | GetLocalI32Load => 5,
+    I64Const | F64Const => 9,
+    BrTable => 1 + 4 + 4 * read_u32(body, ptr + 1) as usize + 4,
+    I32Load | I64Load | F32Load | F64Load | I32Load8Sign | I32Load8Unsign | I32Load16Sign
+    | I32Load16Unsign | I64Load8Sign | I64Load8Unsign | I64Load16Sign | I64Load16Unsign
+    | I64Load32Sign | I64Load32Unsign | I32Store | I64Store | F32Store | F64Store | I32Store8
+    | I32Store16 | I64Store8 | I64Store16 | I64Store32 => 9,
+    _ => 1,
+  }
+}
+
+fn is_code_at(body: &[u8], ptr: usize, expect: Isa) -> bool {
+  match body.get(ptr) {
+    Some(code) => Isa::from(*code) == expect,
+    None => false,
+  }
+}
+
+/// Peephole pass over flattened instructions which merges frequent sequences into superinstructions.
+/// Only the opcode of the first instruction is replaced, so the size of each block is preserved.
+/// Must be applied after validation, since validator does not know superinstructions.
+pub(crate) fn fuse_superinstructions(body: &mut [u8]) {
+  let mut ptr = 0;
+  while ptr < body.len() {
+    let size = size_of_instruction(body, ptr);
+    match Isa::from(body[ptr]) {
+      Isa::GetLocal
+        if is_code_at(body, ptr + 5, Isa::GetLocal) && is_code_at(body, ptr + 10, Isa::I32Add) =>
+      {
+        body[ptr] = Isa::GetLocalGetLocalI32Add.into();
+        ptr += 11;
+      }
+      Isa::GetLocal if is_code_at(body, ptr + 5, Isa::I32Load) => {
+        body[ptr] = Isa::GetLocalI32Load.into();
+        ptr += 14;
+      }
+      Isa::I32Const if is_code_at(body, ptr + 5, Isa::I32Add) => {
+        body[ptr] = Isa::I32ConstI32Add.into();
+        ptr += 6;
+      }
+      _ => ptr += size,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use isa::into_vec_u8;
+  use isa::ComposedCode::*;
+
+  #[test]
+  fn fuse_frequent_sequences() {
+    let mut body = into_vec_u8(&[
+      Code(Isa::Block),
+      Byte(25),
+      Byte(0),
+      Byte(0),
+      Byte(0),
+      Byte(0x40),
+      Code(Isa::GetLocal),
+      Byte(0),
+      Byte(0),
+      Byte(0),
+      Byte(0),
+      Code(Isa::GetLocal),
+      Byte(1),
+      Byte(0),
+      Byte(0),
+      Byte(0),
+      Code(Isa::I32Add),
+      Code(Isa::I32Const),
+      Byte(1),
+      Byte(0),
+      Byte(0),
+      Byte(0),
+      Code(Isa::I32Add),
+      Code(Isa::DropInst),
+      Code(Isa::End),
+      Code(Isa::GetLocal),
+      Byte(0),
+      Byte(0),
+      Byte(0),
+      Byte(0),
+      Code(Isa::I32Load),
+      Byte(2),
+      Byte(0),
+      Byte(0),
+      Byte(0),
+      Byte(0),
+      Byte(0),
+      Byte(0),
+      Byte(0),
+      Code(Isa::End),
+    ]);
+    let size_of_body = body.len();
+    fuse_superinstructions(&mut body);
+    assert_eq!(body.len(), size_of_body);
+    assert_eq!(Isa::from(body[6]), Isa::GetLocalGetLocalI32Add);
+    assert_eq!(Isa::from(body[11]), Isa::GetLocal);
+    assert_eq!(Isa::from(body[17]), Isa::I32ConstI32Add);
+    assert_eq!(Isa::from(body[25]), Isa::GetLocalI32Load);
+  }
+}
@@ -1,12 +1,12 @@
-#[cfg(not(test))]
+#[cfg(not(any(test, feature = "std")))]
 use alloc::prelude::*;
 use alloc::rc::Rc;
 use alloc::string::String;
 use alloc::vec::Vec;
+use const_expr;
 use core::cell::RefCell;
 use error::{Result, Trap, WasmError};
 use indice::Indice;
-use isa::Isa;
 use module::{
   ExternalInterface, ExternalInterfaces, ExternalModules, ImportDescriptor, ModuleDescriptor,
   GLOBAL_DESCRIPTOR,
@@ -61,6 +61,19 @@ impl GlobalInstance {
     self.0.borrow().export_name == Some(name.to_string())
   }
 
+  pub(crate) fn is_mutable(&self) -> bool {
+    match self.0.borrow().global_type {
+      GlobalType::Var(_) => true,
+      GlobalType::Const(_) => false,
+    }
+  }
+
+  pub(crate) fn value_type(&self) -> ValueTypes {
+    match &self.0.borrow().global_type {
+      GlobalType::Var(ty) | GlobalType::Const(ty) => ty.clone(),
+    }
+  }
+
   fn is_same_type(&self, ty: &GlobalType) -> bool {
     &self.0.borrow().global_type == ty
   }
@@ -104,44 +117,13 @@ impl GlobalInstances {
         x => unreachable!("Expected global descriptor, got {:?}", x),
       };
     }
+    // NOTE: Imported globals occupy lower indices of global index space.
+    let count_of_imports = global_instances.len();
     for (idx, (global_type, init)) in globals.into_iter().enumerate() {
       let export_name = exports
-        .find_kind_by_idx(idx as u32, &GLOBAL_DESCRIPTOR)
+        .find_kind_by_idx((count_of_imports + idx) as u32, &GLOBAL_DESCRIPTOR)
         .map(|x| x.name.to_owned());
-      let init_first = init.first()?;
-      let value = match Isa::from(*init_first) {
-        Isa::I32Const => {
-          let mut buf = [0; 4];
-          buf.clone_from_slice(&init[1..5]);
-          Values::I32(unsafe { core::mem::transmute::<_, u32>(buf) } as i32)
-        }
-        Isa::I64Const => {
-          let mut buf = [0; 8];
-          buf.clone_from_slice(&init[1..9]);
-          Values::I64(unsafe { core::mem::transmute::<_, u64>(buf) } as i64)
-        }
-        Isa::F32Const => {
-          let mut buf = [0; 4];
-          buf.clone_from_slice(&init[1..5]);
-          Values::F32(f32::from_bits(unsafe {
-            core::mem::transmute::<_, u32>(buf)
-          }))
-        }
-        Isa::F64Const => {
-          let mut buf = [0; 8];
-          buf.clone_from_slice(&init[1..9]);
-          Values::F64(f64::from_bits(unsafe {
-            core::mem::transmute::<_, u64>(buf)
-          }))
-        }
-        Isa::GetGlobal => {
-          let mut buf = [0; 4];
-          buf.clone_from_slice(&init[1..5]);
-          let idx = Indice::from(unsafe { core::mem::transmute::<_, u32>(buf) });
-          global_instances.get(idx.to_usize())?.get_value()
-        }
-        x => unreachable!("Expected initial value of global, got {:?}", x),
-      };
+      let value = const_expr::evaluate(&init, &global_instances)?;
       global_instances.push(GlobalInstance::new(global_type, value, export_name));
     }
     Ok(GlobalInstances::new(global_instances))
@@ -156,6 +138,22 @@ impl GlobalInstances {
       .cloned()
   }
 
+  /// Globals followed by `global_instances`, which share each global but not the list of them.
+  pub(crate) fn appended(&self, global_instances: Vec<GlobalInstance>) -> GlobalInstances {
+    let mut appended = self.0.borrow().clone();
+    appended.extend(global_instances);
+    GlobalInstances::new(appended)
+  }
+
+  /// Evaluate a constant expression which refers to globals of this instance.
+  pub(crate) fn evaluate(&self, expression: &[u8]) -> Result<Values> {
+    const_expr::evaluate(expression, &self.0.borrow())
+  }
+
+  pub(crate) fn get_global_instance(&self, idx: &Indice) -> Option<GlobalInstance> {
+    self.0.borrow().get(idx.to_usize()).cloned()
+  }
+
   pub fn get_global(&self, idx: &Indice) -> Result<Values> {
     self
       .0
@@ -0,0 +1,81 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use error::{Result, Trap};
+use function::{FunctionInstance, FunctionType};
+use value::{Values, WasmValue};
+use value_type::ValueTypes;
+
+/// Results of host functions made by `IntoHostFunc`, which are a value or nothing.
+pub trait WasmResults {
+  fn value_types() -> Vec<ValueTypes>;
+
+  fn into_values(self) -> Vec<Values>;
+}
+
+impl WasmResults for () {
+  fn value_types() -> Vec<ValueTypes> {
+    vec![]
+  }
+
+  fn into_values(self) -> Vec<Values> {
+    vec![]
+  }
+}
+
+impl<T: WasmValue> WasmResults for T {
+  fn value_types() -> Vec<ValueTypes> {
+    vec![T::value_type()]
+  }
+
+  fn into_values(self) -> Vec<Values> {
+    vec![self.into_value()]
+  }
+}
+
+/// Closures whose parameters and results are `WasmValue`, e.g. `|a: i32, b: i32| a + b`,
+/// which become host functions of the signature derived from their types.
+/// It's implemented for closures of up to 8 parameters, where `Params` is a tuple of their types.
+pub trait IntoHostFunc<Params, Results> {
+  fn into_host_func(self, export_name: Option<String>) -> FunctionInstance;
+}
+
+// NOTE: Imports are linked only when their signatures match, but modules which aren't validated may pass others.
+// Such arguments trap by `TypeMismatch` instead of panicking, which aborts embedders without std.
+macro_rules! impl_into_host_func {
+  ($($param: ident $argument: ident),*) => {
+    impl<F, $($param,)* R> IntoHostFunc<($($param,)*), R> for F
+    where
+      F: Fn($($param),*) -> R + 'static,
+      $($param: WasmValue,)*
+      R: WasmResults,
+    {
+      fn into_host_func(self, export_name: Option<String>) -> FunctionInstance {
+        let function_type = FunctionType::new(
+          vec![$(<$param as WasmValue>::value_type()),*],
+          R::value_types(),
+        );
+        #[allow(unused_variables, unused_mut)]
+        let callable = move |arguments: &[Values]| -> Result<Vec<Values>> {
+          let mut arguments = arguments.iter();
+          $(
+            let $argument = <$param as WasmValue>::from_value(
+              arguments.next().ok_or(Trap::TypeMismatch)?,
+            )?;
+          )*
+          Ok(self($($argument),*).into_values())
+        };
+        FunctionInstance::new_fallible_host_closure(export_name, function_type, callable)
+      }
+    }
+  };
+}
+
+impl_into_host_func!();
+impl_into_host_func!(A1 a1);
+impl_into_host_func!(A1 a1, A2 a2);
+impl_into_host_func!(A1 a1, A2 a2, A3 a3);
+impl_into_host_func!(A1 a1, A2 a2, A3 a3, A4 a4);
+impl_into_host_func!(A1 a1, A2 a2, A3 a3, A4 a4, A5 a5);
+impl_into_host_func!(A1 a1, A2 a2, A3 a3, A4 a4, A5 a5, A6 a6);
+impl_into_host_func!(A1 a1, A2 a2, A3 a3, A4 a4, A5 a5, A6 a6, A7 a7);
+impl_into_host_func!(A1 a1, A2 a2, A3 a3, A4 a4, A5 a5, A6 a6, A7 a7, A8 a8);
@@ -0,0 +1,194 @@
+use core::cell::Ref;
+use fusion::size_of_instruction;
+use isa::Isa;
+use opcode::OpcodeInfo;
+
+/// Iterator over flattened instructions of a function, which yields position of each instruction,
+/// its opcode and count of blocks, loops and ifs enclosing it.
+/// Block, Loop and If are at depth outside of them, and Else and End are at depth inside.
+/// Superinstructions are yielded as instructions which they were fused from.
+pub struct Instructions<'a> {
+  body: Body<'a>,
+  ptr: usize,
+  label_depth: usize,
+}
+
+// NOTE: Bodies of functions are borrowed from their cells, since they may be flattened lazily.
+enum Body<'a> {
+  Slice(&'a [u8]),
+  Borrowed(Ref<'a, [u8]>),
+}
+
+impl<'a> Body<'a> {
+  fn as_slice(&self) -> &[u8] {
+    match self {
+      Body::Slice(body) => body,
+      Body::Borrowed(body) => body,
+    }
+  }
+}
+
+impl<'a> Instructions<'a> {
+  pub(crate) fn new(body: &'a [u8]) -> Self {
+    Instructions::with_body(Body::Slice(body))
+  }
+
+  pub(crate) fn borrowed(body: Ref<'a, [u8]>) -> Self {
+    Instructions::with_body(Body::Borrowed(body))
+  }
+
+  fn with_body(body: Body<'a>) -> Self {
+    Instructions {
+      body,
+      ptr: 0,
+      label_depth: 0,
+    }
+  }
+}
+
+// NOTE: Only the first opcode of fused sequence is replaced, so following ones are yielded as they are.
+fn unfused(code: u8) -> u8 {
+  match Isa::from(code) {
+    Isa::GetLocalGetLocalI32Add | Isa::GetLocalI32Load => Isa::GetLocal.into(),
+    Isa::I32ConstI32Add => Isa::I32Const.into(),
+    _ => code,
+  }
+}
+
+impl<'a> Iterator for Instructions<'a> {
+  type Item = (usize, &'static OpcodeInfo, usize);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let offset = self.ptr;
+    let body = self.body.as_slice();
+    let info = OpcodeInfo::of(unfused(*body.get(offset)?))?;
+    self.ptr += size_of_instruction(body, offset);
+    let label_depth = self.label_depth;
+    match Isa::from(info.code) {
+      Isa::Block | Isa::Loop | Isa::If => self.label_depth += 1,
+      Isa::End => self.label_depth = self.label_depth.saturating_sub(1),
+      _ => {}
+    }
+    Some((offset, info, label_depth))
+  }
+}
+
+/// Callbacks to walk nested structure of a function, all of which do nothing by default.
+/// Else of If is notified between its branches, and End of the function itself is not notified.
+pub trait InstructionVisitor {
+  /// Called for every instruction, including ones which start or end blocks.
+  fn visit(&mut self, _offset: usize, _info: &'static OpcodeInfo, _label_depth: usize) {}
+
+  /// Block, Loop or If starts, `label_depth` is the depth inside of it.
+  fn enter_block(&mut self, _offset: usize, _info: &'static OpcodeInfo, _label_depth: usize) {}
+
+  /// Else-branch of If starts.
+  fn enter_else(&mut self, _offset: usize, _label_depth: usize) {}
+
+  /// Block, Loop or If ends.
+  fn exit_block(&mut self, _offset: usize, _label_depth: usize) {}
+}
+
+pub(crate) fn walk_instructions<V: InstructionVisitor>(body: &[u8], visitor: &mut V) {
+  for (offset, info, label_depth) in Instructions::new(body) {
+    visitor.visit(offset, info, label_depth);
+    match Isa::from(info.code) {
+      Isa::Block | Isa::Loop | Isa::If => visitor.enter_block(offset, info, label_depth + 1),
+      Isa::Else => visitor.enter_else(offset, label_depth),
+      Isa::End if label_depth > 0 => visitor.exit_block(offset, label_depth),
+      _ => {}
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use function::{FunctionInstance, FunctionType};
+  use isa::into_vec_u8;
+  use isa::ComposedCode::*;
+
+  #[derive(Default)]
+  struct Events(Vec<(&'static str, usize, usize)>);
+
+  impl InstructionVisitor for Events {
+    fn enter_block(&mut self, offset: usize, info: &'static OpcodeInfo, label_depth: usize) {
+      self.0.push((info.mnemonic, offset, label_depth));
+    }
+
+    fn enter_else(&mut self, offset: usize, label_depth: usize) {
+      self.0.push(("else", offset, label_depth));
+    }
+
+    fn exit_block(&mut self, offset: usize, label_depth: usize) {
+      self.0.push(("end", offset, label_depth));
+    }
+  }
+
+  #[test]
+  fn walk_nested_instructions() {
+    let body = into_vec_u8(&[
+      Code(Isa::Block),
+      Byte(0),
+      Byte(0),
+      Byte(0),
+      Byte(0),
+      Byte(0x40),
+      Code(Isa::I32ConstI32Add),
+      Byte(1),
+      Byte(0),
+      Byte(0),
+      Byte(0),
+      Code(Isa::I32Add),
+      Code(Isa::If),
+      Byte(0),
+      Byte(0),
+      Byte(0),
+      Byte(0),
+      Byte(0),
+      Byte(0),
+      Byte(0),
+      Byte(0),
+      Byte(0x40),
+      Code(Isa::Nop),
+      Code(Isa::Else),
+      Code(Isa::Nop),
+      Code(Isa::End),
+      Code(Isa::End),
+      Code(Isa::End),
+    ]);
+    let function_instance =
+      FunctionInstance::new(None, FunctionType::new(vec![], vec![]), vec![], body);
+    assert_eq!(
+      function_instance
+        .instructions()
+        .map(|(offset, info, label_depth)| (info.mnemonic, offset, label_depth))
+        .collect::<Vec<_>>(),
+      vec![
+        ("block", 0, 0),
+        ("i32.const", 6, 1),
+        ("i32.add", 11, 1),
+        ("if", 12, 1),
+        ("nop", 22, 2),
+        ("else", 23, 2),
+        ("nop", 24, 2),
+        ("end", 25, 2),
+        ("end", 26, 1),
+        ("end", 27, 0),
+      ]
+    );
+
+    let mut events = Events::default();
+    function_instance.walk_instructions(&mut events);
+    assert_eq!(
+      events.0,
+      vec![
+        ("block", 0, 1),
+        ("if", 12, 2),
+        ("else", 23, 2),
+        ("end", 25, 2),
+        ("end", 26, 1),
+      ]
+    );
+  }
+}
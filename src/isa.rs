@@ -2,7 +2,7 @@ use core::convert::From;
 use core::convert::Into;
 use error::{Result, Trap, WasmError};
 use global::GlobalInstances;
-use indice::Indice;
+use value::Values;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Isa {
@@ -179,6 +179,27 @@ pub enum Isa {
   I64ReinterpretF64,
   F32ReinterpretI32,
   F64ReinterpretI64,
+
+  // NOTE: Superinstructions which never appear in binary format.
+  // Fuser rewrites only the first opcode of a sequence, so operands of following instructions stay in place.
+  GetLocalGetLocalI32Add,
+  I32ConstI32Add,
+  GetLocalI32Load,
+}
+
+/// Post-MVP proposals whose instructions are recognized but not implemented.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Proposal {
+  MultiValue,
+  SignExtension,
+  SaturatingFloatToInt,
+  TailCall,
+  ExceptionHandling,
+  ReferenceTypes,
+  BulkMemory,
+  Simd,
+  Threads,
+  Gc,
 }
 
 impl Isa {
@@ -186,25 +207,10 @@ impl Isa {
     source: &[u8],
     global_instances: &GlobalInstances,
   ) -> Result<usize> {
-    use self::Isa::*;
-    let constant_instruction = Isa::from(*source.first()?);
-    match constant_instruction {
-      I32Const => {
-        let mut buf = [0; 4];
-        buf.clone_from_slice(&source[1..5]);
-        let offset = unsafe { core::mem::transmute::<_, u32>(buf) } as i32;
-        if offset < 0 {
-          return Err(WasmError::Trap(Trap::DataSegmentDoesNotFit));
-        }
-        Ok(offset as usize)
-      }
-      GetGlobal => {
-        let mut buf = [0; 4];
-        buf.clone_from_slice(&source[1..5]);
-        let idx = Indice::from(unsafe { core::mem::transmute::<_, u32>(buf) });
-        Ok(global_instances.get_global_ext(&idx) as usize)
-      }
-      _ => unreachable!("Expected offset value of memory"),
+    match global_instances.evaluate(source)? {
+      Values::I32(offset) if offset < 0 => Err(WasmError::Trap(Trap::DataSegmentDoesNotFit)),
+      Values::I32(offset) => Ok(offset as usize),
+      _ => Err(WasmError::Trap(Trap::TypeMismatch)),
     }
   }
 }
@@ -388,6 +394,9 @@ impl From<u8> for Isa {
       0xbd => I64ReinterpretF64,
       0xbe => F32ReinterpretI32,
       0xbf => F64ReinterpretI64,
+      0xe0 => GetLocalGetLocalI32Add,
+      0xe1 => I32ConstI32Add,
+      0xe2 => GetLocalI32Load,
       x => unreachable!("Code {:x?} does not supported yet.", x),
     }
   }
@@ -570,6 +579,9 @@ impl Into<u8> for Isa {
       I64ReinterpretF64 => 0xbd,
       F32ReinterpretI32 => 0xbe,
       F64ReinterpretI64 => 0xbf,
+      GetLocalGetLocalI32Add => 0xe0,
+      I32ConstI32Add => 0xe1,
+      GetLocalI32Load => 0xe2,
     }
   }
 }
@@ -592,9 +604,38 @@ pub fn into_vec_u8(this: &[ComposedCode]) -> alloc::vec::Vec<u8> {
 }
 
 impl Isa {
-  pub fn is_else_or_end(code: Option<u8>) -> bool {
+  // NOTE: Instructions prefixed by 0xfc are distinguished by following sub-opcode.
+  pub fn proposal_of(code: u8, next: Option<u8>) -> Option<Proposal> {
+    use self::Proposal::*;
+    match code {
+      0xfc if next.map_or(false, |sub| sub <= 0x07) => Some(SaturatingFloatToInt),
+      0x06..=0x09 | 0x18 | 0x19 => Some(ExceptionHandling),
+      0x12 | 0x13 => Some(TailCall),
+      0x1c | 0x25 | 0x26 | 0xd0..=0xd2 => Some(ReferenceTypes),
+      0xc0..=0xc4 => Some(SignExtension),
+      0xfb => Some(Gc),
+      0xfc => Some(BulkMemory),
+      0xfd => Some(Simd),
+      0xfe => Some(Threads),
+      _ => None,
+    }
+  }
+
+  // NOTE: Reserved and unassigned opcodes, and ones of superinstructions which are never encoded in binary format.
+  // Opcodes of proposals must be checked by `proposal_of` before.
+  pub fn is_illegal(code: u8) -> bool {
+    match code {
+      0x06..=0x0a | 0x12..=0x19 | 0x1c..=0x1f | 0x25..=0x27 | 0xc0..=0xff => true,
+      _ => false,
+    }
+  }
+
+  // NOTE: Loads, stores, constants, comparisons and arithmetics of f32 and f64,
+  // and conversions from or into them. 0xa7, 0xac and 0xad are conversions between integers.
+  pub fn is_float(code: u8) -> bool {
     match code {
-      Some(0x5) | Some(0x0b) => true,
+      0x2a | 0x2b | 0x38 | 0x39 | 0x43 | 0x44 => true,
+      0x5b..=0x66 | 0x8b..=0xa6 | 0xa8..=0xab | 0xae..=0xbf => true,
       _ => false,
     }
   }
@@ -608,4 +649,44 @@ mod tests {
   fn instruction_size() {
     assert_eq!(core::mem::size_of::<Isa>(), 1);
   }
+
+  #[test]
+  fn detect_proposals_of_unsupported_opcodes() {
+    assert_eq!(Isa::proposal_of(0xfd, Some(0x0c)), Some(Proposal::Simd));
+    assert_eq!(Isa::proposal_of(0xfe, Some(0x00)), Some(Proposal::Threads));
+    assert_eq!(Isa::proposal_of(0xfb, Some(0x00)), Some(Proposal::Gc));
+    assert_eq!(
+      Isa::proposal_of(0xfc, Some(0x00)),
+      Some(Proposal::SaturatingFloatToInt)
+    );
+    assert_eq!(
+      Isa::proposal_of(0xfc, Some(0x0a)),
+      Some(Proposal::BulkMemory)
+    );
+    assert_eq!(Isa::proposal_of(0x6a, None), None);
+  }
+
+  #[test]
+  fn detect_float_opcodes() {
+    let floats = [
+      Isa::F32Const,
+      Isa::F64Load,
+      Isa::F32Equal,
+      Isa::F64Copysign,
+      Isa::I32TruncSignF64,
+      Isa::F64ReinterpretI64,
+    ];
+    for code in floats.iter() {
+      assert!(Isa::is_float(code.clone().into()), "{:?}", code);
+    }
+    let integers = [
+      Isa::I32Const,
+      Isa::I64Load,
+      Isa::I32WrapI64,
+      Isa::I64ExtendUnsignI32,
+    ];
+    for code in integers.iter() {
+      assert!(!Isa::is_float(code.clone().into()), "{:?}", code);
+    }
+  }
 }
@@ -16,3 +16,14 @@ pub struct Label {
   pub(crate) return_type: ValueTypes,
   pub(crate) continuation: u32,
 }
+
+impl Label {
+  /// Count of values which a branch to the label carries.
+  /// A loop is branched to its beginning, so its arity is of its parameters, which are none before multi-value.
+  pub(crate) fn arity(&self) -> usize {
+    match (&self.source_instruction, &self.return_type) {
+      (LabelKind::Loop, _) | (_, ValueTypes::Unit) => 0,
+      _ => 1,
+    }
+  }
+}
@@ -1,56 +1,142 @@
+//! WebAssembly virtual machine which aims to fit embedded systems.
+//!
+//! See [`prelude`](prelude/index.html) for the stable public API.
+
 #![feature(try_trait)]
 #![feature(try_from)]
 #![feature(int_to_from_bytes)]
 #![feature(alloc)]
 #![feature(core_intrinsics)]
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 #![allow(clippy::needless_range_loop)]
 
-#[cfg(test)]
+#[cfg(any(test, feature = "std"))]
 #[macro_use]
 extern crate std as alloc;
 
 // FIXME: Use 'heapless' crate.
-#[cfg(not(test))]
+#[cfg(not(any(test, feature = "std")))]
 #[macro_use]
 extern crate alloc;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "std"))]
 #[macro_use]
 extern crate core;
 
 extern crate heapless;
 extern crate libm;
+#[cfg(feature = "log")]
+#[macro_use]
+extern crate log;
+#[cfg(feature = "mmap-file")]
+extern crate memmap;
+#[cfg(feature = "parallel")]
+extern crate rayon;
 
+mod backtrace;
+mod cache;
+#[cfg(feature = "checked-interp")]
+mod checked;
+mod component;
+mod const_expr;
 #[macro_use]
 mod decode;
+mod diagnostics;
+mod dylink;
 mod embedder;
+mod engine;
 mod error;
+#[cfg(any(test, feature = "std"))]
+mod execution;
+mod features;
+mod float_literals;
 mod frame;
 mod function;
+mod fusion;
 mod global;
+mod host_func;
 mod indice;
+mod instructions;
 mod isa;
 mod label;
+mod linker;
+pub mod lint;
 mod memory;
+mod memory_backend;
+mod metrics;
 mod module;
+mod namespace;
+mod nan_box;
+mod opcode;
+pub mod prelude;
+mod resume;
+mod script;
+mod snapshot;
 mod spectest;
 mod stack;
+mod stack_usage;
 mod store;
+mod symbol;
 mod table;
+mod trap;
 mod validate;
 mod value;
 mod value_type;
 mod vm;
+mod wasi;
 
-pub use self::embedder::{decode_module, init_store, instantiate_module, validate_module};
-pub use self::error::{Trap, WasmError};
-pub use self::function::{FunctionInstance, FunctionType};
-pub use self::module::{ExternalModule, ExternalModules};
-pub use self::spectest::create_spectest;
-pub use self::value::Values;
+pub use self::backtrace::BacktraceFrame;
+pub use self::cache::ModuleCache;
+pub use self::component::ComponentInstance;
+pub use self::decode::{
+    Component, DebugLines, DecodeLimits, Dylink, ElementType, InstructionOffsets, ProducerVersion,
+    Producers, SectionCode, SourceLocation, TableIndexType, TableType, Verifier,
+};
+pub use self::diagnostics::Warning;
+pub use self::dylink::DynamicLinking;
+pub use self::embedder::{
+    decode_component, decode_module, decode_module_with_limits, decode_module_with_verifier,
+    init_store, instantiate_component, instantiate_module, lint_module, validate_module,
+    validate_module_with_diagnostics, validate_module_with_features, validate_module_with_limits,
+};
+pub use self::engine::{Engine, EngineLimits, InstanceGroup, InterruptHandle};
+pub use self::error::{Trap, TypeError, WasmError, TRAP_CODES, TYPE_ERROR_CODES};
+#[cfg(any(test, feature = "std"))]
+pub use self::execution::{ExecutionService, Job, JobHandle, ModuleHandle};
+pub use self::features::Features;
+pub use self::float_literals::{parse_f32_bits, parse_f64_bits};
+pub use self::function::{
+    BodyDecoding, FunctionInstance, FunctionType, InstructionFusion, RawHostFn, VmContext,
+};
+pub use self::host_func::{IntoHostFunc, WasmResults};
+pub use self::instructions::{InstructionVisitor, Instructions};
+pub use self::isa::Proposal;
+pub use self::linker::Linker;
+pub use self::lint::LintDiagnostic;
+pub use self::memory::{Limit, MemoryGrowth, MemoryInstance, MemoryView};
+pub use self::memory_backend::{EagerBackend, LazyBackend, MemoryBackend, MemoryStrategy};
+#[cfg(feature = "mmap-file")]
+pub use self::memory_backend::{MmapBackend, MmapMode};
+pub use self::metrics::Metrics;
+pub use self::module::{ExportSuggestion, ExternalModule, ExternalModules, WeakExternalModules};
+pub use self::namespace::{Namespace, WeakNamespace};
+pub use self::nan_box::{NanBox, ValueRepresentation};
+pub use self::opcode::{Immediate, OpcodeInfo, StackEffect, OPCODES};
+pub use self::resume::{BoundedRun, ResumeToken};
+pub use self::script::ScriptEnvironment;
+pub use self::snapshot::{FrameSnapshot, StackSnapshot};
+pub use self::spectest::{create_spectest, create_spectest_with_printer};
+pub use self::stack_usage::{StackLimits, StackUsage};
+pub use self::store::{ExecutionLimits, Store};
+pub use self::symbol::{Symbol, SymbolTable};
+pub use self::table::SegmentInitialization;
+pub use self::trap::{RunOutcome, TrapDetails};
+pub use self::value::{
+    ArgumentCoercion, ConditionMode, I64Boundary, SubnormalMode, Values, WasmValue,
+};
 pub use self::value_type::ValueTypes;
 pub use self::vm::ModuleInstance;
+pub use self::wasi::{Wasi, WasiClock, WasiConfig, WasiOutput, WASI_MODULE_NAME};
 
 #[cfg(test)]
 mod tests {
@@ -79,7 +165,7 @@ mod tests {
                 let section = decode_module(&bytes);
                 let mut vm = instantiate_module(store, section, Default::default(), 65536).unwrap();
                 let actual = vm.run("_subject", $call_arguments).unwrap();
-                assert_eq!(actual, Values::I32($expect_value));
+                assert_eq!(actual, vec![Values::I32($expect_value)]);
             }
         };
     }
@@ -124,7 +210,7 @@ mod tests {
                 [Values::I32(3), Values::I32(5)].to_vec(),
             )
             .unwrap();
-        assert_eq!(actual, Values::I32(25));
+        assert_eq!(actual, vec![Values::I32(25)]);
     }
 
     test_eval!(evaluate_cons8, "cons8", vec![], 42);
@@ -155,4 +241,3406 @@ mod tests {
     test_eval!(evaluate_5_count, "count", vec![Values::I32(5)], 35);
     test_eval!(evaluate_10_count, "count", vec![Values::I32(10)], 145);
     test_eval!(evaluate_100_count, "count", vec![Values::I32(100)], 14950);
+
+    #[test]
+    fn evaluate_function_without_result() {
+        // (module (func (export "f")))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x07, 0x05, 0x01, 0x01, 0x66, 0x00, 0x00, // export section
+            0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b, // code section
+        ];
+        let store = init_store();
+        let section = decode_module(&bytes);
+        let mut vm = instantiate_module(store, section, Default::default(), 65536).unwrap();
+        assert_eq!(vm.run("f", vec![]).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn evaluate_call_with_multiple_arguments() {
+        // (module
+        //   (func (param i32 i32) (result i32) get_local 0 get_local 1 i32.sub)
+        //   (func (export "run") (result i32) i32.const 10 i32.const 3 call 0))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x0b, 0x02, // type
+            0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, // (i32, i32) -> (i32)
+            0x60, 0x00, 0x01, 0x7f, // () -> (i32)
+            0x03, 0x03, 0x02, 0x00, 0x01, // function
+            0x07, 0x07, 0x01, 0x03, 0x72, 0x75, 0x6e, 0x00, 0x01, // export
+            0x0a, 0x12, 0x02, // code
+            0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x6b, 0x0b, // body of 0
+            0x08, 0x00, 0x41, 0x0a, 0x41, 0x03, 0x10, 0x00, 0x0b, // body of 1
+        ];
+        let store = init_store();
+        let section = decode_module(&bytes);
+        let mut vm = instantiate_module(store, section, Default::default(), 65536).unwrap();
+        assert_eq!(vm.run("run", vec![]).unwrap(), vec![Values::I32(7)]);
+    }
+
+    #[test]
+    fn validate_functions_independently() {
+        // (module
+        //   (func (param i64) (result i64) get_local 0)
+        //   (func (param i32) (result i32) get_local 0))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x0b, 0x02, // type
+            0x60, 0x01, 0x7e, 0x01, 0x7e, // (i64) -> (i64)
+            0x60, 0x01, 0x7f, 0x01, 0x7f, // (i32) -> (i32)
+            0x03, 0x03, 0x02, 0x00, 0x01, // function
+            0x0a, 0x0b, 0x02, // code
+            0x04, 0x00, 0x20, 0x00, 0x0b, // body of 0
+            0x04, 0x00, 0x20, 0x00, 0x0b, // body of 1
+        ];
+        assert!(validate_module(&decode_module(&bytes)).is_ok());
+    }
+
+    #[test]
+    fn report_index_of_unknown_function_type() {
+        // (module (func (type 1)))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type
+            0x03, 0x02, 0x01, 0x01, // function
+            0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b, // code
+        ];
+        assert_eq!(
+            validate_module(&decode_module(&bytes)),
+            Err(WasmError::TypeError(TypeError::UnknownFunctionType(1)))
+        );
+    }
+
+    #[test]
+    fn carry_values_of_branches_over_inner_labels() {
+        // (module
+        //   (func (export "br") (result i32)
+        //     (block (result i32)
+        //       (i32.const 1)
+        //       (block (result i32) (i32.const 2) (i32.const 3) (br 1))
+        //       (i32.add)))
+        //   (func (export "br_if") (param i32) (result i32)
+        //     (block (result i32)
+        //       (i32.const 1)
+        //       (block (result i32) (i32.const 2) (i32.const 10) (br_if 1 (get_local 0)) (drop))
+        //       (i32.add))))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x0a, 0x02, 0x60, 0x00, 0x01, 0x7f, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type
+            0x03, 0x03, 0x02, 0x00, 0x01, // function
+            0x07, 0x0e, 0x02, 0x02, 0x62, 0x72, 0x00, 0x00, 0x05, 0x62, 0x72, 0x5f, 0x69, 0x66,
+            0x00, 0x01, // export
+            0x0a, 0x28, 0x02, // code
+            0x11, 0x00, 0x02, 0x7f, 0x41, 0x01, 0x02, 0x7f, 0x41, 0x02, 0x41, 0x03, 0x0c, 0x01,
+            0x0b, 0x6a, 0x0b, 0x0b, // body of br
+            0x14, 0x00, 0x02, 0x7f, 0x41, 0x01, 0x02, 0x7f, 0x41, 0x02, 0x41, 0x0a, 0x20, 0x00,
+            0x0d, 0x01, 0x1a, 0x0b, 0x6a, 0x0b, 0x0b, // body of br_if
+        ];
+        let module = decode_module(&bytes);
+        validate_module(&module).unwrap();
+        let mut vm = instantiate_module(init_store(), module, Default::default(), 65536).unwrap();
+        assert_eq!(vm.run("br", vec![]), Ok(vec![Values::I32(3)]));
+        assert_eq!(
+            vm.run("br_if", vec![Values::I32(1)]),
+            Ok(vec![Values::I32(10)])
+        );
+        assert_eq!(
+            vm.run("br_if", vec![Values::I32(0)]),
+            Ok(vec![Values::I32(3)])
+        );
+    }
+
+    #[test]
+    fn discard_values_on_branches_to_loops() {
+        // (module
+        //   (func (export "count") (param i32) (result i32)
+        //     (i32.const 50)
+        //     (loop (result i32)
+        //       (tee_local 0 (i32.sub (get_local 0) (i32.const 1)))
+        //       (br_if 0 (get_local 0)))
+        //     (i32.add)))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x09, 0x01, 0x05, 0x63, 0x6f, 0x75, 0x6e, 0x74, 0x00, 0x00, // export
+            0x0a, 0x15, 0x01, 0x13, 0x00, 0x41, 0x32, 0x03, 0x7f, 0x20, 0x00, 0x41, 0x01, 0x6b,
+            0x22, 0x00, 0x20, 0x00, 0x0d, 0x00, 0x0b, 0x6a, 0x0b, // code
+        ];
+        let module = decode_module(&bytes);
+        validate_module(&module).unwrap();
+        let mut vm = instantiate_module(init_store(), module, Default::default(), 65536).unwrap();
+        assert_eq!(
+            vm.run("count", vec![Values::I32(3)]),
+            Ok(vec![Values::I32(50)])
+        );
+    }
+
+    #[test]
+    fn report_duplicate_export_name() {
+        // (module (func) (export "a" (func 0)) (export "b" (func 0)) (export "a" (func 0)))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x0d, 0x03, 0x01, 0x61, 0x00, 0x00, 0x01, 0x62, 0x00, 0x00, 0x01, 0x61, 0x00,
+            0x00, // export
+            0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b, // code
+        ];
+        assert_eq!(
+            validate_module(&decode_module(&bytes)),
+            Err(WasmError::TypeError(TypeError::DuplicateExportName(
+                "a".to_owned()
+            )))
+        );
+    }
+
+    #[test]
+    fn evaluate_function_exported_after_imports() {
+        // (module
+        //   (import "spectest" "print" (func))
+        //   (func (export "g") (result i32) i32.const 42))
+        let bytes_of_exporter = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x08, 0x02, 0x60, 0x00, 0x00, 0x60, 0x00, 0x01, 0x7f, // type
+            0x02, 0x12, 0x01, // import
+            0x08, 0x73, 0x70, 0x65, 0x63, 0x74, 0x65, 0x73, 0x74, // "spectest"
+            0x05, 0x70, 0x72, 0x69, 0x6e, 0x74, 0x00, 0x00, // "print"
+            0x03, 0x02, 0x01, 0x01, // function
+            0x07, 0x05, 0x01, 0x01, 0x67, 0x00, 0x01, // export
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x41, 0x2a, 0x0b, // code
+        ];
+        // (module
+        //   (import "m" "g" (func (result i32)))
+        //   (func (export "run") (result i32) call 0))
+        let bytes_of_importer = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+            0x02, 0x07, 0x01, 0x01, 0x6d, 0x01, 0x67, 0x00, 0x00, // import
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x07, 0x01, 0x03, 0x72, 0x75, 0x6e, 0x00, 0x01, // export
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x10, 0x00, 0x0b, // code
+        ];
+        let mut external_modules = ExternalModules::default();
+        external_modules
+            .register_module(Some("spectest".to_owned()), create_spectest())
+            .unwrap();
+        let exporter = instantiate_module(
+            init_store(),
+            decode_module(&bytes_of_exporter),
+            external_modules.clone(),
+            65536,
+        )
+        .unwrap();
+        external_modules
+            .register_module(Some("m".to_owned()), exporter.export_module())
+            .unwrap();
+        let mut importer = instantiate_module(
+            init_store(),
+            decode_module(&bytes_of_importer),
+            external_modules,
+            65536,
+        )
+        .unwrap();
+        assert_eq!(importer.run("run", vec![]).unwrap(), vec![Values::I32(42)]);
+    }
+
+    #[test]
+    fn resolve_backtrace_by_debug_line() {
+        // (module
+        //   (func unreachable)
+        //   (func (export "run") call 0))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type
+            0x03, 0x03, 0x02, 0x00, 0x00, // function
+            0x07, 0x07, 0x01, 0x03, 0x72, 0x75, 0x6e, 0x00, 0x01, // export
+            0x0a, 0x0a, 0x02, // code
+            0x03, 0x00, 0x00, 0x0b, // body of 0
+            0x04, 0x00, 0x10, 0x00, 0x0b, // body of 1
+            0x00, 0x48, // custom
+            0x0b, // size of name
+            0x2e, 0x64, 0x65, 0x62, 0x75, 0x67, 0x5f, 0x6c, 0x69, 0x6e, 0x65, // ".debug_line"
+            0x38, 0x00, 0x00, 0x00, 0x04, 0x00, 0x22, 0x00, 0x00, 0x00, // unit header
+            0x01, 0x01, 0x01, 0xfb, 0x0e, 0x0d, // line program parameters
+            0x00, 0x01, 0x01, 0x01, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x01, // opcodes
+            0x73, 0x72, 0x63, 0x00, 0x00, // include_directories
+            0x6c, 0x69, 0x62, 0x2e, 0x72, 0x73, 0x00, 0x01, 0x00, 0x00, 0x00, // file_names
+            0x00, 0x05, 0x02, 0x03, 0x00, 0x00, 0x00, // DW_LNE_set_address 0x03
+            0x03, 0x29, 0x01, // line 42
+            0x4b, // address 0x07 and line 43
+            0x02, 0x03, 0x00, 0x01, 0x01, // DW_LNE_end_sequence at 0x0a
+        ];
+        let mut vm = instantiate_module(
+            init_store(),
+            decode_module(&bytes),
+            Default::default(),
+            65536,
+        )
+        .unwrap();
+        assert_eq!(
+            vm.run("run", vec![]),
+            Err(WasmError::Trap(Trap::Unreachable))
+        );
+        let backtrace = vm.backtrace();
+        assert_eq!(
+            backtrace.iter().map(|f| f.offset).collect::<Vec<_>>(),
+            vec![Some(3), Some(7)]
+        );
+        assert_eq!(
+            backtrace
+                .iter()
+                .map(|f| format!("{}", f))
+                .collect::<Vec<_>>(),
+            vec!["<anonymous> at src/lib.rs:42", "run at src/lib.rs:43"]
+        );
+    }
+
+    #[test]
+    fn snapshot_stack_at_trap() {
+        // (module
+        //   (func (param i32) (result i32) (local i64)
+        //     i32.const 5 i32.const 7 get_local 0 i32.div_u)
+        //   (func (export "run") (param i32) (result i32)
+        //     i32.const 3 get_local 0 call 0 i32.add))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type
+            0x03, 0x03, 0x02, 0x00, 0x00, // function
+            0x07, 0x07, 0x01, 0x03, 0x72, 0x75, 0x6e, 0x00, 0x01, // export
+            0x0a, 0x17, 0x02, // code
+            0x0b, 0x01, 0x01, 0x7e, // size and locals of 0
+            0x41, 0x05, 0x41, 0x07, 0x20, 0x00, 0x6e, 0x0b, // body of 0
+            0x09, 0x00, 0x41, 0x03, 0x20, 0x00, 0x10, 0x00, 0x6a, 0x0b, // body of 1
+        ];
+        let module = decode_module(&bytes);
+        validate_module(&module).unwrap();
+        let mut vm = instantiate_module(init_store(), module, Default::default(), 65536).unwrap();
+        assert_eq!(
+            vm.run("run", vec![Values::I32(2)]).unwrap(),
+            vec![Values::I32(6)]
+        );
+        assert_eq!(vm.trap_snapshot(), None);
+        assert_eq!(vm.stack_snapshot(), StackSnapshot::default());
+
+        assert_eq!(
+            vm.run("run", vec![Values::I32(0)]),
+            Err(WasmError::Trap(Trap::DivisionByZero))
+        );
+        let snapshot = vm.trap_snapshot().unwrap();
+        assert_eq!(
+            snapshot
+                .frames
+                .iter()
+                .map(|frame| (frame.function_index, frame.function_name.clone()))
+                .collect::<Vec<_>>(),
+            vec![(Some(0), None), (Some(1), Some("run".to_owned()))]
+        );
+        assert_eq!(
+            snapshot.frames[0].locals,
+            vec![Values::I32(0), Values::I64(0)]
+        );
+        assert_eq!(snapshot.frames[1].locals, vec![Values::I32(0)]);
+        assert_eq!(snapshot.operands(), vec![Values::I32(3), Values::I32(5)]);
+    }
+
+    #[test]
+    fn run_with_instruction_budget() {
+        // (module
+        //   (func (export "count") (param i32) (result i32) (local i32)
+        //     loop
+        //       get_local 1 i32.const 1 i32.add set_local 1
+        //       get_local 1 get_local 0 i32.ne br_if 0
+        //     end
+        //     get_local 1))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x09, 0x01, 0x05, 0x63, 0x6f, 0x75, 0x6e, 0x74, 0x00, 0x00, // export
+            0x0a, 0x19, 0x01, // code
+            0x17, 0x01, 0x01, 0x7f, // size and locals
+            0x03, 0x40, // loop
+            0x20, 0x01, 0x41, 0x01, 0x6a, 0x21, 0x01, // increment
+            0x20, 0x01, 0x20, 0x00, 0x47, 0x0d, 0x00, // branch
+            0x0b, 0x20, 0x01, 0x0b, // end
+        ];
+        let mut vm = instantiate_module(
+            init_store(),
+            decode_module(&bytes),
+            Default::default(),
+            65536,
+        )
+        .unwrap();
+
+        let mut yields = 0;
+        let mut outcome = vm.run_bounded("count", vec![Values::I32(100)], 0).unwrap();
+        while let BoundedRun::Yielded(token) = outcome {
+            yields += 1;
+            outcome = vm.resume(token, 10).unwrap();
+        }
+        assert_eq!(outcome, BoundedRun::Finished(vec![Values::I32(100)]));
+        assert!(yields > 10);
+        assert_eq!(
+            vm.run("count", vec![Values::I32(3)]).unwrap(),
+            vec![Values::I32(3)]
+        );
+
+        let stale_token = match vm.run_bounded("count", vec![Values::I32(100)], 10) {
+            Ok(BoundedRun::Yielded(token)) => token,
+            x => unreachable!("{:?}", x),
+        };
+        let token = match vm.run_bounded("count", vec![Values::I32(5)], 10) {
+            Ok(BoundedRun::Yielded(token)) => token,
+            x => unreachable!("{:?}", x),
+        };
+        assert_eq!(
+            vm.resume(stale_token, 1000),
+            Err(WasmError::Trap(Trap::StaleResumeToken))
+        );
+        assert_eq!(
+            vm.resume(token, 1000),
+            Ok(BoundedRun::Finished(vec![Values::I32(5)]))
+        );
+    }
+
+    #[test]
+    fn run_again_after_trap_in_nested_call() {
+        // (module
+        //   (func (param i32) (result i32) get_local 0 if unreachable end get_local 0)
+        //   (func (export "outer") (param i32) (result i32)
+        //     i32.const 100 get_local 0 call 0 i32.add))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type
+            0x03, 0x03, 0x02, 0x00, 0x00, // function
+            0x07, 0x09, 0x01, 0x05, 0x6f, 0x75, 0x74, 0x65, 0x72, 0x00, 0x01, // export
+            0x0a, 0x17, 0x02, // code
+            0x0a, 0x00, 0x20, 0x00, 0x04, 0x40, 0x00, 0x0b, 0x20, 0x00, 0x0b, 0x0a, 0x00, 0x41,
+            0xe4, 0x00, 0x20, 0x00, 0x10, 0x00, 0x6a, 0x0b,
+        ];
+        let mut vm = instantiate_module(
+            init_store(),
+            decode_module(&bytes),
+            Default::default(),
+            65536,
+        )
+        .unwrap();
+        assert_eq!(
+            vm.run_resumable("outer", vec![Values::I32(1)]),
+            Err(WasmError::Trap(Trap::Unreachable))
+        );
+        assert_eq!(
+            vm.run_resumable("outer", vec![Values::I32(0)]),
+            Ok(BoundedRun::Finished(vec![Values::I32(100)]))
+        );
+        assert_eq!(
+            vm.run("outer", vec![Values::I32(1)]),
+            Err(WasmError::Trap(Trap::Unreachable))
+        );
+        assert_eq!(
+            vm.run_bounded("outer", vec![Values::I32(2)], 1000),
+            Err(WasmError::Trap(Trap::Unreachable))
+        );
+        assert_eq!(
+            vm.run("outer", vec![Values::I32(0)]),
+            Ok(vec![Values::I32(100)])
+        );
+        assert_eq!(vm.stack_snapshot(), StackSnapshot::default());
+    }
+
+    #[test]
+    fn suspend_by_yield_function() {
+        // (module
+        //   (import "coroutine" "yield" (func (param i32) (result i32)))
+        //   (func (export "gen") (result i32)
+        //     i32.const 1 call 0 i32.const 10 i32.add call 0))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x0a, 0x02, 0x60, 0x01, 0x7f, 0x01, 0x7f, 0x60, 0x00, 0x01, 0x7f, // type
+            0x02, 0x13, 0x01, // import
+            0x09, 0x63, 0x6f, 0x72, 0x6f, 0x75, 0x74, 0x69, 0x6e, 0x65, // "coroutine"
+            0x05, 0x79, 0x69, 0x65, 0x6c, 0x64, 0x00, 0x00, // "yield"
+            0x03, 0x02, 0x01, 0x01, // function
+            0x07, 0x07, 0x01, 0x03, 0x67, 0x65, 0x6e, 0x00, 0x01, // export
+            0x0a, 0x0d, 0x01, // code
+            0x0b, 0x00, 0x41, 0x01, 0x10, 0x00, 0x41, 0x0a, 0x6a, 0x10, 0x00, 0x0b,
+        ];
+        let mut external_modules = ExternalModules::default();
+        external_modules
+            .register_module(
+                Some("coroutine".to_owned()),
+                ExternalModule::new(
+                    vec![FunctionInstance::new_yield(
+                        Some("yield".to_owned()),
+                        FunctionType::new(vec![ValueTypes::I32], vec![ValueTypes::I32]),
+                    )],
+                    vec![],
+                    vec![],
+                    vec![],
+                    vec![],
+                ),
+            )
+            .unwrap();
+        let mut vm =
+            instantiate_module(init_store(), decode_module(&bytes), external_modules, 65536)
+                .unwrap();
+
+        let token = match vm.run_resumable("gen", vec![]) {
+            Ok(BoundedRun::Suspended(values, token)) => {
+                assert_eq!(values, vec![Values::I32(1)]);
+                token
+            }
+            x => unreachable!("{:?}", x),
+        };
+        let token = match vm.resume_with(token, vec![Values::I32(5)], None) {
+            Ok(BoundedRun::Suspended(values, token)) => {
+                assert_eq!(values, vec![Values::I32(15)]);
+                token
+            }
+            x => unreachable!("{:?}", x),
+        };
+        assert_eq!(
+            vm.resume_with(token, vec![Values::I32(7)], None),
+            Ok(BoundedRun::Finished(vec![Values::I32(7)]))
+        );
+
+        let token = match vm.run_resumable("gen", vec![]) {
+            Ok(BoundedRun::Suspended(_, token)) => token,
+            x => unreachable!("{:?}", x),
+        };
+        assert_eq!(
+            vm.resume_with(token.clone(), vec![Values::I64(5)], None),
+            Err(WasmError::Trap(Trap::TypeMismatch))
+        );
+        match vm.resume_with(token, vec![Values::I32(2)], None) {
+            Ok(BoundedRun::Suspended(values, _)) => assert_eq!(values, vec![Values::I32(12)]),
+            x => unreachable!("{:?}", x),
+        };
+        assert_eq!(
+            vm.run("gen", vec![]),
+            Err(WasmError::Trap(Trap::UnexpectedYield))
+        );
+    }
+
+    #[test]
+    fn check_types_of_arguments() {
+        // (module
+        //   (func (export "add") (param i64 i64) (result i64)
+        //     get_local 0 get_local 1 i64.add))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x07, 0x01, 0x60, 0x02, 0x7e, 0x7e, 0x01, 0x7e, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x07, 0x01, 0x03, 0x61, 0x64, 0x64, 0x00, 0x00, // export
+            0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x7c, 0x0b, // code
+        ];
+        let module = decode_module(&bytes);
+        validate_module(&module).unwrap();
+        let mut vm = instantiate_module(init_store(), module, Default::default(), 65536).unwrap();
+        assert_eq!(
+            vm.run("add", vec![Values::I64(1), Values::I64(2)]),
+            Ok(vec![Values::I64(3)])
+        );
+        assert_eq!(
+            vm.run("add", vec![Values::I32(-1), Values::I64(2)]),
+            Err(WasmError::Trap(Trap::ArgumentTypeMismatch {
+                expected: vec![ValueTypes::I64, ValueTypes::I64],
+                actual: vec![ValueTypes::I32, ValueTypes::I64],
+            }))
+        );
+        let arity_mismatch = vm.run("add", vec![Values::I64(1)]).unwrap_err();
+        assert_eq!(
+            arity_mismatch,
+            WasmError::Trap(Trap::ArityMismatch {
+                expected: FunctionType::new(
+                    vec![ValueTypes::I64, ValueTypes::I64],
+                    vec![ValueTypes::I64]
+                ),
+                actual: 1,
+            })
+        );
+        assert!(format!("{:?}", arity_mismatch).contains("(i64, i64) -> (i64)"));
+        match vm.run("add", vec![Values::I64(1), Values::I64(2), Values::I64(3)]) {
+            Err(WasmError::Trap(Trap::ArityMismatch { actual, .. })) => assert_eq!(actual, 3),
+            x => panic!("Expected arity mismatch, got {:?}", x),
+        }
+
+        let mut store = init_store();
+        store.argument_coercion = ArgumentCoercion::Lossless;
+        let mut vm =
+            instantiate_module(store, decode_module(&bytes), Default::default(), 65536).unwrap();
+        assert_eq!(
+            vm.run("add", vec![Values::I32(-1), Values::I64(2)]),
+            Ok(vec![Values::I64(1)])
+        );
+        assert!(vm
+            .run("add", vec![Values::F32(1.0), Values::I64(2)])
+            .is_err());
+    }
+
+    #[test]
+    fn split_or_reject_i64_at_boundary() {
+        // (module
+        //   (func (export "add") (param i64 i64) (result i64)
+        //     get_local 0 get_local 1 i64.add))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x07, 0x01, 0x60, 0x02, 0x7e, 0x7e, 0x01, 0x7e, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x07, 0x01, 0x03, 0x61, 0x64, 0x64, 0x00, 0x00, // export
+            0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x7c, 0x0b, // code
+        ];
+        let instantiate = |i64_boundary: I64Boundary| {
+            let mut store = init_store();
+            store.i64_boundary = i64_boundary;
+            instantiate_module(store, decode_module(&bytes), Default::default(), 65536).unwrap()
+        };
+
+        let mut vm = instantiate(I64Boundary::Split);
+        let (low, high) = Values::split_i64(0x1_0000_0001);
+        assert_eq!(
+            (low.clone(), high.clone()),
+            (Values::I32(1), Values::I32(1))
+        );
+        assert_eq!(
+            vm.run("add", vec![low, high, Values::I32(-1), Values::I32(0)]),
+            Ok(vec![Values::I32(0), Values::I32(2)])
+        );
+        assert_eq!(
+            vm.check_call("add", &vec![ValueTypes::I32; 4]),
+            Ok(vec![ValueTypes::I32, ValueTypes::I32])
+        );
+        assert!(vm.check_call("add", &vec![ValueTypes::I64; 2]).is_err());
+        assert_eq!(Values::join_i64(0, 2), Values::I64(0x2_0000_0000));
+        assert_eq!(Values::join_i64(-1, -1), Values::I64(-1));
+        assert_eq!(
+            vm.run("add", vec![Values::I64(1), Values::I64(2)]),
+            Err(WasmError::Trap(Trap::ArityMismatch {
+                expected: FunctionType::new(
+                    vec![ValueTypes::I32; 4],
+                    vec![ValueTypes::I32, ValueTypes::I32]
+                ),
+                actual: 2,
+            }))
+        );
+
+        let mut vm = instantiate(I64Boundary::Reject);
+        assert_eq!(
+            vm.run("add", vec![Values::I64(1), Values::I64(2)]),
+            Err(WasmError::Trap(Trap::I64AtBoundary))
+        );
+        assert_eq!(
+            vm.check_call("add", &vec![ValueTypes::I64; 2]),
+            Err(WasmError::Trap(Trap::I64AtBoundary))
+        );
+    }
+
+    #[test]
+    fn check_call_without_execution() {
+        // (module
+        //   (func (export "add") (param i64 i64) (result i64)
+        //     get_local 0 get_local 1 i64.add))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x07, 0x01, 0x60, 0x02, 0x7e, 0x7e, 0x01, 0x7e, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x07, 0x01, 0x03, 0x61, 0x64, 0x64, 0x00, 0x00, // export
+            0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x7c, 0x0b, // code
+        ];
+        let mut store = init_store();
+        store.argument_coercion = ArgumentCoercion::Lossless;
+        let mut vm =
+            instantiate_module(store, decode_module(&bytes), Default::default(), 65536).unwrap();
+        vm.enable_metrics(true);
+        assert_eq!(
+            vm.check_call("add", &[ValueTypes::I32, ValueTypes::I64]),
+            Ok(vec![ValueTypes::I64])
+        );
+        assert_eq!(
+            vm.check_call("add", &[ValueTypes::F64, ValueTypes::I64]),
+            Err(WasmError::Trap(Trap::ArgumentTypeMismatch {
+                expected: vec![ValueTypes::I64, ValueTypes::I64],
+                actual: vec![ValueTypes::F64, ValueTypes::I64],
+            }))
+        );
+        assert_eq!(
+            vm.check_call("sub", &[]),
+            Err(WasmError::UnknownExport {
+                name: "sub".to_owned(),
+                similar: vec![],
+            })
+        );
+        assert_eq!(
+            vm.check_call("Add", &[]),
+            Err(WasmError::UnknownExport {
+                name: "Add".to_owned(),
+                similar: vec!["add".to_owned()],
+            })
+        );
+        assert_eq!(vm.metrics().instructions_executed, 0);
+
+        let mut store = init_store();
+        store.export_suggestion = ExportSuggestion::Omitted;
+        let mut vm =
+            instantiate_module(store, decode_module(&bytes), Default::default(), 65536).unwrap();
+        assert_eq!(
+            vm.run("Add", vec![]),
+            Err(WasmError::UnknownExport {
+                name: "Add".to_owned(),
+                similar: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn instantiate_modules_in_order_of_imports() {
+        // (module
+        //   (func (export "g") (result i32) i32.const 42))
+        let bytes_of_exporter = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x05, 0x01, 0x01, 0x67, 0x00, 0x00, // export
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x41, 0x2a, 0x0b, // code
+        ];
+        // (module
+        //   (import "m" "g" (func (result i32)))
+        //   (func (export "g") (result i32) call 0))
+        let bytes_of_importer = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+            0x02, 0x07, 0x01, 0x01, 0x6d, 0x01, 0x67, 0x00, 0x00, // import
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x05, 0x01, 0x01, 0x67, 0x00, 0x01, // export
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x10, 0x00, 0x0b, // code
+        ];
+        let mut linker = Linker::new();
+        linker.register("n", bytes_of_importer.clone());
+        linker.register("m", bytes_of_exporter);
+        let mut instances = linker.instantiate(Default::default(), 65536).unwrap();
+        assert_eq!(
+            instances
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["m", "n"]
+        );
+        assert_eq!(instances[1].1.run("g", vec![]), Ok(vec![Values::I32(42)]));
+
+        let mut linker = Linker::new();
+        linker.register("n", bytes_of_importer.clone());
+        linker.register("m", bytes_of_importer);
+        assert_eq!(
+            linker
+                .instantiate(Default::default(), 65536)
+                .map(|instances| instances.len()),
+            Err(WasmError::Trap(Trap::CircularImport("m".to_owned())))
+        );
+
+        // (module
+        //   (func (export "g") (result i32) i64.const 42))
+        let bytes_of_invalid = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x05, 0x01, 0x01, 0x67, 0x00, 0x00, // export
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x42, 0x2a, 0x0b, // code
+        ];
+        let mut linker = Linker::new();
+        linker.register("m", bytes_of_invalid);
+        assert_eq!(
+            linker
+                .instantiate(Default::default(), 65536)
+                .map(|instances| instances.len()),
+            Err(WasmError::TypeError(TypeError::TypeMismatch))
+        );
+    }
+
+    #[test]
+    fn tear_down_unregistered_modules() {
+        // (module
+        //   (func (export "g") (result i32) i32.const 42))
+        let bytes_of_exporter = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x05, 0x01, 0x01, 0x67, 0x00, 0x00, // export
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x41, 0x2a, 0x0b, // code
+        ];
+        // (module
+        //   (import "m" "g" (func (result i32)))
+        //   (func (export "g") (result i32) call 0))
+        let bytes_of_importer = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+            0x02, 0x07, 0x01, 0x01, 0x6d, 0x01, 0x67, 0x00, 0x00, // import
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x05, 0x01, 0x01, 0x67, 0x00, 0x01, // export
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x10, 0x00, 0x0b, // code
+        ];
+        let mut external_modules = ExternalModules::default();
+        let registry = external_modules.downgrade();
+        let mut linker = Linker::new();
+        linker.register("m", bytes_of_exporter);
+        let instances = linker.instantiate(external_modules.clone(), 65536).unwrap();
+        assert!(linker.unregister("m"));
+        assert!(!linker.unregister("m"));
+        drop(instances);
+        assert!(external_modules
+            .unregister_module(&Some("m".to_owned()))
+            .is_some());
+        assert!(external_modules.get(&Some("m".to_owned())).is_none());
+
+        linker.register("n", bytes_of_importer);
+        assert_eq!(
+            linker
+                .instantiate(external_modules.clone(), 65536)
+                .map(|instances| instances.len()),
+            Err(WasmError::Trap(Trap::UnknownImport))
+        );
+        assert!(registry.upgrade().is_some());
+        drop(external_modules);
+        assert!(registry.upgrade().is_none());
+    }
+
+    #[test]
+    fn shadow_imported_functions_by_overrides() {
+        // (module
+        //   (import "env" "clock" (func (result i32)))
+        //   (import "env" "offset" (func (result i32)))
+        //   (func (export "now") (result i32) call 0 call 1 i32.add))
+        let bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+            0x02, 0x1a, 0x02, // import
+            0x03, 0x65, 0x6e, 0x76, 0x05, 0x63, 0x6c, 0x6f, 0x63, 0x6b, 0x00,
+            0x00, // "env" "clock"
+            0x03, 0x65, 0x6e, 0x76, 0x06, 0x6f, 0x66, 0x66, 0x73, 0x65, 0x74, 0x00,
+            0x00, // "env" "offset"
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x07, 0x01, 0x03, 0x6e, 0x6f, 0x77, 0x00, 0x02, // export
+            0x0a, 0x09, 0x01, 0x07, 0x00, 0x10, 0x00, 0x10, 0x01, 0x6a, 0x0b, // code
+        ];
+        let host_fn = |name: &str, value: i32| {
+            FunctionInstance::new_host_closure(
+                Some(name.to_owned()),
+                FunctionType::new(vec![], vec![ValueTypes::I32]),
+                move |_| vec![Values::I32(value)],
+            )
+        };
+        let env = ExternalModule::new(
+            vec![host_fn("clock", 1), host_fn("offset", 10)],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+        );
+        let mut external_modules = ExternalModules::default();
+        external_modules
+            .register_module(Some("env".to_owned()), env)
+            .unwrap();
+
+        let mut linker = Linker::new();
+        linker.register("m", bytes.clone());
+        linker.override_imports(
+            "env",
+            ExternalModule::new(vec![host_fn("clock", 100)], vec![], vec![], vec![], vec![]),
+        );
+        let mut instances = linker.instantiate(external_modules.clone(), 65536).unwrap();
+        assert_eq!(
+            instances[0].1.run("now", vec![]),
+            Ok(vec![Values::I32(110)])
+        );
+
+        let mut linker = Linker::new();
+        linker.register("m", bytes);
+        let mut instances = linker.instantiate(external_modules, 65536).unwrap();
+        assert_eq!(instances[0].1.run("now", vec![]), Ok(vec![Values::I32(11)]));
+    }
+
+    #[test]
+    fn derive_signatures_of_host_closures() {
+        // (module
+        //   (import "env" "add" (func (param i32 i32) (result i32)))
+        //   (import "env" "log" (func (param i64)))
+        //   (func (export "add") (param i32 i32) (result i32) get_local 0 get_local 1 call 0)
+        //   (func (export "log") i64.const 7 call 1))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x0e, 0x03, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, 0x60, 0x01, 0x7e, 0x00, 0x60,
+            0x00, 0x00, // type
+            0x02, 0x15, 0x02, 0x03, 0x65, 0x6e, 0x76, 0x03, 0x61, 0x64, 0x64, 0x00, 0x00, 0x03,
+            0x65, 0x6e, 0x76, 0x03, 0x6c, 0x6f, 0x67, 0x00, 0x01, // import
+            0x03, 0x03, 0x02, 0x00, 0x02, // function
+            0x07, 0x0d, 0x02, 0x03, 0x61, 0x64, 0x64, 0x00, 0x02, 0x03, 0x6c, 0x6f, 0x67, 0x00,
+            0x03, // export
+            0x0a, 0x11, 0x02, 0x08, 0x00, 0x20, 0x00, 0x20, 0x01, 0x10, 0x00, 0x0b, 0x06, 0x00,
+            0x42, 0x07, 0x10, 0x01, 0x0b, // code
+        ];
+        let logged = std::rc::Rc::new(std::cell::Cell::new(0));
+        let log = {
+            let logged = logged.clone();
+            FunctionInstance::new_typed_host_fn(Some("log".to_owned()), move |n: i64| logged.set(n))
+        };
+        let add = (|a: i32, b: i32| a + b).into_host_func(Some("add".to_owned()));
+        assert_eq!(
+            add.get_function_type(),
+            FunctionType::new(
+                vec![ValueTypes::I32, ValueTypes::I32],
+                vec![ValueTypes::I32]
+            )
+        );
+        assert_eq!(
+            log.get_function_type(),
+            FunctionType::new(vec![ValueTypes::I64], vec![])
+        );
+
+        match &add {
+            FunctionInstance::HostFn(host) => assert_eq!(
+                host.call(&[Values::F32(2.0), Values::I32(3)]),
+                Err(WasmError::Trap(Trap::TypeMismatch))
+            ),
+            _ => unreachable!(),
+        }
+
+        let env = ExternalModule::new(vec![add, log], vec![], vec![], vec![], vec![]);
+        let mut external_modules = ExternalModules::default();
+        external_modules
+            .register_module(Some("env".to_owned()), env)
+            .unwrap();
+        let mut vm =
+            instantiate_module(init_store(), decode_module(&bytes), external_modules, 65536)
+                .unwrap();
+        assert_eq!(
+            vm.run("add", vec![Values::I32(2), Values::I32(3)]),
+            Ok(vec![Values::I32(5)])
+        );
+        assert_eq!(vm.run("log", vec![]), Ok(vec![]));
+        assert_eq!(logged.get(), 7);
+    }
+
+    #[test]
+    fn reject_incompatible_imports_before_initialization() {
+        let mut external_modules = ExternalModules::default();
+        external_modules
+            .register_module(Some("spectest".to_owned()), create_spectest())
+            .unwrap();
+        // (module (import "spectest" "global_i32" (global (mut i32))))
+        let bytes_of_mutable = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x02, 0x18, 0x01, 0x08, 0x73, 0x70, 0x65, 0x63, 0x74, 0x65, 0x73, 0x74, 0x0a, 0x67,
+            0x6c, 0x6f, 0x62, 0x61, 0x6c, 0x5f, 0x69, 0x33, 0x32, 0x03, 0x7f, 0x01, // import
+        ];
+        // (module (import "spectest" "global_i32" (global i64)))
+        let bytes_of_i64 = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x02, 0x18, 0x01, 0x08, 0x73, 0x70, 0x65, 0x63, 0x74, 0x65, 0x73, 0x74, 0x0a, 0x67,
+            0x6c, 0x6f, 0x62, 0x61, 0x6c, 0x5f, 0x69, 0x33, 0x32, 0x03, 0x7e, 0x00, // import
+        ];
+        for bytes in [&bytes_of_mutable, &bytes_of_i64].iter() {
+            let importer = instantiate_module(
+                init_store(),
+                decode_module(*bytes),
+                external_modules.clone(),
+                65536,
+            );
+            assert_eq!(
+                importer.unwrap_err(),
+                WasmError::Trap(Trap::IncompatibleImportType)
+            );
+        }
+
+        // (module
+        //   (type (func (result i32)))
+        //   (table (export "tab") 10 anyfunc)
+        //   (func (export "call") (param i32) (result i32) get_local 0 call_indirect 0))
+        let bytes_of_exporter = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x0a, 0x02, 0x60, 0x00, 0x01, 0x7f, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x01, // function
+            0x04, 0x04, 0x01, 0x70, 0x00, 0x0a, // table
+            0x07, 0x0e, 0x02, 0x03, 0x74, 0x61, 0x62, 0x01, 0x00, // export "tab"
+            0x04, 0x63, 0x61, 0x6c, 0x6c, 0x00, 0x00, // export "call"
+            0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x11, 0x00, 0x00, 0x0b, // code
+        ];
+        // (module
+        //   (import "Mt" "tab" (table 10 anyfunc))
+        //   (import "spectest" "memory" (memory 2))
+        //   (func (result i32) i32.const 5)
+        //   (elem (i32.const 7) 0))
+        let bytes_of_importer = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+            0x02, 0x1f, 0x02, 0x02, 0x4d, 0x74, 0x03, 0x74, 0x61, 0x62, 0x01, 0x70, 0x00, 0x0a,
+            0x08, 0x73, 0x70, 0x65, 0x63, 0x74, 0x65, 0x73, 0x74, 0x06, 0x6d, 0x65, 0x6d, 0x6f,
+            0x72, 0x79, 0x02, 0x00, 0x02, // import
+            0x03, 0x02, 0x01, 0x00, // function
+            0x09, 0x07, 0x01, 0x00, 0x41, 0x07, 0x0b, 0x01, 0x00, // element
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x41, 0x05, 0x0b, // code
+        ];
+        let mut exporter = instantiate_module(
+            init_store(),
+            decode_module(&bytes_of_exporter),
+            Default::default(),
+            65536,
+        )
+        .unwrap();
+        external_modules
+            .register_module(Some("Mt".to_owned()), exporter.export_module())
+            .unwrap();
+        let mut store = init_store();
+        store.segment_initialization = SegmentInitialization::Partial;
+        let importer = instantiate_module(
+            store,
+            decode_module(&bytes_of_importer),
+            external_modules,
+            65536,
+        );
+        assert_eq!(
+            importer.unwrap_err(),
+            WasmError::Trap(Trap::IncompatibleImportType)
+        );
+        assert_eq!(
+            exporter.run("call", vec![Values::I32(7)]),
+            Err(WasmError::Trap(Trap::UninitializedElement))
+        );
+    }
+
+    #[test]
+    fn reject_opcodes_of_unsupported_proposals() {
+        // (module (func v128.const i32x4 0 0 0 0 drop))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x0a, 0x17, 0x01, 0x15, 0x00, 0xfd, 0x0c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1a, 0x0b, // code
+        ];
+        let vm = instantiate_module(
+            init_store(),
+            decode_module(&bytes),
+            Default::default(),
+            65536,
+        );
+        assert_eq!(
+            vm.unwrap_err(),
+            WasmError::Trap(Trap::UnsupportedFeature {
+                proposal: Proposal::Simd,
+                opcode: 0xfd,
+            })
+        );
+    }
+
+    #[test]
+    fn reject_illegal_opcodes() {
+        for opcode in [0x0a, 0x1d, 0x27, 0xc5, 0xe0].iter() {
+            let bytes = [
+                0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+                0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type
+                0x03, 0x02, 0x01, 0x00, // function
+                0x0a, 0x05, 0x01, 0x03, 0x00, *opcode, 0x0b, // code
+            ];
+            let vm = instantiate_module(
+                init_store(),
+                decode_module(&bytes),
+                Default::default(),
+                65536,
+            );
+            assert_eq!(
+                vm.unwrap_err(),
+                WasmError::Trap(Trap::IllegalOpcode { opcode: *opcode })
+            );
+        }
+    }
+
+    #[test]
+    fn accept_proposals_enabled_by_features() {
+        // (module (func v128.const i32x4 0 0 0 0 drop))
+        let bytes_of_simd = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x0a, 0x17, 0x01, 0x15, 0x00, 0xfd, 0x0c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1a, 0x0b, // code
+        ];
+        let module = decode_module(&bytes_of_simd);
+        assert_eq!(
+            validate_module(&module),
+            Err(WasmError::TypeError(TypeError::DisabledFeature(
+                Proposal::Simd
+            )))
+        );
+        let features = Features {
+            simd: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            validate_module_with_features(&module, &features),
+            Err(WasmError::Trap(Trap::UnsupportedFeature {
+                proposal: Proposal::Simd,
+                opcode: 0xfd,
+            }))
+        );
+
+        // (module (type (func (result i32 i32))))
+        let bytes_of_multi_value = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x06, 0x01, 0x60, 0x00, 0x02, 0x7f, 0x7f, // type
+        ];
+        let module = decode_module(&bytes_of_multi_value);
+        assert_eq!(
+            validate_module(&module),
+            Err(WasmError::TypeError(TypeError::InvalidResultArity))
+        );
+        let features = Features {
+            multi_value: true,
+            ..Default::default()
+        };
+        assert_eq!(validate_module_with_features(&module, &features), Ok(()));
+    }
+
+    #[test]
+    fn identify_errors_by_stable_codes() {
+        assert_eq!(WasmError::Trap(Trap::DivisionByZero).code(), 2);
+        assert_eq!(
+            WasmError::Trap(Trap::HostError("failed".to_owned())).code(),
+            41
+        );
+        assert_eq!(
+            WasmError::TypeError(TypeError::UnknownFunction(3)).code(),
+            1015
+        );
+        assert_eq!(WasmError::name_of_code(27), Some("IncompatibleImportType"));
+        let unknown_export = WasmError::UnknownExport {
+            name: "sub".to_owned(),
+            similar: vec![],
+        };
+        assert_eq!(
+            WasmError::name_of_code(unknown_export.code()),
+            Some("UnknownExport")
+        );
+        assert_eq!(WasmError::name_of_code(0), None);
+        let mut codes = TRAP_CODES
+            .iter()
+            .chain(TYPE_ERROR_CODES.iter())
+            .map(|(code, _)| *code)
+            .collect::<Vec<_>>();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), TRAP_CODES.len() + TYPE_ERROR_CODES.len());
+    }
+
+    #[test]
+    fn keep_payload_of_nan_in_sign_operations() {
+        let nan = Values::F32(f32::from_bits(0x7fa0_0001));
+        let sign_bits = |value: Values| match value {
+            Values::F32(v) => v.to_bits(),
+            x => unreachable!("{:?}", x),
+        };
+        assert_eq!(sign_bits(nan.neg()), 0xffa0_0001);
+        assert_eq!(sign_bits(nan.neg().abs()), 0x7fa0_0001);
+        assert_eq!(sign_bits(nan.copy_sign(&Values::F32(-0.0))), 0xffa0_0001);
+        assert_eq!(sign_bits(Values::F32(0.0).neg()), 0x8000_0000);
+        match Values::F64(f64::from_bits(0xfff8_0000_0000_0001)).copy_sign(&Values::F64(1.0)) {
+            Values::F64(v) => assert_eq!(v.to_bits(), 0x7ff8_0000_0000_0001),
+            x => unreachable!("{:?}", x),
+        };
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn evaluate_f64_rounding_and_comparison() {
+        // (module
+        //   (func (export "nearest") (param f64) (result f64) get_local 0 f64.nearest)
+        //   (func (export "min") (param f64 f64) (result f64) get_local 0 get_local 1 f64.min)
+        //   (func (export "max") (param f64 f64) (result f64) get_local 0 get_local 1 f64.max))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x0c, 0x02, 0x60, 0x01, 0x7c, 0x01, 0x7c, 0x60, 0x02, 0x7c, 0x7c, 0x01,
+            0x7c, // type
+            0x03, 0x04, 0x03, 0x00, 0x01, 0x01, // function
+            0x07, 0x17, 0x03, 0x07, 0x6e, 0x65, 0x61, 0x72, 0x65, 0x73, 0x74, 0x00, 0x00, 0x03,
+            0x6d, 0x69, 0x6e, 0x00, 0x01, 0x03, 0x6d, 0x61, 0x78, 0x00, 0x02, // export
+            0x0a, 0x17, 0x03, 0x05, 0x00, 0x20, 0x00, 0x9e, 0x0b, 0x07, 0x00, 0x20, 0x00, 0x20,
+            0x01, 0xa4, 0x0b, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0xa5, 0x0b, // code
+        ];
+        let mut vm = instantiate_module(
+            init_store(),
+            decode_module(&bytes),
+            Default::default(),
+            65536,
+        )
+        .unwrap();
+        let bits_of = |results: ::error::Result<Vec<Values>>| match results.unwrap()[0] {
+            Values::F64(v) => v.to_bits(),
+            ref x => unreachable!("{:?}", x),
+        };
+        for (argument, expected) in [
+            (2.5, 2.0),
+            (3.5, 4.0),
+            (2.7, 3.0),
+            (-0.3, -0.0),
+            (-0.5, -0.0),
+        ]
+        .iter()
+        {
+            assert_eq!(
+                bits_of(vm.run("nearest", vec![Values::F64(*argument)])),
+                f64::to_bits(*expected)
+            );
+        }
+        let zeros = vec![Values::F64(0.0), Values::F64(-0.0)];
+        assert_eq!(bits_of(vm.run("min", zeros.clone())), f64::to_bits(-0.0));
+        assert_eq!(bits_of(vm.run("max", zeros)), f64::to_bits(0.0));
+        assert!(f64::from_bits(bits_of(
+            vm.run("min", vec![Values::F64(1.0), Values::F64(::std::f64::NAN)])
+        ))
+        .is_nan());
+    }
+
+    #[test]
+    fn truncate_floats_at_bounds_of_integers() {
+        let overflow = Err(WasmError::Trap(Trap::IntegerOverflow));
+        assert_eq!(
+            Values::F32(2_147_483_520.0).trunc_f32_to_sign_i32(),
+            Ok(Values::I32(2_147_483_520))
+        );
+        assert_eq!(
+            Values::F32(2_147_483_648.0).trunc_f32_to_sign_i32(),
+            overflow
+        );
+        assert_eq!(
+            Values::F64(-2_147_483_648.9).trunc_f64_to_sign_i32(),
+            Ok(Values::I32(-2_147_483_648))
+        );
+        assert_eq!(
+            Values::F64(-2_147_483_649.0).trunc_f64_to_sign_i32(),
+            overflow
+        );
+        assert_eq!(
+            Values::F64(4_294_967_295.9).trunc_f64_to_unsign_i32(),
+            Ok(Values::I32(-1))
+        );
+        assert_eq!(
+            Values::F32(-0.9).trunc_f32_to_unsign_i32(),
+            Ok(Values::I32(0))
+        );
+        assert_eq!(
+            Values::F64(9_223_372_036_854_775_808.0).trunc_f64_to_sign_i64(),
+            overflow
+        );
+        assert_eq!(
+            Values::F64(18_446_744_073_709_549_568.0).trunc_f64_to_unsign_i64(),
+            Ok(Values::I64(-2048))
+        );
+        assert_eq!(
+            Values::F64(::std::f64::INFINITY).trunc_f64_to_sign_i64(),
+            overflow
+        );
+        assert_eq!(
+            Values::F64(::std::f64::NAN).trunc_f64_to_sign_i32(),
+            Err(WasmError::Trap(Trap::InvalidConversionToInt))
+        );
+        assert_eq!(
+            Values::I64(9_007_199_791_611_905).convert_sign_i64_to_f32(),
+            Values::F32(9_007_200_328_482_816.0)
+        );
+        assert_eq!(
+            Values::I64(-1).convert_unsign_i64_to_f32(),
+            Values::F32(18_446_744_073_709_551_616.0)
+        );
+    }
+
+    #[test]
+    fn mask_counts_of_shift_and_rotate_by_bit_width() {
+        assert_eq!(Values::I32(1).shift_left(&Values::I32(33)), Values::I32(2));
+        assert_eq!(
+            Values::I32(-8).shift_right_sign(&Values::I32(-1)),
+            Values::I32(-1)
+        );
+        assert_eq!(
+            Values::I32(-8).shift_right_unsign(&Values::I32(-31)),
+            Values::I32(0x7fff_fffc)
+        );
+        assert_eq!(
+            Values::I64(1).shift_left(&Values::I64(0x1_0000_0043)),
+            Values::I64(8)
+        );
+        assert_eq!(
+            Values::I64(1).wasm_rotate_right(&Values::I64(0x7fff_ffff_ffff_ff41)),
+            Values::I64(i64::min_value())
+        );
+        assert_eq!(
+            Values::I32(0x4000_0001).wasm_rotate_left(&Values::I32(i32::min_value() + 2)),
+            Values::I32(0x0000_0005)
+        );
+    }
+
+    #[test]
+    fn trap_overflow_of_signed_division_but_not_remainder() {
+        let overflow = Err(WasmError::Trap(Trap::IntegerOverflow));
+        let by_zero = Err(WasmError::Trap(Trap::DivisionByZero));
+        let min_i32 = Values::I32(i32::min_value());
+        let min_i64 = Values::I64(i64::min_value());
+        assert_eq!(min_i32.div_s(&Values::I32(-1)), overflow);
+        assert_eq!(min_i64.div_s(&Values::I64(-1)), overflow);
+        assert_eq!(min_i32.rem_s(&Values::I32(-1)), Ok(Values::I32(0)));
+        assert_eq!(min_i64.rem_s(&Values::I64(-1)), Ok(Values::I64(0)));
+        assert_eq!(min_i32.div_u(&Values::I32(-1)), Ok(Values::I32(0)));
+        assert_eq!(min_i64.rem_u(&Values::I64(-1)), Ok(min_i64.clone()));
+        assert_eq!(min_i32.div_s(&Values::I32(0)), by_zero);
+        assert_eq!(min_i64.rem_s(&Values::I64(0)), by_zero);
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn reject_operands_of_other_types_than_opcode_table() {
+        // (module (func (param i32 i32) (result f32) get_local 0 get_local 1 f32.add))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x07, 0x01, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7d, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x92, 0x0b, // code
+        ];
+        let module = decode_module(&bytes);
+        assert_eq!(
+            validate_module(&module),
+            Err(WasmError::TypeError(TypeError::TypeMismatch))
+        );
+    }
+
+    #[test]
+    fn describe_traps_as_outcomes_of_invocation() {
+        // (module (func (export "div") (param i32 i32) (result i32) get_local 0 get_local 1 i32.div_s))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x07, 0x01, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x07, 0x01, 0x03, 0x64, 0x69, 0x76, 0x00, 0x00, // export
+            0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x6d, 0x0b, // code
+        ];
+        let mut vm = instantiate_module(
+            init_store(),
+            decode_module(&bytes),
+            Default::default(),
+            65536,
+        )
+        .unwrap();
+        assert_eq!(
+            vm.invoke("div", vec![Values::I32(7), Values::I32(2)]),
+            Ok(RunOutcome::Values(vec![Values::I32(3)]))
+        );
+        match vm.invoke("div", vec![Values::I32(7), Values::I32(0)]) {
+            Ok(RunOutcome::Trap(details)) => {
+                assert_eq!(details.trap, Trap::DivisionByZero);
+                assert_eq!(details.code, Trap::DivisionByZero.code());
+                assert_eq!(details.message, "integer divide by zero");
+            }
+            x => unreachable!("{:?}", x),
+        }
+        match vm.invoke("div", vec![Values::I32(i32::min_value()), Values::I32(-1)]) {
+            Ok(RunOutcome::Trap(details)) => assert_eq!(details.message, "integer overflow"),
+            x => unreachable!("{:?}", x),
+        }
+    }
+
+    #[test]
+    fn report_malformed_modules_by_messages_of_reference_interpreter() {
+        let unknown_version = [0x00, 0x61, 0x73, 0x6d, 0x02, 0x00, 0x00, 0x00];
+        let duplicated_sections = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x01, 0x00, // type
+            0x01, 0x01, 0x00, // type
+        ];
+        let messages = [&unknown_version[..], &duplicated_sections[..]]
+            .iter()
+            .map(|bytes| match decode_module(bytes) {
+                Err(WasmError::Trap(trap)) => trap.message(),
+                x => unreachable!("{:?}", x),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec!["unknown binary version", "junk after last section"]
+        );
+    }
+
+    #[test]
+    fn reject_memories_and_tables_beyond_limits_of_spec() {
+        let header = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        let sections: [&[u8]; 8] = [
+            &[0x05, 0x04, 0x01, 0x01, 0x01, 0x00],             // memory 1 0
+            &[0x05, 0x05, 0x01, 0x00, 0x81, 0x80, 0x04],       // memory 65537
+            &[0x05, 0x06, 0x01, 0x01, 0x81, 0x80, 0x04, 0x00], // memory 65537 0
+            &[
+                0x02, 0x0e, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x03, 0x6d, 0x65, 0x6d, 0x02, 0x00, 0x81,
+                0x80, 0x04,
+            ], // import "env" "mem" (memory 65537)
+            &[0x04, 0x05, 0x01, 0x70, 0x01, 0x02, 0x01],       // table 2 1 anyfunc
+            &[0x05, 0x03, 0x01, 0x02, 0x00],                   // memory with flags 2
+            &[
+                0x04, 0x09, 0x01, 0x70, 0x01, 0x00, 0x80, 0x80, 0x80, 0x80, 0x10,
+            ], // table 0 2^32 anyfunc
+            &[
+                0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+                0x03, 0x02, 0x01, 0x00, // function
+                0x0a, 0x06, 0x01, 0x04, 0x00, 0x3f, 0x00, 0x0b, // code
+            ], // (func (result i32) memory.size) without memory
+        ];
+        let messages = sections
+            .iter()
+            .map(|section| {
+                let bytes = [&header[..], section].concat();
+                match validate_module(&decode_module(&bytes)) {
+                    Err(WasmError::TypeError(type_error)) => type_error.message(),
+                    Err(WasmError::Trap(trap)) => trap.message(),
+                    x => unreachable!("{:?}", x),
+                }
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "size minimum must not be greater than maximum",
+                "memory size must be at most 65536 pages (4GiB)",
+                "memory size must be at most 65536 pages (4GiB)",
+                "memory size must be at most 65536 pages (4GiB)",
+                "size minimum must not be greater than maximum",
+                "integer too large",
+                "integer too large",
+                "unknown memory",
+            ]
+        );
+    }
+
+    #[test]
+    fn flatten_bodies_of_functions_on_first_call() {
+        // (module
+        //   (func (export "ok") (result i32) i32.const 42)
+        //   (func (export "bad") (result i32) v128.const ..))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+            0x03, 0x03, 0x02, 0x00, 0x00, // function
+            0x07, 0x0c, 0x02, 0x02, 0x6f, 0x6b, 0x00, 0x00, 0x03, 0x62, 0x61, 0x64, 0x00,
+            0x01, // export
+            0x0a, 0x0b, 0x02, // code
+            0x04, 0x00, 0x41, 0x2a, 0x0b, // body of 0
+            0x04, 0x00, 0xfd, 0x0c, 0x0b, // body of 1
+        ];
+        let unsupported = WasmError::Trap(Trap::UnsupportedFeature {
+            proposal: Proposal::Simd,
+            opcode: 0xfd,
+        });
+        let eager = instantiate_module(
+            init_store(),
+            decode_module(&bytes),
+            Default::default(),
+            65536,
+        );
+        assert_eq!(eager.unwrap_err(), unsupported);
+
+        let mut store = init_store();
+        store.body_decoding = BodyDecoding::Lazy;
+        let mut vm =
+            instantiate_module(store, decode_module(&bytes), Default::default(), 65536).unwrap();
+        assert_eq!(vm.run("ok", vec![]), Ok(vec![Values::I32(42)]));
+        assert_eq!(vm.run("ok", vec![]), Ok(vec![Values::I32(42)]));
+        assert_eq!(vm.run("bad", vec![]), Err(unsupported));
+    }
+
+    #[test]
+    fn reject_memory_beyond_engine_before_allocation() {
+        // (module (memory 60000))
+        let huge = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x05, 0x05, 0x01, 0x00, 0xe0, 0xd4, 0x03, // memory
+        ];
+        // (module (memory 2) (data (i32.const 131072) "\01"))
+        let overflowing = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x05, 0x03, 0x01, 0x00, 0x02, // memory
+            0x0b, 0x09, 0x01, 0x00, 0x41, 0x80, 0x80, 0x08, 0x0b, 0x01, 0x01, // data
+        ];
+        let exceeded = WasmError::Trap(Trap::ResourceLimitExceeded);
+        let instantiate = |engine: &Engine, bytes: &[u8]| {
+            engine.instantiate(
+                init_store(),
+                decode_module(bytes),
+                Default::default(),
+                65536,
+            )
+        };
+        let engine = Engine::new(EngineLimits {
+            max_memory_pages: Some(1),
+            ..Default::default()
+        });
+        assert_eq!(instantiate(&engine, &huge).unwrap_err(), exceeded);
+        // NOTE: Data segments are checked after memories are allocated, so the limit is checked earlier.
+        assert_eq!(instantiate(&engine, &overflowing).unwrap_err(), exceeded);
+        assert_eq!(engine.count_of_instances(), 0);
+
+        let engine = Engine::new(EngineLimits {
+            max_memory_pages: Some(2),
+            ..Default::default()
+        });
+        assert_eq!(
+            instantiate(&engine, &overflowing).unwrap_err(),
+            WasmError::Trap(Trap::DataSegmentDoesNotFit)
+        );
+    }
+
+    #[test]
+    fn limit_instances_memory_pages_and_fuel_by_engine() {
+        // (module
+        //   (memory 1 10)
+        //   (func (export "grow") (param i32) (result i32) get_local 0 grow_memory))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x05, 0x04, 0x01, 0x01, 0x01, 0x0a, // memory
+            0x07, 0x08, 0x01, 0x04, 0x67, 0x72, 0x6f, 0x77, 0x00, 0x00, // export
+            0x0a, 0x08, 0x01, 0x06, 0x00, 0x20, 0x00, 0x40, 0x00, 0x0b, // code
+        ];
+        let exceeded = WasmError::Trap(Trap::ResourceLimitExceeded);
+        let engine = Engine::new(EngineLimits {
+            max_memory_pages: Some(3),
+            max_instances: Some(2),
+            max_fuel_per_epoch: None,
+        });
+        let instantiate = |engine: &Engine| {
+            engine.instantiate(
+                init_store(),
+                decode_module(&bytes),
+                Default::default(),
+                65536,
+            )
+        };
+        let mut a = instantiate(&engine).unwrap();
+        let b = instantiate(&engine).unwrap();
+        assert_eq!(engine.count_of_instances(), 2);
+        assert_eq!(instantiate(&engine).unwrap_err(), exceeded);
+        assert_eq!(
+            a.run("grow", vec![Values::I32(1)]),
+            Ok(vec![Values::I32(1)])
+        );
+        assert_eq!(
+            a.run("grow", vec![Values::I32(1)]),
+            Ok(vec![Values::I32(-1)])
+        );
+        assert_eq!(engine.count_of_memory_pages(), 3);
+        drop(b);
+        assert_eq!(engine.count_of_instances(), 1);
+        assert_eq!(engine.count_of_memory_pages(), 2);
+        let c = instantiate(&engine).unwrap();
+        assert_eq!(
+            a.run("grow", vec![Values::I32(1)]),
+            Ok(vec![Values::I32(-1)])
+        );
+        drop(c);
+        assert_eq!(
+            a.run("grow", vec![Values::I32(1)]),
+            Ok(vec![Values::I32(2)])
+        );
+
+        let engine = Engine::new(EngineLimits {
+            max_fuel_per_epoch: Some(10),
+            ..Default::default()
+        });
+        let mut vm = instantiate(&engine).unwrap();
+        vm.enable_metrics(true);
+        let mut count_of_runs = 0;
+        while vm.run("grow", vec![Values::I32(0)]).is_ok() {
+            count_of_runs += 1;
+            assert!(count_of_runs < 10);
+        }
+        assert_eq!(vm.run("grow", vec![Values::I32(0)]), Err(exceeded));
+        assert_eq!(engine.fuel_consumed(), 10);
+        assert_eq!(vm.metrics().fuel_exhausted, 2);
+        assert_eq!(vm.metrics().traps, 2);
+        engine.next_epoch();
+        assert_eq!(
+            vm.run("grow", vec![Values::I32(0)]),
+            Ok(vec![Values::I32(1)])
+        );
+    }
+
+    #[test]
+    fn limit_and_interrupt_instances_by_group() {
+        // (module
+        //   (memory 1 10)
+        //   (func (export "grow") (param i32) (result i32) get_local 0 grow_memory))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x05, 0x04, 0x01, 0x01, 0x01, 0x0a, // memory
+            0x07, 0x08, 0x01, 0x04, 0x67, 0x72, 0x6f, 0x77, 0x00, 0x00, // export
+            0x0a, 0x08, 0x01, 0x06, 0x00, 0x20, 0x00, 0x40, 0x00, 0x0b, // code
+        ];
+        let exceeded = WasmError::Trap(Trap::ResourceLimitExceeded);
+        let interrupted = WasmError::Trap(Trap::Interrupted);
+        let engine = Engine::new(EngineLimits {
+            max_memory_pages: Some(4),
+            ..Default::default()
+        });
+        let group = engine.group(EngineLimits {
+            max_memory_pages: Some(2),
+            max_instances: Some(2),
+            max_fuel_per_epoch: Some(10),
+        });
+        let instantiate = |group: Option<&InstanceGroup>| {
+            let (store, module) = (init_store(), decode_module(&bytes));
+            match group {
+                Some(group) => group.instantiate(store, module, Default::default(), 65536),
+                None => engine.instantiate(store, module, Default::default(), 65536),
+            }
+        };
+        let mut a = instantiate(Some(&group)).unwrap();
+        let _b = instantiate(Some(&group)).unwrap();
+        assert_eq!(instantiate(Some(&group)).unwrap_err(), exceeded);
+        let mut c = instantiate(None).unwrap();
+        assert_eq!(group.count_of_instances(), 2);
+        assert_eq!(engine.count_of_instances(), 3);
+        assert_eq!(
+            a.run("grow", vec![Values::I32(1)]),
+            Ok(vec![Values::I32(-1)])
+        );
+        assert_eq!(
+            c.run("grow", vec![Values::I32(1)]),
+            Ok(vec![Values::I32(1)])
+        );
+        assert_eq!(engine.count_of_memory_pages(), 4);
+        assert_eq!(group.count_of_memory_pages(), 2);
+
+        while a.run("grow", vec![Values::I32(0)]).is_ok() {}
+        assert_eq!(a.run("grow", vec![Values::I32(0)]), Err(exceeded));
+        assert_eq!(group.fuel_consumed(), 10);
+        assert!(engine.fuel_consumed() >= 10);
+        assert!(c.run("grow", vec![Values::I32(0)]).is_ok());
+        group.next_epoch();
+
+        group.interrupt_handle().interrupt();
+        assert_eq!(
+            a.run("grow", vec![Values::I32(0)]),
+            Err(interrupted.clone())
+        );
+        assert!(c.run("grow", vec![Values::I32(0)]).is_ok());
+        group.interrupt_handle().reset();
+        assert_eq!(
+            a.run("grow", vec![Values::I32(0)]),
+            Ok(vec![Values::I32(1)])
+        );
+
+        engine.interrupt_handle().interrupt();
+        assert_eq!(
+            a.run("grow", vec![Values::I32(0)]),
+            Err(interrupted.clone())
+        );
+        assert_eq!(c.run("grow", vec![Values::I32(0)]), Err(interrupted));
+        engine.interrupt_handle().reset();
+
+        let handle = group.interrupt_handle();
+        std::thread::spawn(move || handle.interrupt())
+            .join()
+            .unwrap();
+        assert_eq!(
+            a.run("grow", vec![Values::I32(0)]),
+            Err(WasmError::Trap(Trap::Interrupted))
+        );
+        assert!(c.run("grow", vec![Values::I32(0)]).is_ok());
+    }
+
+    #[test]
+    fn share_decoded_modules_through_cache_of_engine() {
+        use std::collections::hash_map::RandomState;
+        use std::rc::Rc;
+        // (module (func (export "f") (result i32) i32.const 42))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x05, 0x01, 0x01, 0x66, 0x00, 0x00, // export
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x41, 0x2a, 0x0b, // code
+        ];
+        let engine = Engine::new(Default::default());
+        let decoded = engine.decode_module(&bytes).unwrap();
+        assert!(!Rc::ptr_eq(
+            &decoded,
+            &engine.decode_module(&bytes).unwrap()
+        ));
+
+        engine.cache_modules(ModuleCache::new(1, RandomState::new()));
+        let decoded = engine.decode_module(&bytes).unwrap();
+        assert!(Rc::ptr_eq(&decoded, &engine.decode_module(&bytes).unwrap()));
+        let group = engine.group(Default::default());
+        let mut vm = group
+            .instantiate_bytes(init_store(), &bytes, Default::default(), 65536)
+            .unwrap();
+        assert_eq!(vm.run("f", vec![]), Ok(vec![Values::I32(42)]));
+        assert!(Rc::ptr_eq(&decoded, &engine.decode_module(&bytes).unwrap()));
+        assert!(engine.decode_module(&bytes[..8]).is_ok());
+        assert!(engine.decode_module(&bytes[..10]).is_err());
+    }
+
+    #[test]
+    fn index_tables_by_u64() {
+        let table = create_spectest()
+            .table_instances
+            .get_table_at(&indice::Indice::from(0u32))
+            .unwrap();
+        assert_eq!(table.size(), 10);
+        assert_eq!(table.table_type().max(), 20);
+        assert_eq!(
+            table.get_function_instance(9u32).unwrap_err(),
+            WasmError::Trap(Trap::UninitializedElement)
+        );
+        for idx in [10, u64::from(u32::max_value()) + 10].iter() {
+            assert_eq!(
+                table.get_function_instance_u64(*idx).unwrap_err(),
+                WasmError::Trap(Trap::UndefinedElement)
+            );
+        }
+        assert_eq!(table.index_of(&Values::I32(-1)), Ok(0xffff_ffff));
+        assert_eq!(
+            table.index_of(&Values::I64(0)),
+            Err(WasmError::Trap(Trap::TypeMismatch))
+        );
+
+        let unbounded = TableType::new(ElementType::AnyFunc, Limit::NoUpperLimit(0));
+        assert_eq!(unbounded.max(), 1 << 32);
+        let table64 = TableType::new_with_index_type(
+            ElementType::AnyFunc,
+            Limit::NoUpperLimit(0),
+            TableIndexType::I64,
+        );
+        assert_eq!(table64.max(), u64::max_value());
+        assert!(!table64.matches(&unbounded));
+    }
+
+    #[test]
+    fn keep_declaration_order_of_imports_exports_and_registration() {
+        // (module
+        //   (import "m" "z" (func))
+        //   (import "m" "a" (func))
+        //   (func)
+        //   (export "y" (func 2))
+        //   (export "b" (func 0)))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type
+            0x02, 0x0d, 0x02, 0x01, 0x6d, 0x01, 0x7a, 0x00, 0x00, 0x01, 0x6d, 0x01, 0x61, 0x00,
+            0x00, // import
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x09, 0x02, 0x01, 0x79, 0x00, 0x02, 0x01, 0x62, 0x00, 0x00, // export
+            0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b, // code
+        ];
+        let module = decode_module(&bytes).unwrap();
+        assert_eq!(module.import_names(), vec![("m", "z"), ("m", "a")]);
+        assert_eq!(module.export_names(), vec!["y", "b"]);
+
+        let mut external_modules = ExternalModules::default();
+        for name in ["c", "a", "b"].iter() {
+            external_modules
+                .register_module(Some(name.to_string()), ExternalModule::default())
+                .unwrap();
+        }
+        assert!(external_modules
+            .unregister_module(&Some("c".to_owned()))
+            .is_some());
+        assert_eq!(
+            external_modules.module_names(),
+            vec![Some("a".to_owned()), Some("b".to_owned())]
+        );
+    }
+
+    #[test]
+    fn report_offset_of_invalid_utf8_in_names() {
+        let custom = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x00, 0x03, 0x02, 0x61, 0xff, // custom
+        ];
+        assert_eq!(
+            decode_module(&custom).unwrap_err(),
+            WasmError::Trap(Trap::InvalidUTF8Name { offset: 12 })
+        );
+
+        // (module (import "m" "\c3\28" (func)))
+        let import = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type
+            0x02, 0x08, 0x01, 0x01, 0x6d, 0x02, 0xc3, 0x28, 0x00, 0x00, // import
+        ];
+        let err = decode_module(&import).unwrap_err();
+        assert_eq!(err, WasmError::Trap(Trap::InvalidUTF8Name { offset: 20 }));
+        match err {
+            WasmError::Trap(trap) => assert_eq!(trap.message(), "invalid UTF-8 encoding"),
+            x => unreachable!("{:?}", x),
+        }
+    }
+
+    #[test]
+    fn analyze_stack_usage_of_functions() {
+        // (module
+        //   (func (export "f") (param i32) (result i32) (local i64)
+        //     get_local 0 i32.const 1 i32.add))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x05, 0x01, 0x01, 0x66, 0x00, 0x00, // export
+            0x0a, 0x0b, 0x01, // code
+            0x09, 0x01, 0x01, 0x7e, 0x20, 0x00, 0x41, 0x01, 0x6a, 0x0b, // body of f
+        ];
+        let features = Features::default();
+        let limits = StackLimits {
+            max_depth: Some(2),
+            max_locals: Some(2),
+        };
+        let module = validate_module_with_limits(decode_module(&bytes), &features, &limits);
+        let mut vm = instantiate_module(init_store(), module, Default::default(), 65536).unwrap();
+        let f = vm
+            .get_function_instance(&indice::Indice::from(0u32))
+            .unwrap()
+            .clone();
+        assert_eq!(
+            f.stack_usage(),
+            Some(StackUsage {
+                max_depth: 2,
+                count_of_locals: 2,
+            })
+        );
+        assert_eq!(vm.run("f", vec![Values::I32(1)]), Ok(vec![Values::I32(2)]));
+
+        // NOTE: Locals, frame label and operands don't fit in the stack.
+        let module = validate_module_with_limits(decode_module(&bytes), &features, &limits);
+        let mut vm = instantiate_module(init_store(), module, Default::default(), 4).unwrap();
+        assert_eq!(
+            vm.run("f", vec![Values::I32(1)]),
+            Err(WasmError::Trap(Trap::StackOverflow))
+        );
+
+        for limits in [
+            StackLimits {
+                max_depth: Some(1),
+                max_locals: None,
+            },
+            StackLimits {
+                max_depth: None,
+                max_locals: Some(1),
+            },
+        ]
+        .iter()
+        {
+            assert_eq!(
+                validate_module_with_limits(decode_module(&bytes), &features, limits).unwrap_err(),
+                WasmError::TypeError(TypeError::StackLimitExceeded)
+            );
+        }
+    }
+
+    #[test]
+    fn expose_producers_of_module() {
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x00, 0x14, // custom
+            0x09, 0x70, 0x72, 0x6f, 0x64, 0x75, 0x63, 0x65, 0x72, 0x73, // "producers"
+            0x01, 0x03, 0x73, 0x64, 0x6b, 0x01, 0x01, 0x78, 0x01, 0x31, // "sdk" "x" "1"
+        ];
+        let module = decode_module(&bytes);
+        let vm = instantiate_module(init_store(), module, Default::default(), 65536).unwrap();
+        assert_eq!(
+            vm.producers(),
+            Some(&Producers {
+                language: vec![],
+                processed_by: vec![],
+                sdk: vec![ProducerVersion {
+                    name: "x".to_owned(),
+                    version: "1".to_owned(),
+                }],
+            })
+        );
+
+        let bytes = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        let module = decode_module(&bytes);
+        let vm = instantiate_module(init_store(), module, Default::default(), 65536).unwrap();
+        assert_eq!(vm.producers(), None);
+    }
+
+    #[test]
+    fn convert_primitives_into_and_from_values() {
+        use std::convert::TryFrom;
+        assert_eq!(Values::from(-1i32), Values::I32(-1));
+        assert_eq!(Values::from(u32::max_value()), Values::I32(-1));
+        assert_eq!(Values::from(u64::max_value()), Values::I64(-1));
+        assert_eq!(Values::from(true), Values::I32(1));
+        assert_eq!(Values::from(1.5f64), Values::F64(1.5));
+        assert_eq!(u32::try_from(Values::I32(-1)), Ok(u32::max_value()));
+        assert_eq!(bool::try_from(Values::I32(2)), Ok(true));
+        assert_eq!(f32::try_from(Values::F32(0.5)), Ok(0.5));
+        assert_eq!(
+            i32::try_from(Values::I64(1)),
+            Err(WasmError::Trap(Trap::TypeMismatch))
+        );
+        assert_eq!(<f64 as WasmValue>::value_type(), ValueTypes::F64);
+
+        assert_eq!(i32::from_value_lossy(&Values::I64(0x1_0000_0002)), Ok(2));
+        assert_eq!(u64::from_value_lossy(&Values::I32(-1)), Ok(0xffff_ffff));
+        assert_eq!(i64::from_value_lossy(&Values::I32(-1)), Ok(-1));
+        assert_eq!(f32::from_value_lossy(&Values::F64(0.25)), Ok(0.25));
+        assert_eq!(
+            f64::from_value_lossy(&Values::I64(1)),
+            Err(WasmError::Trap(Trap::TypeMismatch))
+        );
+    }
+
+    #[test]
+    fn reject_function_bodies_exceeding_decode_limits() {
+        // (module (func (local i32 i32) block block i32.const 0 br_table 0 1 0 end end))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x0a, 0x13, 0x01, 0x11, 0x01, 0x02, 0x7f, // code
+            0x02, 0x40, 0x02, 0x40, 0x41, 0x00, 0x0e, 0x02, 0x00, 0x01, 0x00, 0x0b, 0x0b, 0x0b,
+        ];
+        let limits = DecodeLimits {
+            max_body_size: Some(17),
+            max_locals: Some(2),
+            max_nesting_depth: Some(2),
+            max_br_table_targets: Some(3),
+        };
+        assert!(decode_module_with_limits(&bytes, &limits).is_ok());
+        for (limits, trap) in vec![
+            (
+                DecodeLimits {
+                    max_body_size: Some(16),
+                    ..limits.clone()
+                },
+                Trap::FunctionBodyTooLarge,
+            ),
+            (
+                DecodeLimits {
+                    max_locals: Some(1),
+                    ..limits.clone()
+                },
+                Trap::TooManyLocals,
+            ),
+            (
+                DecodeLimits {
+                    max_nesting_depth: Some(1),
+                    ..limits.clone()
+                },
+                Trap::NestingTooDeep,
+            ),
+            (
+                DecodeLimits {
+                    max_br_table_targets: Some(2),
+                    ..limits.clone()
+                },
+                Trap::TooManyBrTableTargets,
+            ),
+        ]
+        .into_iter()
+        {
+            assert_eq!(
+                decode_module_with_limits(&bytes, &limits).map(|_| ()),
+                Err(WasmError::Trap(trap))
+            );
+        }
+    }
+
+    #[test]
+    fn reject_unbalanced_blocks() {
+        let instantiate = |body: &[u8]| {
+            let mut bytes = vec![
+                0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+                0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type
+                0x03, 0x02, 0x01, 0x00, // function
+            ];
+            bytes.extend_from_slice(&[0x0a, body.len() as u8 + 2, 0x01, body.len() as u8]);
+            bytes.extend_from_slice(body);
+            instantiate_module(
+                init_store(),
+                decode_module(&bytes),
+                Default::default(),
+                65536,
+            )
+            .map(|_| ())
+        };
+        // i32.const 1 if else end end
+        assert_eq!(
+            instantiate(&[0x00, 0x41, 0x01, 0x04, 0x40, 0x05, 0x0b, 0x0b]),
+            Ok(())
+        );
+        for (body, trap) in vec![
+            // block end
+            (vec![0x00, 0x02, 0x40, 0x0b], Trap::UnexpectedEnd),
+            // i32.const
+            (vec![0x00, 0x41], Trap::UnexpectedEnd),
+            // block else end end
+            (
+                vec![0x00, 0x02, 0x40, 0x05, 0x0b, 0x0b],
+                Trap::MisplacedElse,
+            ),
+            // else end
+            (vec![0x00, 0x05, 0x0b], Trap::MisplacedElse),
+            // i32.const 0 if else else end end
+            (
+                vec![0x00, 0x41, 0x00, 0x04, 0x40, 0x05, 0x05, 0x0b, 0x0b],
+                Trap::MisplacedElse,
+            ),
+        ]
+        .into_iter()
+        {
+            assert_eq!(instantiate(&body), Err(WasmError::Trap(trap)));
+        }
+    }
+
+    // NOTE: Only release build traps, debug build asserts it to find inconsistency of interpreter.
+    #[test]
+    #[cfg_attr(debug_assertions, should_panic(expected = "out of range"))]
+    fn trap_by_local_out_of_range_of_frame() {
+        // (module (func (export "f") (result i32) get_local 1))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x05, 0x01, 0x01, 0x66, 0x00, 0x00, // export
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x20, 0x01, 0x0b, // code
+        ];
+        assert!(validate_module(&decode_module(&bytes)).is_err());
+        let mut vm = instantiate_module(
+            init_store(),
+            decode_module(&bytes),
+            Default::default(),
+            65536,
+        )
+        .unwrap();
+        assert_eq!(
+            vm.run("f", vec![]),
+            Err(WasmError::Trap(Trap::OutOfRangeLocal))
+        );
+    }
+
+    #[test]
+    fn reject_floats_unless_float_feature() {
+        let validate = |bytes: &[u8]| validate_module(&decode_module(bytes));
+        let expected = if cfg!(feature = "float") {
+            Ok(())
+        } else {
+            Err(WasmError::TypeError(TypeError::DisabledFloat))
+        };
+        // (module (func f32.const 0 drop))
+        let bytes_of_instruction = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x0a, 0x0a, 0x01, 0x08, 0x00, 0x43, 0x00, 0x00, 0x00, 0x00, 0x1a, 0x0b, // code
+        ];
+        assert_eq!(validate(&bytes_of_instruction), expected);
+        // (module (func (local f64)))
+        let bytes_of_local = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x0a, 0x06, 0x01, 0x04, 0x01, 0x01, 0x7c, 0x0b, // code
+        ];
+        assert_eq!(validate(&bytes_of_local), expected);
+        // (module (func (param f32)))
+        let bytes_of_parameter = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x05, 0x01, 0x60, 0x01, 0x7d, 0x00, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b, // code
+        ];
+        assert_eq!(validate(&bytes_of_parameter), expected);
+    }
+
+    #[test]
+    fn refer_modules_of_script_by_names_and_latest() {
+        // (module (func (export "f") (result i32) i32.const n))
+        let constant = |n: u8| {
+            vec![
+                0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+                0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+                0x03, 0x02, 0x01, 0x00, // function
+                0x07, 0x05, 0x01, 0x01, 0x66, 0x00, 0x00, // export
+                0x0a, 0x06, 0x01, 0x04, 0x00, 0x41, n, 0x0b, // code
+            ]
+        };
+        // (module (import "m" "f" (func (result i32))) (func (export "f") (result i32) call 0))
+        let importer = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+            0x02, 0x07, 0x01, 0x01, 0x6d, 0x01, 0x66, 0x00, 0x00, // import
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x05, 0x01, 0x01, 0x66, 0x00, 0x01, // export
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x10, 0x00, 0x0b, // code
+        ];
+        let mut environment = ScriptEnvironment::new(ExternalModules::default());
+        assert_eq!(
+            environment.get(None).unwrap_err(),
+            WasmError::Trap(Trap::UnknownModule(None))
+        );
+        environment
+            .instantiate(Some("a"), &constant(1), 65536)
+            .unwrap();
+        environment.instantiate(None, &constant(2), 65536).unwrap();
+        let run = |environment: &ScriptEnvironment, name: Option<&str>| {
+            let instance = environment.get(name).unwrap();
+            let mut instance = instance.borrow_mut();
+            instance.run("f", vec![]).unwrap()
+        };
+        assert_eq!(run(&environment, None), vec![Values::I32(2)]);
+        assert_eq!(run(&environment, Some("a")), vec![Values::I32(1)]);
+
+        environment.register(Some("a"), "m").unwrap();
+        environment
+            .instantiate(Some("b"), &importer, 65536)
+            .unwrap();
+        // NOTE: Registering again affects only modules instantiated afterwards.
+        environment.instantiate(None, &constant(3), 65536).unwrap();
+        environment.register(None, "m").unwrap();
+        environment
+            .instantiate(Some("c"), &importer, 65536)
+            .unwrap();
+        assert_eq!(run(&environment, Some("b")), vec![Values::I32(1)]);
+        assert_eq!(run(&environment, Some("c")), vec![Values::I32(3)]);
+        assert_eq!(run(&environment, None), vec![Values::I32(3)]);
+        assert_eq!(
+            environment.get(Some("d")).unwrap_err(),
+            WasmError::Trap(Trap::UnknownModule(Some("d".to_owned())))
+        );
+    }
+
+    #[test]
+    fn evaluate_deeply_nested_blocks() {
+        let depth = 10000;
+        let leb128 = |mut n: usize| {
+            let mut bytes = vec![];
+            while n >= 0x80 {
+                bytes.push((n & 0x7f) as u8 | 0x80);
+                n >>= 7;
+            }
+            bytes.push(n as u8);
+            bytes
+        };
+        // (func (export "f") (result i32) (block (block ... (nop) ... end end) (i32.const 7))
+        let mut body = vec![0x00];
+        for _ in 0..depth {
+            body.extend_from_slice(&[0x02, 0x40]);
+        }
+        body.push(0x01);
+        body.extend(vec![0x0b; depth]);
+        body.extend_from_slice(&[0x41, 0x07, 0x0b]);
+        let mut code = vec![0x01];
+        code.extend(leb128(body.len()));
+        code.extend(body);
+        let mut bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x05, 0x01, 0x01, 0x66, 0x00, 0x00, // export
+            0x0a,
+        ];
+        bytes.extend(leb128(code.len()));
+        bytes.extend(code);
+        let module = decode_module(&bytes);
+        validate_module(&module).unwrap();
+        let mut vm = instantiate_module(init_store(), module, Default::default(), 65536).unwrap();
+        assert_eq!(vm.run("f", vec![]).unwrap(), vec![Values::I32(7)]);
+    }
+
+    #[test]
+    fn open_registered_module_from_host_function() {
+        // (module
+        //   (import "env" "dlopen" (func (result i32)))
+        //   (table (export "table") 0 anyfunc)
+        //   (func (export "run") (result i32) call 0 call_indirect (type 0)))
+        let main = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+            0x02, 0x0e, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x06, 0x64, 0x6c, 0x6f, 0x70, 0x65, 0x6e,
+            0x00, 0x00, // import
+            0x03, 0x02, 0x01, 0x00, // function
+            0x04, 0x04, 0x01, 0x70, 0x00, 0x00, // table
+            0x07, 0x0f, 0x02, 0x05, 0x74, 0x61, 0x62, 0x6c, 0x65, 0x01, 0x00, 0x03, 0x72, 0x75,
+            0x6e, 0x00, 0x01, // export
+            0x0a, 0x09, 0x01, 0x07, 0x00, 0x10, 0x00, 0x11, 0x00, 0x00, 0x0b, // code
+        ];
+        // (module (func (export "answer") (result i32) i32.const 42))
+        let lib = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x0a, 0x01, 0x06, 0x61, 0x6e, 0x73, 0x77, 0x65, 0x72, 0x00, 0x00, // export
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x41, 0x2a, 0x0b, // code
+        ];
+        let namespace = Namespace::new(ExternalModules::default(), 65536);
+        namespace.register_module("lib", lib);
+        assert_eq!(
+            namespace.open("missing").unwrap_err(),
+            WasmError::Trap(Trap::UnknownModule(Some("missing".to_owned())))
+        );
+
+        let weak = namespace.downgrade();
+        let dlopen = FunctionInstance::new_host_closure(
+            Some("dlopen".to_owned()),
+            FunctionType::new(vec![], vec![ValueTypes::I32]),
+            move |_| {
+                let namespace = weak.upgrade().unwrap();
+                namespace.open("lib").unwrap();
+                let base = namespace.wire_exports("lib", "main").unwrap();
+                vec![Values::I32(base as i32)]
+            },
+        );
+        let env = ExternalModule::new(vec![dlopen], vec![], vec![], vec![], vec![]);
+        let mut external_modules = namespace.external_modules();
+        external_modules
+            .register_module(Some("env".to_owned()), env)
+            .unwrap();
+
+        let instance = instantiate_module(
+            init_store(),
+            decode_module(&main),
+            namespace.external_modules(),
+            65536,
+        )
+        .unwrap();
+        let instance = namespace.register_instance("main", instance).unwrap();
+        assert!(namespace.instance("lib").is_none());
+        assert_eq!(
+            instance.borrow_mut().run("run", vec![]),
+            Ok(vec![Values::I32(42)])
+        );
+        assert!(namespace.instance("lib").is_some());
+    }
+
+    #[test]
+    fn notify_memory_grow_to_listener() {
+        // (module (memory 1 3) (func (export "grow") (param i32) (result i32) get_local 0 memory.grow))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x05, 0x04, 0x01, 0x01, 0x01, 0x03, // memory
+            0x07, 0x08, 0x01, 0x04, 0x67, 0x72, 0x6f, 0x77, 0x00, 0x00, // export
+            0x0a, 0x08, 0x01, 0x06, 0x00, 0x20, 0x00, 0x40, 0x00, 0x0b, // code
+        ];
+        let store = init_store();
+        let section = decode_module(&bytes);
+        let mut vm = instantiate_module(store, section, Default::default(), 65536).unwrap();
+        let growths = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+        let recorded = growths.clone();
+        vm.on_memory_grow(move |growth| recorded.borrow_mut().push(growth.clone()));
+
+        vm.run("grow", vec![Values::I32(1)]).unwrap();
+        vm.run("grow", vec![Values::I32(2)]).unwrap();
+        assert_eq!(
+            *growths.borrow(),
+            vec![
+                MemoryGrowth::Grown {
+                    requested_pages: 1,
+                    previous_pages: 1,
+                    current_pages: 2,
+                },
+                MemoryGrowth::Failed {
+                    requested_pages: 2,
+                    current_pages: 2,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn persist_mutable_globals_without_memory() {
+        // (module
+        //   (global (export "counter") (mut i32) (i32.const 0))
+        //   (global (export "limit") i32 (i32.const 10))
+        //   (func (export "inc") (result i32)
+        //     get_global 0 i32.const 1 i32.add set_global 0 get_global 0))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x06, 0x0b, 0x02, 0x7f, 0x01, 0x41, 0x00, 0x0b, 0x7f, 0x00, 0x41, 0x0a,
+            0x0b, // global
+            0x07, 0x19, 0x03, 0x07, 0x63, 0x6f, 0x75, 0x6e, 0x74, 0x65, 0x72, 0x03, 0x00, 0x05,
+            0x6c, 0x69, 0x6d, 0x69, 0x74, 0x03, 0x01, 0x03, 0x69, 0x6e, 0x63, 0x00,
+            0x00, // export
+            0x0a, 0x0d, 0x01, 0x0b, 0x00, 0x23, 0x00, 0x41, 0x01, 0x6a, 0x24, 0x00, 0x23, 0x00,
+            0x0b, // code
+        ];
+        let instantiate = || {
+            instantiate_module(
+                init_store(),
+                decode_module(&bytes),
+                Default::default(),
+                65536,
+            )
+            .unwrap()
+        };
+        let mut vm = instantiate();
+        vm.run("inc", vec![]).unwrap();
+        vm.run("inc", vec![]).unwrap();
+        let globals = vm.export_globals();
+        assert_eq!(globals, vec![("counter".to_owned(), Values::I32(2))]);
+
+        let mut restored = instantiate();
+        restored.import_globals(&globals).unwrap();
+        assert_eq!(restored.run("inc", vec![]), Ok(vec![Values::I32(3)]));
+
+        assert_eq!(
+            restored.import_globals(&[("limit".to_owned(), Values::I32(1))]),
+            Err(WasmError::Trap(Trap::IncompatibleGlobal(
+                "limit".to_owned()
+            )))
+        );
+        assert_eq!(
+            restored.import_globals(&[
+                ("counter".to_owned(), Values::I32(0)),
+                ("counter".to_owned(), Values::I64(0)),
+            ]),
+            Err(WasmError::Trap(Trap::IncompatibleGlobal(
+                "counter".to_owned()
+            )))
+        );
+        assert_eq!(
+            restored.import_globals(&[("countr".to_owned(), Values::I32(0))]),
+            Err(WasmError::UnknownExport {
+                name: "countr".to_owned(),
+                similar: vec!["counter".to_owned()],
+            })
+        );
+        assert_eq!(restored.run("inc", vec![]), Ok(vec![Values::I32(4)]));
+    }
+
+    #[test]
+    fn collect_warnings_of_valid_module() {
+        // (module (memory 1)
+        //   (func (export "load") (param i32) (result i32) get_local 0 i32.load align=1)
+        //   (func (param i32) (result i32) i32.const 0 get_local 0 br_table 0 0 .. 0 i32.const 0))
+        let mut bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type
+            0x03, 0x03, 0x02, 0x00, 0x00, // function
+            0x05, 0x03, 0x01, 0x00, 0x01, // memory
+            0x07, 0x08, 0x01, 0x04, 0x6c, 0x6f, 0x61, 0x64, 0x00, 0x00, // export
+            0x0a, 0x97, 0x08, 0x02, // code
+            0x07, 0x00, 0x20, 0x00, 0x28, 0x00, 0x00, 0x0b, // load
+            0x8c, 0x08, 0x00, 0x41, 0x00, 0x20, 0x00, 0x0e, 0x80, 0x08,
+        ];
+        bytes.extend(vec![0x00; 1025]);
+        bytes.extend(vec![0x41, 0x00, 0x0b]);
+        let module = decode_module(&bytes);
+        assert_eq!(validate_module(&module), Ok(()));
+        assert_eq!(
+            validate_module_with_diagnostics(&module, &Features::default()),
+            Ok(vec![
+                Warning::UnderalignedAccess {
+                    function: 0,
+                    mnemonic: "i32.load",
+                    align: 0,
+                    natural: 2,
+                },
+                Warning::HugeBrTable {
+                    function: 1,
+                    count_of_targets: 1025,
+                },
+                Warning::UnusedFunction(1),
+            ])
+        );
+    }
+
+    #[test]
+    fn view_contiguous_memory_without_copying() {
+        // (module (memory 1) (func (export "load") (param i32) (result i32) get_local 0 i32.load8_u))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x05, 0x03, 0x01, 0x00, 0x01, // memory
+            0x07, 0x08, 0x01, 0x04, 0x6c, 0x6f, 0x61, 0x64, 0x00, 0x00, // export
+            0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x2d, 0x00, 0x00, 0x0b, // code
+        ];
+        let mut vm = instantiate_module(
+            init_store(),
+            decode_module(&bytes),
+            Default::default(),
+            65536,
+        )
+        .unwrap();
+        {
+            let mut view = vm.memory_view().unwrap();
+            assert_eq!(view.len(), 65536);
+            view.as_mut_slice()[3] = 42;
+        }
+        assert_eq!(
+            vm.run("load", vec![Values::I32(3)]),
+            Ok(vec![Values::I32(42)])
+        );
+        vm.write_memory(4, &[7]).unwrap();
+        assert_eq!(&vm.memory_view().unwrap().as_slice()[3..5], &[42, 7]);
+
+        let mut store = init_store();
+        store.memory_strategy = MemoryStrategy::Lazy;
+        let lazy =
+            instantiate_module(store, decode_module(&bytes), Default::default(), 65536).unwrap();
+        assert_eq!(
+            lazy.memory_view().err(),
+            Some(WasmError::Trap(Trap::NonContiguousMemory))
+        );
+    }
+
+    #[test]
+    fn instantiate_memory_of_4gib_lazily() {
+        // (module (memory 65536 65536)
+        //   (func (export "load") (param i32) (result i32) get_local 0 i32.load8_u)
+        //   (func (export "size") (result i32) current_memory))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x0a, 0x02, 0x60, 0x01, 0x7f, 0x01, 0x7f, 0x60, 0x00, 0x01, 0x7f, // type
+            0x03, 0x03, 0x02, 0x00, 0x01, // function
+            0x05, 0x08, 0x01, 0x01, 0x80, 0x80, 0x04, 0x80, 0x80, 0x04, // memory
+            0x07, 0x0f, 0x02, 0x04, 0x6c, 0x6f, 0x61, 0x64, 0x00, 0x00, 0x04, 0x73, 0x69, 0x7a,
+            0x65, 0x00, 0x01, // export
+            0x0a, 0x0e, 0x02, 0x07, 0x00, 0x20, 0x00, 0x2d, 0x00, 0x00, 0x0b, 0x04, 0x00, 0x3f,
+            0x00, 0x0b, // code
+        ];
+        let mut store = init_store();
+        store.memory_strategy = MemoryStrategy::Lazy;
+        let mut vm =
+            instantiate_module(store, decode_module(&bytes), Default::default(), 65536).unwrap();
+        assert_eq!(vm.run("size", vec![]), Ok(vec![Values::I32(65536)]));
+        vm.write_memory(u32::max_value() - 1, &[7]).unwrap();
+        assert_eq!(
+            vm.run("load", vec![Values::I32(-2)]),
+            Ok(vec![Values::I32(7)])
+        );
+    }
+
+    #[test]
+    fn intern_module_names_by_engine() {
+        // (module (func (export "answer") (result i32) i32.const 42))
+        let lib = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x0a, 0x01, 0x06, 0x61, 0x6e, 0x73, 0x77, 0x65, 0x72, 0x00, 0x00, // export
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x41, 0x2a, 0x0b, // code
+        ];
+        // (module (import "env" "answer" (func (result i32))) (func (export "run") (result i32) call 0))
+        let app = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+            0x02, 0x0e, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x06, 0x61, 0x6e, 0x73, 0x77, 0x65, 0x72,
+            0x00, 0x00, // import
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x07, 0x01, 0x03, 0x72, 0x75, 0x6e, 0x00, 0x01, // export
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x10, 0x00, 0x0b, // code
+        ];
+        let engine = Engine::new(EngineLimits::default());
+        let lib = engine
+            .instantiate(init_store(), decode_module(&lib), Default::default(), 65536)
+            .unwrap();
+        assert_eq!(engine.symbols().get("env"), None);
+
+        let mut results = vec![];
+        for _ in 0..2 {
+            let mut external_modules = engine.external_modules();
+            external_modules
+                .register_module(Some("env".to_owned()), lib.export_module())
+                .unwrap();
+            let mut app = engine
+                .instantiate(init_store(), decode_module(&app), external_modules, 65536)
+                .unwrap();
+            results.push(app.run("run", vec![]).unwrap());
+        }
+        assert_eq!(results, vec![vec![Values::I32(42)], vec![Values::I32(42)]]);
+        assert_eq!(engine.symbols().len(), 1);
+        assert_eq!(
+            engine.symbols().get("env").map(|env| env.to_string()),
+            Some("env".to_owned())
+        );
+        assert_eq!(
+            engine.symbols().get("env").and_then(|env| env.id()),
+            Some(0)
+        );
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn keep_bits_of_nan_payloads() {
+        // (module
+        //   (func (export "nan") (result f32) f32.const nan:0x200001)
+        //   (func (export "bits") (param f32) (result i32) get_local 0 i32.reinterpret/f32))
+        let bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x0a, 0x02, 0x60, 0x00, 0x01, 0x7d, 0x60, 0x01, 0x7d, 0x01, 0x7f, // type
+            0x03, 0x03, 0x02, 0x00, 0x01, // function
+            0x07, 0x0e, 0x02, 0x03, 0x6e, 0x61, 0x6e, 0x00, 0x00, 0x04, 0x62, 0x69, 0x74, 0x73,
+            0x00, 0x01, // export
+            0x0a, 0x0f, 0x02, 0x07, 0x00, 0x43, 0x01, 0x00, 0xa0, 0x7f, 0x0b, 0x05, 0x00, 0x20,
+            0x00, 0xbc, 0x0b, // code
+        ];
+        let mut vm = instantiate_module(
+            init_store(),
+            decode_module(&bytes),
+            Default::default(),
+            65536,
+        )
+        .unwrap();
+        let nan = vm.run("nan", vec![]).unwrap().pop().unwrap();
+        assert_eq!(nan.to_bits(), 0x7fa0_0001);
+        assert!(nan.bits_eq(&Values::from_f32_bits(0x7fa0_0001)));
+        assert!(!nan.bits_eq(&Values::from_f32_bits(0x7fc0_0000)));
+        assert!(!Values::F64(0.0).bits_eq(&Values::F64(-0.0)));
+
+        let signaling = Values::from_f32_bits(0x7f80_0001);
+        assert_eq!(
+            vm.run("bits", vec![signaling]).unwrap(),
+            vec![Values::I32(0x7f80_0001)]
+        );
+        assert_eq!(Values::from_f64_bits(1u64 << 63).to_bits(), 1u64 << 63);
+    }
+
+    #[test]
+    fn limit_targets_of_br_table() {
+        // (module (func (export "switch") (param i32) (result i32)
+        //   block get_local 0 br_table 0 0 0 end i32.const 7))
+        let bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x0a, 0x01, 0x06, 0x73, 0x77, 0x69, 0x74, 0x63, 0x68, 0x00, 0x00, // export
+            0x0a, 0x10, 0x01, 0x0e, 0x00, 0x02, 0x40, 0x20, 0x00, 0x0e, 0x02, 0x00, 0x00, 0x00,
+            0x0b, 0x41, 0x07, 0x0b, // code
+        ];
+        let instantiate = |max_br_table_targets| {
+            let mut store = init_store();
+            store.execution_limits = ExecutionLimits {
+                max_br_table_targets,
+            };
+            instantiate_module(store, decode_module(&bytes), Default::default(), 65536).unwrap()
+        };
+        let mut vm = instantiate(Some(3));
+        assert_eq!(
+            vm.run("switch", vec![Values::I32(1)]),
+            Ok(vec![Values::I32(7)])
+        );
+        assert_eq!(
+            vm.run("switch", vec![Values::I32(-1)]),
+            Ok(vec![Values::I32(7)])
+        );
+        let mut vm = instantiate(Some(2));
+        assert_eq!(
+            vm.run("switch", vec![Values::I32(1)]),
+            Err(WasmError::Trap(Trap::ExecutionLimitExceeded))
+        );
+    }
+
+    #[test]
+    fn lint_dead_code_of_module() {
+        // (module
+        //   (memory 1)
+        //   (global (mut i32) (i32.const 0))
+        //   (global (mut i32) (i32.const 0))
+        //   (func (export "a") (export "b") call 1)
+        //   (func i32.const 1 set_global 1)
+        //   (func call 3)
+        //   (func))
+        let bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type
+            0x03, 0x05, 0x04, 0x00, 0x00, 0x00, 0x00, // function
+            0x05, 0x03, 0x01, 0x00, 0x01, // memory
+            0x06, 0x0b, 0x02, 0x7f, 0x01, 0x41, 0x00, 0x0b, 0x7f, 0x01, 0x41, 0x00,
+            0x0b, // global
+            0x07, 0x09, 0x02, 0x01, 0x61, 0x00, 0x00, 0x01, 0x62, 0x00, 0x00, // export
+            0x0a, 0x15, 0x04, 0x04, 0x00, 0x10, 0x01, 0x0b, 0x06, 0x00, 0x41, 0x01, 0x24, 0x01,
+            0x0b, 0x04, 0x00, 0x10, 0x03, 0x0b, 0x02, 0x00, 0x0b, // code
+        ];
+        assert_eq!(
+            lint_module(&decode_module(&bytes), &Features::default()),
+            Ok(vec![
+                LintDiagnostic::IdenticalExports {
+                    function: 0,
+                    names: vec!["a".to_owned(), "b".to_owned()],
+                },
+                LintDiagnostic::UnreachableFunction(2),
+                LintDiagnostic::UnreachableFunction(3),
+                LintDiagnostic::UnusedMemory,
+                LintDiagnostic::UnwrittenMutableGlobal(0),
+            ])
+        );
+        let module = decode_module(&bytes);
+        assert_eq!(
+            Ok(lint::check(module.as_ref().unwrap())),
+            lint_module(&module, &Features::default())
+        );
+        // NOTE: Function 3 is called by unreachable function 2, so only function 2 is unused.
+        assert_eq!(
+            validate_module_with_diagnostics(&module, &Features::default()),
+            Ok(vec![Warning::UnusedFunction(2)])
+        );
+
+        let empty = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        assert_eq!(
+            lint_module(&decode_module(&empty), &Features::default()),
+            Ok(vec![LintDiagnostic::MissingEntryPoint])
+        );
+    }
+
+    #[test]
+    fn call_raw_host_function() {
+        extern "C" fn add(context: *mut VmContext, arguments: *const u64, results: *mut u64) {
+            unsafe {
+                let context = &mut *context;
+                *(context.data as *mut u32) += 1;
+                let memory = std::slice::from_raw_parts(context.memory, context.memory_size);
+                let arguments = std::slice::from_raw_parts(arguments, 2);
+                let sum = arguments[0] as u32 + arguments[1] as u32 + u32::from(memory[0]);
+                *results = u64::from(sum);
+            }
+        }
+        // (module
+        //   (import "env" "add" (func (param i32 i32) (result i32)))
+        //   (memory 1)
+        //   (data (i32.const 0) "\05")
+        //   (func (export "run") (param i32) (result i32) get_local 0 i32.const 2 call 0))
+        let bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x0c, 0x02, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, 0x60, 0x01, 0x7f, 0x01,
+            0x7f, // type
+            0x02, 0x0b, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x03, 0x61, 0x64, 0x64, 0x00,
+            0x00, // import
+            0x03, 0x02, 0x01, 0x01, // function
+            0x05, 0x03, 0x01, 0x00, 0x01, // memory
+            0x07, 0x07, 0x01, 0x03, 0x72, 0x75, 0x6e, 0x00, 0x01, // export
+            0x0a, 0x0a, 0x01, 0x08, 0x00, 0x20, 0x00, 0x41, 0x02, 0x10, 0x00, 0x0b, // code
+            0x0b, 0x07, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x01, 0x05, // data
+        ];
+        let mut count_of_calls = 0u32;
+        let add = unsafe {
+            FunctionInstance::new_raw_host_fn(
+                Some("add".to_owned()),
+                FunctionType::new(
+                    vec![ValueTypes::I32, ValueTypes::I32],
+                    vec![ValueTypes::I32],
+                ),
+                add,
+                &mut count_of_calls as *mut u32 as *mut std::ffi::c_void,
+            )
+        };
+        let mut external_modules = ExternalModules::default();
+        external_modules
+            .register_module(
+                Some("env".to_owned()),
+                ExternalModule::new(vec![add], vec![], vec![], vec![], vec![]),
+            )
+            .unwrap();
+        let mut vm =
+            instantiate_module(init_store(), decode_module(&bytes), external_modules, 65536)
+                .unwrap();
+        assert_eq!(
+            vm.run("run", vec![Values::I32(40)]),
+            Ok(vec![Values::I32(47)])
+        );
+        assert_eq!(count_of_calls, 1);
+    }
+
+    #[test]
+    fn call_raw_host_function_indirectly() {
+        extern "C" fn sub(_context: *mut VmContext, arguments: *const u64, results: *mut u64) {
+            unsafe {
+                let arguments = std::slice::from_raw_parts(arguments, 2);
+                *results = u64::from((arguments[0] as u32).wrapping_sub(arguments[1] as u32));
+            }
+        }
+        // (module
+        //   (import "env" "sub" (func (param i32 i32) (result i32)))
+        //   (table 1 anyfunc)
+        //   (elem (i32.const 0) 0)
+        //   (func (export "run") (param i32 i32) (result i32)
+        //     get_local 0 get_local 1 i32.const 0 call_indirect (type 0)))
+        let bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x07, 0x01, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, // type
+            0x02, 0x0b, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x03, 0x73, 0x75, 0x62, 0x00,
+            0x00, // import
+            0x03, 0x02, 0x01, 0x00, // function
+            0x04, 0x04, 0x01, 0x70, 0x00, 0x01, // table
+            0x07, 0x07, 0x01, 0x03, 0x72, 0x75, 0x6e, 0x00, 0x01, // export
+            0x09, 0x07, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x01, 0x00, // element
+            0x0a, 0x0d, 0x01, 0x0b, 0x00, 0x20, 0x00, 0x20, 0x01, 0x41, 0x00, 0x11, 0x00, 0x00,
+            0x0b, // code
+        ];
+        let sub = unsafe {
+            FunctionInstance::new_raw_host_fn(
+                Some("sub".to_owned()),
+                FunctionType::new(
+                    vec![ValueTypes::I32, ValueTypes::I32],
+                    vec![ValueTypes::I32],
+                ),
+                sub,
+                std::ptr::null_mut(),
+            )
+        };
+        let mut external_modules = ExternalModules::default();
+        external_modules
+            .register_module(
+                Some("env".to_owned()),
+                ExternalModule::new(vec![sub], vec![], vec![], vec![], vec![]),
+            )
+            .unwrap();
+        let mut vm =
+            instantiate_module(init_store(), decode_module(&bytes), external_modules, 65536)
+                .unwrap();
+        assert_eq!(
+            vm.run("run", vec![Values::I32(10), Values::I32(3)]),
+            Ok(vec![Values::I32(7)])
+        );
+    }
+
+    #[test]
+    fn call_functions_of_shared_table_in_defining_instances() {
+        // (module
+        //   (table (export "table") 2 anyfunc)
+        //   (global i32 (i32.const 1))
+        //   (func (result i32) get_global 0)
+        //   (func (export "call") (param i32) (result i32) get_local 0 call_indirect (type 0))
+        //   (elem (i32.const 0) 0))
+        let owner = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x0a, 0x02, 0x60, 0x00, 0x01, 0x7f, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type
+            0x03, 0x03, 0x02, 0x00, 0x01, // function
+            0x04, 0x04, 0x01, 0x70, 0x00, 0x02, // table
+            0x06, 0x06, 0x01, 0x7f, 0x00, 0x41, 0x01, 0x0b, // global
+            0x07, 0x10, 0x02, 0x05, 0x74, 0x61, 0x62, 0x6c, 0x65, 0x01, 0x00, 0x04, 0x63, 0x61,
+            0x6c, 0x6c, 0x00, 0x01, // export
+            0x09, 0x07, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x01, 0x00, // element
+            0x0a, 0x0e, 0x02, 0x04, 0x00, 0x23, 0x00, 0x0b, 0x07, 0x00, 0x20, 0x00, 0x11, 0x00,
+            0x00, 0x0b, // code
+        ];
+        // (module
+        //   (import "owner" "table" (table 2 anyfunc))
+        //   (global i32 (i32.const 2))
+        //   (func (result i32) get_global 0)
+        //   (func (export "call") (param i32) (result i32) get_local 0 call_indirect (type 0))
+        //   (elem (i32.const 1) 0))
+        let importer = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x0a, 0x02, 0x60, 0x00, 0x01, 0x7f, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type
+            0x02, 0x11, 0x01, 0x05, 0x6f, 0x77, 0x6e, 0x65, 0x72, 0x05, 0x74, 0x61, 0x62, 0x6c,
+            0x65, 0x01, 0x70, 0x00, 0x02, // import
+            0x03, 0x03, 0x02, 0x00, 0x01, // function
+            0x06, 0x06, 0x01, 0x7f, 0x00, 0x41, 0x02, 0x0b, // global
+            0x07, 0x08, 0x01, 0x04, 0x63, 0x61, 0x6c, 0x6c, 0x00, 0x01, // export
+            0x09, 0x07, 0x01, 0x00, 0x41, 0x01, 0x0b, 0x01, 0x00, // element
+            0x0a, 0x0e, 0x02, 0x04, 0x00, 0x23, 0x00, 0x0b, 0x07, 0x00, 0x20, 0x00, 0x11, 0x00,
+            0x00, 0x0b, // code
+        ];
+        let mut owner = instantiate_module(
+            init_store(),
+            decode_module(&owner),
+            Default::default(),
+            65536,
+        )
+        .unwrap();
+        let mut external_modules = ExternalModules::default();
+        external_modules
+            .register_module(Some("owner".to_owned()), owner.export_module())
+            .unwrap();
+        let mut importer = instantiate_module(
+            init_store(),
+            decode_module(&importer),
+            external_modules,
+            65536,
+        )
+        .unwrap();
+        for vm in [&mut owner, &mut importer].iter_mut() {
+            assert_eq!(
+                vm.run("call", vec![Values::I32(0)]),
+                Ok(vec![Values::I32(1)])
+            );
+            assert_eq!(
+                vm.run("call", vec![Values::I32(1)]),
+                Ok(vec![Values::I32(2)])
+            );
+        }
+    }
+
+    #[test]
+    fn reject_module_by_verifier() {
+        struct DataChecksum(u32);
+        impl Verifier for DataChecksum {
+            fn verify_section(&self, id: u8, payload: &[u8]) -> Result<(), String> {
+                let checksum = payload.iter().map(|byte| u32::from(*byte)).sum::<u32>();
+                if id == 11 && checksum != self.0 {
+                    return Err(format!("checksum of data is {}", checksum));
+                }
+                Ok(())
+            }
+        }
+        // (module (memory 1) (data (i32.const 0) "\05"))
+        let bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x05, 0x03, 0x01, 0x00, 0x01, // memory
+            0x0b, 0x07, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x01, 0x05, // data
+        ];
+        let limits = DecodeLimits::default();
+        assert!(decode_module_with_verifier(&bytes, &limits, &DataChecksum(83)).is_ok());
+
+        let rejected = decode_module_with_verifier(&bytes, &limits, &DataChecksum(0)).unwrap_err();
+        assert_eq!(
+            rejected,
+            WasmError::Rejected("checksum of data is 83".to_owned())
+        );
+        assert_eq!(WasmError::name_of_code(rejected.code()), Some("Rejected"));
+    }
+
+    #[test]
+    fn run_command_with_configured_wasi() {
+        // (module
+        //   (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32)))
+        //   (import "wasi_snapshot_preview1" "clock_time_get" (func (param i32 i64 i32) (result i32)))
+        //   (import "wasi_snapshot_preview1" "args_sizes_get" (func (param i32 i32) (result i32)))
+        //   (import "wasi_snapshot_preview1" "args_get" (func (param i32 i32) (result i32)))
+        //   (memory 1)
+        //   (func (export "_start")
+        //     (drop (call 0 (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 16)))
+        //     (drop (call 1 (i32.const 0) (i64.const 0) (i32.const 24)))
+        //     (drop (call 2 (i32.const 32) (i32.const 36)))
+        //     (drop (call 3 (i32.const 40) (i32.const 48))))
+        //   (data (i32.const 0) "\08\00\00\00\03\00\00\00hi\n"))
+        let bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x19, 0x04, 0x60, 0x04, 0x7f, 0x7f, 0x7f, 0x7f, 0x01, 0x7f, 0x60, 0x03, 0x7f,
+            0x7e, 0x7f, 0x01, 0x7f, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, 0x60, 0x00,
+            0x00, // type
+            0x02, 0x95, 0x01, 0x04, 0x16, 0x77, 0x61, 0x73, 0x69, 0x5f, 0x73, 0x6e, 0x61, 0x70,
+            0x73, 0x68, 0x6f, 0x74, 0x5f, 0x70, 0x72, 0x65, 0x76, 0x69, 0x65, 0x77, 0x31, 0x08,
+            0x66, 0x64, 0x5f, 0x77, 0x72, 0x69, 0x74, 0x65, 0x00, 0x00, 0x16, 0x77, 0x61, 0x73,
+            0x69, 0x5f, 0x73, 0x6e, 0x61, 0x70, 0x73, 0x68, 0x6f, 0x74, 0x5f, 0x70, 0x72, 0x65,
+            0x76, 0x69, 0x65, 0x77, 0x31, 0x0e, 0x63, 0x6c, 0x6f, 0x63, 0x6b, 0x5f, 0x74, 0x69,
+            0x6d, 0x65, 0x5f, 0x67, 0x65, 0x74, 0x00, 0x01, 0x16, 0x77, 0x61, 0x73, 0x69, 0x5f,
+            0x73, 0x6e, 0x61, 0x70, 0x73, 0x68, 0x6f, 0x74, 0x5f, 0x70, 0x72, 0x65, 0x76, 0x69,
+            0x65, 0x77, 0x31, 0x0e, 0x61, 0x72, 0x67, 0x73, 0x5f, 0x73, 0x69, 0x7a, 0x65, 0x73,
+            0x5f, 0x67, 0x65, 0x74, 0x00, 0x02, 0x16, 0x77, 0x61, 0x73, 0x69, 0x5f, 0x73, 0x6e,
+            0x61, 0x70, 0x73, 0x68, 0x6f, 0x74, 0x5f, 0x70, 0x72, 0x65, 0x76, 0x69, 0x65, 0x77,
+            0x31, 0x08, 0x61, 0x72, 0x67, 0x73, 0x5f, 0x67, 0x65, 0x74, 0x00, 0x02, // import
+            0x03, 0x02, 0x01, 0x03, // function
+            0x05, 0x03, 0x01, 0x00, 0x01, // memory
+            0x07, 0x0a, 0x01, 0x06, 0x5f, 0x73, 0x74, 0x61, 0x72, 0x74, 0x00, 0x04, // export
+            0x0a, 0x26, 0x01, 0x24, 0x00, 0x41, 0x01, 0x41, 0x00, 0x41, 0x01, 0x41, 0x10, 0x10,
+            0x00, 0x1a, 0x41, 0x00, 0x42, 0x00, 0x41, 0x18, 0x10, 0x01, 0x1a, 0x41, 0x20, 0x41,
+            0x24, 0x10, 0x02, 0x1a, 0x41, 0x28, 0x41, 0x30, 0x10, 0x03, 0x1a, 0x0b, // code
+            0x0b, 0x11, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x0b, 0x08, 0x00, 0x00, 0x00, 0x03, 0x00,
+            0x00, 0x00, 0x68, 0x69, 0x0a, // data
+        ];
+        let wasi = Wasi::new(WasiConfig {
+            args: vec!["app".to_owned(), "-v".to_owned()],
+            clock: WasiClock::Fixed(0x0102_0304_0506_0708),
+            ..WasiConfig::default()
+        });
+        let mut external_modules = ExternalModules::default();
+        external_modules
+            .register_module(Some(WASI_MODULE_NAME.to_owned()), wasi.module())
+            .unwrap();
+        let mut vm =
+            instantiate_module(init_store(), decode_module(&bytes), external_modules, 65536)
+                .unwrap();
+        wasi.attach(&vm);
+        assert_eq!(vm.run("_start", vec![]), Ok(vec![]));
+        assert_eq!(wasi.stdout(), b"hi\n".to_vec());
+        assert_eq!(wasi.stderr(), vec![]);
+        // NOTE: Count of written bytes, time, sizes of args, pointers to args and args.
+        assert_eq!(vm.read_memory(16, 4), Ok(vec![3, 0, 0, 0]));
+        assert_eq!(
+            vm.read_memory(24, 8),
+            Ok(vec![0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01])
+        );
+        assert_eq!(vm.read_memory(32, 8), Ok(vec![2, 0, 0, 0, 7, 0, 0, 0]));
+        assert_eq!(vm.read_memory(40, 8), Ok(vec![48, 0, 0, 0, 52, 0, 0, 0]));
+        assert_eq!(vm.read_string(48, 7), Ok("app\0-v\0".to_owned()));
+    }
+
+    #[test]
+    fn exit_process_and_fill_random_bytes_in_bounds_by_wasi() {
+        // (module
+        //   (import "wasi_snapshot_preview1" "random_get" (func (param i32 i32) (result i32)))
+        //   (import "wasi_snapshot_preview1" "fd_fdstat_get" (func (param i32 i32) (result i32)))
+        //   (import "wasi_snapshot_preview1" "fd_close" (func (param i32) (result i32)))
+        //   (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+        //   (memory 1)
+        //   (func (export "_start")
+        //     (i32.store (i32.const 0) (call 0 (i32.const 65530) (i32.const 16)))
+        //     (i32.store (i32.const 4) (call 2 (i32.const 1)))
+        //     (drop (call 1 (i32.const 1) (i32.const 8)))
+        //     (drop (call 0 (i32.const 32) (i32.const 300)))
+        //     (call 3 (i32.const 3))))
+        let bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x13, 0x04, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, 0x60, 0x01, 0x7f, 0x01, 0x7f,
+            0x60, 0x01, 0x7f, 0x00, 0x60, 0x00, 0x00, // type
+            0x02, 0x91, 0x01, 0x04, 0x16, 0x77, 0x61, 0x73, 0x69, 0x5f, 0x73, 0x6e, 0x61, 0x70,
+            0x73, 0x68, 0x6f, 0x74, 0x5f, 0x70, 0x72, 0x65, 0x76, 0x69, 0x65, 0x77, 0x31, 0x0a,
+            0x72, 0x61, 0x6e, 0x64, 0x6f, 0x6d, 0x5f, 0x67, 0x65, 0x74, 0x00, 0x00, 0x16, 0x77,
+            0x61, 0x73, 0x69, 0x5f, 0x73, 0x6e, 0x61, 0x70, 0x73, 0x68, 0x6f, 0x74, 0x5f, 0x70,
+            0x72, 0x65, 0x76, 0x69, 0x65, 0x77, 0x31, 0x0d, 0x66, 0x64, 0x5f, 0x66, 0x64, 0x73,
+            0x74, 0x61, 0x74, 0x5f, 0x67, 0x65, 0x74, 0x00, 0x00, 0x16, 0x77, 0x61, 0x73, 0x69,
+            0x5f, 0x73, 0x6e, 0x61, 0x70, 0x73, 0x68, 0x6f, 0x74, 0x5f, 0x70, 0x72, 0x65, 0x76,
+            0x69, 0x65, 0x77, 0x31, 0x08, 0x66, 0x64, 0x5f, 0x63, 0x6c, 0x6f, 0x73, 0x65, 0x00,
+            0x01, 0x16, 0x77, 0x61, 0x73, 0x69, 0x5f, 0x73, 0x6e, 0x61, 0x70, 0x73, 0x68, 0x6f,
+            0x74, 0x5f, 0x70, 0x72, 0x65, 0x76, 0x69, 0x65, 0x77, 0x31, 0x09, 0x70, 0x72, 0x6f,
+            0x63, 0x5f, 0x65, 0x78, 0x69, 0x74, 0x00, 0x02, // import
+            0x03, 0x02, 0x01, 0x03, // function
+            0x05, 0x03, 0x01, 0x00, 0x01, // memory
+            0x07, 0x0a, 0x01, 0x06, 0x5f, 0x73, 0x74, 0x61, 0x72, 0x74, 0x00, 0x04, // export
+            0x0a, 0x2d, 0x01, 0x2b, 0x00, 0x41, 0x00, 0x41, 0xfa, 0xff, 0x03, 0x41, 0x10, 0x10,
+            0x00, 0x36, 0x02, 0x00, 0x41, 0x04, 0x41, 0x01, 0x10, 0x02, 0x36, 0x02, 0x00, 0x41,
+            0x01, 0x41, 0x08, 0x10, 0x01, 0x1a, 0x41, 0x20, 0x41, 0xac, 0x02, 0x10, 0x00, 0x1a,
+            0x41, 0x03, 0x10, 0x03, 0x0b, // code
+        ];
+        let wasi = Wasi::new(WasiConfig::default());
+        let mut external_modules = ExternalModules::default();
+        external_modules
+            .register_module(Some(WASI_MODULE_NAME.to_owned()), wasi.module())
+            .unwrap();
+        let mut vm =
+            instantiate_module(init_store(), decode_module(&bytes), external_modules, 65536)
+                .unwrap();
+        wasi.attach(&vm);
+        assert_eq!(
+            vm.run("_start", vec![]),
+            Err(WasmError::Trap(Trap::ProcessExit(3)))
+        );
+        // NOTE: EFAULT of random_get beyond memory, ENOSYS of fd_close and fdstat of stdout.
+        assert_eq!(vm.read_memory(0, 8), Ok(vec![21, 0, 0, 0, 52, 0, 0, 0]));
+        assert_eq!(vm.read_memory(65530, 6), Ok(vec![0; 6]));
+        assert_eq!(
+            vm.read_memory(8, 24),
+            Ok(vec![
+                2, 0, 0, 0, 0, 0, 0, 0, 0x42, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
+            ])
+        );
+        let random = vm.read_memory(32, 301).unwrap();
+        assert!(random[..300].iter().any(|byte| *byte != 0));
+        assert_eq!(random[300], 0);
+    }
+
+    #[test]
+    fn load_side_module_into_regions_of_main_module() {
+        // (module
+        //   (type (func (param i32) (result i32)))
+        //   (type (func (result i32)))
+        //   (table (export "table") 1 anyfunc)
+        //   (memory (export "memory") 1)
+        //   (func (export "load") (type 0) get_local 0 i32.load)
+        //   (func (export "call") (type 0) get_local 0 call_indirect (type 1)))
+        let main = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x0a, 0x02, 0x60, 0x01, 0x7f, 0x01, 0x7f, 0x60, 0x00, 0x01, 0x7f, // type
+            0x03, 0x03, 0x02, 0x00, 0x00, // function
+            0x04, 0x04, 0x01, 0x70, 0x00, 0x01, // table
+            0x05, 0x03, 0x01, 0x00, 0x01, // memory
+            0x07, 0x20, 0x04, 0x06, 0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, 0x02, 0x00, 0x05, 0x74,
+            0x61, 0x62, 0x6c, 0x65, 0x01, 0x00, 0x04, 0x6c, 0x6f, 0x61, 0x64, 0x00, 0x00, 0x04,
+            0x63, 0x61, 0x6c, 0x6c, 0x00, 0x01, // export
+            0x0a, 0x11, 0x02, 0x07, 0x00, 0x20, 0x00, 0x28, 0x02, 0x00, 0x0b, 0x07, 0x00, 0x20,
+            0x00, 0x11, 0x01, 0x00, 0x0b, // code
+        ];
+        // (module
+        //   (import "env" "memory" (memory 1))
+        //   (import "env" "table" (table 0 anyfunc))
+        //   (import "env" "__memory_base" (global i32))
+        //   (import "env" "__table_base" (global i32))
+        //   (func (result i32) i32.const 42)
+        //   (elem (get_global 1) 0)
+        //   (data (get_global 0) "\07"))
+        // with dylink section of 4 bytes aligned by 4 and 1 element of table.
+        let side = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x00, 0x0c, 0x06, 0x64, 0x79, 0x6c, 0x69, 0x6e, 0x6b, 0x04, 0x02, 0x01, 0x00,
+            0x00, // dylink
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+            0x02, 0x46, 0x04, 0x03, 0x65, 0x6e, 0x76, 0x06, 0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79,
+            0x02, 0x00, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x05, 0x74, 0x61, 0x62, 0x6c, 0x65, 0x01,
+            0x70, 0x00, 0x00, 0x03, 0x65, 0x6e, 0x76, 0x0d, 0x5f, 0x5f, 0x6d, 0x65, 0x6d, 0x6f,
+            0x72, 0x79, 0x5f, 0x62, 0x61, 0x73, 0x65, 0x03, 0x7f, 0x00, 0x03, 0x65, 0x6e, 0x76,
+            0x0c, 0x5f, 0x5f, 0x74, 0x61, 0x62, 0x6c, 0x65, 0x5f, 0x62, 0x61, 0x73, 0x65, 0x03,
+            0x7f, 0x00, // import
+            0x03, 0x02, 0x01, 0x00, // function
+            0x09, 0x07, 0x01, 0x00, 0x23, 0x01, 0x0b, 0x01, 0x00, // element
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x41, 0x2a, 0x0b, // code
+            0x0b, 0x07, 0x01, 0x00, 0x23, 0x00, 0x0b, 0x01, 0x07, // data
+        ];
+        let mut main = instantiate_module(
+            init_store(),
+            decode_module(&main),
+            Default::default(),
+            65536,
+        )
+        .unwrap();
+        let side = decode_module(&side).unwrap();
+        let dylink = side.dylink().unwrap();
+        assert_eq!(dylink.memory_size, 4);
+
+        let mut linking = DynamicLinking::new(main.export_module(), 1024);
+        let mut external_modules = ExternalModules::default();
+        external_modules
+            .register_module(
+                Some("env".to_owned()),
+                linking.side_module_env(&dylink).unwrap(),
+            )
+            .unwrap();
+        let _side = instantiate_module(init_store(), Ok(side), external_modules, 65536).unwrap();
+        assert_eq!(
+            main.run("load", vec![Values::I32(1024)]),
+            Ok(vec![Values::I32(7)])
+        );
+        assert_eq!(
+            main.run("call", vec![Values::I32(1)]),
+            Ok(vec![Values::I32(42)])
+        );
+        // NOTE: Regions are allocated after the side module.
+        assert_eq!(linking.allocate_memory(8, 3), Ok(1032));
+        assert_eq!(linking.allocate_table(2, 0), Ok(2));
+        assert_eq!(linking.allocate_memory(65536, 0), Ok(1040));
+        assert_eq!(
+            main.run("load", vec![Values::I32(66572)]),
+            Ok(vec![Values::I32(0)])
+        );
+    }
+
+    #[test]
+    fn capture_prints_of_spectest() {
+        // (module
+        //   (import "spectest" "print_i32" (func (param i32)))
+        //   (func (export "run") i32.const 42 call 0))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x08, 0x02, 0x60, 0x01, 0x7f, 0x00, 0x60, 0x00, 0x00, // type
+            0x02, 0x16, 0x01, // import
+            0x08, 0x73, 0x70, 0x65, 0x63, 0x74, 0x65, 0x73, 0x74, // "spectest"
+            0x09, 0x70, 0x72, 0x69, 0x6e, 0x74, 0x5f, 0x69, 0x33, 0x32, // "print_i32"
+            0x00, 0x00, // import descriptor
+            0x03, 0x02, 0x01, 0x01, // function
+            0x07, 0x07, 0x01, 0x03, 0x72, 0x75, 0x6e, 0x00, 0x01, // export
+            0x0a, 0x08, 0x01, 0x06, 0x00, 0x41, 0x2a, 0x10, 0x00, 0x0b, // code
+        ];
+        let lines = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+        let sink = lines.clone();
+        let mut external_modules = ExternalModules::default();
+        external_modules
+            .register_module(
+                Some("spectest".to_owned()),
+                create_spectest_with_printer(move |line| sink.borrow_mut().push(line.to_owned())),
+            )
+            .unwrap();
+        let mut vm =
+            instantiate_module(init_store(), decode_module(&bytes), external_modules, 65536)
+                .unwrap();
+        assert_eq!(vm.run("run", vec![]).unwrap(), vec![]);
+        assert_eq!(*lines.borrow(), vec!["42 : i32".to_owned()]);
+    }
+
+    #[test]
+    fn write_and_read_memory() {
+        // (module (memory 1) (func (export "load") (param i32) (result i32) get_local 0 i32.load))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x05, 0x03, 0x01, 0x00, 0x01, // memory
+            0x07, 0x08, 0x01, 0x04, 0x6c, 0x6f, 0x61, 0x64, 0x00, 0x00, // export
+            0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x28, 0x02, 0x00, 0x0b, // code
+        ];
+        let store = init_store();
+        let section = decode_module(&bytes);
+        let mut vm = instantiate_module(store, section, Default::default(), 65536).unwrap();
+        vm.write_memory(16, &[0x78, 0x56, 0x34, 0x12]).unwrap();
+        assert_eq!(
+            vm.run("load", vec![Values::I32(16)]).unwrap(),
+            vec![Values::I32(0x1234_5678)]
+        );
+        assert_eq!(vm.read_memory(14, 4).unwrap(), vec![0x00, 0x00, 0x78, 0x56]);
+        assert_eq!(
+            vm.write_memory(65535, &[0x00, 0x00]),
+            Err(WasmError::Trap(Trap::MemoryAccessOutOfBounds))
+        );
+        assert_eq!(
+            vm.read_memory(65536, 1),
+            Err(WasmError::Trap(Trap::MemoryAccessOutOfBounds))
+        );
+    }
+
+    #[test]
+    fn collect_metrics() {
+        // (module (memory 1) (func (export "load") (param i32) (result i32) get_local 0 i32.load))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x05, 0x03, 0x01, 0x00, 0x01, // memory
+            0x07, 0x08, 0x01, 0x04, 0x6c, 0x6f, 0x61, 0x64, 0x00, 0x00, // export
+            0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x28, 0x02, 0x00, 0x0b, // code
+        ];
+        let store = init_store();
+        let section = decode_module(&bytes);
+        let mut vm = instantiate_module(store, section, Default::default(), 65536).unwrap();
+        vm.run("load", vec![Values::I32(0)]).unwrap();
+        assert_eq!(vm.metrics(), Metrics::default());
+
+        vm.enable_metrics(true);
+        vm.run("load", vec![Values::I32(0)]).unwrap();
+        vm.run("load", vec![Values::I32(4)]).unwrap();
+        assert_eq!(
+            vm.run("load", vec![Values::I32(65535)]),
+            Err(WasmError::Trap(Trap::MemoryAccessOutOfBounds))
+        );
+        let metrics = vm.metrics();
+        // NOTE: get_local and i32.load are fused into a single instruction.
+        assert_eq!(metrics.instructions_executed, 5);
+        assert_eq!(metrics.executed_of(0x0b), 2);
+        assert_eq!(metrics.memory_bytes_read, 8);
+        assert_eq!(metrics.memory_bytes_written, 0);
+        assert_eq!(metrics.calls, 0);
+        assert_eq!(metrics.traps, 1);
+
+        vm.reset_metrics();
+        vm.enable_metrics(false);
+        vm.run("load", vec![Values::I32(0)]).unwrap();
+        assert_eq!(vm.metrics(), Metrics::default());
+    }
+
+    #[test]
+    fn evaluate_memory_grow() {
+        // (module (memory 1 3) (func (export "grow") (param i32) (result i32) get_local 0 memory.grow))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x05, 0x04, 0x01, 0x01, 0x01, 0x03, // memory
+            0x07, 0x08, 0x01, 0x04, 0x67, 0x72, 0x6f, 0x77, 0x00, 0x00, // export
+            0x0a, 0x08, 0x01, 0x06, 0x00, 0x20, 0x00, 0x40, 0x00, 0x0b, // code
+        ];
+        let store = init_store();
+        let section = decode_module(&bytes);
+        let mut vm = instantiate_module(store, section, Default::default(), 65536).unwrap();
+        vm.write_memory(0, &[1, 2]).unwrap();
+        assert_eq!(
+            vm.run("grow", vec![Values::I32(1)]).unwrap(),
+            vec![Values::I32(1)]
+        );
+        assert_eq!(
+            vm.run("grow", vec![Values::I32(2)]).unwrap(),
+            vec![Values::I32(-1)]
+        );
+        assert_eq!(
+            vm.run("grow", vec![Values::I32(-1)]).unwrap(),
+            vec![Values::I32(-1)]
+        );
+        assert_eq!(
+            vm.run("grow", vec![Values::I32(0)]).unwrap(),
+            vec![Values::I32(2)]
+        );
+        assert_eq!(vm.read_memory(0, 2).unwrap(), vec![1, 2]);
+        assert_eq!(vm.read_memory(65536, 4).unwrap(), vec![0, 0, 0, 0]);
+
+        vm.limit_memory_pages(2).unwrap();
+        assert_eq!(
+            vm.run("grow", vec![Values::I32(1)]).unwrap(),
+            vec![Values::I32(-1)]
+        );
+    }
+
+    #[test]
+    fn marshal_string_between_host_and_guest() {
+        // (module (memory 1) (global (mut i32) (i32.const 1024))
+        //   (func (export "__wbindgen_malloc") (param i32) (result i32)
+        //     get_global 0 get_global 0 get_local 0 i32.add set_global 0))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x05, 0x03, 0x01, 0x00, 0x01, // memory
+            0x06, 0x07, 0x01, 0x7f, 0x01, 0x41, 0x80, 0x08, 0x0b, // global
+            0x07, 0x15, 0x01, 0x11, 0x5f, 0x5f, 0x77, 0x62, 0x69, 0x6e, 0x64, 0x67, 0x65, 0x6e,
+            0x5f, 0x6d, 0x61, 0x6c, 0x6c, 0x6f, 0x63, 0x00, 0x00, // export
+            0x0a, 0x0d, 0x01, 0x0b, 0x00, 0x23, 0x00, 0x23, 0x00, 0x20, 0x00, 0x6a, 0x24, 0x00,
+            0x0b, // code
+        ];
+        let store = init_store();
+        let section = decode_module(&bytes);
+        let mut vm = instantiate_module(store, section, Default::default(), 65536).unwrap();
+        let (offset, length) = vm.pass_string("__wbindgen_malloc", "héllo").unwrap();
+        assert_eq!((offset, length), (1024, 6));
+        assert_eq!(vm.read_string(offset, length).unwrap(), "héllo".to_owned());
+
+        let (offset, length) = vm.pass_bytes("__wbindgen_malloc", &[0xff, 0xfe]).unwrap();
+        assert_eq!((offset, length), (1030, 2));
+        assert_eq!(vm.read_bytes(offset, length).unwrap(), vec![0xff, 0xfe]);
+        assert_eq!(
+            vm.read_string(offset, length),
+            Err(WasmError::Trap(Trap::InvalidUTF8Encoding))
+        );
+        assert_eq!(
+            vm.read_bytes(65535, 2),
+            Err(WasmError::Trap(Trap::MemoryAccessOutOfBounds))
+        );
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn flush_subnormal_results_of_float_arithmetic() {
+        // (module (func (export "mul") (param f32 f32) (result f32) get_local 0 get_local 1 f32.mul))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x07, 0x01, 0x60, 0x02, 0x7d, 0x7d, 0x01, 0x7d, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x07, 0x01, 0x03, 0x6d, 0x75, 0x6c, 0x00, 0x00, // export
+            0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x94, 0x0b, // code
+        ];
+        let arguments = vec![Values::F32(-core::f32::MIN_POSITIVE), Values::F32(0.5)];
+
+        let mut vm = instantiate_module(
+            init_store(),
+            decode_module(&bytes),
+            Default::default(),
+            65536,
+        )
+        .unwrap();
+        match vm.run("mul", arguments.clone()).unwrap().as_slice() {
+            [Values::F32(n)] => assert_eq!(n.to_bits(), 0x8040_0000),
+            x => unreachable!("{:?}", x),
+        };
+
+        let mut store = init_store();
+        store.subnormal_mode = SubnormalMode::FlushToZero;
+        let mut vm =
+            instantiate_module(store, decode_module(&bytes), Default::default(), 65536).unwrap();
+        match vm.run("mul", arguments).unwrap().as_slice() {
+            [Values::F32(n)] => assert_eq!(n.to_bits(), 0x8000_0000),
+            x => unreachable!("{:?}", x),
+        };
+    }
+
+    #[test]
+    fn coerce_conditions_which_are_not_i32() {
+        // (module
+        //   (func (export "i64") (result i32) i32.const 1 i32.const 2 i64.const 0x1_0000_0000 select)
+        //   (func (export "negative") (result i32) i32.const 1 i32.const 2 i32.const -1 select))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+            0x03, 0x03, 0x02, 0x00, 0x00, // function
+            0x07, 0x12, 0x02, // export
+            0x03, 0x69, 0x36, 0x34, 0x00, 0x00, // "i64"
+            0x08, 0x6e, 0x65, 0x67, 0x61, 0x74, 0x69, 0x76, 0x65, 0x00, 0x01, // "negative"
+            0x0a, 0x19, 0x02, // code
+            0x0d, 0x00, 0x41, 0x01, 0x41, 0x02, 0x42, 0x80, 0x80, 0x80, 0x80, 0x10, 0x1b, 0x0b,
+            0x09, 0x00, 0x41, 0x01, 0x41, 0x02, 0x41, 0x7f, 0x1b, 0x0b,
+        ];
+        // NOTE: Instantiate without validation, since the first function is invalid.
+        let mut vm = instantiate_module(
+            init_store(),
+            decode_module(&bytes),
+            Default::default(),
+            65536,
+        )
+        .unwrap();
+        assert_eq!(vm.run("negative", vec![]).unwrap(), vec![Values::I32(1)]);
+        assert_eq!(
+            vm.run("i64", vec![]),
+            Err(WasmError::Trap(Trap::TypeMismatch))
+        );
+
+        let mut store = init_store();
+        store.condition_mode = ConditionMode::Coerce;
+        let mut vm =
+            instantiate_module(store, decode_module(&bytes), Default::default(), 65536).unwrap();
+        assert_eq!(vm.run("i64", vec![]).unwrap(), vec![Values::I32(1)]);
+    }
+
+    fn instantiate_with_element_segment_out_of_table(
+        segment_initialization: SegmentInitialization,
+    ) -> ModuleInstance {
+        // (module
+        //   (type (func (result i32)))
+        //   (table (export "tab") 10 anyfunc)
+        //   (func (export "call") (param i32) (result i32) get_local 0 call_indirect 0))
+        let bytes_of_exporter = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x0a, 0x02, 0x60, 0x00, 0x01, 0x7f, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x01, // function
+            0x04, 0x04, 0x01, 0x70, 0x00, 0x0a, // table
+            0x07, 0x0e, 0x02, 0x03, 0x74, 0x61, 0x62, 0x01, 0x00, // export "tab"
+            0x04, 0x63, 0x61, 0x6c, 0x6c, 0x00, 0x00, // export "call"
+            0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x11, 0x00, 0x00, 0x0b, // code
+        ];
+        // (module
+        //   (import "Mt" "tab" (table 10 anyfunc))
+        //   (func (result i32) i32.const 5)
+        //   (elem (i32.const 7) 0)
+        //   (elem (i32.const 12) 0))
+        let bytes_of_importer = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+            0x02, 0x0c, 0x01, 0x02, 0x4d, 0x74, 0x03, 0x74, 0x61, 0x62, 0x01, 0x70, 0x00,
+            0x0a, // import
+            0x03, 0x02, 0x01, 0x00, // function
+            0x09, 0x0d, 0x02, // element
+            0x00, 0x41, 0x07, 0x0b, 0x01, 0x00, // (elem (i32.const 7) 0)
+            0x00, 0x41, 0x0c, 0x0b, 0x01, 0x00, // (elem (i32.const 12) 0)
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x41, 0x05, 0x0b, // code
+        ];
+        let exporter = instantiate_module(
+            init_store(),
+            decode_module(&bytes_of_exporter),
+            Default::default(),
+            65536,
+        )
+        .unwrap();
+        let mut external_modules = ExternalModules::default();
+        external_modules
+            .register_module(Some("Mt".to_owned()), exporter.export_module())
+            .unwrap();
+        let mut store = init_store();
+        store.segment_initialization = segment_initialization;
+        let importer = instantiate_module(
+            store,
+            decode_module(&bytes_of_importer),
+            external_modules,
+            65536,
+        );
+        assert_eq!(
+            importer.unwrap_err(),
+            WasmError::Trap(Trap::ElementSegmentDoesNotFit)
+        );
+        exporter
+    }
+
+    #[test]
+    fn element_segments_are_not_applied_when_one_is_out_of_table() {
+        let mut exporter =
+            instantiate_with_element_segment_out_of_table(SegmentInitialization::Atomic);
+        assert_eq!(
+            exporter.run("call", vec![Values::I32(7)]),
+            Err(WasmError::Trap(Trap::UninitializedElement))
+        );
+    }
+
+    #[test]
+    fn earlier_element_segments_remain_applied_when_one_is_out_of_table() {
+        let mut exporter =
+            instantiate_with_element_segment_out_of_table(SegmentInitialization::Partial);
+        assert_eq!(
+            exporter.run("call", vec![Values::I32(7)]).unwrap(),
+            vec![Values::I32(5)]
+        );
+        assert_eq!(
+            exporter.run("call", vec![Values::I32(8)]),
+            Err(WasmError::Trap(Trap::UninitializedElement))
+        );
+    }
+
+    #[test]
+    fn instantiate_component_with_core_instances() {
+        // (component
+        //   (core module $a (func (export "answer") (result i32) i32.const 42))
+        //   (core module $b
+        //     (import "env" "answer" (func (result i32)))
+        //     (func (export "double") (result i32) call 0 i32.const 2 i32.mul))
+        //   (core instance $a (instantiate $a))
+        //   (core instance (instantiate $b (with "env" (instance $a)))))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x0d, 0x00, 0x01, 0x00, // magic, version and layer
+            0x01, 0x27, // core module $a
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x0a, 0x01, 0x06, 0x61, 0x6e, 0x73, 0x77, 0x65, 0x72, 0x00, 0x00, // export
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x41, 0x2a, 0x0b, // code
+            0x01, 0x3a, // core module $b
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type
+            0x02, 0x0e, 0x01, 0x03, 0x65, 0x6e, 0x76, // import
+            0x06, 0x61, 0x6e, 0x73, 0x77, 0x65, 0x72, 0x00, 0x00, // import
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x0a, 0x01, 0x06, 0x64, 0x6f, 0x75, 0x62, 0x6c, 0x65, 0x00, 0x01, // export
+            0x0a, 0x09, 0x01, 0x07, 0x00, 0x10, 0x00, 0x41, 0x02, 0x6c, 0x0b, // code
+            0x02, 0x0d, 0x02, // core instances
+            0x00, 0x00, 0x00, // instantiate $a
+            0x00, 0x01, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x12, 0x00, // instantiate $b
+        ];
+        let component = decode_component(&bytes);
+        let mut instance = instantiate_component(component, 65536).unwrap();
+        assert_eq!(
+            instance.run("double", vec![]).unwrap(),
+            vec![Values::I32(84)]
+        );
+        assert_eq!(
+            instance.run("answer", vec![]).unwrap(),
+            vec![Values::I32(42)]
+        );
+        assert_eq!(
+            instance.run("answr", vec![]),
+            Err(WasmError::UnknownExport {
+                name: "answr".to_owned(),
+                similar: vec!["answer".to_owned()],
+            })
+        );
+
+        let module_bytes = &bytes[10..49];
+        assert_eq!(
+            decode_component(module_bytes).unwrap_err(),
+            WasmError::Trap(Trap::UnknownComponentVersion)
+        );
+    }
+
+    fn panicking_host_function(_arguments: &[Values]) -> alloc::vec::Vec<Values> {
+        panic!("host function failed")
+    }
+
+    #[test]
+    fn evaluate_panicking_host_function() {
+        // (module (import "env" "f" (func)) (func (export "run") call 0))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type
+            0x02, 0x09, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x01, 0x66, 0x00, 0x00, // import
+            0x03, 0x02, 0x01, 0x00, // function
+            0x07, 0x07, 0x01, 0x03, 0x72, 0x75, 0x6e, 0x00, 0x01, // export
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x10, 0x00, 0x0b, // code
+        ];
+        let mut external_modules = ExternalModules::default();
+        let external_module = ExternalModule::new(
+            [FunctionInstance::new_host_fn(
+                Some("f".to_owned()),
+                FunctionType::new([].to_vec(), [].to_vec()),
+                &panicking_host_function,
+            )]
+            .to_vec(),
+            [].to_vec(),
+            [].to_vec(),
+            [].to_vec(),
+            [].to_vec(),
+        );
+        external_modules
+            .register_module(Some("env".to_owned()), external_module)
+            .unwrap();
+        let store = init_store();
+        let section = decode_module(&bytes);
+        let mut vm = instantiate_module(store, section, external_modules, 65536).unwrap();
+        assert_eq!(
+            vm.run("run", vec![]).unwrap_err(),
+            WasmError::Trap(Trap::HostError("host function failed".to_owned()))
+        );
+    }
 }
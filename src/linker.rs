@@ -0,0 +1,192 @@
+#[cfg(not(any(test, feature = "std")))]
+use alloc::prelude::*;
+use alloc::string::String;
+use alloc::vec::Vec;
+use decode::Module;
+use embedder::{decode_module, init_store, instantiate_module, validate_module};
+use error::{Result, Trap, WasmError};
+use module::{ExternalModule, ExternalModules};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use vm::ModuleInstance;
+
+/// Set of modules which import each other by their registered names.
+#[derive(Debug, Default)]
+pub struct Linker {
+  modules: Vec<(String, Vec<u8>)>,
+  overrides: Vec<(String, ExternalModule)>,
+}
+
+impl Linker {
+  pub fn new() -> Self {
+    Linker::default()
+  }
+
+  /// Register bytes of a module, whose exports are imported by `name` from other modules.
+  /// A module registered later by the same name replaces former one.
+  pub fn register(&mut self, name: &str, bytes: Vec<u8>) {
+    self.modules.retain(|(registered, _)| registered != name);
+    self.modules.push((name.to_owned(), bytes));
+  }
+
+  /// Forget bytes of a module registered by `name`, and returns whether it was registered.
+  /// Its instance is still registered in `ExternalModules` until `ExternalModules::unregister_module`.
+  pub fn unregister(&mut self, name: &str) -> bool {
+    let count_of_modules = self.modules.len();
+    self.modules.retain(|(registered, _)| registered != name);
+    self.modules.len() < count_of_modules
+  }
+
+  /// Shadow functions which modules import from `name` by ones of `overrides` with the same names,
+  /// e.g. to mock a host function in tests while keeping the rest of the module registered by `name`.
+  /// Overrides are applied to both of registered modules and `ExternalModules` passed to `instantiate`,
+  /// and ones registered later by the same name are merged into former ones.
+  pub fn override_imports(&mut self, name: &str, overrides: ExternalModule) {
+    if let Some((_, registered)) = self
+      .overrides
+      .iter_mut()
+      .find(|(registered, _)| registered == name)
+    {
+      *registered = registered.shadowed_by(&overrides);
+      return;
+    }
+    self.overrides.push((name.to_owned(), overrides));
+  }
+
+  fn overrides_of(&self, name: &str) -> Option<&ExternalModule> {
+    self
+      .overrides
+      .iter()
+      .find(|(registered, _)| registered == name)
+      .map(|(_, overrides)| overrides)
+  }
+
+  // Registry which modules import from, whose registrations are shadowed by overrides.
+  fn shadow_imports(&self, external_modules: &ExternalModules) -> Result<ExternalModules> {
+    if self.overrides.is_empty() {
+      return Ok(external_modules.clone());
+    }
+    let mut imports = ExternalModules::with_symbols(external_modules.symbols().clone());
+    for name in external_modules.module_names().into_iter() {
+      let module = external_modules.get(&name)?;
+      let module = match name.as_ref().and_then(|name| self.overrides_of(name)) {
+        Some(overrides) => module.shadowed_by(overrides),
+        None => module,
+      };
+      imports.register_module(name, module)?;
+    }
+    for (name, overrides) in self.overrides.iter() {
+      if imports.get(&Some(name.to_owned())).is_none() {
+        imports.register_module(Some(name.to_owned()), overrides.clone())?;
+      }
+    }
+    Ok(imports)
+  }
+
+  fn decode_and_validate(bytes: &[u8]) -> Result<Module> {
+    let module = decode_module(bytes);
+    validate_module(&module)?;
+    module
+  }
+
+  #[cfg(not(feature = "parallel"))]
+  fn decode_modules(&self) -> Result<Vec<Module>> {
+    self
+      .modules
+      .iter()
+      .map(|(_, bytes)| Linker::decode_and_validate(bytes))
+      .collect()
+  }
+
+  // NOTE: Instances share stores through Rc, so only decoding and validation run across threads.
+  #[cfg(feature = "parallel")]
+  fn decode_modules(&self) -> Result<Vec<Module>> {
+    self
+      .modules
+      .par_iter()
+      .map(|(_, bytes)| Linker::decode_and_validate(bytes))
+      .collect()
+  }
+
+  // Indices of registered modules which a module imports from.
+  fn dependencies_of(&self, module: &Module) -> Vec<usize> {
+    let mut dependencies = vec![];
+    for import in module.imports.iter() {
+      let position = self
+        .modules
+        .iter()
+        .position(|(name, _)| Some(name) == import.module_name.as_ref());
+      match position {
+        Some(idx) if !dependencies.contains(&idx) => dependencies.push(idx),
+        _ => {}
+      }
+    }
+    dependencies
+  }
+
+  // Indices of modules sorted topologically, the order of registration is kept among independent modules.
+  fn instantiation_order(&self, modules: &[Module]) -> Result<Vec<usize>> {
+    let dependencies = modules
+      .iter()
+      .map(|module| self.dependencies_of(module))
+      .collect::<Vec<_>>();
+    let mut order: Vec<usize> = vec![];
+    while order.len() < modules.len() {
+      let next = (0..modules.len()).find(|idx| {
+        !order.contains(idx) && dependencies[*idx].iter().all(|dep| order.contains(dep))
+      });
+      match next {
+        Some(idx) => order.push(idx),
+        None => {
+          // NOTE: Every remaining module waits for another one, so following them reaches a cycle.
+          let mut idx = (0..modules.len()).find(|idx| !order.contains(idx))?;
+          let mut visited = vec![];
+          while !visited.contains(&idx) {
+            visited.push(idx);
+            idx = *dependencies[idx].iter().find(|dep| !order.contains(dep))?;
+          }
+          return Err(WasmError::Trap(Trap::CircularImport(
+            self.modules[idx].0.to_owned(),
+          )));
+        }
+      }
+    }
+    Ok(order)
+  }
+
+  /// Instantiate registered modules after modules which they import from.
+  /// Imports from other names are resolved by `external_modules`, where instantiated modules are also registered.
+  /// Every module is validated before any of them is instantiated, so that an invalid module links none of them.
+  /// Modules are decoded and validated in parallel with "parallel" feature,
+  /// but instantiated one by one, since instances share their stores through `Rc`.
+  /// Instances are registered to `external_modules` without overrides of `override_imports`.
+  ///
+  /// To tear down a module, drop its instance and unregister it from both of `Linker` and `external_modules`.
+  /// Its memories, tables and globals are released as soon as no other instances import them.
+  pub fn instantiate(
+    &self,
+    mut external_modules: ExternalModules,
+    max_stack_height: usize,
+  ) -> Result<Vec<(String, ModuleInstance)>> {
+    let modules = self.decode_modules()?;
+    let order = self.instantiation_order(&modules)?;
+    let mut imports = self.shadow_imports(&external_modules)?;
+    let mut modules = modules.into_iter().map(Some).collect::<Vec<_>>();
+    let mut instances = vec![];
+    for idx in order.into_iter() {
+      let name = &self.modules[idx].0;
+      let module = modules[idx].take()?;
+      let instance =
+        instantiate_module(init_store(), Ok(module), imports.clone(), max_stack_height)?;
+      let exports = instance.export_module();
+      let shadowed = match self.overrides_of(name) {
+        Some(overrides) => exports.shadowed_by(overrides),
+        None => exports.clone(),
+      };
+      imports.register_module(Some(name.to_owned()), shadowed)?;
+      external_modules.register_module(Some(name.to_owned()), exports)?;
+      instances.push((name.to_owned(), instance));
+    }
+    Ok(instances)
+  }
+}
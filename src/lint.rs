@@ -0,0 +1,132 @@
+#[cfg(not(any(test, feature = "std")))]
+use alloc::prelude::*;
+use alloc::string::String;
+use alloc::vec::Vec;
+use decode::Module;
+use diagnostics::{diagnose_body, BodyUses, CallGraph};
+use global::GlobalType;
+use module::{ExportDescriptor, ImportDescriptor, ModuleDescriptor};
+
+/// Finding of static checks by `lint_module`, e.g. for authors of modules to find dead code in CI.
+/// Functions and globals are referred by indices in their index spaces, which imported ones occupy first.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LintDiagnostic {
+  /// Module has neither a start function nor exported functions, so that nothing of it runs.
+  MissingEntryPoint,
+  /// Exports refer to the same function by different names, which are in order of declaration.
+  IdenticalExports { function: u32, names: Vec<String> },
+  /// A defined function is reachable from neither exports, the start function nor element segments,
+  /// even through calls of other functions.
+  UnreachableFunction(u32),
+  /// Memory is neither exported nor accessed by any instruction, regardless of its data segments.
+  UnusedMemory,
+  /// A defined mutable global is neither exported nor written by any set_global.
+  UnwrittenMutableGlobal(u32),
+}
+
+/// Findings of static checks in order of their kinds, without validation of the module.
+/// Functions whose bodies fail to decode are regarded as using nothing, so `lint_module` should be used
+/// for modules which may be invalid.
+pub fn check(module: &Module) -> Vec<LintDiagnostic> {
+  let bodies = module
+    .codes
+    .iter()
+    .map(|code| match code {
+      Ok((raw_body, _)) => raw_body.flatten().map(|(body, _)| body).unwrap_or_default(),
+      Err(_) => vec![],
+    })
+    .collect::<Vec<_>>();
+  check_bodies(
+    module,
+    &bodies
+      .iter()
+      .map(|body| body.as_slice())
+      .collect::<Vec<_>>(),
+  )
+}
+
+/// Same as `check`, but with flattened bodies of defined functions, e.g. ones flattened by validation.
+pub(crate) fn check_bodies(module: &Module, bodies: &[&[u8]]) -> Vec<LintDiagnostic> {
+  let count_of_functions =
+    Module::count_of_imports(&module.imports, |descriptor| match descriptor {
+      ImportDescriptor::Function(_) => true,
+      _ => false,
+    });
+  let count_of_globals = Module::count_of_imports(&module.imports, |descriptor| match descriptor {
+    ImportDescriptor::Global(_) => true,
+    _ => false,
+  });
+  let has_memory = !module.limits.is_empty()
+    || Module::count_of_imports(&module.imports, |descriptor| match descriptor {
+      ImportDescriptor::Memory(_) => true,
+      _ => false,
+    }) > 0;
+
+  let mut exported_functions: Vec<(u32, Vec<String>)> = vec![];
+  let mut exported_globals = vec![];
+  let mut exports_memory = false;
+  for export in module.exports.iter() {
+    match &export.descriptor {
+      ModuleDescriptor::ExportDescriptor(ExportDescriptor::Function(idx)) => {
+        let function = idx.to_u32();
+        match exported_functions.iter().position(|(f, _)| *f == function) {
+          Some(position) => exported_functions[position].1.push(export.name.to_owned()),
+          None => exported_functions.push((function, vec![export.name.to_owned()])),
+        }
+      }
+      ModuleDescriptor::ExportDescriptor(ExportDescriptor::Global(idx)) => {
+        exported_globals.push(idx.to_u32())
+      }
+      ModuleDescriptor::ExportDescriptor(ExportDescriptor::Memory(_)) => exports_memory = true,
+      _ => {}
+    }
+  }
+
+  let mut diagnostics = vec![];
+  if module.start.is_none() && exported_functions.is_empty() {
+    diagnostics.push(LintDiagnostic::MissingEntryPoint);
+  }
+  for (function, names) in exported_functions.iter() {
+    if names.len() > 1 {
+      diagnostics.push(LintDiagnostic::IdenticalExports {
+        function: *function,
+        names: names.to_owned(),
+      });
+    }
+  }
+
+  // NOTE: Warnings are reported by `validate_module_with_diagnostics` instead.
+  let mut warnings = vec![];
+  let uses = bodies
+    .iter()
+    .enumerate()
+    .map(|(idx, body)| diagnose_body(count_of_functions + idx as u32, body, &mut warnings))
+    .collect::<Vec<BodyUses>>();
+  let call_graph = CallGraph::new(
+    count_of_functions,
+    &uses,
+    &module.exports,
+    &module.start,
+    &module.elements,
+  );
+  for function in call_graph.unreachable() {
+    diagnostics.push(LintDiagnostic::UnreachableFunction(function));
+  }
+
+  if has_memory && !exports_memory && !uses.iter().any(|uses| uses.accesses_memory) {
+    diagnostics.push(LintDiagnostic::UnusedMemory);
+  }
+  for (idx, (global_type, _)) in module.globals.iter().enumerate() {
+    let global = count_of_globals + idx as u32;
+    let written = exported_globals.contains(&global)
+      || uses
+        .iter()
+        .any(|uses| uses.written_globals.contains(&global));
+    if let GlobalType::Var(_) = global_type {
+      if !written {
+        diagnostics.push(LintDiagnostic::UnwrittenMutableGlobal(global));
+      }
+    }
+  }
+  diagnostics
+}
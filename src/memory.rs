@@ -1,10 +1,10 @@
-#[cfg(not(test))]
+#[cfg(not(any(test, feature = "std")))]
 use alloc::prelude::*;
-use alloc::rc::Rc;
+use alloc::rc::{Rc, Weak};
 use alloc::string::String;
 use alloc::vec::Vec;
-use core::cell::RefCell;
-use core::cmp::{Ordering, PartialOrd};
+use core::cell::{RefCell, RefMut};
+use core::convert::TryFrom;
 use core::fmt;
 use core::mem::transmute;
 use core::u32;
@@ -12,11 +12,25 @@ use decode::Data;
 use error::{Result, Trap, WasmError};
 use global::GlobalInstances;
 use isa::Isa;
+use memory_backend::{MemoryBackend, MemoryStrategy};
 use module::{ExternalInterface, ImportDescriptor, ModuleDescriptor};
+#[cfg(any(test, feature = "std"))]
+use std::io;
 use value::Values;
 
 // NOTE: 65536(64KiB) is constant data size per page.
-const PAGE_SIZE: u32 = 65536;
+pub(crate) const PAGE_SIZE: u32 = 65536;
+// NOTE: Size of linear memory is limited to 4GiB, so its size in bytes is represented by u64.
+pub(crate) const MAX_PAGES: u32 = 65536;
+
+// NOTE: Memory image consists of magic, count of pages in little endian u32,
+// then each page as a tag of ZERO_PAGE, or a tag of DATA_PAGE followed by bytes of the page.
+#[cfg(any(test, feature = "std"))]
+const IMAGE_MAGIC: [u8; 4] = *b"\0wmi";
+#[cfg(any(test, feature = "std"))]
+const ZERO_PAGE: u8 = 0;
+#[cfg(any(test, feature = "std"))]
+const DATA_PAGE: u8 = 1;
 
 // Prefer to rename MemoryType
 #[derive(Clone, PartialEq)]
@@ -28,62 +42,40 @@ pub enum Limit {
 }
 
 impl Limit {
-  fn initial_min_size(&self) -> usize {
-    let min_size = match self {
-      Limit::NoUpperLimit(min) => min,
-      Limit::HasUpperLimit(min, _) => min,
-    };
-    (PAGE_SIZE * min_size) as usize
+  // NOTE: 65536 pages are 4GiB, which overflows u32 and may not fit in usize of 32-bit targets.
+  fn initial_min_size(&self) -> Result<usize> {
+    usize::try_from(u64::from(PAGE_SIZE) * u64::from(self.min_pages()))
+      .map_err(|_| WasmError::Trap(Trap::ResourceLimitExceeded))
   }
-}
-impl PartialOrd for Limit {
-  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+
+  pub(crate) fn min_pages(&self) -> u32 {
+    match self {
+      Limit::NoUpperLimit(min) => *min,
+      Limit::HasUpperLimit(min, _) => *min,
+    }
+  }
+
+  /// Whether an instance limited by `self` can be imported as `expected`.
+  /// It has to have at least expected minimum, and at most expected maximum when it's declared.
+  pub fn matches(&self, expected: &Limit) -> bool {
     use self::Limit::*;
-    match (self, other) {
-      // NOTE: In the mean of Limit, to compare min bound `A.min < B.min` represents self > other.
-      // However, `A.max < B.max` self < other.
-      // It seems the specification assumes to compare range of limitations.
-      //
-      // Example: in case of `A < B`.
-      // +---+---+---+---+---+---+---+---+---+---+
-      // | 0 | 1 | 2 | 3 | 4 | 5 | 6 | 7 | 8 | 9 |
-      // +---+---+---+---+---+---+---+---+---+---+
-      //       ^       ^           ^       ^
-      //     B.min    A.min ----- A.max   B.max
-      (NoUpperLimit(min), NoUpperLimit(min_other)) => {
-        if min > min_other {
-          Some(Ordering::Less)
-        } else if min == min_other {
-          Some(Ordering::Equal)
-        } else {
-          Some(Ordering::Greater)
-        }
-      }
-      (NoUpperLimit(min), HasUpperLimit(min_other, _)) => {
-        if min > min_other {
-          Some(Ordering::Less)
-        } else {
-          Some(Ordering::Greater)
-        }
-      }
-      (HasUpperLimit(min, _), NoUpperLimit(min_other)) => {
-        if min < min_other {
-          Some(Ordering::Greater)
-        } else {
-          Some(Ordering::Less)
-        }
-      }
-      (HasUpperLimit(min, max), HasUpperLimit(min_other, max_other)) => {
-        if min > min_other || max < max_other {
-          return Some(Ordering::Less);
-        }
-        if min < min_other || max > max_other {
-          return Some(Ordering::Greater);
-        }
-        Some(Ordering::Equal)
+    match (self, expected) {
+      (NoUpperLimit(min), NoUpperLimit(expected_min))
+      | (HasUpperLimit(min, _), NoUpperLimit(expected_min)) => min >= expected_min,
+      (NoUpperLimit(_), HasUpperLimit(_, _)) => false,
+      (HasUpperLimit(min, max), HasUpperLimit(expected_min, expected_max)) => {
+        min >= expected_min && max <= expected_max
       }
     }
   }
+
+  // NOTE: Instances may have grown since instantiation, so its current size is regarded as minimum.
+  pub(crate) fn with_min(&self, min: u32) -> Limit {
+    match self {
+      Limit::NoUpperLimit(_) => Limit::NoUpperLimit(min),
+      Limit::HasUpperLimit(_, max) => Limit::HasUpperLimit(min, *max),
+    }
+  }
 }
 
 impl fmt::Debug for Limit {
@@ -102,21 +94,32 @@ impl fmt::Debug for Limit {
   }
 }
 
-#[derive(Clone)]
 pub struct MemoryInstance {
-  data: Vec<u8>,
+  data: Box<MemoryBackend>,
   limit: Limit,
   export_name: Option<String>,
-  surface_size: u32,
+  surface_size: u64,
+  max_pages_by_host: Option<u32>,
+}
+
+impl Clone for MemoryInstance {
+  fn clone(&self) -> Self {
+    MemoryInstance {
+      data: self.data.box_clone(),
+      limit: self.limit.clone(),
+      export_name: self.export_name.clone(),
+      surface_size: self.surface_size,
+      max_pages_by_host: self.max_pages_by_host,
+    }
+  }
 }
 
 macro_rules! impl_load_data {
   ($name: ident, $ty: ty, $conv_fn: path) => {
     pub fn $name(&mut self, from: u32, to: u32) -> $ty {
-      if (to as usize) > self.data.len() {
-        self.data.resize(to as usize, 0);
-      };
-      let data = &self.data[(from as usize)..(to as usize)];
+      let mut data = [0u8; 8];
+      let data = &mut data[0..((to - from) as usize)];
+      self.data.read(from as usize, data);
       let mut bit_buf: $ty = 0;
       for (idx, d) in data.iter().enumerate() {
         let bits = $conv_fn(*d) << idx * 8;
@@ -129,10 +132,11 @@ macro_rules! impl_load_data {
 
 macro_rules! impl_store_data {
   ($name: ident, $length: expr, $ty: ty) => {
-    fn $name (&mut self, v: $ty, from: u32, to: u32) {
-        let bytes: [u8; $length] = unsafe { transmute(v) };
-        let data: &mut Vec<u8> = self.data.as_mut();
-        MemoryInstance::allocate(data, &bytes[0..(to - from) as usize], from as usize);
+    fn $name(&mut self, v: $ty, from: u32, to: u32) {
+      let bytes: [u8; $length] = unsafe { transmute(v) };
+      self
+        .data
+        .write(from as usize, &bytes[0..(to - from) as usize]);
     }
   };
 }
@@ -141,36 +145,48 @@ impl MemoryInstance {
   impl_load_data!(load_data_32, u32, u32::from);
   impl_load_data!(load_data_64, u64, u64::from);
 
-  fn allocate(data: &mut Vec<u8>, allocatable: &[u8], offset: usize) {
-    let end = offset + allocatable.len();
-    if end > data.len() {
-      data.resize(end, 0);
-    }
-    data[offset..end].copy_from_slice(allocatable);
-  }
-
   pub fn new(
     datas: Vec<Data>,
     limit: Limit,
     export_name: Option<String>,
     global_instances: &GlobalInstances,
+    strategy: &MemoryStrategy,
   ) -> Result<Self> {
-    let initial_size = limit.initial_min_size();
-    let mut data = Vec::new();
+    let initial_size = limit.initial_min_size()?;
+    let mut data = strategy.create_backend();
+    data.resize(initial_size);
     for Data { offset, init, .. } in datas.into_iter() {
       let offset = Isa::constant_expression(&offset, global_instances)?;
       let size = offset + init.len();
       if size > initial_size {
         return Err(WasmError::Trap(Trap::DataSegmentDoesNotFit));
       }
-      MemoryInstance::allocate(&mut data, &init, offset);
+      data.write(offset, &init);
     }
 
     Ok(MemoryInstance {
       data,
       limit,
       export_name,
-      surface_size: initial_size as u32,
+      surface_size: initial_size as u64,
+      max_pages_by_host: None,
+    })
+  }
+
+  /// Memory instance whose contents are provided by `backend`, e.g. a memory mapped file.
+  pub fn new_with_backend(
+    mut backend: Box<MemoryBackend>,
+    limit: Limit,
+    export_name: Option<String>,
+  ) -> Result<Self> {
+    let initial_size = limit.initial_min_size()?;
+    backend.resize(initial_size);
+    Ok(MemoryInstance {
+      data: backend,
+      limit,
+      export_name,
+      surface_size: initial_size as u64,
+      max_pages_by_host: None,
     })
   }
 
@@ -183,10 +199,13 @@ impl MemoryInstance {
     if let Some(limit) = limit {
       self.limit = limit;
     };
-    let data: &mut Vec<u8> = self.data.as_mut();
     for Data { offset, init, .. } in datas.into_iter() {
       let offset = Isa::constant_expression(&offset, global_instances)?;
-      MemoryInstance::allocate(data, &init, offset);
+      // NOTE: Backends don't check bounds, so segments beyond imported memory must be rejected here.
+      match offset.checked_add(init.len()) {
+        Some(end) if end as u64 <= self.data_size() => self.data.write(offset, &init),
+        _ => return Err(WasmError::Trap(Trap::DataSegmentDoesNotFit)),
+      }
     }
     Ok(())
   }
@@ -198,8 +217,8 @@ impl MemoryInstance {
     global_instances: &GlobalInstances,
   ) -> Result<()> {
     let initial_size = match limit {
-      Some(limit) => limit.initial_min_size(),
-      None => self.limit.initial_min_size(),
+      Some(limit) => limit.initial_min_size()?,
+      None => self.limit.initial_min_size()?,
     };
     for Data { offset, init, .. } in datas.iter() {
       let offset = Isa::constant_expression(&offset, global_instances)?;
@@ -211,37 +230,43 @@ impl MemoryInstance {
     Ok(())
   }
 
-  fn data_size(&self) -> u32 {
+  fn data_size(&self) -> u64 {
     self.surface_size
   }
 
   pub fn data_size_smaller_than(&self, ptr: u32) -> bool {
-    ptr > self.data_size()
+    u64::from(ptr) > self.data_size()
   }
 
   pub fn size_by_pages(&self) -> u32 {
-    self.data_size() / PAGE_SIZE
+    (self.data_size() / u64::from(PAGE_SIZE)) as u32
   }
 
-  pub fn memory_grow(&mut self, increase_page: u32) -> Result<()> {
-    match self.limit {
-      Limit::HasUpperLimit(_, max) if self.size_by_pages() + increase_page > max => {
-        Err(WasmError::Trap(Trap::FailToGrow))
-      }
-      _ => {
-        let current_size = self.data_size();
-        match increase_page.checked_mul(PAGE_SIZE) {
-          Some(growing_size) => match current_size.checked_add(growing_size) {
-            Some(next_size) => {
-              self.surface_size = next_size;
-              Ok(())
-            }
-            None => Err(WasmError::Trap(Trap::FailToGrow)),
-          },
-          None => Err(WasmError::Trap(Trap::FailToGrow)),
-        }
-      }
+  /// Returns count of pages before growing.
+  /// Growing never changes existing contents, and new pages are filled by zero lazily on access.
+  pub fn memory_grow(&mut self, increase_page: u32) -> Result<u32> {
+    let current_pages = self.size_by_pages();
+    let next_pages = current_pages
+      .checked_add(increase_page)
+      .ok_or(Trap::FailToGrow)?;
+    let max_pages = match self.limit {
+      Limit::HasUpperLimit(_, max) => max,
+      Limit::NoUpperLimit(_) => MAX_PAGES,
+    };
+    let max_pages = match self.max_pages_by_host {
+      Some(max_pages_by_host) if max_pages_by_host < max_pages => max_pages_by_host,
+      _ => max_pages,
+    };
+    if next_pages > max_pages || next_pages > MAX_PAGES {
+      return Err(WasmError::Trap(Trap::FailToGrow));
     }
+    self.surface_size = u64::from(next_pages) * u64::from(PAGE_SIZE);
+    self.data.resize(self.surface_size as usize);
+    Ok(current_pages)
+  }
+
+  pub fn limit_pages_by_host(&mut self, max_pages: u32) {
+    self.max_pages_by_host = Some(max_pages);
   }
 
   pub fn load_data_f32(&mut self, from: u32, to: u32) -> f32 {
@@ -266,8 +291,98 @@ impl MemoryInstance {
     };
   }
 
-  pub fn limit_gt(&self, other_limit: &Limit) -> bool {
-    &self.limit > other_limit
+  pub fn matches_limit(&self, expected: &Limit) -> bool {
+    self.limit.with_min(self.size_by_pages()).matches(expected)
+  }
+
+  fn in_bounds(&self, offset: u32, length: usize) -> Result<usize> {
+    let offset = offset as usize;
+    match offset.checked_add(length) {
+      Some(end) if end as u64 <= self.data_size() => Ok(end),
+      _ => Err(WasmError::Trap(Trap::MemoryAccessOutOfBounds)),
+    }
+  }
+
+  pub fn write_data(&mut self, offset: u32, bytes: &[u8]) -> Result<()> {
+    self.in_bounds(offset, bytes.len())?;
+    self.data.write(offset as usize, bytes);
+    Ok(())
+  }
+
+  pub fn read_data(&mut self, offset: u32, length: usize) -> Result<Vec<u8>> {
+    self.in_bounds(offset, length)?;
+    let mut buf = vec![0; length];
+    self.data.read(offset as usize, &mut buf);
+    Ok(buf)
+  }
+}
+
+#[cfg(any(test, feature = "std"))]
+fn invalid_image(message: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(any(test, feature = "std"))]
+impl MemoryInstance {
+  /// Write contents of linear memory to `writer`, where a page filled by zero takes only a byte.
+  pub fn save_image<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+    let pages = self.size_by_pages();
+    writer.write_all(&IMAGE_MAGIC)?;
+    writer.write_all(&pages.to_le_bytes())?;
+    let mut page = vec![0u8; PAGE_SIZE as usize];
+    for idx in 0..pages {
+      self.data.read((idx * PAGE_SIZE) as usize, &mut page);
+      if page.iter().all(|byte| *byte == 0) {
+        writer.write_all(&[ZERO_PAGE])?;
+      } else {
+        writer.write_all(&[DATA_PAGE])?;
+        writer.write_all(&page)?;
+      }
+    }
+    Ok(())
+  }
+
+  /// Restore contents of linear memory saved by `save_image`, growing memory up to pages of the image.
+  /// Images smaller than current memory or larger than its limit are rejected as invalid data.
+  /// Memory may be restored partially when reading from `reader` fails.
+  pub fn load_image<R: io::Read>(&mut self, reader: &mut R) -> io::Result<()> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != IMAGE_MAGIC {
+      return Err(invalid_image("magic header of memory image not detected"));
+    }
+    let mut pages = [0u8; 4];
+    reader.read_exact(&mut pages)?;
+    let pages = u32::from_le_bytes(pages);
+    let current_pages = self.size_by_pages();
+    if pages < current_pages {
+      return Err(invalid_image("memory image is smaller than memory"));
+    }
+    self
+      .memory_grow(pages - current_pages)
+      .map_err(|_| invalid_image("memory image exceeds limit of memory"))?;
+
+    let mut page = vec![0u8; PAGE_SIZE as usize];
+    for idx in 0..pages {
+      let offset = (idx * PAGE_SIZE) as usize;
+      let mut tag = [0u8];
+      reader.read_exact(&mut tag)?;
+      match tag[0] {
+        ZERO_PAGE => {
+          // NOTE: Pages which are already zero are left untouched, so that lazy backend doesn't commit them.
+          self.data.read(offset, &mut page);
+          if page.iter().any(|byte| *byte != 0) {
+            self.data.write(offset, &vec![0u8; PAGE_SIZE as usize]);
+          }
+        }
+        DATA_PAGE => {
+          reader.read_exact(&mut page)?;
+          self.data.write(offset, &page);
+        }
+        _ => return Err(invalid_image("unknown tag of page in memory image")),
+      }
+    }
+    Ok(())
   }
 }
 
@@ -275,17 +390,8 @@ impl fmt::Debug for MemoryInstance {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     f.debug_struct("MemoryInstance")
       .field("export_name", &self.export_name)
-      .field(
-        "data",
-        &self
-          .data
-          .iter()
-          .filter(|d| d != &&0)
-          .map(|d| format!("{}", d))
-          .collect::<Vec<String>>()
-          .join(", "),
-      )
-      .field("data.len()", &self.data.len())
+      .field("committed_size", &self.data.committed_size())
+      .field("surface_size", &self.surface_size)
       .field("limit", &self.limit)
       .finish()
   }
@@ -294,6 +400,81 @@ impl fmt::Debug for MemoryInstance {
 #[derive(Debug, Clone)]
 pub struct MemoryInstances(Rc<RefCell<Vec<MemoryInstance>>>);
 
+#[derive(Clone)]
+pub(crate) struct WeakMemoryInstances(Weak<RefCell<Vec<MemoryInstance>>>);
+
+impl WeakMemoryInstances {
+  pub fn upgrade(&self) -> Option<MemoryInstances> {
+    self.0.upgrade().map(MemoryInstances)
+  }
+}
+
+impl fmt::Debug for WeakMemoryInstances {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_tuple("WeakMemoryInstances").finish()
+  }
+}
+
+/// Linear memory borrowed as a slice, e.g. to pass a buffer of guest to I/O of host without copying it.
+/// Memory stays borrowed while the view lives, so other accesses to it, e.g. by instances importing it, panic.
+pub struct MemoryView<'a>(RefMut<'a, Vec<MemoryInstance>>);
+
+impl<'a> MemoryView<'a> {
+  fn memory_instance(&self) -> &MemoryInstance {
+    &self.0[0]
+  }
+
+  /// Size of memory in bytes.
+  pub fn len(&self) -> usize {
+    self.memory_instance().surface_size as usize
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  pub fn as_slice(&self) -> &[u8] {
+    let memory_instance = self.memory_instance();
+    memory_instance
+      .data
+      .as_slice(memory_instance.surface_size as usize)
+      .expect("Contiguous memory expected")
+  }
+
+  pub fn as_mut_slice(&mut self) -> &mut [u8] {
+    let memory_instance = &mut self.0[0];
+    let size = memory_instance.surface_size as usize;
+    memory_instance
+      .data
+      .as_mut_slice(size)
+      .expect("Contiguous memory expected")
+  }
+}
+
+/// Outcome of memory.grow executed by guest, which is notified to a listener of `ModuleInstance::on_memory_grow`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MemoryGrowth {
+  Grown {
+    requested_pages: u32,
+    previous_pages: u32,
+    current_pages: u32,
+  },
+  /// Guest got -1, because the memory would exceed its maximum, a cap by host or limits of an engine.
+  Failed {
+    requested_pages: u32,
+    current_pages: u32,
+  },
+}
+
+#[derive(Clone)]
+pub(crate) struct MemoryGrowListener(pub(crate) Rc<Fn(&MemoryGrowth)>);
+
+impl fmt::Debug for MemoryGrowListener {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_tuple("MemoryGrowListener").finish()
+  }
+}
+
 impl MemoryInstances {
   pub fn new(memory_instances: Vec<MemoryInstance>) -> Self {
     MemoryInstances(Rc::new(RefCell::new(memory_instances)))
@@ -303,6 +484,23 @@ impl MemoryInstances {
     MemoryInstances(Rc::new(RefCell::new(vec![])))
   }
 
+  /// Sum of pages of all memories, which is 0 when no memory is defined.
+  pub(crate) fn count_of_pages(&self) -> u32 {
+    self.0.borrow().iter().map(|m| m.size_by_pages()).sum()
+  }
+
+  pub(crate) fn is_empty(&self) -> bool {
+    self.0.borrow().is_empty()
+  }
+
+  pub(crate) fn downgrade(&self) -> WeakMemoryInstances {
+    WeakMemoryInstances(Rc::downgrade(&self.0))
+  }
+
+  pub(crate) fn ptr_eq(&self, other: &MemoryInstances) -> bool {
+    Rc::ptr_eq(&self.0, &other.0)
+  }
+
   pub fn from(
     that: &MemoryInstances,
     limit: Option<Limit>,
@@ -318,6 +516,28 @@ impl MemoryInstances {
     Ok(that.clone())
   }
 
+  /// Check that the memory instance provides what `import` requires by its name and limit.
+  pub fn match_import(&self, import: &ExternalInterface) -> Result<()> {
+    let memory_instances = self.0.borrow();
+    let instance = memory_instances.first().ok_or(Trap::UnknownImport)?;
+    if instance.export_name.as_ref() != Some(&import.name) {
+      return Err(WasmError::Trap(Trap::UnknownImport));
+    }
+    match import {
+      ExternalInterface {
+        descriptor: ModuleDescriptor::ImportDescriptor(ImportDescriptor::Memory(limit)),
+        ..
+      } => {
+        if instance.matches_limit(limit) {
+          Ok(())
+        } else {
+          Err(WasmError::Trap(Trap::IncompatibleImportType))
+        }
+      }
+      x => unreachable!("Expected memory descriptor, got {:?}", x),
+    }
+  }
+
   pub fn validate(
     that: &MemoryInstances,
     limit: &Option<Limit>,
@@ -325,31 +545,12 @@ impl MemoryInstances {
     datas: &[Data],
     global_instances: &GlobalInstances,
   ) -> Result<()> {
+    that.match_import(import)?;
     that
       .0
       .borrow_mut()
       .get_mut(0)
-      .ok_or(Trap::UnknownImport)
-      .and_then(|instance| {
-        if instance.export_name.as_ref() != Some(&import.name) {
-          Err(Trap::UnknownImport)
-        } else {
-          Ok(instance)
-        }
-      })
-      .and_then(|instance| match import {
-        ExternalInterface {
-          descriptor: ModuleDescriptor::ImportDescriptor(ImportDescriptor::Memory(limit)),
-          ..
-        } => {
-          if instance.limit_gt(limit) {
-            Err(Trap::IncompatibleImportType)
-          } else {
-            Ok(instance)
-          }
-        }
-        x => unreachable!("Expected memory descriptor, got {:?}", x),
-      })?
+      .ok_or(Trap::UnknownImport)?
       .validate(datas, limit, global_instances)
   }
 
@@ -416,7 +617,7 @@ impl MemoryInstances {
       .store_data(from, to, value)
   }
 
-  pub fn memory_grow(&self, increase_page: u32) -> Result<()> {
+  pub fn memory_grow(&self, increase_page: u32) -> Result<u32> {
     self
       .0
       .borrow_mut()
@@ -425,6 +626,63 @@ impl MemoryInstances {
       .memory_grow(increase_page)
   }
 
+  /// Borrow the first memory as a slice without copying, which fails if its backend isn't contiguous.
+  pub(crate) fn view(&self) -> Result<MemoryView> {
+    let memory_instances = self.0.borrow_mut();
+    {
+      let memory_instance = memory_instances.first().ok_or(Trap::Notfound)?;
+      memory_instance
+        .data
+        .as_slice(memory_instance.surface_size as usize)
+        .ok_or(Trap::NonContiguousMemory)?;
+    }
+    Ok(MemoryView(memory_instances))
+  }
+
+  pub fn limit_pages_by_host(&self, max_pages: u32) -> Result<()> {
+    self
+      .0
+      .borrow_mut()
+      .get_mut(0)
+      .ok_or(Trap::Notfound)?
+      .limit_pages_by_host(max_pages);
+    Ok(())
+  }
+
+  pub fn write_data(&self, offset: u32, bytes: &[u8]) -> Result<()> {
+    self
+      .0
+      .borrow_mut()
+      .get_mut(0)
+      .ok_or(Trap::Notfound)?
+      .write_data(offset, bytes)
+  }
+
+  pub fn read_data(&self, offset: u32, length: usize) -> Result<Vec<u8>> {
+    self
+      .0
+      .borrow_mut()
+      .get_mut(0)
+      .ok_or(Trap::Notfound)?
+      .read_data(offset, length)
+  }
+
+  #[cfg(any(test, feature = "std"))]
+  pub fn save_image<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+    match self.0.borrow().get(0) {
+      Some(instance) => instance.save_image(writer),
+      None => Err(io::Error::new(io::ErrorKind::NotFound, "no memory")),
+    }
+  }
+
+  #[cfg(any(test, feature = "std"))]
+  pub fn load_image<R: io::Read>(&self, reader: &mut R) -> io::Result<()> {
+    match self.0.borrow_mut().get_mut(0) {
+      Some(instance) => instance.load_image(reader),
+      None => Err(io::Error::new(io::ErrorKind::NotFound, "no memory")),
+    }
+  }
+
   pub fn clone_instance_by_name(&self, name: &str) -> Option<MemoryInstance> {
     let instance = self.0.borrow().get(0)?.clone();
     if instance.export_name == Some(name.to_owned()) {
@@ -434,3 +692,86 @@ impl MemoryInstances {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::Limit::*;
+  use super::*;
+  use memory_backend::LazyBackend;
+
+  #[test]
+  fn match_limits_of_imports() {
+    assert!(NoUpperLimit(2).matches(&NoUpperLimit(1)));
+    assert!(!NoUpperLimit(1).matches(&NoUpperLimit(2)));
+    assert!(HasUpperLimit(1, 2).matches(&NoUpperLimit(1)));
+    assert!(!NoUpperLimit(2).matches(&HasUpperLimit(1, 3)));
+    assert!(HasUpperLimit(2, 3).matches(&HasUpperLimit(1, 5)));
+    assert!(!HasUpperLimit(3, 10).matches(&HasUpperLimit(2, 5)));
+    assert!(!HasUpperLimit(1, 5).matches(&HasUpperLimit(2, 5)));
+  }
+
+  fn memory_of(limit: Limit) -> MemoryInstance {
+    MemoryInstance::new_with_backend(Box::new(LazyBackend::default()), limit, None).unwrap()
+  }
+
+  #[test]
+  fn grow_memory_up_to_4gib() {
+    let mut memory = memory_of(NoUpperLimit(MAX_PAGES - 1));
+    assert_eq!(memory.memory_grow(1), Ok(MAX_PAGES - 1));
+    assert_eq!(memory.size_by_pages(), MAX_PAGES);
+    assert_eq!(
+      memory.memory_grow(1),
+      Err(WasmError::Trap(Trap::FailToGrow))
+    );
+    assert!(memory.write_data(u32::max_value(), &[1]).is_ok());
+  }
+
+  #[test]
+  fn reject_data_segments_beyond_linked_memory() {
+    let mut memory = memory_of(NoUpperLimit(1));
+    let data = |offset: u32| {
+      let mut expr = vec![0x41];
+      expr.extend_from_slice(&offset.to_le_bytes());
+      expr.push(0x0b);
+      Data::new(0, expr, vec![1, 2])
+    };
+    let global_instances = GlobalInstances::empty();
+    assert_eq!(
+      memory.link(vec![data(PAGE_SIZE - 2)], None, &global_instances),
+      Ok(())
+    );
+    assert_eq!(
+      memory.link(vec![data(PAGE_SIZE - 1)], None, &global_instances),
+      Err(WasmError::Trap(Trap::DataSegmentDoesNotFit))
+    );
+  }
+
+  #[test]
+  fn save_and_load_image_with_zero_pages_sparse() {
+    let mut memory = memory_of(HasUpperLimit(1, 4));
+    memory.memory_grow(2).unwrap();
+    memory.write_data(2 * PAGE_SIZE + 1, &[1, 2, 3]).unwrap();
+    let mut image = vec![];
+    memory.save_image(&mut image).unwrap();
+    assert_eq!(image.len(), 4 + 4 + 1 + 1 + 1 + PAGE_SIZE as usize);
+
+    let mut restored = memory_of(HasUpperLimit(1, 4));
+    restored.write_data(0, &[0xff]).unwrap();
+    restored.load_image(&mut image.as_slice()).unwrap();
+    assert_eq!(restored.size_by_pages(), 3);
+    assert_eq!(restored.read_data(0, 1).unwrap(), vec![0]);
+    assert_eq!(
+      restored.read_data(2 * PAGE_SIZE, 4).unwrap(),
+      vec![0, 1, 2, 3]
+    );
+    assert_eq!(restored.data.committed_size(), 2 * PAGE_SIZE as usize);
+
+    let mut smaller = memory_of(HasUpperLimit(1, 2));
+    let err = smaller.load_image(&mut image.as_slice()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    let err = memory_of(HasUpperLimit(1, 4))
+      .load_image(&mut &image[..10])
+      .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+  }
+}
@@ -0,0 +1,323 @@
+#[cfg(not(any(test, feature = "std")))]
+use alloc::prelude::*;
+use alloc::vec::Vec;
+#[cfg(feature = "mmap-file")]
+use core::ops::Range;
+#[cfg(feature = "mmap-file")]
+use memmap::{MmapMut, MmapOptions};
+#[cfg(feature = "mmap-file")]
+use std::fs::File;
+#[cfg(feature = "mmap-file")]
+use std::io;
+
+// NOTE: Lazy backend materializes linear memory by this size.
+const CHUNK_SIZE: usize = 65536;
+
+/// Storage of linear memory.
+/// Callers must check bounds by surface size of memory instance before read and write.
+pub trait MemoryBackend {
+  /// Make linear memory addressable up to `size` bytes.
+  fn resize(&mut self, size: usize);
+  fn read(&self, offset: usize, buf: &mut [u8]);
+  fn write(&mut self, offset: usize, bytes: &[u8]);
+  /// Count of bytes actually allocated.
+  fn committed_size(&self) -> usize;
+  fn box_clone(&self) -> Box<MemoryBackend>;
+  /// Linear memory up to `size` bytes as a slice, None if the backend doesn't store it contiguously.
+  fn as_slice(&self, _size: usize) -> Option<&[u8]> {
+    None
+  }
+  fn as_mut_slice(&mut self, _size: usize) -> Option<&mut [u8]> {
+    None
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MemoryStrategy {
+  /// Allocate whole of linear memory when instantiating and growing.
+  Eager,
+  /// Allocate pages on the first write to them.
+  Lazy,
+}
+
+// NOTE: Eager is the default, so that memory can be viewed as a slice and passed to raw host functions.
+impl Default for MemoryStrategy {
+  fn default() -> Self {
+    MemoryStrategy::Eager
+  }
+}
+
+impl MemoryStrategy {
+  pub(crate) fn create_backend(&self) -> Box<MemoryBackend> {
+    match self {
+      MemoryStrategy::Eager => Box::new(EagerBackend::default()),
+      MemoryStrategy::Lazy => Box::new(LazyBackend::default()),
+    }
+  }
+}
+
+#[derive(Clone, Default)]
+pub struct EagerBackend(Vec<u8>);
+
+impl MemoryBackend for EagerBackend {
+  fn resize(&mut self, size: usize) {
+    if size > self.0.len() {
+      self.0.resize(size, 0);
+    }
+  }
+
+  fn read(&self, offset: usize, buf: &mut [u8]) {
+    let end = offset + buf.len();
+    buf.copy_from_slice(&self.0[offset..end]);
+  }
+
+  fn write(&mut self, offset: usize, bytes: &[u8]) {
+    let end = offset + bytes.len();
+    self.0[offset..end].copy_from_slice(bytes);
+  }
+
+  fn committed_size(&self) -> usize {
+    self.0.len()
+  }
+
+  fn box_clone(&self) -> Box<MemoryBackend> {
+    Box::new(self.clone())
+  }
+
+  fn as_slice(&self, size: usize) -> Option<&[u8]> {
+    self.0.get(0..size)
+  }
+
+  fn as_mut_slice(&mut self, size: usize) -> Option<&mut [u8]> {
+    self.0.get_mut(0..size)
+  }
+}
+
+#[derive(Clone, Default)]
+pub struct LazyBackend(Vec<Option<Box<[u8]>>>);
+
+impl LazyBackend {
+  // Split access to [offset, offset + len) into (index of chunk, start in chunk, start in buffer, length).
+  fn split(offset: usize, len: usize) -> Vec<(usize, usize, usize, usize)> {
+    let mut ranges = vec![];
+    let mut consumed = 0;
+    while consumed < len {
+      let ptr = offset + consumed;
+      let start = ptr % CHUNK_SIZE;
+      let length = core::cmp::min(CHUNK_SIZE - start, len - consumed);
+      ranges.push((ptr / CHUNK_SIZE, start, consumed, length));
+      consumed += length;
+    }
+    ranges
+  }
+}
+
+impl MemoryBackend for LazyBackend {
+  fn resize(&mut self, size: usize) {
+    let count_of_chunks = (size + CHUNK_SIZE - 1) / CHUNK_SIZE;
+    if count_of_chunks > self.0.len() {
+      self.0.resize(count_of_chunks, None);
+    }
+  }
+
+  fn read(&self, offset: usize, buf: &mut [u8]) {
+    for (idx, start, buf_start, length) in LazyBackend::split(offset, buf.len()) {
+      let dest = &mut buf[buf_start..(buf_start + length)];
+      match &self.0[idx] {
+        Some(chunk) => dest.copy_from_slice(&chunk[start..(start + length)]),
+        None => {
+          for byte in dest.iter_mut() {
+            *byte = 0;
+          }
+        }
+      }
+    }
+  }
+
+  fn write(&mut self, offset: usize, bytes: &[u8]) {
+    for (idx, start, buf_start, length) in LazyBackend::split(offset, bytes.len()) {
+      let chunk = self.0[idx].get_or_insert_with(|| vec![0; CHUNK_SIZE].into_boxed_slice());
+      chunk[start..(start + length)].copy_from_slice(&bytes[buf_start..(buf_start + length)]);
+    }
+  }
+
+  fn committed_size(&self) -> usize {
+    self.0.iter().filter(|chunk| chunk.is_some()).count() * CHUNK_SIZE
+  }
+
+  fn box_clone(&self) -> Box<MemoryBackend> {
+    Box::new(self.clone())
+  }
+}
+
+/// How writes to memory mapped file are handled.
+#[cfg(feature = "mmap-file")]
+#[derive(Debug, Clone, PartialEq)]
+pub enum MmapMode {
+  /// Writes are only visible to the memory instance, and the file is left as is.
+  CopyOnWrite,
+  /// Writes are persisted to the file.
+  Persistent,
+}
+
+/// Backend which maps a file into linear memory from its beginning,
+/// so that large datasets can be exposed to guest without copying them.
+/// Memory grown beyond length of the file is allocated in heap and never persisted.
+#[cfg(feature = "mmap-file")]
+pub struct MmapBackend {
+  map: MmapMut,
+  tail: EagerBackend,
+}
+
+#[cfg(feature = "mmap-file")]
+impl MmapBackend {
+  /// Mapping an empty file fails.
+  pub fn new(file: &File, mode: &MmapMode) -> io::Result<Self> {
+    // NOTE: Contents of memory are undefined if other processes modify the file while mapped.
+    let map = unsafe {
+      match mode {
+        MmapMode::CopyOnWrite => MmapOptions::new().map_copy(file)?,
+        MmapMode::Persistent => MmapOptions::new().map_mut(file)?,
+      }
+    };
+    Ok(MmapBackend {
+      map,
+      tail: EagerBackend::default(),
+    })
+  }
+
+  /// Write modified pages back to the file in persistent mode.
+  pub fn flush(&self) -> io::Result<()> {
+    self.map.flush()
+  }
+
+  // Range of access from offset which lies in mapped file, which is empty when it starts beyond the file.
+  fn mapped_range(&self, offset: usize, len: usize) -> Range<usize> {
+    let start = core::cmp::min(offset, self.map.len());
+    let end = core::cmp::min(offset.saturating_add(len), self.map.len());
+    start..end
+  }
+}
+
+#[cfg(feature = "mmap-file")]
+impl MemoryBackend for MmapBackend {
+  fn resize(&mut self, size: usize) {
+    if size > self.map.len() {
+      self.tail.resize(size - self.map.len());
+    }
+  }
+
+  fn read(&self, offset: usize, buf: &mut [u8]) {
+    let mapped = self.mapped_range(offset, buf.len());
+    let (head, tail) = buf.split_at_mut(mapped.len());
+    let tail_offset = offset + mapped.len();
+    head.copy_from_slice(&self.map[mapped]);
+    if !tail.is_empty() {
+      self.tail.read(tail_offset - self.map.len(), tail);
+    }
+  }
+
+  fn write(&mut self, offset: usize, bytes: &[u8]) {
+    let mapped = self.mapped_range(offset, bytes.len());
+    let (head, tail) = bytes.split_at(mapped.len());
+    let tail_offset = offset + mapped.len();
+    self.map[mapped].copy_from_slice(head);
+    if !tail.is_empty() {
+      let map_len = self.map.len();
+      self.tail.write(tail_offset - map_len, tail);
+    }
+  }
+
+  // NOTE: Pages of mapped file are managed by OS, not allocated by the backend.
+  fn committed_size(&self) -> usize {
+    self.tail.committed_size()
+  }
+
+  // NOTE: Cloned memory is detached from the file.
+  fn box_clone(&self) -> Box<MemoryBackend> {
+    let mut bytes = self.map.to_vec();
+    bytes.extend_from_slice(&self.tail.0);
+    Box::new(EagerBackend(bytes))
+  }
+
+  // NOTE: Memory is contiguous only while it lies in mapped file.
+  fn as_slice(&self, size: usize) -> Option<&[u8]> {
+    self.map.get(0..size)
+  }
+
+  fn as_mut_slice(&mut self, size: usize) -> Option<&mut [u8]> {
+    self.map.get_mut(0..size)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn lazy_backend_commits_touched_chunks() {
+    let mut backend = LazyBackend::default();
+    backend.resize(CHUNK_SIZE * 4);
+    assert_eq!(backend.committed_size(), 0);
+
+    backend.write(CHUNK_SIZE - 2, &[1, 2, 3, 4]);
+    assert_eq!(backend.committed_size(), CHUNK_SIZE * 2);
+
+    let mut buf = [0xff; 6];
+    backend.read(CHUNK_SIZE - 3, &mut buf);
+    assert_eq!(buf, [0, 1, 2, 3, 4, 0]);
+
+    let mut buf = [0xff; 2];
+    backend.read(CHUNK_SIZE * 3, &mut buf);
+    assert_eq!(buf, [0, 0]);
+  }
+
+  #[test]
+  fn eager_backend_commits_whole_size() {
+    let mut backend = EagerBackend::default();
+    backend.resize(CHUNK_SIZE * 2);
+    assert_eq!(backend.committed_size(), CHUNK_SIZE * 2);
+    backend.write(3, &[42]);
+    let mut buf = [0; 2];
+    backend.read(3, &mut buf);
+    assert_eq!(buf, [42, 0]);
+    assert_eq!(backend.as_slice(5), Some(&[0, 0, 0, 42, 0][..]));
+    assert_eq!(LazyBackend::default().as_slice(0), None);
+  }
+
+  #[cfg(feature = "mmap-file")]
+  #[test]
+  fn mmap_backend_maps_file_into_head_of_memory() {
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+    // NOTE: Path is unique to the process so that concurrent runs of tests don't share the file.
+    let path = ::std::env::temp_dir().join(format!("wasvm-mmap-backend-{}", ::std::process::id()));
+    let mut file = OpenOptions::new()
+      .read(true)
+      .write(true)
+      .create(true)
+      .truncate(true)
+      .open(&path)
+      .unwrap();
+    file.write_all(&[1, 2, 3, 4]).unwrap();
+
+    let mut backend = MmapBackend::new(&file, &MmapMode::CopyOnWrite).unwrap();
+    backend.resize(8);
+    assert_eq!(backend.committed_size(), 4);
+    backend.write(2, &[0xff; 4]);
+    let mut buf = [0; 8];
+    backend.read(0, &mut buf);
+    assert_eq!(buf, [1, 2, 0xff, 0xff, 0xff, 0xff, 0, 0]);
+    backend.write(6, &[7]);
+    let mut buf = [0; 2];
+    backend.read(6, &mut buf);
+    assert_eq!(buf, [7, 0]);
+    assert_eq!(fs::read(&path).unwrap(), vec![1, 2, 3, 4]);
+
+    let mut backend = MmapBackend::new(&file, &MmapMode::Persistent).unwrap();
+    backend.write(0, &[5]);
+    backend.flush().unwrap();
+    assert_eq!(fs::read(&path).unwrap(), vec![5, 2, 3, 4]);
+    fs::remove_file(&path).unwrap();
+  }
+}
@@ -0,0 +1,39 @@
+use alloc::vec::Vec;
+
+const COUNT_OF_OPCODES: usize = 256;
+
+/// Snapshot of counters accumulated by a module instance while `ModuleInstance::enable_metrics` is on,
+/// since it was created or reset.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Metrics {
+  /// Count of dispatched instructions. A fused superinstruction is counted once.
+  pub instructions_executed: u64,
+  /// Count of `call` and `call_indirect` including calls of host functions.
+  pub calls: u64,
+  pub memory_bytes_read: u64,
+  pub memory_bytes_written: u64,
+  pub traps: u64,
+  /// Count of invocations trapped by `ResourceLimitExceeded` as fuel of engine ran out, included in `traps`.
+  pub fuel_exhausted: u64,
+  // NOTE: Indexed by internal opcode, allocated at the first count.
+  executed_by_opcode: Vec<u64>,
+}
+
+impl Metrics {
+  pub(crate) fn count_instruction(&mut self, opcode: u8) {
+    if self.executed_by_opcode.is_empty() {
+      self.executed_by_opcode = vec![0; COUNT_OF_OPCODES];
+    }
+    self.instructions_executed += 1;
+    self.executed_by_opcode[opcode as usize] += 1;
+  }
+
+  /// Count of executions of an instruction by its opcode.
+  pub fn executed_of(&self, opcode: u8) -> u64 {
+    self
+      .executed_by_opcode
+      .get(opcode as usize)
+      .cloned()
+      .unwrap_or(0)
+  }
+}
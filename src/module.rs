@@ -1,4 +1,4 @@
-use alloc::rc::Rc;
+use alloc::rc::{Rc, Weak};
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::cell::RefCell;
@@ -7,15 +7,16 @@ use core::default::Default;
 use core::fmt;
 use core::iter::Iterator;
 use core::slice::Iter;
-use decode::TableType;
+use decode::{DebugLines, Module, Producers, TableType};
 use error::{Result, Trap, WasmError};
 use function::{FunctionInstance, FunctionType};
 use global::{GlobalInstance, GlobalInstances, GlobalType};
-use heapless::consts::{U32, U4};
+use heapless::consts::U4;
 use heapless::LinearMap;
 use indice::Indice;
 use memory::{Limit, MemoryInstance, MemoryInstances};
 use store::Store;
+use symbol::{Symbol, SymbolTable};
 use table::{TableInstance, TableInstances};
 
 #[derive(Debug, Clone)]
@@ -99,7 +100,8 @@ impl ExternalInterface {
   }
 }
 
-#[derive(Debug)]
+/// Imports or exports of a module, kept in the order declared in its binary.
+#[derive(Debug, Clone)]
 pub struct ExternalInterfaces(Vec<ExternalInterface>);
 
 impl ExternalInterfaces {
@@ -140,6 +142,10 @@ impl ExternalInterfaces {
     self.0.iter()
   }
 
+  pub fn names(&self) -> Vec<&str> {
+    self.iter().map(|x| x.name.as_str()).collect()
+  }
+
   pub fn group_by_kind(
     &self,
   ) -> Result<LinearMap<ModuleDescriptorKind, Vec<ExternalInterface>, U4>> {
@@ -190,30 +196,115 @@ impl Default for ExternalInterfaces {
 
 #[derive(Debug)]
 pub struct InternalModule {
-  exports: ExternalInterfaces,
+  pub(crate) exports: ExternalInterfaces,
   pub start: Option<Indice>,
+  pub(crate) debug_lines: Option<DebugLines>,
+  pub(crate) producers: Option<Producers>,
 }
 
 impl InternalModule {
-  pub fn new(exports: ExternalInterfaces, start: Option<u32>) -> Self {
+  pub fn new(
+    exports: ExternalInterfaces,
+    start: Option<u32>,
+    debug_lines: Option<DebugLines>,
+    producers: Option<Producers>,
+  ) -> Self {
     InternalModule {
       exports,
       start: start.map(Indice::from),
+      debug_lines,
+      producers,
     }
   }
 
   pub fn get_export_by_key(&self, invoke: &str) -> Option<&ExternalInterface> {
     self.exports.0.iter().find(|x| x.name == invoke)
   }
+
+  /// Same as `get_export_by_key`, but fails by `WasmError::UnknownExport` when no export has the name.
+  pub(crate) fn find_export(
+    &self,
+    invoke: &str,
+    suggestion: &ExportSuggestion,
+  ) -> Result<&ExternalInterface> {
+    self
+      .get_export_by_key(invoke)
+      .ok_or_else(|| unknown_export(invoke, &self.export_names(), suggestion))
+  }
+
+  pub fn export_names(&self) -> Vec<&str> {
+    self.exports.names()
+  }
+}
+
+/// Names suggested by `WasmError::UnknownExport` when an export is missing.
+#[derive(PartialEq, Clone, Debug)]
+pub enum ExportSuggestion {
+  /// Exports within a third of length of the name in edit distance, the nearest first.
+  Similar,
+  /// Nothing, which spares computing edit distances against every export.
+  Omitted,
+}
+
+impl Default for ExportSuggestion {
+  fn default() -> Self {
+    ExportSuggestion::Similar
+  }
+}
+
+// NOTE: Count of edits by insertion, deletion or substitution of a character.
+fn edit_distance(a: &str, b: &str) -> usize {
+  let b = b.chars().collect::<Vec<_>>();
+  let mut previous = (0..=b.len()).collect::<Vec<_>>();
+  for (i, x) in a.chars().enumerate() {
+    let mut current = vec![i + 1];
+    for (j, y) in b.iter().enumerate() {
+      let substitution = previous[j] + if x == *y { 0 } else { 1 };
+      let deletion = previous[j + 1] + 1;
+      let insertion = current[j] + 1;
+      current.push(substitution.min(deletion).min(insertion));
+    }
+    previous = current;
+  }
+  previous[b.len()]
+}
+
+/// Error of looking up `name` from exports, which suggests ones as `suggestion` configures.
+pub(crate) fn unknown_export(
+  name: &str,
+  export_names: &[&str],
+  suggestion: &ExportSuggestion,
+) -> WasmError {
+  if suggestion == &ExportSuggestion::Omitted {
+    return WasmError::UnknownExport {
+      name: String::from(name),
+      similar: vec![],
+    };
+  }
+  let max_distance = core::cmp::max(1, name.chars().count() / 3);
+  let mut similar = export_names
+    .iter()
+    .map(|export_name| (edit_distance(name, export_name), *export_name))
+    .filter(|(distance, _)| *distance <= max_distance)
+    .collect::<Vec<_>>();
+  // NOTE: Sort stably, so that exports of the same distance keep the declaration order.
+  similar.sort_by_key(|(distance, _)| *distance);
+  WasmError::UnknownExport {
+    name: String::from(name),
+    similar: similar
+      .into_iter()
+      .map(|(_, export_name)| String::from(export_name))
+      .collect(),
+  }
 }
 
 #[derive(Debug, Clone)]
 pub struct ExternalModule {
   pub function_instances: Vec<FunctionInstance>,
-  function_types: Vec<FunctionType>,
+  pub(crate) function_types: Vec<FunctionType>,
   pub(crate) memory_instances: MemoryInstances,
-  table_instances: TableInstances,
-  global_instances: GlobalInstances,
+  pub(crate) table_instances: TableInstances,
+  pub(crate) global_instances: GlobalInstances,
 }
 
 impl ExternalModule {
@@ -233,9 +324,29 @@ impl ExternalModule {
     }
   }
 
+  /// Copy of the module whose functions are replaced by ones of `overrides` which have the same names.
+  /// Functions only `overrides` has are added, and others are kept as they are.
+  pub fn shadowed_by(&self, overrides: &ExternalModule) -> ExternalModule {
+    let mut shadowed = self.clone();
+    shadowed.function_instances.retain(|instance| {
+      !overrides
+        .function_instances
+        .iter()
+        .any(|other| other.export_name() == instance.export_name())
+    });
+    shadowed
+      .function_instances
+      .extend(overrides.function_instances.iter().cloned());
+    shadowed
+      .function_types
+      .extend(overrides.function_types.iter().cloned());
+    shadowed
+  }
+
   // FIXME: Consider to rename import-function-instance
   fn find_function_instance(
     &self,
+    module_name: &Option<Symbol>,
     key: &ExternalInterface,
     function_types: &[FunctionType],
   ) -> Result<FunctionInstance> {
@@ -243,9 +354,9 @@ impl ExternalModule {
       ExternalInterface {
         descriptor: ModuleDescriptor::ImportDescriptor(ImportDescriptor::Function(idx)),
         name,
-        module_name,
+        ..
       } => {
-        let expected_type = function_types.get(idx.to_usize())?;
+        let expected_type = Module::type_of(function_types, idx.to_u32())?;
         let instance = self
           .function_instances
           .iter()
@@ -257,7 +368,9 @@ impl ExternalModule {
           .validate_type(expected_type)
           .map_err(|_| Trap::IncompatibleImportType)?;
 
-        instance.set_source_module_name(module_name);
+        if let Some(module_name) = module_name {
+          instance.set_source_symbol(module_name.clone());
+        }
         Ok(instance)
       }
       x => unreachable!("Expected function descriptor, got {:?}", x),
@@ -277,7 +390,7 @@ impl ExternalModule {
         if !self.table_instances.find_by_name(name) {
           return Err(WasmError::Trap(Trap::UnknownImport));
         }
-        if self.table_instances.gt_table_type(table_type) {
+        if !self.table_instances.matches_table_type(table_type) {
           return Err(WasmError::Trap(Trap::IncompatibleImportType));
         }
         Ok(self.table_instances.clone())
@@ -311,78 +424,235 @@ impl From<&Store> for ExternalModule {
   }
 }
 
+// NOTE: Same capacity as the registry had by LinearMap.
+const MAX_COUNT_OF_MODULES: usize = 32;
+
+/// Registered modules in the order of registration, which are indexed by ids of their symbols.
+#[derive(Default)]
+struct Modules {
+  entries: Vec<(Option<Symbol>, ExternalModule)>,
+  positions: Vec<Option<usize>>,
+}
+
+impl Modules {
+  fn position(&self, key: &Option<Symbol>) -> Option<usize> {
+    match key {
+      Some(symbol) => {
+        let position = (*self.positions.get(symbol.id()?)?)?;
+        // NOTE: Symbols of other tables may have the same id.
+        match &self.entries[position].0 {
+          Some(registered) if registered == symbol => Some(position),
+          _ => None,
+        }
+      }
+      None => self.entries.iter().position(|(name, _)| name.is_none()),
+    }
+  }
+
+  fn get(&self, key: &Option<Symbol>) -> Option<&ExternalModule> {
+    self.position(key).map(|position| &self.entries[position].1)
+  }
+
+  fn insert(&mut self, key: Option<Symbol>, module: ExternalModule) -> Result<()> {
+    if let Some(position) = self.position(&key) {
+      self.entries[position].1 = module;
+      return Ok(());
+    }
+    if self.entries.len() >= MAX_COUNT_OF_MODULES {
+      return Err(WasmError::Trap(Trap::LinearMapOverflowed));
+    }
+    self.entries.push((key, module));
+    self.index();
+    Ok(())
+  }
+
+  fn remove(&mut self, key: &Option<Symbol>) -> Option<ExternalModule> {
+    let position = self.position(key)?;
+    let (_, module) = self.entries.remove(position);
+    self.index();
+    Some(module)
+  }
+
+  fn index(&mut self) {
+    self.positions.clear();
+    for (position, (name, _)) in self.entries.iter().enumerate() {
+      if let Some(id) = name.as_ref().and_then(|name| name.id()) {
+        if self.positions.len() <= id {
+          self.positions.resize(id + 1, None);
+        }
+        self.positions[id] = Some(position);
+      }
+    }
+  }
+}
+
+/// Modules which others import from by their names.
+/// Names are interned, so that instances look up modules which their functions came from by symbols.
 #[derive(Clone)]
-pub struct ExternalModules(Rc<RefCell<LinearMap<ModuleName, ExternalModule, U32>>>);
+pub struct ExternalModules {
+  modules: Rc<RefCell<Modules>>,
+  symbols: SymbolTable,
+}
 
 impl fmt::Debug for ExternalModules {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     f.debug_map()
-      .entries(self.0.borrow().iter().map(|(k, v)| (k, v)))
+      .entries(self.modules.borrow().entries.iter().map(|(k, v)| (k, v)))
       .finish()
   }
 }
 
+#[derive(Clone)]
+pub struct WeakExternalModules {
+  modules: Weak<RefCell<Modules>>,
+  symbols: SymbolTable,
+}
+
+impl WeakExternalModules {
+  pub fn upgrade(&self) -> Option<ExternalModules> {
+    self.modules.upgrade().map(|modules| ExternalModules {
+      modules,
+      symbols: self.symbols.clone(),
+    })
+  }
+}
+
+impl fmt::Debug for WeakExternalModules {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_tuple("WeakExternalModules").finish()
+  }
+}
+
 impl Default for ExternalModules {
   fn default() -> Self {
-    ExternalModules(Rc::new(RefCell::new(LinearMap::new())))
+    ExternalModules::with_symbols(SymbolTable::default())
   }
 }
 
 impl ExternalModules {
+  /// Registry whose names are interned by `symbols`, e.g. of an `Engine`.
+  pub fn with_symbols(symbols: SymbolTable) -> Self {
+    ExternalModules {
+      modules: Rc::new(RefCell::new(Modules::default())),
+      symbols,
+    }
+  }
+
+  pub fn symbols(&self) -> &SymbolTable {
+    &self.symbols
+  }
+
+  // NOTE: A name which isn't interned yet is never registered, so it isn't interned by lookups.
+  fn key_of(&self, module_name: &ModuleName) -> Option<Option<Symbol>> {
+    match module_name {
+      Some(name) => self.symbols.get(name).map(Some),
+      None => Some(None),
+    }
+  }
+
+  fn find<T, F>(&self, module_name: &ModuleName, f: F) -> Result<T>
+  where
+    F: FnOnce(&Option<Symbol>, &ExternalModule) -> Result<T>,
+  {
+    let key = self.key_of(module_name).ok_or(Trap::UnknownImport)?;
+    let modules = self.modules.borrow();
+    let module = modules.get(&key).ok_or(Trap::UnknownImport)?;
+    f(&key, module)
+  }
+
+  // NOTE: Symbols interned by the registry are found by their ids, and others by their names.
+  fn find_by_symbol<T, F>(&self, module_name: &Symbol, f: F) -> Result<T>
+  where
+    F: FnOnce(&ExternalModule) -> Result<T>,
+  {
+    let modules = self.modules.borrow();
+    let module = match modules.get(&Some(module_name.clone())) {
+      Some(module) => module,
+      None => {
+        let key = self.symbols.get(module_name).ok_or(Trap::UnknownImport)?;
+        modules.get(&Some(key)).ok_or(Trap::UnknownImport)?
+      }
+    };
+    f(module)
+  }
+
   pub fn get(&self, module_name: &ModuleName) -> Option<ExternalModule> {
-    self.0.borrow().get(module_name).cloned()
+    let key = self.key_of(module_name)?;
+    self.modules.borrow().get(&key).cloned()
   }
 
   pub fn register_module(&mut self, key: ModuleName, value: ExternalModule) -> Result<()> {
-    self
-      .0
-      .borrow_mut()
-      .insert(key, value)
-      .map_err(|_| Trap::LinearMapOverflowed)?;
-    Ok(())
+    let key = key.map(|name| self.symbols.intern(&name));
+    self.modules.borrow_mut().insert(key, value)
   }
 
-  pub fn get_table_instance(
-    &self,
-    module_name: &ModuleName,
-    idx: &Indice,
-  ) -> Result<TableInstance> {
-    self
-      .0
-      .borrow()
-      .get(module_name)
-      .ok_or(Trap::UnknownImport)?
-      .table_instances
-      .get_table_at(idx)
-      .ok_or(WasmError::Trap(Trap::Notfound))
+  /// Remove a module registered by `key`, whose instances are dropped unless other modules import them.
+  /// Functions of the module which are imported by others fail to call its internal functions afterwards,
+  /// so modules should be unregistered after ones importing from them.
+  pub fn unregister_module(&mut self, key: &ModuleName) -> Option<ExternalModule> {
+    let key = self.key_of(key)?;
+    self.modules.borrow_mut().remove(&key)
   }
 
-  pub fn get_function_type(&self, module_name: &ModuleName, idx: u32) -> Result<FunctionType> {
-    self
-      .0
-      .borrow()
-      .get(module_name)
-      .ok_or(WasmError::Trap(Trap::UnknownImport))?
-      .function_types
-      .get(idx as usize)
-      .ok_or(WasmError::Trap(Trap::Notfound))
-      .map(|x| x.clone())
+  /// Names of registered modules in the order of registration.
+  pub fn module_names(&self) -> Vec<ModuleName> {
+    let modules = self.modules.borrow();
+    let names = modules
+      .entries
+      .iter()
+      .map(|(name, _)| name)
+      .map(|name| name.as_ref().map(|name| String::from(name.as_str())))
+      .collect();
+    names
+  }
+
+  /// Reference which doesn't keep registered modules alive, e.g. for host functions capturing the registry.
+  pub fn downgrade(&self) -> WeakExternalModules {
+    WeakExternalModules {
+      modules: Rc::downgrade(&self.modules),
+      symbols: self.symbols.clone(),
+    }
+  }
+
+  pub fn get_table_instance(&self, module_name: &Symbol, idx: &Indice) -> Result<TableInstance> {
+    self.find_by_symbol(module_name, |module| {
+      module
+        .table_instances
+        .get_table_at(idx)
+        .ok_or(WasmError::Trap(Trap::Notfound))
+    })
+  }
+
+  pub fn get_function_type(&self, module_name: &Symbol, idx: u32) -> Result<FunctionType> {
+    self.find_by_symbol(module_name, |module| {
+      module
+        .function_types
+        .get(idx as usize)
+        .cloned()
+        .ok_or(WasmError::Trap(Trap::Notfound))
+    })
   }
 
   pub fn get_function_instance(
     &self,
-    module_name: &ModuleName,
+    module_name: &Symbol,
     idx: usize,
   ) -> Result<FunctionInstance> {
-    self
-      .0
-      .borrow()
-      .get(module_name)
-      .ok_or(Trap::UnknownImport)?
-      .function_instances
-      .get(idx)
-      .cloned()
-      .ok_or(WasmError::Trap(Trap::Notfound))
+    self.find_by_symbol(module_name, |module| {
+      module
+        .function_instances
+        .get(idx)
+        .cloned()
+        .ok_or(WasmError::Trap(Trap::Notfound))
+    })
+  }
+
+  pub fn get_memory_instances(&self, module_name: &Symbol) -> Result<MemoryInstances> {
+    self.find_by_symbol(module_name, |module| Ok(module.memory_instances.clone()))
+  }
+
+  pub fn get_global_instances(&self, module_name: &Symbol) -> Result<GlobalInstances> {
+    self.find_by_symbol(module_name, |module| Ok(module.global_instances.clone()))
   }
 
   pub fn find_function_instances(
@@ -390,38 +660,24 @@ impl ExternalModules {
     import: &ExternalInterface,
     function_types: &[FunctionType],
   ) -> Result<FunctionInstance> {
-    self
-      .0
-      .borrow()
-      .get(&import.module_name)
-      .ok_or(Trap::UnknownImport)?
-      .find_function_instance(import, function_types)
+    self.find(&import.module_name, |key, module| {
+      module.find_function_instance(key, import, function_types)
+    })
   }
 
   pub fn find_memory_instances(&self, import: &ExternalInterface) -> Result<MemoryInstances> {
-    self
-      .0
-      .borrow()
-      .get(&import.module_name)
-      .ok_or(WasmError::Trap(Trap::UnknownImport))
-      .map(|x| x.memory_instances.clone())
+    self.find(&import.module_name, |_, module| {
+      Ok(module.memory_instances.clone())
+    })
   }
 
   pub fn find_table_instances(&self, import: &ExternalInterface) -> Result<TableInstances> {
-    self
-      .0
-      .borrow()
-      .get(&import.module_name)
-      .ok_or(Trap::UnknownImport)?
-      .find_table_instance(import)
+    self.find(&import.module_name, |_, module| {
+      module.find_table_instance(import)
+    })
   }
 
   pub fn find_global_instances(&self, module_name: &ModuleName) -> Result<GlobalInstances> {
-    self
-      .0
-      .borrow()
-      .get(module_name)
-      .ok_or(WasmError::Trap(Trap::UnknownImport))
-      .map(|x| x.global_instances.clone())
+    self.find(module_name, |_, module| Ok(module.global_instances.clone()))
   }
 }
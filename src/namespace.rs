@@ -0,0 +1,186 @@
+#[cfg(not(any(test, feature = "std")))]
+use alloc::prelude::*;
+use alloc::rc::{Rc, Weak};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt;
+use embedder::{decode_module, init_store, instantiate_module};
+use error::{Result, Trap, WasmError};
+use module::ExternalModules;
+use vm::ModuleInstance;
+
+struct NamespaceImpl {
+  modules: Vec<(String, Vec<u8>)>,
+  instances: Vec<(String, Rc<RefCell<ModuleInstance>>)>,
+  external_modules: ExternalModules,
+  max_stack_height: usize,
+}
+
+/// Modules and their instances registered by names, which host functions can look up while guest runs,
+/// e.g. to implement dlopen-style imports which instantiate a module on demand.
+/// Clones share the same registry.
+///
+/// Instances are registered to `ExternalModules` passed to `new` by their names, and every instance
+/// instantiated by `open` imports from and calls through it. So an instance which calls functions wired by
+/// `wire_exports` must be instantiated with the same `ExternalModules`, or opened by the namespace.
+#[derive(Clone)]
+pub struct Namespace(Rc<RefCell<NamespaceImpl>>);
+
+impl Namespace {
+  pub fn new(external_modules: ExternalModules, max_stack_height: usize) -> Self {
+    Namespace(Rc::new(RefCell::new(NamespaceImpl {
+      modules: vec![],
+      instances: vec![],
+      external_modules,
+      max_stack_height,
+    })))
+  }
+
+  /// Register bytes of a module, which is instantiated by `open` on demand.
+  /// A module registered later by the same name replaces former one, but not its instance if opened already.
+  pub fn register_module(&self, name: &str, bytes: Vec<u8>) {
+    let mut namespace = self.0.borrow_mut();
+    namespace
+      .modules
+      .retain(|(registered, _)| registered != name);
+    namespace.modules.push((name.to_owned(), bytes));
+  }
+
+  /// Register an instance by `name`, whose exports other modules import by the name.
+  pub fn register_instance(
+    &self,
+    name: &str,
+    instance: ModuleInstance,
+  ) -> Result<Rc<RefCell<ModuleInstance>>> {
+    let exports = instance.export_module();
+    let instance = Rc::new(RefCell::new(instance));
+    let mut namespace = self.0.borrow_mut();
+    namespace
+      .external_modules
+      .register_module(Some(name.to_owned()), exports)?;
+    namespace
+      .instances
+      .retain(|(registered, _)| registered != name);
+    namespace
+      .instances
+      .push((name.to_owned(), instance.clone()));
+    Ok(instance)
+  }
+
+  /// Instance registered by `name`, which might be still running when a host function looks it up.
+  pub fn instance(&self, name: &str) -> Option<Rc<RefCell<ModuleInstance>>> {
+    self
+      .0
+      .borrow()
+      .instances
+      .iter()
+      .find(|(registered, _)| registered == name)
+      .map(|(_, instance)| instance.clone())
+  }
+
+  /// Instance registered by `name`, or instantiate the module registered by `name` and register its instance.
+  /// Start function of the module may open other modules, since the namespace isn't borrowed while it runs.
+  pub fn open(&self, name: &str) -> Result<Rc<RefCell<ModuleInstance>>> {
+    if let Some(instance) = self.instance(name) {
+      return Ok(instance);
+    }
+    let (module, external_modules, max_stack_height) = {
+      let namespace = self.0.borrow();
+      let bytes = namespace
+        .modules
+        .iter()
+        .find(|(registered, _)| registered == name)
+        .map(|(_, bytes)| bytes)
+        .ok_or_else(|| WasmError::Trap(Trap::UnknownModule(Some(name.to_owned()))))?;
+      (
+        decode_module(bytes),
+        namespace.external_modules.clone(),
+        namespace.max_stack_height,
+      )
+    };
+    let instance = instantiate_module(init_store(), module, external_modules, max_stack_height)?;
+    self.register_instance(name, instance)
+  }
+
+  /// Append functions exported by the instance of `name` to the table of the module registered by `table_owner`
+  /// in order of their indices, and returns the table index of the first one.
+  /// Functions are called through the registry by `name`, as same as imported ones.
+  pub fn wire_exports(&self, name: &str, table_owner: &str) -> Result<u32> {
+    let base = self.wire_exports_u64(name, table_owner)?;
+    if base > u64::from(u32::max_value()) {
+      return Err(WasmError::Trap(Trap::FailToGrow));
+    }
+    Ok(base as u32)
+  }
+
+  /// Same as `wire_exports`, but the index is of u64, e.g. for a table indexed by i64.
+  pub fn wire_exports_u64(&self, name: &str, table_owner: &str) -> Result<u64> {
+    let external_modules = self.0.borrow().external_modules.clone();
+    let find = |name: &str| {
+      external_modules
+        .get(&Some(name.to_owned()))
+        .ok_or_else(|| WasmError::Trap(Trap::UnknownModule(Some(name.to_owned()))))
+    };
+    let exports = find(name)?
+      .function_instances
+      .into_iter()
+      .filter(|function_instance| function_instance.export_name().is_some())
+      .collect::<Vec<_>>();
+    let symbol = external_modules.symbols().intern(name);
+    for function_instance in exports.iter() {
+      function_instance.set_source_symbol(symbol.clone());
+    }
+    find(table_owner)?.table_instances.append(exports)
+  }
+
+  /// Registry where instances of the namespace are registered.
+  pub fn external_modules(&self) -> ExternalModules {
+    self.0.borrow().external_modules.clone()
+  }
+
+  /// Reference which doesn't keep the namespace alive, e.g. for host functions capturing it,
+  /// since instances importing such functions are owned by the namespace.
+  pub fn downgrade(&self) -> WeakNamespace {
+    WeakNamespace(Rc::downgrade(&self.0))
+  }
+}
+
+impl fmt::Debug for Namespace {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let namespace = self.0.borrow();
+    f.debug_struct("Namespace")
+      .field(
+        "modules",
+        &namespace
+          .modules
+          .iter()
+          .map(|(name, _)| name)
+          .collect::<Vec<_>>(),
+      )
+      .field(
+        "instances",
+        &namespace
+          .instances
+          .iter()
+          .map(|(name, _)| name)
+          .collect::<Vec<_>>(),
+      )
+      .finish()
+  }
+}
+
+#[derive(Clone)]
+pub struct WeakNamespace(Weak<RefCell<NamespaceImpl>>);
+
+impl WeakNamespace {
+  pub fn upgrade(&self) -> Option<Namespace> {
+    self.0.upgrade().map(Namespace)
+  }
+}
+
+impl fmt::Debug for WeakNamespace {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_tuple("WeakNamespace").finish()
+  }
+}
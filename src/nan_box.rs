@@ -0,0 +1,111 @@
+use value::Values;
+
+// NOTE: Negative quiet NaNs whose upper 16 bits are these tags hold 32-bit values in their lower half.
+const TAG_SHIFT: u64 = 48;
+const TAG_I32: u64 = 0xfff9;
+const TAG_F32: u64 = 0xfffa;
+const PAYLOAD_MASK: u64 = 0xffff_ffff;
+
+/// Internal representation of values which entries of the operand stack use.
+/// It's chosen at compile time by "nan-boxing" feature, see `Engine::value_representation`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueRepresentation {
+  /// `Values` as it is, whose discriminant is stored beside the value.
+  TaggedEnum,
+  /// `NanBox`, which falls back to `TaggedEnum` for values it can't encode.
+  NanBoxed,
+}
+
+#[cfg(not(feature = "nan-boxing"))]
+pub(crate) const VALUE_REPRESENTATION: ValueRepresentation = ValueRepresentation::TaggedEnum;
+#[cfg(feature = "nan-boxing")]
+pub(crate) const VALUE_REPRESENTATION: ValueRepresentation = ValueRepresentation::NanBoxed;
+
+/// A value in a single f64 slot. f64 is stored as its bits, and i32 and f32 are stored in payloads of NaNs
+/// which no arithmetic of f64 produces, so that float-heavy workloads don't pay for decoding of tags.
+///
+/// i64 and f64 NaNs whose bits collide with the tags don't fit in the slot, so `encode` returns None for them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NanBox(u64);
+
+impl NanBox {
+  pub fn encode(value: &Values) -> Option<Self> {
+    match value {
+      Values::I32(n) => Some(NanBox::tagged(TAG_I32, u64::from(*n as u32))),
+      Values::F32(n) => Some(NanBox::tagged(TAG_F32, u64::from(n.to_bits()))),
+      Values::F64(n) => {
+        let bits = n.to_bits();
+        match bits >> TAG_SHIFT {
+          TAG_I32 | TAG_F32 => None,
+          _ => Some(NanBox(bits)),
+        }
+      }
+      Values::I64(_) => None,
+    }
+  }
+
+  pub fn decode(self) -> Values {
+    let payload = self.0 & PAYLOAD_MASK;
+    match self.0 >> TAG_SHIFT {
+      TAG_I32 => Values::I32(payload as u32 as i32),
+      TAG_F32 => Values::from_f32_bits(payload as u32),
+      _ => Values::from_f64_bits(self.0),
+    }
+  }
+
+  pub fn to_bits(self) -> u64 {
+    self.0
+  }
+
+  fn tagged(tag: u64, payload: u64) -> Self {
+    NanBox(tag << TAG_SHIFT | payload)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use engine::{Engine, EngineLimits};
+
+  #[test]
+  fn nan_box_round_trip() {
+    let values = vec![
+      Values::I32(-1),
+      Values::I32(0),
+      Values::F32(-0.0),
+      Values::from_f32_bits(0x7fa0_0001),
+      Values::F64(1.5),
+      Values::F64(-0.0),
+      Values::from_f64_bits(0xfff8_0000_0000_0000),
+      Values::from_f64_bits(0x7ff4_0000_0000_0001),
+    ];
+    for value in values {
+      let decoded = NanBox::encode(&value).unwrap().decode();
+      assert!(decoded.bits_eq(&value), "{:?} {:?}", decoded, value);
+    }
+  }
+
+  #[test]
+  fn nan_box_reject_unboxable_values() {
+    assert_eq!(NanBox::encode(&Values::I64(1)), None);
+    assert_eq!(
+      NanBox::encode(&Values::from_f64_bits(0xfff9_0000_0000_0001)),
+      None
+    );
+    assert_eq!(
+      NanBox::encode(&Values::from_f64_bits(0xfffa_ffff_ffff_ffff)),
+      None
+    );
+  }
+
+  #[test]
+  fn engine_reports_value_representation() {
+    let engine = Engine::new(EngineLimits::default());
+    let expected = if cfg!(feature = "nan-boxing") {
+      ValueRepresentation::NanBoxed
+    } else {
+      ValueRepresentation::TaggedEnum
+    };
+    assert_eq!(engine.value_representation(), expected);
+  }
+}
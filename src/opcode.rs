@@ -0,0 +1,297 @@
+use value_type::ValueTypes;
+use value_type::ValueTypes::{F32, F64, I32, I64};
+
+/// Kind of immediate which follows an opcode in binary format.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Immediate {
+  BlockType,
+  LabelIndex,
+  /// Vector of label indices followed by the default label index.
+  LabelTable,
+  FunctionIndex,
+  /// Type index followed by reserved table index 0x00.
+  TypeIndex,
+  LocalIndex,
+  GlobalIndex,
+  /// Alignment and offset of memory access.
+  MemoryArgument,
+  /// Reserved memory index 0x00.
+  MemoryIndex,
+  Int32,
+  Int64,
+  Float32,
+  Float64,
+}
+
+/// Types of operands an instruction pops and results it pushes.
+#[derive(Debug, PartialEq, Clone)]
+pub enum StackEffect {
+  /// Operands from bottom to top, and results.
+  Fixed(&'static [ValueTypes], &'static [ValueTypes]),
+  /// Depends on labels, locals, globals, function types or types of operands.
+  Dynamic,
+}
+
+/// Description of an opcode in binary format.
+#[derive(Debug, PartialEq, Clone)]
+pub struct OpcodeInfo {
+  pub code: u8,
+  /// Name in text format.
+  pub mnemonic: &'static str,
+  pub immediates: &'static [Immediate],
+  pub stack_effect: StackEffect,
+}
+
+impl OpcodeInfo {
+  /// Metadata of an opcode, `None` for reserved opcodes and opcodes of unsupported proposals.
+  pub fn of(code: u8) -> Option<&'static OpcodeInfo> {
+    OPCODES
+      .binary_search_by_key(&code, |info| info.code)
+      .ok()
+      .map(|idx| &OPCODES[idx])
+  }
+}
+
+macro_rules! opcodes {
+  ($($code: expr => $mnemonic: expr, [$($immediate: ident),*], $effect: expr;)*) => {
+    /// Every opcode of MVP which the interpreter supports, ordered by code.
+    /// Superinstructions are excluded, as they never appear in binary format.
+    pub const OPCODES: &[OpcodeInfo] = &[$(
+      OpcodeInfo {
+        code: $code,
+        mnemonic: $mnemonic,
+        immediates: &[$(Immediate::$immediate),*],
+        stack_effect: $effect,
+      },
+    )*];
+  };
+}
+
+use self::StackEffect::{Dynamic, Fixed};
+
+const NONE: StackEffect = Fixed(&[], &[]);
+const I32_UNARY: StackEffect = Fixed(&[I32], &[I32]);
+const I64_UNARY: StackEffect = Fixed(&[I64], &[I64]);
+const F32_UNARY: StackEffect = Fixed(&[F32], &[F32]);
+const F64_UNARY: StackEffect = Fixed(&[F64], &[F64]);
+const I32_BINARY: StackEffect = Fixed(&[I32, I32], &[I32]);
+const I64_BINARY: StackEffect = Fixed(&[I64, I64], &[I64]);
+const F32_BINARY: StackEffect = Fixed(&[F32, F32], &[F32]);
+const F64_BINARY: StackEffect = Fixed(&[F64, F64], &[F64]);
+const I64_TEST: StackEffect = Fixed(&[I64], &[I32]);
+const I64_RELATION: StackEffect = Fixed(&[I64, I64], &[I32]);
+const F32_RELATION: StackEffect = Fixed(&[F32, F32], &[I32]);
+const F64_RELATION: StackEffect = Fixed(&[F64, F64], &[I32]);
+const LOAD_I32: StackEffect = Fixed(&[I32], &[I32]);
+const LOAD_I64: StackEffect = Fixed(&[I32], &[I64]);
+const LOAD_F32: StackEffect = Fixed(&[I32], &[F32]);
+const LOAD_F64: StackEffect = Fixed(&[I32], &[F64]);
+const STORE_I32: StackEffect = Fixed(&[I32, I32], &[]);
+const STORE_I64: StackEffect = Fixed(&[I32, I64], &[]);
+const STORE_F32: StackEffect = Fixed(&[I32, F32], &[]);
+const STORE_F64: StackEffect = Fixed(&[I32, F64], &[]);
+
+opcodes! {
+  0x00 => "unreachable", [], Dynamic;
+  0x01 => "nop", [], NONE;
+  0x02 => "block", [BlockType], Dynamic;
+  0x03 => "loop", [BlockType], Dynamic;
+  0x04 => "if", [BlockType], Dynamic;
+  0x05 => "else", [], Dynamic;
+  0x0b => "end", [], Dynamic;
+  0x0c => "br", [LabelIndex], Dynamic;
+  0x0d => "br_if", [LabelIndex], Dynamic;
+  0x0e => "br_table", [LabelTable], Dynamic;
+  0x0f => "return", [], Dynamic;
+  0x10 => "call", [FunctionIndex], Dynamic;
+  0x11 => "call_indirect", [TypeIndex], Dynamic;
+  0x1a => "drop", [], Dynamic;
+  0x1b => "select", [], Dynamic;
+  0x20 => "local.get", [LocalIndex], Dynamic;
+  0x21 => "local.set", [LocalIndex], Dynamic;
+  0x22 => "local.tee", [LocalIndex], Dynamic;
+  0x23 => "global.get", [GlobalIndex], Dynamic;
+  0x24 => "global.set", [GlobalIndex], Dynamic;
+  0x28 => "i32.load", [MemoryArgument], LOAD_I32;
+  0x29 => "i64.load", [MemoryArgument], LOAD_I64;
+  0x2a => "f32.load", [MemoryArgument], LOAD_F32;
+  0x2b => "f64.load", [MemoryArgument], LOAD_F64;
+  0x2c => "i32.load8_s", [MemoryArgument], LOAD_I32;
+  0x2d => "i32.load8_u", [MemoryArgument], LOAD_I32;
+  0x2e => "i32.load16_s", [MemoryArgument], LOAD_I32;
+  0x2f => "i32.load16_u", [MemoryArgument], LOAD_I32;
+  0x30 => "i64.load8_s", [MemoryArgument], LOAD_I64;
+  0x31 => "i64.load8_u", [MemoryArgument], LOAD_I64;
+  0x32 => "i64.load16_s", [MemoryArgument], LOAD_I64;
+  0x33 => "i64.load16_u", [MemoryArgument], LOAD_I64;
+  0x34 => "i64.load32_s", [MemoryArgument], LOAD_I64;
+  0x35 => "i64.load32_u", [MemoryArgument], LOAD_I64;
+  0x36 => "i32.store", [MemoryArgument], STORE_I32;
+  0x37 => "i64.store", [MemoryArgument], STORE_I64;
+  0x38 => "f32.store", [MemoryArgument], STORE_F32;
+  0x39 => "f64.store", [MemoryArgument], STORE_F64;
+  0x3a => "i32.store8", [MemoryArgument], STORE_I32;
+  0x3b => "i32.store16", [MemoryArgument], STORE_I32;
+  0x3c => "i64.store8", [MemoryArgument], STORE_I64;
+  0x3d => "i64.store16", [MemoryArgument], STORE_I64;
+  0x3e => "i64.store32", [MemoryArgument], STORE_I64;
+  0x3f => "memory.size", [MemoryIndex], Fixed(&[], &[I32]);
+  0x40 => "memory.grow", [MemoryIndex], I32_UNARY;
+  0x41 => "i32.const", [Int32], Fixed(&[], &[I32]);
+  0x42 => "i64.const", [Int64], Fixed(&[], &[I64]);
+  0x43 => "f32.const", [Float32], Fixed(&[], &[F32]);
+  0x44 => "f64.const", [Float64], Fixed(&[], &[F64]);
+  0x45 => "i32.eqz", [], I32_UNARY;
+  0x46 => "i32.eq", [], I32_BINARY;
+  0x47 => "i32.ne", [], I32_BINARY;
+  0x48 => "i32.lt_s", [], I32_BINARY;
+  0x49 => "i32.lt_u", [], I32_BINARY;
+  0x4a => "i32.gt_s", [], I32_BINARY;
+  0x4b => "i32.gt_u", [], I32_BINARY;
+  0x4c => "i32.le_s", [], I32_BINARY;
+  0x4d => "i32.le_u", [], I32_BINARY;
+  0x4e => "i32.ge_s", [], I32_BINARY;
+  0x4f => "i32.ge_u", [], I32_BINARY;
+  0x50 => "i64.eqz", [], I64_TEST;
+  0x51 => "i64.eq", [], I64_RELATION;
+  0x52 => "i64.ne", [], I64_RELATION;
+  0x53 => "i64.lt_s", [], I64_RELATION;
+  0x54 => "i64.lt_u", [], I64_RELATION;
+  0x55 => "i64.gt_s", [], I64_RELATION;
+  0x56 => "i64.gt_u", [], I64_RELATION;
+  0x57 => "i64.le_s", [], I64_RELATION;
+  0x58 => "i64.le_u", [], I64_RELATION;
+  0x59 => "i64.ge_s", [], I64_RELATION;
+  0x5a => "i64.ge_u", [], I64_RELATION;
+  0x5b => "f32.eq", [], F32_RELATION;
+  0x5c => "f32.ne", [], F32_RELATION;
+  0x5d => "f32.lt", [], F32_RELATION;
+  0x5e => "f32.gt", [], F32_RELATION;
+  0x5f => "f32.le", [], F32_RELATION;
+  0x60 => "f32.ge", [], F32_RELATION;
+  0x61 => "f64.eq", [], F64_RELATION;
+  0x62 => "f64.ne", [], F64_RELATION;
+  0x63 => "f64.lt", [], F64_RELATION;
+  0x64 => "f64.gt", [], F64_RELATION;
+  0x65 => "f64.le", [], F64_RELATION;
+  0x66 => "f64.ge", [], F64_RELATION;
+  0x67 => "i32.clz", [], I32_UNARY;
+  0x68 => "i32.ctz", [], I32_UNARY;
+  0x69 => "i32.popcnt", [], I32_UNARY;
+  0x6a => "i32.add", [], I32_BINARY;
+  0x6b => "i32.sub", [], I32_BINARY;
+  0x6c => "i32.mul", [], I32_BINARY;
+  0x6d => "i32.div_s", [], I32_BINARY;
+  0x6e => "i32.div_u", [], I32_BINARY;
+  0x6f => "i32.rem_s", [], I32_BINARY;
+  0x70 => "i32.rem_u", [], I32_BINARY;
+  0x71 => "i32.and", [], I32_BINARY;
+  0x72 => "i32.or", [], I32_BINARY;
+  0x73 => "i32.xor", [], I32_BINARY;
+  0x74 => "i32.shl", [], I32_BINARY;
+  0x75 => "i32.shr_s", [], I32_BINARY;
+  0x76 => "i32.shr_u", [], I32_BINARY;
+  0x77 => "i32.rotl", [], I32_BINARY;
+  0x78 => "i32.rotr", [], I32_BINARY;
+  0x79 => "i64.clz", [], I64_UNARY;
+  0x7a => "i64.ctz", [], I64_UNARY;
+  0x7b => "i64.popcnt", [], I64_UNARY;
+  0x7c => "i64.add", [], I64_BINARY;
+  0x7d => "i64.sub", [], I64_BINARY;
+  0x7e => "i64.mul", [], I64_BINARY;
+  0x7f => "i64.div_s", [], I64_BINARY;
+  0x80 => "i64.div_u", [], I64_BINARY;
+  0x81 => "i64.rem_s", [], I64_BINARY;
+  0x82 => "i64.rem_u", [], I64_BINARY;
+  0x83 => "i64.and", [], I64_BINARY;
+  0x84 => "i64.or", [], I64_BINARY;
+  0x85 => "i64.xor", [], I64_BINARY;
+  0x86 => "i64.shl", [], I64_BINARY;
+  0x87 => "i64.shr_s", [], I64_BINARY;
+  0x88 => "i64.shr_u", [], I64_BINARY;
+  0x89 => "i64.rotl", [], I64_BINARY;
+  0x8a => "i64.rotr", [], I64_BINARY;
+  0x8b => "f32.abs", [], F32_UNARY;
+  0x8c => "f32.neg", [], F32_UNARY;
+  0x8d => "f32.ceil", [], F32_UNARY;
+  0x8e => "f32.floor", [], F32_UNARY;
+  0x8f => "f32.trunc", [], F32_UNARY;
+  0x90 => "f32.nearest", [], F32_UNARY;
+  0x91 => "f32.sqrt", [], F32_UNARY;
+  0x92 => "f32.add", [], F32_BINARY;
+  0x93 => "f32.sub", [], F32_BINARY;
+  0x94 => "f32.mul", [], F32_BINARY;
+  0x95 => "f32.div", [], F32_BINARY;
+  0x96 => "f32.min", [], F32_BINARY;
+  0x97 => "f32.max", [], F32_BINARY;
+  0x98 => "f32.copysign", [], F32_BINARY;
+  0x99 => "f64.abs", [], F64_UNARY;
+  0x9a => "f64.neg", [], F64_UNARY;
+  0x9b => "f64.ceil", [], F64_UNARY;
+  0x9c => "f64.floor", [], F64_UNARY;
+  0x9d => "f64.trunc", [], F64_UNARY;
+  0x9e => "f64.nearest", [], F64_UNARY;
+  0x9f => "f64.sqrt", [], F64_UNARY;
+  0xa0 => "f64.add", [], F64_BINARY;
+  0xa1 => "f64.sub", [], F64_BINARY;
+  0xa2 => "f64.mul", [], F64_BINARY;
+  0xa3 => "f64.div", [], F64_BINARY;
+  0xa4 => "f64.min", [], F64_BINARY;
+  0xa5 => "f64.max", [], F64_BINARY;
+  0xa6 => "f64.copysign", [], F64_BINARY;
+  0xa7 => "i32.wrap_i64", [], Fixed(&[I64], &[I32]);
+  0xa8 => "i32.trunc_f32_s", [], Fixed(&[F32], &[I32]);
+  0xa9 => "i32.trunc_f32_u", [], Fixed(&[F32], &[I32]);
+  0xaa => "i32.trunc_f64_s", [], Fixed(&[F64], &[I32]);
+  0xab => "i32.trunc_f64_u", [], Fixed(&[F64], &[I32]);
+  0xac => "i64.extend_i32_s", [], Fixed(&[I32], &[I64]);
+  0xad => "i64.extend_i32_u", [], Fixed(&[I32], &[I64]);
+  0xae => "i64.trunc_f32_s", [], Fixed(&[F32], &[I64]);
+  0xaf => "i64.trunc_f32_u", [], Fixed(&[F32], &[I64]);
+  0xb0 => "i64.trunc_f64_s", [], Fixed(&[F64], &[I64]);
+  0xb1 => "i64.trunc_f64_u", [], Fixed(&[F64], &[I64]);
+  0xb2 => "f32.convert_i32_s", [], Fixed(&[I32], &[F32]);
+  0xb3 => "f32.convert_i32_u", [], Fixed(&[I32], &[F32]);
+  0xb4 => "f32.convert_i64_s", [], Fixed(&[I64], &[F32]);
+  0xb5 => "f32.convert_i64_u", [], Fixed(&[I64], &[F32]);
+  0xb6 => "f32.demote_f64", [], Fixed(&[F64], &[F32]);
+  0xb7 => "f64.convert_i32_s", [], Fixed(&[I32], &[F64]);
+  0xb8 => "f64.convert_i32_u", [], Fixed(&[I32], &[F64]);
+  0xb9 => "f64.convert_i64_s", [], Fixed(&[I64], &[F64]);
+  0xba => "f64.convert_i64_u", [], Fixed(&[I64], &[F64]);
+  0xbb => "f64.promote_f32", [], Fixed(&[F32], &[F64]);
+  0xbc => "i32.reinterpret_f32", [], Fixed(&[F32], &[I32]);
+  0xbd => "i64.reinterpret_f64", [], Fixed(&[F64], &[I64]);
+  0xbe => "f32.reinterpret_i32", [], Fixed(&[I32], &[F32]);
+  0xbf => "f64.reinterpret_i64", [], Fixed(&[I64], &[F64]);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use isa::Isa;
+
+  #[test]
+  fn describe_every_opcode_in_order() {
+    assert!(OPCODES.windows(2).all(|pair| pair[0].code < pair[1].code));
+    for info in OPCODES.iter() {
+      let code: u8 = Isa::from(info.code).into();
+      assert_eq!(code, info.code);
+    }
+    assert_eq!(OPCODES.len(), 172);
+  }
+
+  #[test]
+  fn look_up_metadata_of_opcodes() {
+    let info = OpcodeInfo::of(0x6a).unwrap();
+    assert_eq!(info.mnemonic, "i32.add");
+    assert_eq!(info.stack_effect, Fixed(&[I32, I32], &[I32]));
+    assert_eq!(
+      OpcodeInfo::of(0x36).unwrap().immediates,
+      &[Immediate::MemoryArgument]
+    );
+    assert_eq!(OpcodeInfo::of(0x20).unwrap().stack_effect, Dynamic);
+    assert_eq!(OpcodeInfo::of(0x06), None);
+    assert_eq!(OpcodeInfo::of(0xe0), None);
+  }
+}
@@ -0,0 +1,42 @@
+//! Public API of wasvm.
+//!
+//! Items re-exported here follow semantic versioning,
+//! so embedders should import from `wasvm::prelude` instead of paths of internal modules.
+//!
+//! ```ignore
+//! use wasvm::prelude::*;
+//!
+//! let store = init_store();
+//! let module = decode_module(&bytes);
+//! validate_module(&module)?;
+//! let mut vm = instantiate_module(store, module, ExternalModules::default(), 65536)?;
+//! let results = vm.run("main", vec![Values::I32(42)])?;
+//! ```
+
+pub use decode::DecodeLimits;
+pub use embedder::{
+  decode_module, decode_module_with_limits, init_store, instantiate_module, validate_module,
+  validate_module_with_features, validate_module_with_limits,
+};
+pub use engine::{Engine, EngineLimits, InstanceGroup, InterruptHandle};
+pub use error::{Trap, TypeError, WasmError};
+pub use features::Features;
+pub use function::{BodyDecoding, FunctionInstance, FunctionType, InstructionFusion};
+pub use host_func::IntoHostFunc;
+pub use isa::Proposal;
+pub use linker::Linker;
+pub use memory_backend::MemoryStrategy;
+pub use metrics::Metrics;
+pub use module::{ExportSuggestion, ExternalModule, ExternalModules};
+pub use namespace::Namespace;
+pub use script::ScriptEnvironment;
+pub use spectest::{create_spectest, create_spectest_with_printer};
+pub use stack_usage::StackLimits;
+pub use store::{ExecutionLimits, Store};
+pub use table::SegmentInitialization;
+pub use trap::{RunOutcome, TrapDetails};
+pub use value::{ArgumentCoercion, ConditionMode, I64Boundary, SubnormalMode, Values, WasmValue};
+pub use value_type::ValueTypes;
+pub use vm::ModuleInstance;
+/// Former name of `ModuleInstance`, which instantiated modules are.
+pub use vm::ModuleInstance as Vm;
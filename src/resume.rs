@@ -0,0 +1,21 @@
+use alloc::vec::Vec;
+use value::Values;
+
+/// Handle of an invocation suspended by running out of its instruction budget or calling yield function.
+/// It is consumed by `ModuleInstance::resume`, and gets stale when the instance runs another invocation.
+/// A clone of it can retry resumption which is rejected by mismatched results of yield function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResumeToken {
+  pub(crate) generation: u64,
+  pub(crate) count_of_returns: u32,
+}
+
+/// Result of an invocation which can be suspended.
+#[derive(Debug, PartialEq)]
+pub enum BoundedRun {
+  Finished(Vec<Values>),
+  /// Instruction budget ran out.
+  Yielded(ResumeToken),
+  /// Guest called yield function with the values.
+  Suspended(Vec<Values>, ResumeToken),
+}
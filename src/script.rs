@@ -0,0 +1,108 @@
+#[cfg(not(any(test, feature = "std")))]
+use alloc::prelude::*;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use embedder::{decode_module, init_store, instantiate_module};
+use error::{Result, Trap, WasmError};
+use module::ExternalModules;
+use vm::ModuleInstance;
+
+/// Modules defined by a script of the spec testsuite, which its commands refer to as the wast format does.
+///
+/// - A module becomes the latest one when it's defined, and commands without a module name refer to it.
+/// - A module defined with a name is also referred by the name, even after other modules are defined later.
+///   A module defined by the same name again replaces former one.
+/// - `register` makes exports of a module importable by a name. It affects only modules defined afterwards,
+///   since each module resolves imports by registrations at its definition.
+///   A name registered again refers to the later module.
+/// - Start functions run while modules are defined, so they run in order of definitions in the script.
+///   A module whose start function traps is not defined, and the latest module stays as it was.
+pub struct ScriptEnvironment {
+  latest: Option<Rc<RefCell<ModuleInstance>>>,
+  modules: Vec<(String, Rc<RefCell<ModuleInstance>>)>,
+  external_modules: ExternalModules,
+}
+
+impl ScriptEnvironment {
+  /// `external_modules` are importable from every module, e.g. "spectest".
+  pub fn new(external_modules: ExternalModules) -> Self {
+    ScriptEnvironment {
+      latest: None,
+      modules: vec![],
+      external_modules,
+    }
+  }
+
+  // NOTE: Instances look up modules which they import from by names while running,
+  // so each of them holds a copy of registrations at its definition.
+  fn snapshot(&self) -> Result<ExternalModules> {
+    let mut snapshot = ExternalModules::with_symbols(self.external_modules.symbols().clone());
+    for name in self.external_modules.module_names().into_iter() {
+      let module = self.external_modules.get(&name)?;
+      snapshot.register_module(name, module)?;
+    }
+    Ok(snapshot)
+  }
+
+  /// Decode and instantiate a module, then define it as the latest one and by `name` if any.
+  pub fn instantiate(
+    &mut self,
+    name: Option<&str>,
+    bytes: &[u8],
+    max_stack_height: usize,
+  ) -> Result<Rc<RefCell<ModuleInstance>>> {
+    let instance = instantiate_module(
+      init_store(),
+      decode_module(bytes),
+      self.snapshot()?,
+      max_stack_height,
+    )?;
+    Ok(self.define(name, instance))
+  }
+
+  /// Define an instance as the latest module and by `name` if any.
+  pub fn define(
+    &mut self,
+    name: Option<&str>,
+    instance: ModuleInstance,
+  ) -> Rc<RefCell<ModuleInstance>> {
+    let instance = Rc::new(RefCell::new(instance));
+    if let Some(name) = name {
+      self.modules.retain(|(defined, _)| defined != name);
+      self.modules.push((name.to_owned(), instance.clone()));
+    }
+    self.latest = Some(instance.clone());
+    instance
+  }
+
+  /// Module defined by `name`, or the latest one for None.
+  pub fn get(&self, name: Option<&str>) -> Result<Rc<RefCell<ModuleInstance>>> {
+    let instance = match name {
+      Some(name) => self
+        .modules
+        .iter()
+        .find(|(defined, _)| defined == name)
+        .map(|(_, instance)| instance),
+      None => self.latest.as_ref(),
+    };
+    instance
+      .cloned()
+      .ok_or_else(|| WasmError::Trap(Trap::UnknownModule(name.map(|name| name.to_owned()))))
+  }
+
+  /// Make exports of the module referred by `name` importable by `as_name` from modules defined afterwards.
+  pub fn register(&mut self, name: Option<&str>, as_name: &str) -> Result<()> {
+    let instance = self.get(name)?;
+    let exports = instance.borrow().export_module();
+    self
+      .external_modules
+      .register_module(Some(as_name.to_owned()), exports)
+  }
+
+  /// Copy of registrations, which a module defined next would import from.
+  pub fn external_modules(&self) -> Result<ExternalModules> {
+    self.snapshot()
+  }
+}
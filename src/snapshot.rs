@@ -0,0 +1,37 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use value::Values;
+
+/// A frame of call stack at the time of snapshot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameSnapshot {
+  /// Index of the function in function index space of the module.
+  pub function_index: Option<u32>,
+  /// Export name of the function if it has.
+  pub function_name: Option<String>,
+  /// Offset of the next instruction in flattened body, which is 0 for host functions.
+  pub ptr: u32,
+  /// Parameters followed by declared locals, ordered by local index.
+  pub locals: Vec<Values>,
+  /// Operands pushed by the frame from bottom to top, labels of blocks are excluded.
+  pub operands: Vec<Values>,
+}
+
+/// Read-only copy of call stack and operand stack, for debuggers, tracers and trap reports.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StackSnapshot {
+  /// Frames from the innermost to the outermost.
+  pub frames: Vec<FrameSnapshot>,
+}
+
+impl StackSnapshot {
+  /// Operands of all frames from bottom to top.
+  pub fn operands(&self) -> Vec<Values> {
+    self
+      .frames
+      .iter()
+      .rev()
+      .flat_map(|frame| frame.operands.iter().cloned())
+      .collect()
+  }
+}
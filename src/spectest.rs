@@ -1,56 +1,64 @@
-#[cfg(not(test))]
+#[cfg(not(any(test, feature = "std")))]
 use alloc::prelude::*;
+use alloc::rc::Rc;
 use decode::{ElementType, TableType};
 use function::{FunctionInstance, FunctionType};
 use global::{GlobalInstance, GlobalInstances, GlobalType};
 use memory::{Limit, MemoryInstance};
+use memory_backend::MemoryStrategy;
 use module::ExternalModule;
 use table::TableInstance;
 use value::Values;
-use value_type::{TYPE_F32, TYPE_F64, TYPE_I32};
+use value_type::{ValueTypes, TYPE_F32, TYPE_F64, TYPE_I32};
 
-fn host_function(_values: &[Values]) -> Vec<Values> {
-  vec![]
+fn print_values(printer: &Fn(&str), values: &[Values]) {
+  for value in values.iter() {
+    let line = match value {
+      Values::I32(v) => format!("{} : i32", v),
+      Values::I64(v) => format!("{} : i64", v),
+      Values::F32(v) => format!("{} : f32", v),
+      Values::F64(v) => format!("{} : f64", v),
+    };
+    printer(&line);
+  }
 }
 
+/// Create spectest module whose print functions discard their arguments.
 pub fn create_spectest() -> ExternalModule {
+  create_spectest_with_printer(|_: &str| {})
+}
+
+/// Create spectest module whose print functions pass each argument to `printer`
+/// formatted as `<value> : <type>`, e.g. `|line| println!("{}", line)` to write it to stdout.
+pub fn create_spectest_with_printer<P>(printer: P) -> ExternalModule
+where
+  P: Fn(&str) + 'static,
+{
+  let printer = Rc::new(printer);
+  let print_function = |name: &str, parameters: Vec<ValueTypes>| {
+    let printer = printer.clone();
+    FunctionInstance::new_host_closure(
+      Some(name.to_owned()),
+      FunctionType::new(parameters, vec![]),
+      move |values| {
+        print_values(&*printer, values);
+        vec![]
+      },
+    )
+  };
   ExternalModule::new(
     vec![
-      FunctionInstance::new_host_fn(
-        Some("print".to_owned()),
-        FunctionType::new(vec![], vec![]),
-        &host_function,
-      ),
+      print_function("print", vec![]),
       // 4
-      FunctionInstance::new_host_fn(
-        Some("print_i32".to_owned()),
-        FunctionType::new(vec![TYPE_I32], vec![]),
-        &host_function,
-      ),
+      print_function("print_i32", vec![TYPE_I32]),
       // 5
-      FunctionInstance::new_host_fn(
-        Some("print_i32_f32".to_owned()),
-        FunctionType::new(vec![TYPE_I32, TYPE_F32], vec![]),
-        &host_function,
-      ),
+      print_function("print_i32_f32", vec![TYPE_I32, TYPE_F32]),
       // 6
-      FunctionInstance::new_host_fn(
-        Some("print_f64_f64".to_owned()),
-        FunctionType::new(vec![TYPE_F64, TYPE_F64], vec![]),
-        &host_function,
-      ),
+      print_function("print_f64_f64", vec![TYPE_F64, TYPE_F64]),
       // 2
-      FunctionInstance::new_host_fn(
-        Some("print_f32".to_owned()),
-        FunctionType::new(vec![TYPE_F32], vec![]),
-        &host_function,
-      ),
+      print_function("print_f32", vec![TYPE_F32]),
       // 3
-      FunctionInstance::new_host_fn(
-        Some("print_f64".to_owned()),
-        FunctionType::new(vec![TYPE_F64], vec![]),
-        &host_function,
-      ),
+      print_function("print_f64", vec![TYPE_F64]),
     ],
     vec![],
     // MemoryInstances
@@ -59,6 +67,7 @@ pub fn create_spectest() -> ExternalModule {
       Limit::HasUpperLimit(1, 2),
       Some("memory".to_owned()),
       &GlobalInstances::empty(),
+      &MemoryStrategy::default(),
     )
     .unwrap()],
     // TableInstances
@@ -1,15 +1,18 @@
-#[cfg(not(test))]
+#[cfg(not(any(test, feature = "std")))]
 use alloc::prelude::*;
 use alloc::rc::Rc;
 use alloc::vec::Vec;
-use core::cell::{Cell, RefCell};
+use core::cell::{Cell, Ref, RefCell};
 use core::fmt;
 use error::{Result, Trap, WasmError};
-use frame::Frame;
+use frame::{ActivationRecord, Frame};
+use function::FunctionType;
 use indice::Indice;
 use label::{Label, LabelKind};
+#[cfg(feature = "nan-boxing")]
+use nan_box::NanBox;
 use value::Values;
-use value_type::ValueTypes;
+use value_type::{ValueTypes, TYPE_UNIT};
 
 #[derive(PartialEq)]
 enum StackEntryImpl {
@@ -19,15 +22,27 @@ enum StackEntryImpl {
 }
 
 #[derive(PartialEq, Clone)]
-pub struct StackEntry(Rc<StackEntryImpl>);
+enum Slot {
+  // NOTE: NaN-boxed values are held by slots themselves, so that pushing them doesn't allocate.
+  #[cfg(feature = "nan-boxing")]
+  Boxed(NanBox),
+  Shared(Rc<StackEntryImpl>),
+}
+
+#[derive(PartialEq, Clone)]
+pub struct StackEntry(Slot);
 
 impl fmt::Debug for StackEntry {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     use self::StackEntryImpl::*;
-    let label = match *self.0 {
-      Empty => "_".to_owned(),
-      Value(ref v) => format!("{:?}", v),
-      Label(ref v) => format!("{:?}", v),
+    let label = match self.0 {
+      #[cfg(feature = "nan-boxing")]
+      Slot::Boxed(v) => format!("{:?}", v.decode()),
+      Slot::Shared(ref entry) => match **entry {
+        Empty => "_".to_owned(),
+        Value(ref v) => format!("{:?}", v),
+        Label(ref v) => format!("{:?}", v),
+      },
     };
     write!(f, "{}", label)
   }
@@ -35,17 +50,35 @@ impl fmt::Debug for StackEntry {
 
 impl StackEntry {
   fn new(entry: StackEntryImpl) -> Self {
-    StackEntry(Rc::new(entry))
+    StackEntry(Slot::Shared(Rc::new(entry)))
+  }
+
+  fn shared(&self) -> Option<&StackEntryImpl> {
+    match self.0 {
+      #[cfg(feature = "nan-boxing")]
+      Slot::Boxed(_) => None,
+      Slot::Shared(ref entry) => Some(entry),
+    }
   }
 
   pub fn new_empty() -> Self {
     StackEntry::new(StackEntryImpl::Empty)
   }
 
+  #[cfg(not(feature = "nan-boxing"))]
   pub fn new_value(value: Values) -> Self {
     StackEntry::new(StackEntryImpl::Value(value))
   }
 
+  // NOTE: Values which don't fit in a NaN-boxed slot are stored as they are.
+  #[cfg(feature = "nan-boxing")]
+  pub fn new_value(value: Values) -> Self {
+    match NanBox::encode(&value) {
+      Some(boxed) => StackEntry(Slot::Boxed(boxed)),
+      None => StackEntry::new(StackEntryImpl::Value(value)),
+    }
+  }
+
   pub fn new_label(
     continuation: u32,
     return_type: ValueTypes,
@@ -58,21 +91,49 @@ impl StackEntry {
     }))
   }
 
-  fn is_label(&self) -> bool {
+  fn value(&self) -> Option<Values> {
     use self::StackEntryImpl::*;
-    match *self.0 {
-      Label(_) => true,
-      _ => false,
+    match self.0 {
+      #[cfg(feature = "nan-boxing")]
+      Slot::Boxed(v) => Some(v.decode()),
+      Slot::Shared(ref entry) => match **entry {
+        Value(ref v) => Some(v.to_owned()),
+        _ => None,
+      },
     }
   }
+
+  fn label(&self) -> Option<&Label> {
+    use self::StackEntryImpl::*;
+    match self.shared() {
+      Some(Label(ref label)) => Some(label),
+      _ => None,
+    }
+  }
+
+  fn is_value(&self) -> bool {
+    use self::StackEntryImpl::*;
+    match self.0 {
+      #[cfg(feature = "nan-boxing")]
+      Slot::Boxed(_) => true,
+      Slot::Shared(ref entry) => match **entry {
+        Value(_) => true,
+        _ => false,
+      },
+    }
+  }
+
+  fn is_label(&self) -> bool {
+    self.label().is_some()
+  }
 }
 
 macro_rules! impl_pop {
   ($name: ident, $name_ext: ident, $path: path, $ret: ty, $error_decription: expr) => {
     pub fn $name(&self) -> Result<$ret> {
       let value = self.pop()?;
-      match *value.0 {
-        $path(ref v) => Ok(v.to_owned()),
+      match value.shared() {
+        Some($path(ref v)) => Ok(v.to_owned()),
         _ => {
           self.push(value.to_owned())?;
           Err(WasmError::Trap(Trap::Notfound))
@@ -145,22 +206,92 @@ impl Stack {
     self.stack_ptr.get()
   }
 
-  pub(crate) fn frame_ptr(&self) -> usize {
-    self.frame_ptr.get()
+  /// Types of entries on top of the stack ordered from bottom to top, None for labels.
+  #[cfg(feature = "checked-interp")]
+  pub(crate) fn types_on_top(&self, count: usize) -> Vec<Option<ValueTypes>> {
+    let stack_ptr = self.stack_ptr();
+    let start = stack_ptr.saturating_sub(count);
+    self.operand_stack.borrow()[start..stack_ptr]
+      .iter()
+      .map(|entry| entry.value().as_ref().map(Values::value_type))
+      .collect()
   }
 
-  pub fn get(&self, ptr: usize) -> Option<StackEntry> {
-    self.operand_stack.borrow().get(ptr).cloned()
+  pub(crate) fn frame_ptr(&self) -> usize {
+    self.frame_ptr.get()
   }
 
-  pub fn set(&self, ptr: usize, entry: StackEntry) -> Result<()> {
+  // NOTE: Entries at or above stack pointer are not pushed yet, or already popped.
+  fn check_ptr(&self, ptr: usize) -> Result<()> {
     if ptr >= self.stack_size {
       return Err(WasmError::Trap(Trap::StackOverflow));
     }
-    self.operand_stack.borrow_mut()[ptr] = entry;
+    if ptr >= self.stack_ptr() {
+      return Err(WasmError::Trap(Trap::StackUnderflow));
+    }
+    Ok(())
+  }
+
+  // NOTE: Accessors trap by `StackUnderflow` for an entry of another kind than expected,
+  // since values below a label or a frame are out of reach of instructions inside of it.
+
+  /// Entry of a value at `ptr`, e.g. of a local, which shares the value instead of copying it.
+  pub fn get_value(&self, ptr: usize) -> Result<StackEntry> {
+    self.check_ptr(ptr)?;
+    let entry = &self.operand_stack.borrow()[ptr];
+    if !entry.is_value() {
+      return Err(WasmError::Trap(Trap::StackUnderflow));
+    }
+    Ok(entry.clone())
+  }
+
+  /// Replace a value at `ptr` by `entry` of a value, which never overwrites labels.
+  pub fn set_value(&self, ptr: usize, entry: StackEntry) -> Result<()> {
+    self.check_ptr(ptr)?;
+    let mut operand_stack = self.operand_stack.borrow_mut();
+    if !operand_stack[ptr].is_value() || !entry.is_value() {
+      return Err(WasmError::Trap(Trap::StackUnderflow));
+    }
+    operand_stack[ptr] = entry;
+    Ok(())
+  }
+
+  /// Flush a subnormal float on top to zero in place, other values are left untouched.
+  #[cfg(feature = "float")]
+  pub(crate) fn flush_subnormal_on_top(&self) -> Result<()> {
+    let ptr = self
+      .stack_ptr()
+      .checked_sub(1)
+      .ok_or(Trap::StackUnderflow)?;
+    let mut operand_stack = self.operand_stack.borrow_mut();
+    let flushed = match operand_stack[ptr].value() {
+      Some(ref value) if !value.is_subnormal() => return Ok(()),
+      Some(value) => value.flush_subnormal(),
+      None => return Err(WasmError::Trap(Trap::StackUnderflow)),
+    };
+    operand_stack[ptr] = StackEntry::new_value(flushed);
     Ok(())
   }
 
+  /// Label at `ptr`.
+  pub fn get_label(&self, ptr: usize) -> Result<Label> {
+    self.check_ptr(ptr)?;
+    match self.operand_stack.borrow()[ptr].label() {
+      Some(label) => Ok(label.to_owned()),
+      None => Err(WasmError::Trap(Trap::StackUnderflow)),
+    }
+  }
+
+  /// Frame at `depth` of the call stack, where the innermost frame is at 0.
+  pub(crate) fn get_frame(&self, depth: usize) -> Result<Ref<Frame>> {
+    let calls = self.call_stack.borrow();
+    if depth >= calls.len() {
+      return Err(WasmError::Trap(Trap::StackUnderflow));
+    }
+    let idx = calls.len() - 1 - depth;
+    Ok(Ref::map(calls, |calls| &calls[idx]))
+  }
+
   pub fn push(&self, entry: StackEntry) -> Result<()> {
     if self.stack_ptr() >= self.stack_size {
       return Err(WasmError::Trap(Trap::StackOverflow));
@@ -212,6 +343,23 @@ impl Stack {
     calls.pop()
   }
 
+  pub(crate) fn frames(&self) -> Ref<Vec<Frame>> {
+    self.call_stack.borrow()
+  }
+
+  /// Values in range of the operand stack, labels and empty entries are skipped.
+  pub(crate) fn values_between(&self, start: usize, end: usize) -> Vec<Values> {
+    let operands = self.operand_stack.borrow();
+    let end = end.min(operands.len());
+    if start >= end {
+      return vec![];
+    }
+    operands[start..end]
+      .iter()
+      .filter_map(|entry| entry.value())
+      .collect()
+  }
+
   pub fn call_stack_is_empty(&self) -> bool {
     let calls = self.call_stack.borrow();
     calls.is_empty()
@@ -242,13 +390,33 @@ impl Stack {
     }
   }
 
-  impl_pop!(
-    pop_value,
-    pop_value_ext,
-    StackEntryImpl::Value,
-    Values,
-    "Expect to pop up Value, but got None"
-  );
+  pub fn pop_value(&self) -> Result<Values> {
+    let entry = self.pop()?;
+    match entry.value() {
+      Some(value) => Ok(value),
+      None => {
+        self.push(entry)?;
+        Err(WasmError::Trap(Trap::Notfound))
+      }
+    }
+  }
+
+  /// Same as `pop_value`, but returns the entry to share the value instead of copying it.
+  pub fn pop_value_entry(&self) -> Result<StackEntry> {
+    let entry = self.pop()?;
+    if !entry.is_value() {
+      self.push(entry)?;
+      return Err(WasmError::Trap(Trap::StackUnderflow));
+    }
+    Ok(entry)
+  }
+
+  pub fn pop_value_ext(&self) -> Values {
+    self
+      .pop_value()
+      .expect("Expect to pop up Value, but got None")
+  }
+
   impl_pop!(
     pop_label,
     pop_label_ext,
@@ -265,50 +433,94 @@ impl Stack {
     Ok(entry_buffer)
   }
 
-  pub fn jump_to_label(&self, depth_of_label: &Indice) -> Result<u32> /* point to continue */ {
-    let mut buf_values = vec![];
-    let mut label = None;
-    for _ in 0..=depth_of_label.to_u32() {
-      let mut bufs = self.pop_until_label()?;
-      buf_values.append(&mut bufs);
-      label = Some(self.pop_label_ext());
-    }
-    let continuation = match label {
-      Some(Label {
-        return_type: ValueTypes::Unit,
-        continuation,
-        ..
-      }) => continuation,
-      Some(Label {
-        continuation,
-        source_instruction,
-        ..
-      }) => {
-        match source_instruction {
-          LabelKind::Loop => {
-            self.push_entries(&mut buf_values)?;
-          }
-          _ => {
-            // FIXME: Prefer to pop and push with count of return_types.
-            let return_val = buf_values
-              .first()
-              .expect("At least one return value should exists.")
-              .to_owned();
-            self.push(return_val)?;
-          }
-        };
-        continuation
+  // Pointer of the label at `depth`, where the innermost label is at 0.
+  fn label_ptr(&self, depth: u32) -> Result<usize> {
+    let operand_stack = self.operand_stack.borrow();
+    let mut depth = depth;
+    for ptr in (0..self.stack_ptr()).rev() {
+      if !operand_stack[ptr].is_label() {
+        continue;
       }
-      x => unreachable!("At least one label should exists.\n{:?}", x),
-    };
+      if depth == 0 {
+        return Ok(ptr);
+      }
+      depth -= 1;
+    }
+    Err(WasmError::Trap(Trap::StackUnderflow))
+  }
+
+  /// Branch to the label at `depth_of_label`, and returns the point to continue.
+  /// Values as many as arity of the label are carried from the top of the stack to the height of the label,
+  /// so that entries between them, including the label and inner ones, are discarded.
+  pub fn jump_to_label(&self, depth_of_label: &Indice) -> Result<u32> {
+    let label_ptr = self.label_ptr(depth_of_label.to_u32())?;
+    let label = self.get_label(label_ptr)?;
+    let (arity, continuation) = (label.arity(), label.continuation);
+    let stack_ptr = self.stack_ptr();
+    if stack_ptr < label_ptr + 1 + arity {
+      return Err(WasmError::Trap(Trap::StackUnderflow));
+    }
+    let start_of_values = stack_ptr - arity;
+    let mut operand_stack = self.operand_stack.borrow_mut();
+    for offset in 0..arity {
+      operand_stack.swap(label_ptr + offset, start_of_values + offset);
+    }
+    self.stack_ptr.set(label_ptr + arity);
     Ok(continuation)
   }
 
   impl_pop_value_ext!(pop_value_ext_i32, Values::I32, i32);
 
-  pub fn update_frame_ptr(&self, frame: &Frame) {
-    self.stack_ptr.set(self.frame_ptr());
-    self.frame_ptr.set(frame.prev_return_ptr);
+  /// Pop arguments as many as parameters of callee.
+  /// Popped entries are ordered from the last parameter to the first one.
+  pub(crate) fn pop_arguments(&self, function_type: &FunctionType) -> Result<Vec<StackEntry>> {
+    let mut arguments = vec![];
+    for parameter in function_type.parameters().iter().rev() {
+      let argument = self.pop_value()?;
+      if cfg!(debug_assertions) && &argument.value_type() != parameter {
+        return Err(WasmError::Trap(Trap::TypeMismatch));
+      }
+      arguments.push(StackEntry::new_value(argument));
+    }
+    Ok(arguments)
+  }
+
+  pub(crate) fn activation_record(&self) -> ActivationRecord {
+    ActivationRecord {
+      base_ptr: self.stack_ptr(),
+      caller_frame_ptr: self.frame_ptr(),
+    }
+  }
+
+  /// Lay out locals of callee and its frame label on top of the stack.
+  pub(crate) fn enter_frame(&self, frame: &Frame) -> Result<()> {
+    let return_type = frame
+      .get_return_type()
+      .first()
+      .map_or(TYPE_UNIT, |x| x.to_owned());
+    let label = StackEntry::new_label(frame.last_ptr, return_type, LabelKind::Frame);
+    // NOTE: Function validated with limits overflows on its call rather than in the middle of its body.
+    if let Some(stack_usage) = frame.function_instance.stack_usage() {
+      if self.stack_ptr() + stack_usage.count_of_entries() > self.stack_size {
+        return Err(WasmError::Trap(Trap::StackOverflow));
+      }
+    }
+    self.frame_ptr.set(frame.activation.base_ptr);
+    frame.mark_entered();
+    self.push_entries(&mut frame.get_local_variables())?;
+    self.push(label)
+  }
+
+  /// Discard operands of callee and restore pointers of caller,
+  /// then results of callee are pushed back.
+  pub(crate) fn leave_frame(&self, frame: &Frame) -> Result<()> {
+    let mut returns = vec![];
+    for _ in 0..frame.get_return_count() {
+      returns.push(StackEntry::new_value(self.pop_value()?));
+    }
+    self.stack_ptr.set(frame.activation.base_ptr);
+    self.frame_ptr.set(frame.activation.caller_frame_ptr);
+    self.push_entries(&mut returns)
   }
 }
 
@@ -335,7 +547,9 @@ mod tests {
 
   #[test]
   fn stack_entry_size() {
-    assert_eq!(core::mem::size_of::<StackEntry>(), 8);
+    // NOTE: Slots by "nan-boxing" hold values themselves beside their tags.
+    let expected = if cfg!(feature = "nan-boxing") { 16 } else { 8 };
+    assert_eq!(core::mem::size_of::<StackEntry>(), expected);
   }
 
   #[test]
@@ -348,8 +562,84 @@ mod tests {
   #[test]
   fn stack_set() {
     let stack = Stack::new(4);
+    stack.push(StackEntry::new_value(Values::I32(0))).unwrap();
+    stack
+      .push(StackEntry::new_label(0, TYPE_UNIT, LabelKind::Block))
+      .unwrap();
+    stack.push(StackEntry::new_value(Values::I32(1))).unwrap();
     let value = StackEntry::new_value(Values::I32(2));
-    stack.set(2, value).unwrap();
-    assert_eq!(stack.get(2).unwrap(), StackEntry::new_value(Values::I32(2)));
+    stack.set_value(2, value.clone()).unwrap();
+    assert_eq!(stack.get_value(2), Ok(value.clone()));
+    assert_eq!(stack.get_label(1).map(|label| label.continuation), Ok(0));
+
+    assert_eq!(
+      stack.set_value(1, value.clone()),
+      Err(WasmError::Trap(Trap::StackUnderflow))
+    );
+    assert_eq!(
+      stack.get_value(1),
+      Err(WasmError::Trap(Trap::StackUnderflow))
+    );
+    assert_eq!(
+      stack.get_label(2),
+      Err(WasmError::Trap(Trap::StackUnderflow))
+    );
+    assert_eq!(
+      stack.set_value(3, value),
+      Err(WasmError::Trap(Trap::StackUnderflow))
+    );
+    assert_eq!(
+      stack.get_value(4),
+      Err(WasmError::Trap(Trap::StackOverflow))
+    );
+    assert_eq!(
+      stack.get_frame(0).map(|_| ()),
+      Err(WasmError::Trap(Trap::StackUnderflow))
+    );
+  }
+
+  #[test]
+  fn stack_enter_and_leave_frame() {
+    use function::{FunctionInstance, FunctionType};
+    let stack = Stack::new(16);
+    stack.push(StackEntry::new_value(Values::I32(0))).unwrap();
+    let function_instance = FunctionInstance::new(
+      None,
+      FunctionType::new(
+        vec![ValueTypes::I32, ValueTypes::I32],
+        vec![ValueTypes::I32],
+      ),
+      vec![ValueTypes::I64],
+      vec![],
+    );
+    let mut arguments = vec![
+      StackEntry::new_value(Values::I32(2)),
+      StackEntry::new_value(Values::I32(1)),
+    ];
+    let frame = Frame::new(stack.activation_record(), function_instance, &mut arguments).unwrap();
+    stack.enter_frame(&frame).unwrap();
+    assert_eq!(stack.frame_ptr(), 1);
+    assert_eq!(frame.arity(), 2);
+    assert_eq!(frame.count_of_locals(), 3);
+    assert_eq!(frame.get_return_count(), 1);
+    assert_eq!(
+      stack.get_value(1),
+      Ok(StackEntry::new_value(Values::I32(1)))
+    );
+    assert_eq!(
+      stack.get_value(2),
+      Ok(StackEntry::new_value(Values::I32(2)))
+    );
+    assert_eq!(
+      stack.get_value(3),
+      Ok(StackEntry::new_value(Values::I64(0)))
+    );
+
+    stack.push(StackEntry::new_value(Values::I32(3))).unwrap();
+    stack.leave_frame(&frame).unwrap();
+    assert_eq!(stack.frame_ptr(), 0);
+    assert_eq!(stack.stack_ptr(), 2);
+    assert_eq!(stack.pop_value().unwrap(), Values::I32(3));
+    assert_eq!(stack.pop_value().unwrap(), Values::I32(0));
   }
 }
@@ -0,0 +1,34 @@
+use error::{Result, TypeError, WasmError};
+
+/// Stack space a function occupies while it runs, which is computed by validation.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct StackUsage {
+  /// Maximum count of operands and labels of blocks at the same time.
+  pub max_depth: usize,
+  /// Count of parameters and declared locals.
+  pub count_of_locals: usize,
+}
+
+impl StackUsage {
+  /// Count of stack entries a call reserves, which includes its frame label.
+  pub fn count_of_entries(&self) -> usize {
+    self.count_of_locals + 1 + self.max_depth
+  }
+}
+
+/// Limits of stack usage per function, None means unlimited.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StackLimits {
+  pub max_depth: Option<usize>,
+  pub max_locals: Option<usize>,
+}
+
+impl StackLimits {
+  pub(crate) fn check(&self, usage: &StackUsage) -> Result<()> {
+    let exceeds = |max: Option<usize>, count: usize| max.map_or(false, |max| count > max);
+    if exceeds(self.max_depth, usage.max_depth) || exceeds(self.max_locals, usage.count_of_locals) {
+      return Err(WasmError::TypeError(TypeError::StackLimitExceeded));
+    }
+    Ok(())
+  }
+}
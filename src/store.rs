@@ -1,12 +1,32 @@
 use alloc::vec::Vec;
 use core::default::Default;
-use error::Result;
-use function::{FunctionInstance, FunctionType};
+use engine::Engine;
+use error::{Result, Trap, WasmError};
+use function::{BodyDecoding, FunctionInstance, FunctionType, InstructionFusion};
 use global::GlobalInstances;
 use indice::Indice;
 use memory::MemoryInstances;
-use table::{TableInstance, TableInstances};
-use value::Values;
+use memory_backend::MemoryStrategy;
+use module::ExportSuggestion;
+use table::{SegmentInitialization, TableInstance, TableInstances};
+use value::{ArgumentCoercion, ConditionMode, I64Boundary, SubnormalMode, Values};
+
+/// Caps of work a single instruction does, so that latency of each instruction is bounded
+/// even for valid but pathological modules. None means unlimited, and exceeding traps by `ExecutionLimitExceeded`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExecutionLimits {
+  /// Count of labels of a br_table, which includes its default label.
+  pub max_br_table_targets: Option<u32>,
+}
+
+impl ExecutionLimits {
+  pub(crate) fn check_br_table(&self, count_of_targets: u32) -> Result<()> {
+    match self.max_br_table_targets {
+      Some(max) if count_of_targets > max => Err(WasmError::Trap(Trap::ExecutionLimitExceeded)),
+      _ => Ok(()),
+    }
+  }
+}
 
 #[derive(Debug)]
 pub struct Store {
@@ -15,6 +35,18 @@ pub struct Store {
   pub memory_instances: MemoryInstances,
   pub table_instances: TableInstances,
   pub global_instances: GlobalInstances,
+  pub memory_strategy: MemoryStrategy,
+  pub segment_initialization: SegmentInitialization,
+  pub subnormal_mode: SubnormalMode,
+  pub condition_mode: ConditionMode,
+  pub argument_coercion: ArgumentCoercion,
+  pub i64_boundary: I64Boundary,
+  pub body_decoding: BodyDecoding,
+  pub instruction_fusion: InstructionFusion,
+  pub execution_limits: ExecutionLimits,
+  pub export_suggestion: ExportSuggestion,
+  /// Engine whose limits are shared with other instances, set by `Engine::instantiate`.
+  pub engine: Option<Engine>,
 }
 
 impl Store {
@@ -31,11 +63,22 @@ impl Store {
       memory_instances,
       table_instances,
       global_instances,
+      memory_strategy: MemoryStrategy::default(),
+      segment_initialization: SegmentInitialization::default(),
+      subnormal_mode: SubnormalMode::default(),
+      condition_mode: ConditionMode::default(),
+      argument_coercion: ArgumentCoercion::default(),
+      i64_boundary: I64Boundary::default(),
+      body_decoding: BodyDecoding::default(),
+      instruction_fusion: InstructionFusion::default(),
+      execution_limits: ExecutionLimits::default(),
+      export_suggestion: ExportSuggestion::default(),
+      engine: None,
     }
   }
 
-  pub fn get_function_instance(&self, fn_idx: &Indice) -> Option<FunctionInstance> {
-    self.function_instances.get(fn_idx.to_usize()).cloned()
+  pub fn get_function_instance(&self, fn_idx: &Indice) -> Option<&FunctionInstance> {
+    self.function_instances.get(fn_idx.to_usize())
   }
 
   pub fn get_function_type(&self, idx: &Indice) -> Option<&FunctionType> {
@@ -59,6 +102,14 @@ impl Store {
   pub fn get_table_at(&self, idx: &Indice) -> Option<TableInstance> {
     self.table_instances.get_table_at(idx)
   }
+
+  pub fn write_memory(&self, offset: u32, bytes: &[u8]) -> Result<()> {
+    self.memory_instances.write_data(offset, bytes)
+  }
+
+  pub fn read_memory(&self, offset: u32, length: usize) -> Result<Vec<u8>> {
+    self.memory_instances.read_data(offset, length)
+  }
 }
 
 impl Default for Store {
@@ -69,6 +120,17 @@ impl Default for Store {
       memory_instances: MemoryInstances::empty(),
       table_instances: TableInstances::empty(),
       global_instances: GlobalInstances::empty(),
+      memory_strategy: MemoryStrategy::default(),
+      segment_initialization: SegmentInitialization::default(),
+      subnormal_mode: SubnormalMode::default(),
+      condition_mode: ConditionMode::default(),
+      argument_coercion: ArgumentCoercion::default(),
+      i64_boundary: I64Boundary::default(),
+      body_decoding: BodyDecoding::default(),
+      instruction_fusion: InstructionFusion::default(),
+      execution_limits: ExecutionLimits::default(),
+      export_suggestion: ExportSuggestion::default(),
+      engine: None,
     }
   }
 }
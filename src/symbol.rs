@@ -0,0 +1,124 @@
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt;
+use core::ops::Deref;
+
+/// Name interned by a `SymbolTable`, which is cloned without allocation.
+/// Symbols are compared by their addresses, so that ones of the same name interned by other tables differ.
+#[derive(Clone, Eq)]
+pub struct Symbol {
+  name: Rc<str>,
+  id: Option<usize>,
+}
+
+impl Symbol {
+  pub fn as_str(&self) -> &str {
+    &self.name
+  }
+
+  /// Sequential number in the table which interned the symbol, e.g. to index entries by symbols.
+  pub fn id(&self) -> Option<usize> {
+    self.id
+  }
+}
+
+impl PartialEq for Symbol {
+  fn eq(&self, other: &Symbol) -> bool {
+    Rc::ptr_eq(&self.name, &other.name)
+  }
+}
+
+impl Deref for Symbol {
+  type Target = str;
+
+  fn deref(&self) -> &str {
+    &self.name
+  }
+}
+
+// NOTE: A symbol not interned by any table, which has no id and is found by its name.
+impl<'a> From<&'a str> for Symbol {
+  fn from(name: &'a str) -> Self {
+    Symbol {
+      name: Rc::from(name),
+      id: None,
+    }
+  }
+}
+
+impl fmt::Debug for Symbol {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{:?}", self.as_str())
+  }
+}
+
+/// Names of modules interned while linking, which an `Engine` shares among its instances.
+/// Clones share the same table.
+#[derive(Clone, Default)]
+pub struct SymbolTable(Rc<RefCell<Vec<Symbol>>>);
+
+impl SymbolTable {
+  /// Symbol of `name`, which is added to the table at the first time.
+  /// Ids are numbered in the order of interning.
+  pub fn intern(&self, name: &str) -> Symbol {
+    let mut symbols = self.0.borrow_mut();
+    match symbols.binary_search_by(|symbol| symbol.as_str().cmp(name)) {
+      Ok(idx) => symbols[idx].clone(),
+      Err(idx) => {
+        let symbol = Symbol {
+          name: Rc::from(name),
+          id: Some(symbols.len()),
+        };
+        symbols.insert(idx, symbol.clone());
+        symbol
+      }
+    }
+  }
+
+  /// Symbol of `name` if it's interned already.
+  pub fn get(&self, name: &str) -> Option<Symbol> {
+    let symbols = self.0.borrow();
+    symbols
+      .binary_search_by(|symbol| symbol.as_str().cmp(name))
+      .ok()
+      .map(|idx| symbols[idx].clone())
+  }
+
+  pub fn len(&self) -> usize {
+    self.0.borrow().len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+}
+
+impl fmt::Debug for SymbolTable {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_list().entries(self.0.borrow().iter()).finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn intern_names_once() {
+    let symbols = SymbolTable::default();
+    let env = symbols.intern("env");
+    let spectest = symbols.intern("spectest");
+    let again = symbols.intern("env");
+    assert!(Rc::ptr_eq(&env.name, &again.name));
+    assert_eq!(symbols.len(), 2);
+    assert_eq!((env.id(), spectest.id()), (Some(0), Some(1)));
+    assert_eq!(
+      symbols.get("spectest").map(|s| s.to_string()),
+      Some("spectest".to_owned())
+    );
+    assert_eq!(symbols.get("wasi"), None);
+    assert_ne!(Symbol::from("env"), env);
+    assert_ne!(SymbolTable::default().intern("env"), env);
+  }
+}
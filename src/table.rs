@@ -1,17 +1,34 @@
-#[cfg(not(test))]
+#[cfg(not(any(test, feature = "std")))]
 use alloc::prelude::*;
 use alloc::rc::Rc;
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::cell::RefCell;
 use core::clone::Clone;
-use decode::{Element, TableType};
+use decode::{Element, TableIndexType, TableType};
 use error::{Result, Trap, WasmError};
 use function::FunctionInstance;
 use global::GlobalInstances;
 use indice::Indice;
 use isa::Isa;
-use memory::Limit;
+use value::Values;
+
+/// How active element segments are written into tables at instantiation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SegmentInitialization {
+  /// WebAssembly 1.0: Check all segments fit before writing any of them,
+  /// so a failed instantiation never modifies imported tables.
+  Atomic,
+  /// Since bulk memory operations: Write segments in order and trap at the first one which does not fit,
+  /// leaving earlier segments applied. Element segments are written before data segments.
+  Partial,
+}
+
+impl Default for SegmentInitialization {
+  fn default() -> Self {
+    SegmentInitialization::Atomic
+  }
+}
 
 #[derive(Debug, Clone)]
 pub struct TableInstance {
@@ -28,10 +45,7 @@ impl TableInstance {
     global_instances: &GlobalInstances,
     function_instances: &[FunctionInstance],
   ) -> Result<Self> {
-    let table_size = match table_type.limit {
-      Limit::NoUpperLimit(min) | Limit::HasUpperLimit(min, _) => min,
-    } as usize;
-    let mut function_elements = vec![None; table_size];
+    let mut function_elements = vec![None; table_type.min() as usize];
     for el in elements.into_iter() {
       let offset = Isa::constant_expression(&el.offset, global_instances)?;
       let mut function_addresses = el.wrap_by_option(function_instances);
@@ -54,9 +68,7 @@ impl TableInstance {
     global_instances: &GlobalInstances,
     function_instances: &[FunctionInstance],
   ) -> Result<()> {
-    let table_size = match table_type.limit {
-      Limit::NoUpperLimit(min) | Limit::HasUpperLimit(min, _) => min,
-    } as usize;
+    let table_size = table_type.min() as usize;
     for el in elements.iter() {
       let offset = Isa::constant_expression(&el.offset, global_instances)?;
       let mut function_addresses = el.wrap_by_option(function_instances);
@@ -72,11 +84,37 @@ impl TableInstance {
     self.function_elements.len()
   }
 
+  /// Count of elements, same as `len` but in the type of indices.
+  pub fn size(&self) -> u64 {
+    self.function_elements.len() as u64
+  }
+
+  pub fn table_type(&self) -> &TableType {
+    &self.table_type
+  }
+
+  /// Index which an operand of call_indirect points, traps by `TypeMismatch` unless it has the index type.
+  /// Negative i32 is regarded as unsigned, so that it's out of bounds.
+  pub(crate) fn index_of(&self, operand: &Values) -> Result<u64> {
+    match (self.table_type.index_type(), operand) {
+      (TableIndexType::I32, Values::I32(idx)) => Ok(u64::from(*idx as u32)),
+      (TableIndexType::I64, Values::I64(idx)) => Ok(*idx as u64),
+      _ => Err(WasmError::Trap(Trap::TypeMismatch)),
+    }
+  }
+
   pub fn get_function_instance(&self, idx: u32) -> Result<FunctionInstance> {
-    match self.function_elements.get(idx as usize) {
-      Some(Some(x)) => Ok(x.clone()),
-      Some(None) => Err(WasmError::Trap(Trap::UninitializedElement)),
-      None => Err(WasmError::Trap(Trap::UndefinedElement)),
+    self.get_function_instance_u64(u64::from(idx))
+  }
+
+  /// Same as `get_function_instance`, but the index is of u64, e.g. of a table indexed by i64.
+  pub fn get_function_instance_u64(&self, idx: u64) -> Result<FunctionInstance> {
+    if idx >= self.size() {
+      return Err(WasmError::Trap(Trap::UndefinedElement));
+    }
+    match &self.function_elements[idx as usize] {
+      Some(x) => Ok(x.clone()),
+      None => Err(WasmError::Trap(Trap::UninitializedElement)),
     }
   }
 }
@@ -100,10 +138,12 @@ impl TableInstances {
     }
   }
 
-  // NOTE: It represents `self.table_type > other_table_type`
-  pub fn gt_table_type(&self, other: &TableType) -> bool {
+  pub fn matches_table_type(&self, expected: &TableType) -> bool {
     match self.0.borrow().first() {
-      Some(table_instance) => &table_instance.table_type > other,
+      Some(table_instance) => table_instance
+        .table_type
+        .with_min(table_instance.len() as u32)
+        .matches(expected),
       None => false,
     }
   }
@@ -127,11 +167,54 @@ impl TableInstances {
       let offset = Isa::constant_expression(&el.offset, global_instances)?;
       let mut function_addresses = el.wrap_by_option(function_instances);
       let end = offset + function_addresses.len();
+      // NOTE: Earlier segments remain written, as partial initialization requires.
+      if end > function_elements.len() {
+        return Err(WasmError::Trap(Trap::ElementSegmentDoesNotFit));
+      }
       function_addresses.swap_with_slice(&mut function_elements[offset..end]);
     }
     Ok(())
   }
 
+  /// Append functions to the first table, and returns the index of the first one.
+  /// Traps by `FailToGrow` if the table would exceed its maximum.
+  pub(crate) fn append(&self, function_instances: Vec<FunctionInstance>) -> Result<u64> {
+    self.extend(function_instances.into_iter().map(Some).collect())
+  }
+
+  /// Append uninitialized elements to the first table as same as `append`, e.g. to reserve a region of it.
+  pub(crate) fn append_uninitialized(&self, count: u64) -> Result<u64> {
+    let count = Self::to_usize(count)?;
+    self.extend(vec![None; count])
+  }
+
+  fn to_usize(size: u64) -> Result<usize> {
+    if size > core::usize::MAX as u64 {
+      return Err(WasmError::Trap(Trap::FailToGrow));
+    }
+    Ok(size as usize)
+  }
+
+  fn extend(&self, function_elements: Vec<Option<FunctionInstance>>) -> Result<u64> {
+    let mut table_instances = self.0.borrow_mut();
+    let table_instance = table_instances.first_mut()?;
+    let base = table_instance.size();
+    let size = base.saturating_add(function_elements.len() as u64);
+    if size > table_instance.table_type.max() {
+      return Err(WasmError::Trap(Trap::FailToGrow));
+    }
+    table_instance.function_elements.extend(function_elements);
+    Ok(base)
+  }
+
+  pub(crate) fn first_size(&self) -> Option<u64> {
+    self
+      .0
+      .borrow()
+      .first()
+      .map(|table_instance| table_instance.size())
+  }
+
   pub fn validate(
     &self,
     elements: &[Element],
@@ -0,0 +1,140 @@
+use alloc::vec::Vec;
+use backtrace::BacktraceFrame;
+use error::{Trap, TypeError};
+use value::Values;
+
+impl Trap {
+  /// Canonical message of the trap, which spec tests expect as a prefix of trap message.
+  /// Traps which the reference interpreter reports have the same messages as it does.
+  pub fn message(&self) -> &'static str {
+    use self::Trap::*;
+    match self {
+      // NOTE: Reference interpreter doesn't distinguish overflow of division from one of truncation.
+      IntegerOverflow => "integer overflow",
+      DivisionByZero => "integer divide by zero",
+      InvalidConversionToInt => "invalid conversion to integer",
+      MemoryAccessOutOfBounds => "out of bounds memory access",
+      DataSegmentDoesNotFit => "data segment does not fit",
+      ElementSegmentDoesNotFit => "elements segment does not fit",
+      UndefinedElement => "undefined element",
+      UninitializedElement => "uninitialized element",
+      IndirectCallTypeMismatch => "indirect call type mismatch",
+      Unreachable => "unreachable",
+      StackOverflow => "call stack exhausted",
+      UnknownImport => "unknown import",
+      IncompatibleImportType => "incompatible import type",
+      BitshiftOverflow => "integer too large",
+      IntegerRepresentationTooLong => "integer representation too long",
+      InvalidMutability => "invalid mutability",
+      StackUnderflow => "stack underflow",
+      Notfound => "not found",
+      Undefined => "undefined",
+      TypeMismatch => "type mismatch",
+      FailToGrow => "failed to grow",
+      UnexpectedEnd => "unexpected end",
+      InvalidSectionId => "invalid section id",
+      UnexpectedSection => "junk after last section",
+      DataCountAndDataInconsistent => "data count and data section have inconsistent lengths",
+      LengthOutofBounds => "length out of bounds",
+      MagicHeaderNotDetected => "magic header not detected",
+      UnsupportedTextform => "unsupported text form",
+      FunctionAndCodeInconsitent => "function and code section have inconsistent lengths",
+      InvalidUTF8Encoding | InvalidUTF8Name { .. } => "invalid UTF-8 encoding",
+      LinearMapOverflowed => "linear map overflowed",
+      UnsupportedComponentDefinition => "unsupported component definition",
+      UnsupportedDebugInfo => "unsupported debug info",
+      UnsupportedFeature { .. } => "unsupported feature",
+      StaleResumeToken => "stale resume token",
+      UnexpectedYield => "unexpected yield",
+      CircularImport(_) => "circular import",
+      ArgumentTypeMismatch { .. } => "argument type mismatch",
+      HostError(_) => "host error",
+      ResourceLimitExceeded => "resource limit exceeded",
+      Timeout => "timeout",
+      MalformedFloatLiteral => "unknown operator",
+      ConstantOutOfRange => "constant out of range",
+      FunctionBodyTooLarge => "function body too large",
+      TooManyLocals => "too many locals",
+      NestingTooDeep => "nesting too deep",
+      TooManyBrTableTargets => "too many br_table targets",
+      MisplacedElse => "misplaced ELSE opcode",
+      OutOfRangeLocal => "out of range local",
+      UninitializedGlobal(_) => "unknown global",
+      UnsupportedFloat { .. } => "unsupported float",
+      UnknownModule(_) => "unknown module",
+      ArityMismatch { .. } => "arity mismatch",
+      I64AtBoundary => "i64 at boundary",
+      IncompatibleGlobal(_) => "incompatible global",
+      NonContiguousMemory => "non-contiguous memory",
+      ExecutionLimitExceeded => "execution limit exceeded",
+      Interrupted => "interrupted",
+      UnknownBinaryVersion => "unknown binary version",
+      ProcessExit(_) => "process exit",
+      UnknownComponentVersion => "unknown component version",
+      IllegalOpcode { .. } => "illegal opcode",
+      Unknown => "unknown",
+    }
+  }
+}
+
+impl TypeError {
+  /// Canonical message of the error, which spec tests expect as a prefix of messages of invalid modules.
+  pub fn message(&self) -> &'static str {
+    use self::TypeError::*;
+    match self {
+      NotFound => "not found",
+      MultipleTables => "multiple tables",
+      MultipleMemories => "multiple memories",
+      TypeMismatch | IndirectCallTypeMismatch => "type mismatch",
+      IncompatibleImportType => "incompatible import type",
+      InvalidResultArity => "invalid result arity",
+      InvalidAlignment => "alignment must not be larger than natural",
+      InvalidMemorySize => "memory size must be at most 65536 pages (4GiB)",
+      InvalidStartFunction => "start function",
+      UnknownLabel => "unknown label",
+      UnknownLocal => "unknown local",
+      UnknownMemory => "unknown memory",
+      UnknownFunctionType(_) => "unknown type",
+      UnknownFunction(_) => "unknown function",
+      UnknownTable(_) => "unknown table",
+      UnknownGlobal(_) => "unknown global",
+      ConstantExpressionRequired => "constant expression required",
+      DuplicateExportName(_) => "duplicate export name",
+      GlobalIsImmutable => "global is immutable",
+      DisabledFeature(_) => "unsupported feature",
+      StackLimitExceeded => "stack limit exceeded",
+      DisabledFloat => "unsupported float",
+      InvalidLimits => "size minimum must not be greater than maximum",
+    }
+  }
+}
+
+/// Machine-readable description of a trap raised by an invocation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrapDetails {
+  pub trap: Trap,
+  /// Stable code of the trap, same as `Trap::code`.
+  pub code: u32,
+  /// Canonical message of the trap, same as `Trap::message`.
+  pub message: &'static str,
+  /// Call stack at the time of the trap, innermost first.
+  pub backtrace: Vec<BacktraceFrame>,
+}
+
+impl TrapDetails {
+  pub(crate) fn new(trap: Trap, backtrace: Vec<BacktraceFrame>) -> Self {
+    TrapDetails {
+      code: trap.code(),
+      message: trap.message(),
+      trap,
+      backtrace,
+    }
+  }
+}
+
+/// Result of an invocation which finished by returning values or by trapping.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunOutcome {
+  Values(Vec<Values>),
+  Trap(TrapDetails),
+}
@@ -1,22 +1,81 @@
 use alloc::collections::VecDeque;
-#[cfg(not(test))]
+#[cfg(not(any(test, feature = "std")))]
 use alloc::prelude::*;
 use alloc::vec::Vec;
+#[cfg(feature = "checked-interp")]
+use checked::TypeStream;
 use core::cell::{Cell, RefCell};
 use decode::{Data, Element, Module, TableType};
-use error::{Result, TypeError, WasmError};
+use diagnostics::{diagnose_body, CallGraph, Warning};
+use error::{Result, Trap, TypeError, WasmError};
+use features::Features;
 use function::FunctionType;
 use global::GlobalType;
 use indice::Indice;
-use isa::Isa;
-use memory::Limit;
+use isa::{Isa, Proposal};
+use memory::{Limit, MAX_PAGES};
 use module::{
   ExportDescriptor, ExternalInterface, ExternalInterfaces, ImportDescriptor, ModuleDescriptor,
 };
+use opcode::{OpcodeInfo, StackEffect};
+use stack_usage::{StackLimits, StackUsage};
 use value_type::{ValueTypes, TYPE_F32, TYPE_F64, TYPE_I32, TYPE_I64};
 
 type ResultType = [ValueTypes; 1];
 
+fn validate_limit(limit: &Limit) -> Result<()> {
+  match limit {
+    Limit::HasUpperLimit(min, max) if min > max => {
+      Err(WasmError::TypeError(TypeError::InvalidLimits))
+    }
+    _ => Ok(()),
+  }
+}
+
+// NOTE: As the reference interpreter does, sizes are checked before the order of minimum and maximum.
+fn validate_memory_limit(limit: &Limit) -> Result<()> {
+  let exceeds = |size: &u32| *size > MAX_PAGES;
+  let is_exceeded = match limit {
+    Limit::NoUpperLimit(min) => exceeds(min),
+    Limit::HasUpperLimit(min, max) => exceeds(min) || exceeds(max),
+  };
+  if is_exceeded {
+    return Err(WasmError::TypeError(TypeError::InvalidMemorySize));
+  }
+  validate_limit(limit)
+}
+
+// NOTE: Limits of tables are decoded as u32, so that ones beyond the range are rejected as malformed.
+fn validate_table_type(table_type: &TableType) -> Result<()> {
+  validate_limit(&table_type.limit)
+}
+
+/// Types of a control instruction, which differ only for loops since their labels refer to their beginning.
+#[derive(Debug, Clone)]
+struct ControlTypes {
+  /// Types carried by branches to the label.
+  label_types: ResultType,
+  /// Types which remain after the end.
+  end_types: ResultType,
+}
+
+impl ControlTypes {
+  fn block(result_type: ValueTypes) -> Self {
+    ControlTypes {
+      label_types: [result_type.clone(); 1],
+      end_types: [result_type; 1],
+    }
+  }
+
+  // NOTE: Loops have no parameters before multi-value, so branches to them carry nothing.
+  fn loop_of(result_type: ValueTypes) -> Self {
+    ControlTypes {
+      label_types: [ValueTypes::Unit; 1],
+      end_types: [result_type; 1],
+    }
+  }
+}
+
 #[derive(Debug, Clone)]
 enum Entry {
   Type(ValueTypes),
@@ -24,27 +83,66 @@ enum Entry {
 }
 
 #[derive(Debug)]
-struct TypeStack(RefCell<Vec<Entry>>);
+struct TypeStack {
+  entries: RefCell<Vec<Entry>>,
+  max_depth: Cell<usize>,
+  // NOTE: Types popped by current instruction from top to bottom.
+  #[cfg(feature = "checked-interp")]
+  popped: RefCell<Vec<ValueTypes>>,
+}
 
 impl TypeStack {
   fn new() -> Self {
-    TypeStack(RefCell::new(Vec::new()))
+    TypeStack {
+      entries: RefCell::new(Vec::new()),
+      max_depth: Cell::new(0),
+      #[cfg(feature = "checked-interp")]
+      popped: RefCell::new(Vec::new()),
+    }
   }
 
   fn len(&self) -> usize {
-    self.0.borrow().len()
+    self.entries.borrow().len()
+  }
+
+  fn push_entry(&self, entry: Entry) {
+    let mut entries = self.entries.borrow_mut();
+    entries.push(entry);
+    if entries.len() > self.max_depth.get() {
+      self.max_depth.set(entries.len());
+    }
   }
 
   fn push(&self, ty: ValueTypes) {
-    self.0.borrow_mut().push(Entry::Type(ty));
+    self.push_entry(Entry::Type(ty));
   }
 
   fn push_label(&self) {
-    self.0.borrow_mut().push(Entry::Label);
+    self.push_entry(Entry::Label);
   }
 
   fn pop(&self) -> Option<Entry> {
-    self.0.borrow_mut().pop()
+    let entry = self.entries.borrow_mut().pop();
+    #[cfg(feature = "checked-interp")]
+    {
+      if let Some(Entry::Type(ty)) = &entry {
+        self.popped.borrow_mut().push(ty.clone());
+      }
+    }
+    entry
+  }
+
+  /// Types popped since last call, ordered from bottom to top.
+  #[cfg(feature = "checked-interp")]
+  fn take_popped(&self) -> Vec<ValueTypes> {
+    let mut popped = self.popped.replace(Vec::new());
+    popped.reverse();
+    popped
+  }
+
+  /// Maximum count of entries which have been on the stack.
+  fn max_depth(&self) -> usize {
+    self.max_depth.get()
   }
 
   fn pop_type(&self) -> Result<ValueTypes> {
@@ -62,14 +160,36 @@ impl TypeStack {
     Ok(buf)
   }
 
+  /// Pop values which a branch carries to a label of `label_type`, and returns their type if any.
+  fn pop_label_values(&self, label_type: &ValueTypes) -> Result<Option<ValueTypes>> {
+    if label_type == &ValueTypes::Unit {
+      return Ok(None);
+    }
+    let actual = self.pop_type()?;
+    if &actual != label_type {
+      return Err(WasmError::TypeError(TypeError::TypeMismatch));
+    }
+    Ok(Some(actual))
+  }
+
   fn pop_i32(&self) -> Result<ValueTypes> {
-    match self.0.borrow_mut().pop() {
+    match self.pop() {
       Some(Entry::Type(ValueTypes::I32)) => Ok(ValueTypes::I32),
       _ => Err(WasmError::TypeError(TypeError::TypeMismatch)),
     }
   }
 }
 
+/// Definition of a function body, which is shared among functions being validated.
+#[derive(Debug)]
+struct FunctionDefinition<'a> {
+  function_type: &'a FunctionType,
+  locals: &'a [ValueTypes],
+  // NOTE: Bodies are flattened only for validation, instances flatten them again by `BodyDecoding`.
+  body: Vec<u8>,
+}
+
+/// State of validation for a single function.
 #[derive(Debug)]
 struct Function<'a> {
   function_type: &'a FunctionType,
@@ -77,20 +197,32 @@ struct Function<'a> {
   body: &'a [u8],
   body_ptr: Cell<usize>,
   type_stack: TypeStack,
+  #[cfg(feature = "checked-interp")]
+  type_stream: RefCell<TypeStream>,
 }
 
 impl<'a> Function<'a> {
-  fn new(
-    function_type: &'a FunctionType,
-    locals: &'a [ValueTypes],
-    body: &'a [u8],
-  ) -> Function<'a> {
+  fn new(definition: &'a FunctionDefinition) -> Function<'a> {
     Function {
-      function_type,
-      locals,
-      body,
+      function_type: definition.function_type,
+      locals: definition.locals,
+      body: &definition.body,
       body_ptr: Cell::new(0),
       type_stack: TypeStack::new(),
+      #[cfg(feature = "checked-interp")]
+      type_stream: RefCell::new(TypeStream::default()),
+    }
+  }
+
+  #[cfg(feature = "checked-interp")]
+  fn record_operands(&self, ptr: usize, code: u8) {
+    use self::Isa::*;
+    let operands = self.type_stack.take_popped();
+    match Isa::from(code) {
+      // NOTE: Branches pop entries until labels, and index of call doesn't count imported functions yet,
+      // so that types modeled for them are not reliable.
+      Else | End | Br | BrIf | BrTable | Return | Call => {}
+      _ => self.type_stream.borrow_mut().push(ptr, operands),
     }
   }
 
@@ -126,9 +258,20 @@ impl<'a> Function<'a> {
   }
 }
 
+// NOTE: Without "float" feature, interpreter can neither compute nor pass values of f32 and f64,
+// so they are rejected wherever value types are declared.
+fn check_float(value_type: &ValueTypes) -> Result<()> {
+  match value_type {
+    ValueTypes::F32 | ValueTypes::F64 if !cfg!(feature = "float") => {
+      Err(WasmError::TypeError(TypeError::DisabledFloat))
+    }
+    _ => Ok(()),
+  }
+}
+
 pub struct Context<'a> {
   function_types: &'a Vec<FunctionType>,
-  functions: Vec<Function<'a>>,
+  functions: Vec<FunctionDefinition<'a>>,
   exports: &'a ExternalInterfaces,
   imports: &'a ExternalInterfaces,
   datas: &'a Vec<Data>,
@@ -137,35 +280,37 @@ pub struct Context<'a> {
   globals: &'a Vec<(GlobalType, Vec<u8>)>,
   elements: &'a Vec<Element>,
   start: &'a Option<u32>,
-  locals: RefCell<Vec<ValueTypes>>,
-  labels: RefCell<VecDeque<ResultType>>,
-  return_type: RefCell<ResultType>,
-}
-
-macro_rules! bin_op {
-  ($stack: ident) => {{
-    let l = $stack.pop_type()?;
-    let r = $stack.pop_type()?;
-    if l != r {
-      return Err(WasmError::TypeError(TypeError::TypeMismatch));
-    }
-    $stack.push(l);
-  }};
-}
-
-macro_rules! rel_op {
-  ($stack: ident) => {{
-    let l = $stack.pop_type()?;
-    let r = $stack.pop_type()?;
-    if l != r {
-      return Err(WasmError::TypeError(TypeError::TypeMismatch));
-    }
-    $stack.push(ValueTypes::I32);
-  }};
+  features: Features,
+  // NOTE: Index spaces of functions, tables, memories and globals start from imported ones.
+  imported_functions: Vec<Indice>,
+  imported_globals: Vec<&'a GlobalType>,
+  count_of_imported_tables: usize,
+  count_of_imported_memories: usize,
 }
 
 impl<'a> Context<'a> {
-  pub fn new(module: &'a Module) -> Result<Self> {
+  pub fn new(module: &'a Module, features: Features) -> Result<Self> {
+    let mut imported_functions = vec![];
+    let mut imported_globals = vec![];
+    let mut count_of_imported_tables = 0;
+    let mut count_of_imported_memories = 0;
+    for import in module.imports.iter() {
+      match &import.descriptor {
+        ModuleDescriptor::ImportDescriptor(ImportDescriptor::Function(idx)) => {
+          imported_functions.push(idx.clone())
+        }
+        ModuleDescriptor::ImportDescriptor(ImportDescriptor::Global(ty)) => {
+          imported_globals.push(ty)
+        }
+        ModuleDescriptor::ImportDescriptor(ImportDescriptor::Table(_)) => {
+          count_of_imported_tables += 1
+        }
+        ModuleDescriptor::ImportDescriptor(ImportDescriptor::Memory(_)) => {
+          count_of_imported_memories += 1
+        }
+        _ => {}
+      }
+    }
     Ok(Context {
       function_types: &module.function_types,
       functions: module
@@ -173,13 +318,30 @@ impl<'a> Context<'a> {
         .iter()
         .enumerate()
         .map(|(idx, code)| {
-          let idx = module.functions.get(idx).map(|n| Indice::from(*n))?;
-          let function_type = module.function_types.get(idx.to_usize())?;
-          let (body, locals) = match code {
-            Ok((body, locals)) => Ok((body, locals)),
-            Err(ref err) => Err(err.to_owned()),
+          let function_type =
+            Module::function_type_of(&module.function_types, &module.functions, idx as u32)?;
+          let flattened = match code {
+            Ok((raw_body, locals)) => raw_body.flatten().map(|(body, _)| (body, locals)),
+            Err(err) => Err(err.to_owned()),
+          };
+          let (body, locals) = match flattened {
+            Err(WasmError::Trap(Trap::UnsupportedFeature { ref proposal, .. }))
+              if !features.accepts(proposal) =>
+            {
+              Err(WasmError::TypeError(TypeError::DisabledFeature(
+                proposal.to_owned(),
+              )))
+            }
+            Err(WasmError::Trap(Trap::UnsupportedFloat { .. })) => {
+              Err(WasmError::TypeError(TypeError::DisabledFloat))
+            }
+            result => result,
           }?;
-          Ok(Function::new(function_type, locals, body))
+          Ok(FunctionDefinition {
+            function_type,
+            locals,
+            body,
+          })
         })
         .collect::<Result<Vec<_>>>()?,
       exports: &module.exports,
@@ -190,13 +352,45 @@ impl<'a> Context<'a> {
       elements: &module.elements,
       limits: &module.limits,
       start: &module.start,
-
-      locals: RefCell::new(Vec::new()),
-      labels: RefCell::new(VecDeque::new()),
-      return_type: RefCell::new([ValueTypes::Unit; 1]),
+      features,
+      imported_functions,
+      imported_globals,
+      count_of_imported_tables,
+      count_of_imported_memories,
     })
   }
 
+  fn function_type_at(&self, idx: &Indice) -> Result<&'a FunctionType> {
+    let count_of_imports = self.imported_functions.len();
+    match self.imported_functions.get(idx.to_usize()) {
+      Some(type_idx) => Module::type_of(self.function_types, type_idx.to_u32()),
+      None => self
+        .functions
+        .get(idx.to_usize() - count_of_imports)
+        .map(|definition| definition.function_type)
+        .ok_or_else(|| WasmError::TypeError(TypeError::UnknownFunction(idx.to_u32()))),
+    }
+  }
+
+  fn global_type_at(&self, idx: &Indice) -> Option<&'a GlobalType> {
+    let count_of_imports = self.imported_globals.len();
+    match self.imported_globals.get(idx.to_usize()) {
+      Some(global_type) => Some(global_type),
+      None => self
+        .globals
+        .get(idx.to_usize() - count_of_imports)
+        .map(|(global_type, _)| global_type),
+    }
+  }
+
+  fn has_table(&self, idx: &Indice) -> bool {
+    idx.to_usize() < self.count_of_imported_tables + self.tables.len()
+  }
+
+  fn has_memory(&self, idx: &Indice) -> bool {
+    idx.to_usize() < self.count_of_imported_memories + self.limits.len()
+  }
+
   fn validate_constant(&self, expr: &[u8]) -> Result<ValueTypes> {
     let type_stack = TypeStack::new();
     let mut idx = 0;
@@ -227,10 +421,8 @@ impl<'a> Context<'a> {
             buf[i] = expr[idx];
           }
           let idx = Indice::from(unsafe { core::mem::transmute::<_, u32>(buf) });
-          match self.globals.get(idx.to_usize()) {
-            Some((GlobalType::Const(ty), _)) | Some((GlobalType::Var(ty), _)) => {
-              type_stack.push(ty.clone())
-            }
+          match self.global_type_at(&idx) {
+            Some(GlobalType::Const(ty)) | Some(GlobalType::Var(ty)) => type_stack.push(ty.clone()),
             _ => return Err(WasmError::TypeError(TypeError::ConstantExpressionRequired)),
           }
         }
@@ -248,10 +440,9 @@ impl<'a> Context<'a> {
 
   fn validate_datas(&self) -> Result<()> {
     for Data { memidx, offset, .. } in self.datas.iter() {
-      self
-        .limits
-        .get(*memidx as usize)
-        .ok_or(TypeError::UnknownMemory)?;
+      if !self.has_memory(&Indice::from(*memidx)) {
+        return Err(WasmError::TypeError(TypeError::UnknownMemory));
+      }
       if ValueTypes::I32 != self.validate_constant(offset)? {
         return Err(WasmError::TypeError(TypeError::TypeMismatch));
       }
@@ -266,7 +457,7 @@ impl<'a> Context<'a> {
       init,
     } in self.elements.iter()
     {
-      if self.tables.get(table_idx.to_usize()).is_none() {
+      if !self.has_table(table_idx) {
         return Err(WasmError::TypeError(TypeError::UnknownTable(
           table_idx.to_u32(),
         )));
@@ -275,10 +466,7 @@ impl<'a> Context<'a> {
         return Err(WasmError::TypeError(TypeError::TypeMismatch));
       }
       for i in init.iter() {
-        self
-          .functions
-          .get(i.to_usize())
-          .ok_or_else(|| TypeError::UnknownFunction(i.to_u32()))?;
+        self.function_type_at(i)?;
       }
     }
     Ok(())
@@ -322,11 +510,11 @@ impl<'a> Context<'a> {
         return Err(WasmError::TypeError(TypeError::TypeMismatch));
       }
       let ty = type_stack.pop_type()?;
-      if &ty
-        != match global_type {
-          GlobalType::Const(expect) | GlobalType::Var(expect) => expect,
-        }
-      {
+      let expect = match global_type {
+        GlobalType::Const(expect) | GlobalType::Var(expect) => expect,
+      };
+      check_float(expect)?;
+      if &ty != expect {
         return Err(WasmError::TypeError(TypeError::TypeMismatch));
       }
     }
@@ -341,38 +529,34 @@ impl<'a> Context<'a> {
     {
       match descriptor {
         ModuleDescriptor::ExportDescriptor(ExportDescriptor::Function(x)) => {
-          self
-            .functions
-            .get(x.to_usize())
-            .ok_or_else(|| TypeError::UnknownFunction(x.to_u32()))?;
+          self.function_type_at(x)?;
         }
         ModuleDescriptor::ExportDescriptor(ExportDescriptor::Table(x)) => {
-          self
-            .tables
-            .get(x.to_usize())
-            .ok_or_else(|| TypeError::UnknownTable(x.to_u32()))?;
+          if !self.has_table(x) {
+            return Err(WasmError::TypeError(TypeError::UnknownTable(x.to_u32())));
+          }
         }
         ModuleDescriptor::ExportDescriptor(ExportDescriptor::Memory(x)) => {
-          self
-            .limits
-            .get(x.to_usize())
-            .ok_or_else(|| TypeError::UnknownMemory)?;
+          if !self.has_memory(x) {
+            return Err(WasmError::TypeError(TypeError::UnknownMemory));
+          }
         }
         ModuleDescriptor::ExportDescriptor(ExportDescriptor::Global(x)) => {
           self
-            .globals
-            .get(x.to_usize())
+            .global_type_at(x)
             .ok_or_else(|| TypeError::UnknownGlobal(x.to_u32()))?;
         }
         _ => unreachable!(),
       };
       names.push(name);
     }
-    names.dedup();
-    if names.len() != self.exports.len() {
-      return Err(WasmError::TypeError(TypeError::DuplicateExportName));
+    names.sort();
+    match names.windows(2).find(|pair| pair[0] == pair[1]) {
+      Some(pair) => Err(WasmError::TypeError(TypeError::DuplicateExportName(
+        pair[0].to_owned(),
+      ))),
+      None => Ok(()),
     }
-    Ok(())
   }
 
   fn validate_imports(&self) -> Result<()> {
@@ -381,21 +565,20 @@ impl<'a> Context<'a> {
     for ExternalInterface { descriptor, .. } in self.imports.iter() {
       match descriptor {
         ModuleDescriptor::ImportDescriptor(ImportDescriptor::Function(x)) => {
-          self
-            .function_types
-            .get(x.to_usize())
-            .ok_or_else(|| TypeError::UnknownFunction(x.to_u32()))?;
+          Module::type_of(self.function_types, x.to_u32())?;
         }
         ModuleDescriptor::ImportDescriptor(ImportDescriptor::Table(ty)) => {
           if !self.tables.is_empty() {
             return Err(WasmError::TypeError(TypeError::MultipleTables));
           }
+          validate_table_type(ty)?;
           tables.push(ty);
         }
         ModuleDescriptor::ImportDescriptor(ImportDescriptor::Memory(limit)) => {
           if !self.limits.is_empty() {
             return Err(WasmError::TypeError(TypeError::MultipleMemories));
           }
+          validate_memory_limit(limit)?;
           memories.push(limit);
         }
         ModuleDescriptor::ImportDescriptor(ImportDescriptor::Global(_ty)) => {}
@@ -412,6 +595,9 @@ impl<'a> Context<'a> {
   }
 
   fn validate_tables(&self) -> Result<()> {
+    for table_type in self.tables.iter() {
+      validate_table_type(table_type)?;
+    }
     if self.tables.len() > 1 {
       return Err(WasmError::TypeError(TypeError::MultipleTables));
     }
@@ -420,18 +606,7 @@ impl<'a> Context<'a> {
 
   fn validate_memories(&self) -> Result<()> {
     for limit in self.limits.iter() {
-      match limit {
-        Limit::NoUpperLimit(min) => {
-          if *min > 65536 {
-            return Err(WasmError::TypeError(TypeError::InvalidMemorySize));
-          }
-        }
-        Limit::HasUpperLimit(min, max) => {
-          if min > max || *min > 65536 || *max > 65536 {
-            return Err(WasmError::TypeError(TypeError::InvalidMemorySize));
-          }
-        }
-      }
+      validate_memory_limit(limit)?;
     }
     if self.limits.len() > 1 {
       return Err(WasmError::TypeError(TypeError::MultipleMemories));
@@ -441,11 +616,7 @@ impl<'a> Context<'a> {
 
   fn validate_start(&self) -> Result<()> {
     if let Some(idx) = self.start {
-      let func = self
-        .functions
-        .get(*idx as usize)
-        .ok_or_else(|| TypeError::UnknownFunction(*idx))?;
-      let ty = func.function_type;
+      let ty = self.function_type_at(&Indice::from(*idx))?;
       if !ty.parameters().is_empty() || !ty.returns().is_empty() {
         return Err(WasmError::TypeError(TypeError::InvalidStartFunction));
       }
@@ -455,18 +626,39 @@ impl<'a> Context<'a> {
 
   fn validate_function_types(&self) -> Result<()> {
     for fy in self.function_types.iter() {
-      if fy.returns().len() > 1 {
+      for value_type in fy.parameters().iter().chain(fy.returns().iter()) {
+        check_float(value_type)?;
+      }
+      if fy.returns().len() > 1 && !self.features.accepts(&Proposal::MultiValue) {
         return Err(WasmError::TypeError(TypeError::InvalidResultArity));
       }
     }
     Ok(())
   }
 
-  fn validate_functions(&self) -> Result<()> {
+  #[cfg(not(feature = "parallel"))]
+  fn validate_functions(&self, limits: &StackLimits) -> Result<Vec<StackUsage>> {
+    let mut stack_usages = Vec::with_capacity(self.functions.len());
     for f in self.functions.iter() {
-      self.validate_function(f)?;
+      let stack_usage = self.validate_function(f)?;
+      limits.check(&stack_usage)?;
+      stack_usages.push(stack_usage);
     }
-    Ok(())
+    Ok(stack_usages)
+  }
+
+  #[cfg(feature = "parallel")]
+  fn validate_functions(&self, limits: &StackLimits) -> Result<Vec<StackUsage>> {
+    use rayon::prelude::*;
+    self
+      .functions
+      .par_iter()
+      .map(|f| {
+        let stack_usage = self.validate_function(f)?;
+        limits.check(&stack_usage)?;
+        Ok(stack_usage)
+      })
+      .collect()
   }
 
   fn validate_load(
@@ -478,7 +670,9 @@ impl<'a> Context<'a> {
   ) -> Result<()> {
     let align = function.pop_raw_u32()?;
     let _offset = function.pop_raw_u32()?;
-    self.limits.first().ok_or(TypeError::UnknownMemory)?;
+    if !self.has_memory(&Indice::from(0u32)) {
+      return Err(WasmError::TypeError(TypeError::UnknownMemory));
+    }
     if 2u32.pow(align) > bit_width / 8 {
       return Err(WasmError::TypeError(TypeError::InvalidAlignment));
     };
@@ -496,7 +690,9 @@ impl<'a> Context<'a> {
   ) -> Result<()> {
     let align = function.pop_raw_u32()?;
     let _offset = function.pop_raw_u32()?;
-    self.limits.first().ok_or(TypeError::UnknownMemory)?;
+    if !self.has_memory(&Indice::from(0u32)) {
+      return Err(WasmError::TypeError(TypeError::UnknownMemory));
+    }
     if 2u32.pow(align) > bit_width / 8 {
       return Err(WasmError::TypeError(TypeError::InvalidAlignment));
     };
@@ -508,67 +704,71 @@ impl<'a> Context<'a> {
     Ok(())
   }
 
-  fn validate_unary(&self, cxt: &TypeStack) -> Result<()> {
-    let t = cxt.pop_type()?;
-    cxt.push(t);
+  // NOTE: Instructions without immediates to look up are validated by their stack effects in opcode table.
+  fn validate_fixed(&self, cxt: &TypeStack, code: u8) -> Result<()> {
+    let (operands, results) = match OpcodeInfo::of(code).map(|info| &info.stack_effect) {
+      Some(StackEffect::Fixed(operands, results)) => (operands, results),
+      _ => unreachable!("Code {:x?} has no fixed stack effect.", code),
+    };
+    for expect in operands.iter().rev() {
+      if &cxt.pop_type()? != expect {
+        return Err(WasmError::TypeError(TypeError::TypeMismatch));
+      }
+    }
+    for ty in results.iter() {
+      cxt.push(ty.clone());
+    }
     Ok(())
   }
 
-  fn validate_test_inst(&self, cxt: &TypeStack, expect: &ValueTypes) -> Result<()> {
-    let actual = cxt.pop_type()?;
-    if &actual != expect {
-      return Err(WasmError::TypeError(TypeError::TypeMismatch));
-    }
-    cxt.push(ValueTypes::I32);
-    Ok(())
+  /// Validate a function body without mutating the context,
+  /// so that each function can be validated independently of others.
+  fn validate_function(&self, definition: &FunctionDefinition) -> Result<StackUsage> {
+    self.validate_body(&Function::new(definition))
   }
 
-  fn validate_convert(&self, cxt: &TypeStack, from: &ValueTypes, to: ValueTypes) -> Result<()> {
-    let from_ty = cxt.pop_type()?;
-    if &from_ty != from {
-      return Err(WasmError::TypeError(TypeError::TypeMismatch));
-    }
-    cxt.push(to);
-    Ok(())
+  #[cfg(feature = "checked-interp")]
+  fn type_stream_of(&self, definition: &FunctionDefinition) -> Result<TypeStream> {
+    let function = Function::new(definition);
+    self.validate_body(&function)?;
+    Ok(function.type_stream.into_inner())
   }
 
-  fn validate_function(&self, function: &Function) -> Result<()> {
+  fn validate_body(&self, function: &Function) -> Result<StackUsage> {
     use self::Isa::*;
     let cxt = &function.type_stack;
-    let labels = &mut self.labels.borrow_mut();
-    let locals = &mut self.locals.borrow_mut();
+    let mut labels: VecDeque<ControlTypes> = VecDeque::new();
+    let mut locals: Vec<ValueTypes> = Vec::new();
     for param in function.function_type.parameters().iter() {
       locals.push(param.clone());
     }
     for local in function.locals.iter() {
+      check_float(local)?;
       locals.push(local.clone());
     }
-    if let Some(ret) = function.function_type.returns().first() {
-      self.return_type.replace([ret.clone(); 1]);
+    let return_type: ResultType = match function.function_type.returns().first() {
+      Some(ret) => [ret.clone(); 1],
+      None => [ValueTypes::Unit; 1],
     };
-    let return_type = &self.return_type.borrow();
 
-    labels.push_front(
-      [match function.function_type.returns().first() {
-        Some(ty) => ty.clone(),
-        None => ValueTypes::Unit,
-      }; 1],
-    );
+    labels.push_front(ControlTypes::block(return_type[0].clone()));
 
     while let Some(inst) = function.pop() {
+      #[cfg(feature = "checked-interp")]
+      let ptr = function.body_ptr.get() - 1;
       match Isa::from(*inst) {
-        Reserved => unreachable!(),
+        Reserved | GetLocalGetLocalI32Add | I32ConstI32Add | GetLocalI32Load => unreachable!(),
         Unreachable => {}
         Nop => {}
         Block => {
           let _ = function.pop_raw_u32()?; // Drop size of block.
           let expect_type = function.pop_value_type()?;
-          labels.push_front([expect_type; 1]);
+          labels.push_front(ControlTypes::block(expect_type));
           cxt.push_label();
         }
         Loop => {
           let expect_type = function.pop_value_type()?;
-          labels.push_front([expect_type; 1]);
+          labels.push_front(ControlTypes::loop_of(expect_type));
           cxt.push_label();
         }
         If => {
@@ -576,26 +776,28 @@ impl<'a> Context<'a> {
           let _ = function.pop_raw_u32()?; // Drop size of if.
           let _ = function.pop_raw_u32()?; // Drop size of else.
           let expect_type = function.pop_value_type()?;
-          labels.push_front([expect_type; 1]);
+          labels.push_front(ControlTypes::block(expect_type));
           cxt.push_label();
         }
         Else => {
-          let expect = labels.pop_front().ok_or(TypeError::TypeMismatch)?[0].clone();
+          let expect = labels.pop_front().ok_or(TypeError::TypeMismatch)?.end_types[0].clone();
           let actual = cxt.pop_type()?;
           if expect != actual {
             return Err(WasmError::TypeError(TypeError::TypeMismatch));
           }
           cxt.pop_until_label()?;
-          labels.push_front([expect; 1]);
+          labels.push_front(ControlTypes::block(expect));
         }
         End => {
-          let expect = labels.pop_front().ok_or(TypeError::TypeMismatch)?[0].clone();
+          let expect = labels.pop_front().ok_or(TypeError::TypeMismatch)?.end_types[0].clone();
           match cxt.pop() {
             Some(Entry::Type(actual)) => {
               if expect != actual {
                 return Err(WasmError::TypeError(TypeError::TypeMismatch));
               };
               cxt.pop_until_label()?;
+              // NOTE: Result of the block remains for instructions following it.
+              cxt.push(actual);
             }
             _ => {
               if expect != ValueTypes::Unit {
@@ -607,19 +809,24 @@ impl<'a> Context<'a> {
 
         Br => {
           let idx = Indice::from(function.pop_raw_u32()?);
-          let expect = labels.get(idx.to_usize()).ok_or(TypeError::UnknownLabel)?[0].clone();
-          let actual = cxt.pop_type()?;
-          if expect != actual {
-            return Err(WasmError::TypeError(TypeError::TypeMismatch));
-          }
+          let expect = labels
+            .get(idx.to_usize())
+            .ok_or(TypeError::UnknownLabel)?
+            .label_types[0]
+            .clone();
+          cxt.pop_label_values(&expect)?;
         }
         BrIf => {
           let idx = Indice::from(function.pop_raw_u32()?);
-          let expect = labels.get(idx.to_usize()).ok_or(TypeError::UnknownLabel)?[0].clone();
-          let actual = cxt.pop_type()?;
+          let expect = labels
+            .get(idx.to_usize())
+            .ok_or(TypeError::UnknownLabel)?
+            .label_types[0]
+            .clone();
           cxt.pop_i32()?;
-          if expect != actual {
-            return Err(WasmError::TypeError(TypeError::TypeMismatch));
+          // NOTE: Values carried by the branch remain when it isn't taken.
+          if let Some(actual) = cxt.pop_label_values(&expect)? {
+            cxt.push(actual);
           }
         }
         BrTable => {
@@ -630,18 +837,23 @@ impl<'a> Context<'a> {
             indices.push(Indice::from(idx));
           }
           let idx = Indice::from(function.pop_raw_u32()?);
-          let expect = labels.get(idx.to_usize()).ok_or(TypeError::UnknownLabel)?[0].clone();
+          let expect = labels
+            .get(idx.to_usize())
+            .ok_or(TypeError::UnknownLabel)?
+            .label_types[0]
+            .clone();
           for i in indices.iter() {
-            let actual = labels.get(i.to_usize()).ok_or(TypeError::UnknownLabel)?[0].clone();
+            let actual = labels
+              .get(i.to_usize())
+              .ok_or(TypeError::UnknownLabel)?
+              .label_types[0]
+              .clone();
             if expect != actual {
               return Err(WasmError::TypeError(TypeError::TypeMismatch));
             }
           }
-          let actual = cxt.pop_type()?;
           cxt.pop_i32()?;
-          if expect != actual {
-            return Err(WasmError::TypeError(TypeError::TypeMismatch));
-          }
+          cxt.pop_label_values(&expect)?;
         }
         Return => {
           let expect = return_type[0].clone();
@@ -653,11 +865,7 @@ impl<'a> Context<'a> {
         }
         Call => {
           let idx = Indice::from(function.pop_raw_u32()?);
-          let function_type = self
-            .functions
-            .get(idx.to_usize())
-            .map(|f| f.function_type)
-            .ok_or(TypeError::TypeMismatch)?;
+          let function_type = self.function_type_at(&idx)?;
           let mut parameters = function_type.parameters().clone();
           while let Some(ty) = parameters.pop() {
             if ty != cxt.pop_type()? {
@@ -670,11 +878,10 @@ impl<'a> Context<'a> {
         }
         CallIndirect => {
           let idx = Indice::from(function.pop_raw_u32()?);
-          self.tables.first().ok_or(TypeError::UnknownTable(0))?;
-          let function_type = self
-            .function_types
-            .get(idx.to_usize())
-            .ok_or_else(|| TypeError::UnknownFunctionType(idx.to_u32()))?;
+          if !self.has_table(&Indice::from(0u32)) {
+            return Err(WasmError::TypeError(TypeError::UnknownTable(0)));
+          }
+          let function_type = Module::type_of(self.function_types, idx.to_u32())?;
           let mut parameters = function_type.parameters().clone();
           cxt.pop_i32()?;
           while let Some(ty) = parameters.pop() {
@@ -730,10 +937,9 @@ impl<'a> Context<'a> {
         GetGlobal => {
           let idx = Indice::from(function.pop_raw_u32()?);
           let ty = self
-            .globals
-            .get(idx.to_usize())
+            .global_type_at(&idx)
             .ok_or_else(|| TypeError::UnknownGlobal(idx.to_u32()))
-            .map(|(global_type, _)| match global_type {
+            .map(|global_type| match global_type {
               GlobalType::Const(ty) | GlobalType::Var(ty) => ty,
             })?;
           cxt.push(ty.clone());
@@ -743,10 +949,9 @@ impl<'a> Context<'a> {
           let idx = function.pop_raw_u32()?;
           let idx: Indice = From::from(idx);
           let ty = self
-            .globals
-            .get(idx.to_usize())
+            .global_type_at(&idx)
             .ok_or_else(|| TypeError::UnknownGlobal(idx.to_u32()))
-            .and_then(|(global_type, _)| match global_type {
+            .and_then(|global_type| match global_type {
               GlobalType::Var(ty) => Ok(ty),
               GlobalType::Const(_) => Err(TypeError::GlobalIsImmutable),
             })?;
@@ -781,124 +986,19 @@ impl<'a> Context<'a> {
         I64Store32 => self.validate_store(cxt, 32, &TYPE_I64, function)?,
 
         MemorySize => {
-          self.limits.first().ok_or(TypeError::TypeMismatch)?;
+          if !self.has_memory(&Indice::from(0u32)) {
+            return Err(WasmError::TypeError(TypeError::UnknownMemory));
+          }
           cxt.push(ValueTypes::I32);
         }
         MemoryGrow => {
-          self.limits.first().ok_or(TypeError::TypeMismatch)?;
+          if !self.has_memory(&Indice::from(0u32)) {
+            return Err(WasmError::TypeError(TypeError::UnknownMemory));
+          }
           cxt.pop_i32()?;
           cxt.push(ValueTypes::I32);
         }
 
-        I32CountLeadingZero => self.validate_unary(cxt)?,
-        I32CountTrailingZero => self.validate_unary(cxt)?,
-        I32CountNonZero => self.validate_unary(cxt)?,
-        I64CountLeadingZero => self.validate_unary(cxt)?,
-        I64CountTrailingZero => self.validate_unary(cxt)?,
-        I64CountNonZero => self.validate_unary(cxt)?,
-
-        I32Add => bin_op!(cxt),
-        I32Sub => bin_op!(cxt),
-        I32Mul => bin_op!(cxt),
-        I32DivSign => bin_op!(cxt),
-        I32DivUnsign => bin_op!(cxt),
-        I32RemSign => bin_op!(cxt),
-        I32RemUnsign => bin_op!(cxt),
-        I32And => bin_op!(cxt),
-        I32Or => bin_op!(cxt),
-        I32Xor => bin_op!(cxt),
-        I32ShiftLeft => bin_op!(cxt),
-        I32ShiftRIghtSign => bin_op!(cxt),
-        I32ShiftRightUnsign => bin_op!(cxt),
-        I32RotateLeft => bin_op!(cxt),
-        I32RotateRight => bin_op!(cxt),
-
-        I64Add => bin_op!(cxt),
-        I64Sub => bin_op!(cxt),
-        I64Mul => bin_op!(cxt),
-        I64DivSign => bin_op!(cxt),
-        I64DivUnsign => bin_op!(cxt),
-        I64RemSign => bin_op!(cxt),
-        I64RemUnsign => bin_op!(cxt),
-        I64And => bin_op!(cxt),
-        I64Or => bin_op!(cxt),
-        I64Xor => bin_op!(cxt),
-        I64ShiftLeft => bin_op!(cxt),
-        I64ShiftRightSign => bin_op!(cxt),
-        I64ShiftRightUnsign => bin_op!(cxt),
-        I64RotateLeft => bin_op!(cxt),
-        I64RotateRight => bin_op!(cxt),
-
-        I32EqualZero => self.validate_test_inst(cxt, &TYPE_I32)?,
-        I64EqualZero => self.validate_test_inst(cxt, &TYPE_I64)?,
-
-        I32Equal => rel_op!(cxt),
-        I32NotEqual => rel_op!(cxt),
-        I32LessThanSign => rel_op!(cxt),
-        I32LessThanUnsign => rel_op!(cxt),
-        I32GreaterThanSign => rel_op!(cxt),
-        I32GreaterThanUnsign => rel_op!(cxt),
-        I32LessEqualSign => rel_op!(cxt),
-        I32LessEqualUnsign => rel_op!(cxt),
-        I32GreaterEqualSign => rel_op!(cxt),
-        I32GreaterEqualUnsign => rel_op!(cxt),
-
-        I64Equal => rel_op!(cxt),
-        I64NotEqual => rel_op!(cxt),
-        I64LessThanSign => rel_op!(cxt),
-        I64LessThanUnSign => rel_op!(cxt),
-        I64GreaterThanSign => rel_op!(cxt),
-        I64GreaterThanUnSign => rel_op!(cxt),
-        I64LessEqualSign => rel_op!(cxt),
-        I64LessEqualUnSign => rel_op!(cxt),
-        I64GreaterEqualSign => rel_op!(cxt),
-        I64GreaterEqualUnSign => rel_op!(cxt),
-
-        F32Equal => rel_op!(cxt),
-        F32NotEqual => rel_op!(cxt),
-        F32LessThan => rel_op!(cxt),
-        F32GreaterThan => rel_op!(cxt),
-        F32LessEqual => rel_op!(cxt),
-        F32GreaterEqual => rel_op!(cxt),
-
-        F64Equal => rel_op!(cxt),
-        F64NotEqual => rel_op!(cxt),
-        F64LessThan => rel_op!(cxt),
-        F64GreaterThan => rel_op!(cxt),
-        F64LessEqual => rel_op!(cxt),
-        F64GreaterEqual => rel_op!(cxt),
-
-        F32Abs => self.validate_unary(cxt)?,
-        F32Neg => self.validate_unary(cxt)?,
-        F32Ceil => self.validate_unary(cxt)?,
-        F32Floor => self.validate_unary(cxt)?,
-        F32Trunc => self.validate_unary(cxt)?,
-        F32Nearest => self.validate_unary(cxt)?,
-        F32Sqrt => self.validate_unary(cxt)?,
-
-        F32Add => bin_op!(cxt),
-        F32Sub => bin_op!(cxt),
-        F32Mul => bin_op!(cxt),
-        F32Div => bin_op!(cxt),
-        F32Min => bin_op!(cxt),
-        F32Max => bin_op!(cxt),
-        F32Copysign => bin_op!(cxt),
-
-        F64Abs => self.validate_unary(cxt)?,
-        F64Neg => self.validate_unary(cxt)?,
-        F64Ceil => self.validate_unary(cxt)?,
-        F64Floor => self.validate_unary(cxt)?,
-        F64Trunc => self.validate_unary(cxt)?,
-        F64Nearest => self.validate_unary(cxt)?,
-        F64Sqrt => self.validate_unary(cxt)?,
-        F64Add => bin_op!(cxt),
-        F64Sub => bin_op!(cxt),
-        F64Mul => bin_op!(cxt),
-        F64Div => bin_op!(cxt),
-        F64Min => bin_op!(cxt),
-        F64Max => bin_op!(cxt),
-        F64Copysign => bin_op!(cxt),
-
         Select => {
           cxt.pop_type()?;
           cxt.pop_type()?;
@@ -908,40 +1008,36 @@ impl<'a> Context<'a> {
         DropInst => {
           cxt.pop_type()?;
         }
-
-        // To_convert_name_From
-        // macro(cxt, from, to)
-        I32WrapI64 => self.validate_convert(cxt, &TYPE_I64, ValueTypes::I32)?,
-        I32TruncSignF32 => self.validate_convert(cxt, &TYPE_F32, ValueTypes::I32)?,
-        I32TruncUnsignF32 => self.validate_convert(cxt, &TYPE_F32, ValueTypes::I32)?,
-        I32TruncSignF64 => self.validate_convert(cxt, &TYPE_F64, ValueTypes::I32)?,
-        I32TruncUnsignF64 => self.validate_convert(cxt, &TYPE_F64, ValueTypes::I32)?,
-        I64ExtendSignI32 => self.validate_convert(cxt, &TYPE_I32, ValueTypes::I64)?,
-        I64ExtendUnsignI32 => self.validate_convert(cxt, &TYPE_I32, ValueTypes::I64)?,
-        I64TruncSignF32 => self.validate_convert(cxt, &TYPE_F32, ValueTypes::I64)?,
-        I64TruncUnsignF32 => self.validate_convert(cxt, &TYPE_F32, ValueTypes::I64)?,
-        I64TruncSignF64 => self.validate_convert(cxt, &TYPE_F64, ValueTypes::I64)?,
-        I64TruncUnsignF64 => self.validate_convert(cxt, &TYPE_F64, ValueTypes::I64)?,
-        F32ConvertSignI32 => self.validate_convert(cxt, &TYPE_I32, ValueTypes::F32)?,
-        F32ConvertUnsignI32 => self.validate_convert(cxt, &TYPE_I32, ValueTypes::F32)?,
-        F32ConvertSignI64 => self.validate_convert(cxt, &TYPE_I64, ValueTypes::F32)?,
-        F32ConvertUnsignI64 => self.validate_convert(cxt, &TYPE_I64, ValueTypes::F32)?,
-        F32DemoteF64 => self.validate_convert(cxt, &TYPE_F64, ValueTypes::F32)?,
-        F64ConvertSignI32 => self.validate_convert(cxt, &TYPE_I32, ValueTypes::F64)?,
-        F64ConvertUnsignI32 => self.validate_convert(cxt, &TYPE_I32, ValueTypes::F64)?,
-        F64ConvertSignI64 => self.validate_convert(cxt, &TYPE_I64, ValueTypes::F64)?,
-        F64ConvertUnsignI64 => self.validate_convert(cxt, &TYPE_I64, ValueTypes::F64)?,
-        F64PromoteF32 => self.validate_convert(cxt, &TYPE_F32, ValueTypes::F64)?,
-        I32ReinterpretF32 => self.validate_convert(cxt, &TYPE_F32, ValueTypes::I32)?,
-        I64ReinterpretF64 => self.validate_convert(cxt, &TYPE_F64, ValueTypes::I64)?,
-        F32ReinterpretI32 => self.validate_convert(cxt, &TYPE_I32, ValueTypes::F32)?,
-        F64ReinterpretI64 => self.validate_convert(cxt, &TYPE_I64, ValueTypes::F64)?,
+        _ => self.validate_fixed(cxt, *inst)?,
       }
+      #[cfg(feature = "checked-interp")]
+      function.record_operands(ptr, *inst);
     }
-    Ok(())
+    Ok(StackUsage {
+      max_depth: cxt.max_depth(),
+      count_of_locals: locals.len(),
+    })
   }
 
   pub fn validate(&self) -> Result<()> {
+    self.validate_with_limits(&StackLimits::default())?;
+    Ok(())
+  }
+
+  /// Types of operands which each instruction of defined functions pops,
+  /// which interpreter compares with its stack under "checked-interp" feature.
+  #[cfg(feature = "checked-interp")]
+  pub(crate) fn type_streams(&self) -> Result<Vec<TypeStream>> {
+    self.validate()?;
+    self
+      .functions
+      .iter()
+      .map(|f| self.type_stream_of(f))
+      .collect()
+  }
+
+  /// Validate the module, and returns stack usages of defined functions in order of their indices.
+  pub fn validate_with_limits(&self, limits: &StackLimits) -> Result<Vec<StackUsage>> {
     self.validate_exports()?;
     self.validate_imports()?;
     self.validate_datas()?;
@@ -950,8 +1046,55 @@ impl<'a> Context<'a> {
     self.validate_elements()?;
     self.validate_globals()?;
     self.validate_function_types()?;
-    self.validate_functions()?;
+    let stack_usages = self.validate_functions(limits)?;
     self.validate_start()?;
-    Ok(())
+    Ok(stack_usages)
+  }
+
+  /// Validate the module, and returns warnings of functions in order of their indices,
+  /// followed by unused functions.
+  pub fn validate_with_diagnostics(&self) -> Result<Vec<Warning>> {
+    self.validate()?;
+    let count_of_imports = self.count_of_imports(|descriptor| match descriptor {
+      ImportDescriptor::Function(_) => true,
+      _ => false,
+    });
+    let mut warnings = vec![];
+    let uses = self
+      .functions
+      .iter()
+      .enumerate()
+      .map(|(idx, definition)| {
+        diagnose_body(
+          count_of_imports + idx as u32,
+          &definition.body,
+          &mut warnings,
+        )
+      })
+      .collect::<Vec<_>>();
+    let call_graph = CallGraph::new(
+      count_of_imports,
+      &uses,
+      self.exports,
+      self.start,
+      self.elements,
+    );
+    for function in call_graph.unused() {
+      warnings.push(Warning::UnusedFunction(function));
+    }
+    Ok(warnings)
+  }
+
+  fn count_of_imports(&self, matches: fn(&ImportDescriptor) -> bool) -> u32 {
+    Module::count_of_imports(self.imports, matches)
+  }
+
+  /// Flattened bodies of defined functions in order of their indices.
+  pub(crate) fn bodies(&self) -> Vec<&[u8]> {
+    self
+      .functions
+      .iter()
+      .map(|definition| definition.body.as_slice())
+      .collect()
   }
 }
@@ -1,16 +1,166 @@
-#[cfg(not(test))]
+#[cfg(not(any(test, feature = "std")))]
 use alloc::prelude::*;
 use alloc::string::String;
-use core::intrinsics::{fabsf32, fabsf64};
+use core::convert::TryFrom;
 use core::mem::transmute;
-use core::ops::Rem;
+use core::num::FpCategory;
 use core::ops::{BitAnd, BitOr, BitXor, Neg};
 use core::{f32, f64};
 use error::{Result, Trap, WasmError};
-#[cfg(not(test))]
+use function::FunctionType;
+#[cfg(not(any(test, feature = "std")))]
 use libm::{F32Ext, F64Ext};
 use value_type::ValueTypes;
 
+/// Handling of subnormal results of float arithmetic.
+#[derive(PartialEq, Clone, Debug)]
+pub enum SubnormalMode {
+  /// Fully IEEE 754 compliant as WebAssembly specification requires.
+  Strict,
+  /// Flush subnormal results to zero with preserving sign.
+  /// Intended for soft-float targets which trade accuracy for speed,
+  /// so results differ from ones the spec testsuite expects.
+  FlushToZero,
+}
+
+impl Default for SubnormalMode {
+  fn default() -> Self {
+    SubnormalMode::Strict
+  }
+}
+
+/// Handling of conditions of `if`, `br_if` and `select` which are not i32.
+/// Such conditions only appear when a module is instantiated without validation.
+#[derive(PartialEq, Clone, Debug)]
+pub enum ConditionMode {
+  /// Trap with `Trap::TypeMismatch`.
+  Strict,
+  /// Treat nonzero integers and floats except zero and NaN as true.
+  /// Conditions of valid modules are evaluated as same as `Strict`, as select.wast and if.wast verify.
+  Coerce,
+}
+
+impl Default for ConditionMode {
+  fn default() -> Self {
+    ConditionMode::Strict
+  }
+}
+
+/// Conversion of arguments passed by embedder into types of parameters.
+#[derive(PartialEq, Clone, Debug)]
+pub enum ArgumentCoercion {
+  /// Arguments must have the same types as parameters.
+  Exact,
+  /// Widen arguments only when no information is lost, i.e. i32 to i64 and f32 to f64.
+  Lossless,
+}
+
+impl Default for ArgumentCoercion {
+  fn default() -> Self {
+    ArgumentCoercion::Exact
+  }
+}
+
+impl ArgumentCoercion {
+  pub(crate) fn accepts(&self, argument: &ValueTypes, parameter: &ValueTypes) -> bool {
+    match (argument, parameter, self) {
+      (argument, parameter, _) if argument == parameter => true,
+      (ValueTypes::I32, ValueTypes::I64, ArgumentCoercion::Lossless) => true,
+      (ValueTypes::F32, ValueTypes::F64, ArgumentCoercion::Lossless) => true,
+      _ => false,
+    }
+  }
+}
+
+/// Handling of i64 in arguments and results of invocations by embedder, so that modules designed for
+/// JavaScript hosts without BigInt are exercised as same as on the web.
+#[derive(PartialEq, Clone, Debug)]
+pub enum I64Boundary {
+  /// Pass i64 as it is.
+  Native,
+  /// Refuse to invoke a function which has i64 in its parameters or results, as such hosts throw TypeError.
+  Reject,
+  /// Pass each i64 as two i32 of its low and high halves in this order, as glue code of legalized modules does.
+  Split,
+}
+
+impl Default for I64Boundary {
+  fn default() -> Self {
+    I64Boundary::Native
+  }
+}
+
+impl I64Boundary {
+  pub(crate) fn check(&self, value_types: &[ValueTypes]) -> Result<()> {
+    match self {
+      I64Boundary::Reject if value_types.contains(&ValueTypes::I64) => {
+        Err(WasmError::Trap(Trap::I64AtBoundary))
+      }
+      _ => Ok(()),
+    }
+  }
+
+  /// Signature which embedder sees, where i64 is replaced by two i32 under `Split`.
+  pub(crate) fn legalize(&self, function_type: &FunctionType) -> FunctionType {
+    let legalize = |value_types: &[ValueTypes]| match self {
+      I64Boundary::Split => value_types
+        .iter()
+        .flat_map(|value_type| match value_type {
+          ValueTypes::I64 => vec![ValueTypes::I32, ValueTypes::I32],
+          value_type => vec![value_type.clone()],
+        })
+        .collect(),
+      _ => value_types.to_vec(),
+    };
+    FunctionType::new(
+      legalize(function_type.parameters()),
+      legalize(function_type.returns()),
+    )
+  }
+
+  /// Callers must check that `arguments` have types of legalized parameters.
+  pub(crate) fn join_arguments(
+    &self,
+    parameters: &[ValueTypes],
+    arguments: Vec<Values>,
+  ) -> Vec<Values> {
+    if *self != I64Boundary::Split {
+      return arguments;
+    }
+    let mut arguments = arguments.into_iter();
+    let mut joined = vec![];
+    for parameter in parameters.iter() {
+      match (parameter, arguments.next()) {
+        (ValueTypes::I64, Some(Values::I32(low))) => match arguments.next() {
+          Some(Values::I32(high)) => joined.push(Values::join_i64(low, high)),
+          x => unreachable!("Expected high half of i64, got {:?}", x),
+        },
+        (_, Some(argument)) => joined.push(argument),
+        (_, None) => unreachable!("Arguments run out"),
+      }
+    }
+    joined
+  }
+
+  pub(crate) fn split_results(&self, results: Vec<Values>) -> Vec<Values> {
+    if *self != I64Boundary::Split {
+      return results;
+    }
+    let mut split = vec![];
+    for result in results.into_iter() {
+      match result {
+        Values::I64(n) => {
+          let (low, high) = Values::split_i64(n);
+          split.push(low);
+          split.push(high);
+        }
+        result => split.push(result),
+      }
+    }
+    split
+  }
+}
+
 #[derive(PartialEq, Clone, Debug)]
 pub enum Values {
   I32(i32),
@@ -134,8 +284,14 @@ trait ArithmeticInteger {
   fn copy_sign(&self, other: Self) -> Self;
 }
 
+// NOTE: Counts of shift and rotate are taken modulo bit width,
+// so only lower bits of counts are used, even if they're negative or wider than 32 bits.
+fn count_of_shift<T: Into<i64>>(count: T, bits: u32) -> u32 {
+  (count.into() as u32) & (bits - 1)
+}
+
 macro_rules! impl_integer_traits {
-  ($ty: ty, $unsign: ty) => {
+  ($ty: ty, $unsign: ty, $bits: expr) => {
     impl ArithmeticInteger for $ty {
       fn equal_zero(&self) -> Self {
         if self == &0 {
@@ -239,29 +395,29 @@ macro_rules! impl_integer_traits {
         }
       }
       fn shift_left(&self, other: Self) -> Self {
-        self.wrapping_shl(other as u32)
+        self << count_of_shift(other, $bits)
       }
       fn shift_right_sign(&self, other: Self) -> Self {
-        let shifted = self.wrapping_shr(other as u32);
-        (shifted as $unsign) as $ty
+        self >> count_of_shift(other, $bits)
       }
       fn shift_right_unsign(&self, other: Self) -> Self {
         let i1 = *self as $unsign;
-        i1.wrapping_shr(other as u32) as $ty
+        (i1 >> count_of_shift(other, $bits)) as $ty
       }
 
       fn wasm_rotate_left(&self, other: Self) -> Self {
-        self.rotate_left(other as u32)
+        self.rotate_left(count_of_shift(other, $bits))
       }
 
       fn wasm_rotate_right(&self, other: Self) -> Self {
-        self.rotate_right(other as u32)
+        self.rotate_right(count_of_shift(other, $bits))
       }
 
       fn rem_s(&self, other: Self) -> Result<Self> {
         if other == 0 {
           return Err(WasmError::Trap(Trap::DivisionByZero));
         }
+        // NOTE: Remainder of MIN / -1 is 0 by spec, though its quotient overflows.
         let (divined, _) = self.overflowing_rem(other);
         Ok(divined)
       }
@@ -270,34 +426,21 @@ macro_rules! impl_integer_traits {
         if other == 0 {
           return Err(WasmError::Trap(Trap::DivisionByZero));
         }
-        let (divined, overflowed) = (*self as $unsign).overflowing_rem(other as $unsign);
-        if overflowed {
-          Err(WasmError::Trap(Trap::DivisionOverflow))
-        } else {
-          Ok(divined as $ty)
-        }
+        Ok((*self as $unsign).wrapping_rem(other as $unsign) as $ty)
       }
       fn div_u(&self, other: Self) -> Result<Self> {
         if other == 0 {
           return Err(WasmError::Trap(Trap::DivisionByZero));
         }
-        let (divined, overflowed) = (*self as $unsign).overflowing_div(other as $unsign);
-        if overflowed {
-          Err(WasmError::Trap(Trap::DivisionOverflow))
-        } else {
-          Ok(divined as $ty)
-        }
+        Ok((*self as $unsign).wrapping_div(other as $unsign) as $ty)
       }
       fn div_s(&self, other: Self) -> Result<Self> {
         if other == 0 {
           return Err(WasmError::Trap(Trap::DivisionByZero));
         }
-        let (divined, overflowed) = self.overflowing_div(other);
-        if overflowed {
-          Err(WasmError::Trap(Trap::DivisionOverflow))
-        } else {
-          Ok(divined)
-        }
+        self
+          .checked_div(other)
+          .ok_or(WasmError::Trap(Trap::IntegerOverflow))
       }
       fn copy_sign(&self, other: Self) -> Self {
         if self.signum() == other.signum() {
@@ -380,7 +523,7 @@ trait ArithmeticFloat {
 }
 
 macro_rules! impl_float_traits {
-  ($ty: ty) => {
+  ($ty: ty, $sign: expr) => {
     impl ArithmeticFloat for $ty {
       fn equal_zero(&self) -> i32 {
         if *self == 0.0 {
@@ -464,14 +607,9 @@ macro_rules! impl_float_traits {
       fn wasm_rotate_right(&self, _x: Self) -> Self {
         unreachable!();
       }
+      // NOTE: Sign bit is copied as it is even from NaN, so arithmetic of floats isn't used.
       fn copy_sign(&self, other: Self) -> Self {
-        if (self.is_sign_positive() == other.is_sign_positive())
-          || (self.is_sign_negative() == other.is_sign_negative())
-        {
-          *self
-        } else {
-          -*self
-        }
+        <$ty>::from_bits((self.to_bits() & !$sign) | (other.to_bits() & $sign))
       }
     }
   };
@@ -481,22 +619,21 @@ trait TruncFloat<T> {
   fn try_trunc_to(&self) -> Result<T>;
 }
 
+// NOTE: Casting a float out of range of integer is undefined behavior,
+// so truncated value is checked against bounds which are powers of two and exact in both of f32 and f64.
 macro_rules! impl_try_trunc {
-  ($from: ty, $to: ty) => {
+  ($from: ty, $to: ty, $min: expr, $max_exclusive: expr) => {
     impl TruncFloat<$to> for $from {
       #![allow(clippy::cast_lossless)]
       fn try_trunc_to(&self) -> Result<$to> {
         if self.is_nan() {
           return Err(WasmError::Trap(Trap::InvalidConversionToInt));
         }
-        if self.is_infinite() {
-          return Err(WasmError::Trap(Trap::IntegerOverflow));
-        }
-        let result = *self as $to;
-        if (result as $from).ne(&self.trunc()) {
+        let truncated = self.trunc();
+        if truncated < $min || truncated >= $max_exclusive {
           return Err(WasmError::Trap(Trap::IntegerOverflow));
         }
-        Ok(result as $to)
+        Ok(truncated as $to)
       }
     }
   };
@@ -521,19 +658,29 @@ impl_traits!(i64);
 impl_traits!(f32);
 impl_traits!(f64);
 
-impl_integer_traits!(i32, u32);
-impl_integer_traits!(i64, u64);
-impl_float_traits!(f32);
-impl_float_traits!(f64);
+impl_integer_traits!(i32, u32, 32);
+impl_integer_traits!(i64, u64, 64);
+impl_float_traits!(f32, 0x8000_0000);
+impl_float_traits!(f64, 0x8000_0000_0000_0000);
 
-impl_try_trunc!(f32, i32);
-impl_try_trunc!(f32, u32);
-impl_try_trunc!(f32, i64);
-impl_try_trunc!(f32, u64);
-impl_try_trunc!(f64, i32);
-impl_try_trunc!(f64, u32);
-impl_try_trunc!(f64, i64);
-impl_try_trunc!(f64, u64);
+impl_try_trunc!(f32, i32, -2_147_483_648.0, 2_147_483_648.0);
+impl_try_trunc!(f32, u32, 0.0, 4_294_967_296.0);
+impl_try_trunc!(
+  f32,
+  i64,
+  -9_223_372_036_854_775_808.0,
+  9_223_372_036_854_775_808.0
+);
+impl_try_trunc!(f32, u64, 0.0, 18_446_744_073_709_551_616.0);
+impl_try_trunc!(f64, i32, -2_147_483_648.0, 2_147_483_648.0);
+impl_try_trunc!(f64, u32, 0.0, 4_294_967_296.0);
+impl_try_trunc!(
+  f64,
+  i64,
+  -9_223_372_036_854_775_808.0,
+  9_223_372_036_854_775_808.0
+);
+impl_try_trunc!(f64, u64, 0.0, 18_446_744_073_709_551_616.0);
 
 impl Values {
   binary_inst!(and, bitand);
@@ -571,14 +718,43 @@ impl Values {
   unary_inst!(count_leading_zero, count_leading_zero);
   unary_inst!(count_trailing_zero, count_trailing_zero);
   unary_inst!(pop_count, pop_count);
-  unary_inst!(neg, neg);
+
+  // NOTE: Only sign bit of floats is flipped, which keeps payload of NaN.
+  pub fn neg(&self) -> Self {
+    match self {
+      Values::I32(l) => Values::I32(l.neg()),
+      Values::I64(l) => Values::I64(l.neg()),
+      Values::F32(l) => Values::F32(f32::from_bits(l.to_bits() ^ 0x8000_0000)),
+      Values::F64(l) => Values::F64(f64::from_bits(l.to_bits() ^ 0x8000_0000_0000_0000)),
+    }
+  }
+
+  pub fn is_subnormal(&self) -> bool {
+    match self {
+      Values::F32(l) => l.classify() == FpCategory::Subnormal,
+      Values::F64(l) => l.classify() == FpCategory::Subnormal,
+      _ => false,
+    }
+  }
+
+  pub fn flush_subnormal(self) -> Self {
+    match self {
+      Values::F32(l) if l.classify() == FpCategory::Subnormal => {
+        Values::F32(f32::from_bits(l.to_bits() & 0x8000_0000))
+      }
+      Values::F64(l) if l.classify() == FpCategory::Subnormal => {
+        Values::F64(f64::from_bits(l.to_bits() & 0x8000_0000_0000_0000))
+      }
+      x => x,
+    }
+  }
 
   pub fn abs(&self) -> Self {
     match self {
       Values::I32(l) => Values::I32(l.abs()),
       Values::I64(l) => Values::I64(l.abs()),
-      Values::F32(l) => Values::F32(unsafe { fabsf32(*l) }),
-      Values::F64(l) => Values::F64(unsafe { fabsf64(*l) }),
+      Values::F32(l) => Values::F32(f32::from_bits(l.to_bits() & !0x8000_0000)),
+      Values::F64(l) => Values::F64(f64::from_bits(l.to_bits() & !0x8000_0000_0000_0000)),
     }
   }
 
@@ -675,10 +851,79 @@ impl Values {
     }
   }
 
-  pub fn is_truthy(&self) -> bool {
-    match &self {
-      Values::I32(n) => *n != 0,
-      _ => unimplemented!(),
+  /// f32 of the exact bit pattern, e.g. NaN with a payload given by spec tests.
+  pub fn from_f32_bits(bits: u32) -> Self {
+    Values::F32(f32::from_bits(bits))
+  }
+
+  /// f64 of the exact bit pattern, e.g. NaN with a payload given by spec tests.
+  pub fn from_f64_bits(bits: u64) -> Self {
+    Values::F64(f64::from_bits(bits))
+  }
+
+  /// Bit pattern of the value as stored in memory, zero-extended for 32-bit types.
+  pub fn to_bits(&self) -> u64 {
+    match self {
+      Values::I32(n) => u64::from(*n as u32),
+      Values::I64(n) => *n as u64,
+      Values::F32(n) => u64::from(n.to_bits()),
+      Values::F64(n) => n.to_bits(),
+    }
+  }
+
+  /// Value of `value_type` whose bit pattern is `bits`, inverse of `to_bits`.
+  /// Upper half of `bits` is ignored for 32-bit types.
+  pub fn from_bits(value_type: &ValueTypes, bits: u64) -> Self {
+    match value_type {
+      ValueTypes::I32 => Values::I32(bits as u32 as i32),
+      ValueTypes::I64 => Values::I64(bits as i64),
+      ValueTypes::F32 => Values::from_f32_bits(bits as u32),
+      ValueTypes::F64 => Values::from_f64_bits(bits),
+      ValueTypes::Unit => unreachable!(),
+    }
+  }
+
+  /// Unlike `==`, NaNs with the same payload are equal and 0.0 differs from -0.0.
+  pub fn bits_eq(&self, other: &Self) -> bool {
+    self.value_type() == other.value_type() && self.to_bits() == other.to_bits()
+  }
+
+  pub fn value_type(&self) -> ValueTypes {
+    match self {
+      Values::I32(_) => ValueTypes::I32,
+      Values::I64(_) => ValueTypes::I64,
+      Values::F32(_) => ValueTypes::F32,
+      Values::F64(_) => ValueTypes::F64,
+    }
+  }
+
+  /// Low and high halves of i64 as i32, in the order which `I64Boundary::Split` passes them.
+  pub fn split_i64(n: i64) -> (Values, Values) {
+    (Values::I32(n as i32), Values::I32((n >> 32) as i32))
+  }
+
+  /// i64 whose low and high halves are `low` and `high`, inverse of `split_i64`.
+  pub fn join_i64(low: i32, high: i32) -> Values {
+    Values::I64(i64::from(high) << 32 | i64::from(low as u32))
+  }
+
+  /// Callers must check that the type is accepted by `ArgumentCoercion`.
+  pub(crate) fn coerce_into(&self, value_type: &ValueTypes) -> Values {
+    match (self, value_type) {
+      (Values::I32(v), ValueTypes::I64) => Values::I64(i64::from(*v)),
+      (Values::F32(v), ValueTypes::F64) => Values::F64(f64::from(*v)),
+      (v, _) => v.clone(),
+    }
+  }
+
+  /// Any nonzero i32 including negative one is true as specification defines.
+  pub fn is_truthy(&self, mode: &ConditionMode) -> Result<bool> {
+    match (self, mode) {
+      (Values::I32(n), _) => Ok(*n != 0),
+      (_, ConditionMode::Strict) => Err(WasmError::Trap(Trap::TypeMismatch)),
+      (Values::I64(n), ConditionMode::Coerce) => Ok(*n != 0),
+      (Values::F32(n), ConditionMode::Coerce) => Ok(*n != 0.0 && !n.is_nan()),
+      (Values::F64(n), ConditionMode::Coerce) => Ok(*n != 0.0 && !n.is_nan()),
     }
   }
 
@@ -707,6 +952,8 @@ impl Values {
       (Values::F32(l), Values::F32(r)) => {
         if l.is_nan() || r.is_nan() {
           Values::F32(f32::NAN)
+        } else if l == r {
+          Values::F32(f32::from_bits(l.to_bits() | r.to_bits()))
         } else {
           Values::F32(l.min(*r))
         }
@@ -714,6 +961,8 @@ impl Values {
       (Values::F64(l), Values::F64(r)) => {
         if l.is_nan() || r.is_nan() {
           Values::F64(f64::NAN)
+        } else if l == r {
+          Values::F64(f64::from_bits(l.to_bits() | r.to_bits()))
         } else {
           Values::F64(l.min(*r))
         }
@@ -727,6 +976,8 @@ impl Values {
       (Values::F32(l), Values::F32(r)) => {
         if l.is_nan() || r.is_nan() {
           Values::F32(f32::NAN)
+        } else if l == r {
+          Values::F32(f32::from_bits(l.to_bits() & r.to_bits()))
         } else {
           Values::F32(l.max(*r))
         }
@@ -734,6 +985,8 @@ impl Values {
       (Values::F64(l), Values::F64(r)) => {
         if l.is_nan() || r.is_nan() {
           Values::F64(f64::NAN)
+        } else if l == r {
+          Values::F64(f64::from_bits(l.to_bits() & r.to_bits()))
         } else {
           Values::F64(l.max(*r))
         }
@@ -774,36 +1027,21 @@ impl Values {
     }
   }
 
+  // NOTE: Ties are rounded to even, and sign of zero is kept as `round` does.
   pub fn nearest(&self) -> Self {
     match self {
       Values::F32(l) => {
-        if (*l > 0.0 && *l <= 0.5) || (*l < 0.0 && *l >= -0.5) {
-          Values::F32(0.0)
+        if (l - l.trunc()).abs() == 0.5 {
+          Values::F32(2.0 * (l / 2.0).round())
         } else {
-          let round = l.round();
-          let result = if round.rem(2.0).eq(&1.0) {
-            l.floor()
-          } else if round.rem(2.0).eq(&-1.0) {
-            l.ceil()
-          } else {
-            round
-          };
-          Values::F32(result)
+          Values::F32(l.round())
         }
       }
       Values::F64(l) => {
-        if (*l > 0.0 && *l <= 0.5) || (*l < 0.0 && *l >= -0.5) {
-          Values::F64(0.0)
+        if (l - l.trunc()).abs() == 0.5 {
+          Values::F64(2.0 * (l / 2.0).round())
         } else {
-          let round = l.round();
-          let result = if round.rem(2.0).eq(&1.0) {
-            l.floor()
-          } else if round.rem(2.0).eq(&-1.0) {
-            l.ceil()
-          } else {
-            round
-          };
-          Values::F64(result)
+          Values::F64(l.round())
         }
       }
       _ => unimplemented!(),
@@ -855,3 +1093,90 @@ macro_rules! impl_from_valuetypes {
 
 impl_from_valuetypes!(ValueTypes);
 impl_from_valuetypes!(&ValueTypes);
+
+/// Rust primitive which corresponds to a type of `Values`,
+/// so that host functions convert their arguments and results without matching `Values` by hand.
+/// Unsigned integers and bool are stored in signed integers of the same width.
+pub trait WasmValue: Sized {
+  fn value_type() -> ValueTypes;
+
+  fn into_value(self) -> Values;
+
+  /// Lossless conversion, which traps by `TypeMismatch` unless `value` has the type of `Self`.
+  fn from_value(value: &Values) -> Result<Self>;
+
+  /// Conversion which also wraps integers and rounds floats of the other width,
+  /// conversion between integers and floats still traps by `TypeMismatch`.
+  fn from_value_lossy(value: &Values) -> Result<Self>;
+}
+
+macro_rules! impl_wasm_value {
+  ($ty: ty, $value_type: ident, $into: expr, $from: expr, $from_other: path, $from_lossy: expr) => {
+    impl WasmValue for $ty {
+      fn value_type() -> ValueTypes {
+        ValueTypes::$value_type
+      }
+
+      fn into_value(self) -> Values {
+        Values::$value_type($into(self))
+      }
+
+      fn from_value(value: &Values) -> Result<Self> {
+        match value {
+          Values::$value_type(n) => Ok($from(*n)),
+          _ => Err(WasmError::Trap(Trap::TypeMismatch)),
+        }
+      }
+
+      fn from_value_lossy(value: &Values) -> Result<Self> {
+        match value {
+          $from_other(n) => Ok($from_lossy(*n)),
+          value => Self::from_value(value),
+        }
+      }
+    }
+
+    impl From<$ty> for Values {
+      fn from(x: $ty) -> Self {
+        x.into_value()
+      }
+    }
+
+    impl TryFrom<Values> for $ty {
+      type Error = WasmError;
+      fn try_from(value: Values) -> Result<Self> {
+        Self::from_value(&value)
+      }
+    }
+  };
+}
+
+impl_wasm_value!(i32, I32, |n| n, |n| n, Values::I64, |n: i64| n as i32);
+impl_wasm_value!(
+  u32,
+  I32,
+  |n| n as i32,
+  |n: i32| n as u32,
+  Values::I64,
+  |n: i64| n as u32
+);
+impl_wasm_value!(i64, I64, |n| n, |n| n, Values::I32, |n: i32| i64::from(n));
+// NOTE: i32 is regarded as unsigned for u64, so it's extended by zero.
+impl_wasm_value!(
+  u64,
+  I64,
+  |n| n as i64,
+  |n: i64| n as u64,
+  Values::I32,
+  |n: i32| u64::from(n as u32)
+);
+impl_wasm_value!(f32, F32, |n| n, |n| n, Values::F64, |n: f64| n as f32);
+impl_wasm_value!(f64, F64, |n| n, |n| n, Values::F32, |n: f32| f64::from(n));
+impl_wasm_value!(
+  bool,
+  I32,
+  |b| if b { 1 } else { 0 },
+  |n| n != 0,
+  Values::I64,
+  |n: i64| n != 0
+);
@@ -1,25 +1,51 @@
-#[cfg(not(test))]
+#[cfg(not(any(test, feature = "std")))]
 use alloc::prelude::*;
+use alloc::rc::Rc;
 use alloc::vec::Vec;
+use backtrace::BacktraceFrame;
+#[cfg(feature = "checked-interp")]
+use checked;
+use core::cell::{Cell, RefCell};
+use core::convert::TryFrom;
+use decode::Producers;
+use engine::InstanceSlot;
 use error::{Result, Trap, WasmError};
 use frame::Frame;
-use function::FunctionInstance;
+use function::{FunctionInstance, FunctionType, RawHostFunction, MAX_RAW_HOST_FN_VALUES};
+use global::{GlobalInstance, GlobalInstances};
 use indice::Indice;
 use isa::Isa;
 use label::{Label, LabelKind};
-use memory::MemoryInstances;
+use memory::{MemoryGrowListener, MemoryGrowth, MemoryInstances, MemoryView};
+use metrics::Metrics;
 use module::{
-    ExportDescriptor, ExternalInterface, ExternalModule, ExternalModules, InternalModule,
-    ModuleDescriptor, ModuleName,
+    unknown_export, ExportDescriptor, ExternalInterface, ExternalModule, ExternalModules,
+    InternalModule, ModuleDescriptor,
 };
+use resume::{BoundedRun, ResumeToken};
+use snapshot::{FrameSnapshot, StackSnapshot};
 use stack::{Stack, StackEntry};
+#[cfg(any(test, feature = "std"))]
+use std::io;
 use store::Store;
-use value::Values;
-use value_type::TYPE_UNIT;
+use symbol::Symbol;
+use trap::{RunOutcome, TrapDetails};
+#[cfg(feature = "float")]
+use value::SubnormalMode;
+use value::{I64Boundary, Values};
+use value_type::ValueTypes;
+
+// Instance whose functions, tables, memory and globals instructions of a frame refer to.
+enum SourceOfFrame {
+    Own,
+    // NOTE: Defining instance is preferred, since a function may be called through a table shared by instances.
+    Defining(Rc<ExternalModule>),
+    Registered(Symbol),
+}
 
 macro_rules! impl_load_inst {
     ($fn_name: ident, $load_fn: ident, $ty: ty) => {
-        fn $fn_name(&self, offset: u32, load_data_width: u32, source_of_frame: &ModuleName) -> Result<$ty> {
+        fn $fn_name(&self, offset: u32, load_data_width: u32, source_of_frame: &SourceOfFrame) -> Result<$ty> {
             let memory_instances = self.get_memory_instances(source_of_frame)?;
             let width = load_data_width / 8;
             let i = self.stack.pop_value_ext_i32() as u32;
@@ -33,6 +59,9 @@ macro_rules! impl_load_inst {
             };
             let data = memory_instances
                 .$load_fn(effective_address, ptr);
+            if self.counts_metrics {
+                self.metrics.borrow_mut().memory_bytes_read += u64::from(width);
+            }
             Ok(data)
         }
     };
@@ -40,7 +69,7 @@ macro_rules! impl_load_inst {
 
 macro_rules! impl_load_to {
     ($fn_name: ident, $load_fn: ident, $path: path, $ty: ty) => {
-        fn $fn_name(&mut self, offset: u32, width: u32, sign: bool, source_of_frame: &ModuleName) -> Result<()> {
+        fn $fn_name(&mut self, offset: u32, width: u32, sign: bool, source_of_frame: &SourceOfFrame) -> Result<()> {
             let mut value = self.$load_fn(offset, width, source_of_frame)?;
             if sign {
                 let is_msb_one = value & (1 << (width - 1)) != 0;
@@ -66,6 +95,7 @@ macro_rules! impl_unary_inst {
     };
 }
 
+#[cfg(feature = "float")]
 macro_rules! impl_try_unary_inst {
     ($op: ident) => {
         fn $op(&self) -> Result<()> {
@@ -121,56 +151,104 @@ pub struct ModuleInstance {
     pub(crate) stack: Stack,
     internal_module: InternalModule,
     external_modules: ExternalModules,
+    metrics: RefCell<Metrics>,
+    // NOTE: Counters are not touched unless enabled, since some of them are counted on every instruction.
+    counts_metrics: bool,
+    backtrace: RefCell<Vec<BacktraceFrame>>,
+    trap_snapshot: RefCell<Option<StackSnapshot>>,
+    // NOTE: None means unlimited.
+    instruction_budget: Cell<Option<u64>>,
+    // Incremented on each invocation, so that tokens of discarded invocations get stale.
+    generation: Cell<u64>,
+    suspended: Cell<Option<u64>>,
+    // Arguments of yield function which suspended evaluation.
+    yielded: RefCell<Option<Vec<Values>>>,
+    // Count of instances of an engine is kept while the instance lives.
+    pub(crate) engine_slot: Option<InstanceSlot>,
+    memory_grow_listener: Option<MemoryGrowListener>,
+    // Functions of the instance refer to it weakly, wherever they're called from.
+    defining_instance: Rc<ExternalModule>,
 }
 
 impl ModuleInstance {
     impl_load_inst!(load_data_32, load_data_32, u32);
     impl_load_inst!(load_data_64, load_data_64, u64);
+    #[cfg(feature = "float")]
     impl_load_inst!(load_data_f32, load_data_f32, f32);
+    #[cfg(feature = "float")]
     impl_load_inst!(load_data_f64, load_data_f64, f64);
 
     impl_load_to!(load_data_to_i32, load_data_32, Values::I32, i32);
     impl_load_to!(load_data_to_i64, load_data_64, Values::I64, i64);
 
+    #[cfg(feature = "float")]
     impl_unary_inst!(sqrt);
+    #[cfg(feature = "float")]
     impl_unary_inst!(ceil);
+    #[cfg(feature = "float")]
     impl_unary_inst!(floor);
+    #[cfg(feature = "float")]
     impl_unary_inst!(trunc);
+    #[cfg(feature = "float")]
     impl_unary_inst!(nearest);
     impl_unary_inst!(count_leading_zero);
     impl_unary_inst!(count_trailing_zero);
     impl_unary_inst!(pop_count);
     impl_unary_inst!(equal_zero);
+    #[cfg(feature = "float")]
     impl_unary_inst!(reinterpret);
+    #[cfg(feature = "float")]
     impl_unary_inst!(abs);
+    #[cfg(feature = "float")]
     impl_unary_inst!(neg);
     impl_unary_inst!(extend_u32_to_i64);
     impl_unary_inst!(extend_i32_to_i64);
+    #[cfg(feature = "float")]
     impl_unary_inst!(convert_sign_i32_to_f32);
+    #[cfg(feature = "float")]
     impl_unary_inst!(convert_unsign_i32_to_f32);
+    #[cfg(feature = "float")]
     impl_unary_inst!(convert_sign_i64_to_f64);
+    #[cfg(feature = "float")]
     impl_unary_inst!(convert_unsign_i64_to_f64);
+    #[cfg(feature = "float")]
     impl_unary_inst!(convert_sign_i32_to_f64);
+    #[cfg(feature = "float")]
     impl_unary_inst!(convert_unsign_i32_to_f64);
+    #[cfg(feature = "float")]
     impl_unary_inst!(convert_sign_i64_to_f32);
+    #[cfg(feature = "float")]
     impl_unary_inst!(convert_unsign_i64_to_f32);
+    #[cfg(feature = "float")]
     impl_unary_inst!(promote_f32_to_f64);
+    #[cfg(feature = "float")]
     impl_unary_inst!(demote_f64_to_f32);
 
+    #[cfg(feature = "float")]
     impl_try_unary_inst!(trunc_f32_to_sign_i32);
+    #[cfg(feature = "float")]
     impl_try_unary_inst!(trunc_f32_to_unsign_i32);
+    #[cfg(feature = "float")]
     impl_try_unary_inst!(trunc_f64_to_sign_i64);
+    #[cfg(feature = "float")]
     impl_try_unary_inst!(trunc_f64_to_unsign_i64);
+    #[cfg(feature = "float")]
     impl_try_unary_inst!(trunc_f64_to_sign_i32);
+    #[cfg(feature = "float")]
     impl_try_unary_inst!(trunc_f64_to_unsign_i32);
+    #[cfg(feature = "float")]
     impl_try_unary_inst!(trunc_f32_to_sign_i64);
+    #[cfg(feature = "float")]
     impl_try_unary_inst!(trunc_f32_to_unsign_i64);
 
     impl_binary_inst!(add);
     impl_binary_inst!(sub);
     impl_binary_inst!(mul);
+    #[cfg(feature = "float")]
     impl_binary_inst!(div_f);
+    #[cfg(feature = "float")]
     impl_binary_inst!(min);
+    #[cfg(feature = "float")]
     impl_binary_inst!(max);
     impl_binary_inst!(less_than);
     impl_binary_inst!(less_than_unsign);
@@ -190,6 +268,7 @@ impl ModuleInstance {
     impl_binary_inst!(shift_right_unsign);
     impl_binary_inst!(wasm_rotate_left);
     impl_binary_inst!(wasm_rotate_right);
+    #[cfg(feature = "float")]
     impl_binary_inst!(copy_sign);
 
     impl_try_binary_inst!(div_u);
@@ -197,7 +276,7 @@ impl ModuleInstance {
     impl_try_binary_inst!(rem_s);
     impl_try_binary_inst!(rem_u);
 
-    fn store(&self, data_width: u32, offset: u32, source_of_frame: &ModuleName) -> Result<()> {
+    fn store(&self, data_width: u32, offset: u32, source_of_frame: &SourceOfFrame) -> Result<()> {
         let memory_instances = self.get_memory_instances(source_of_frame)?;
         let c = self.stack.pop_value_ext();
         let width = data_width / 8;
@@ -211,6 +290,9 @@ impl ModuleInstance {
             return Err(WasmError::Trap(Trap::MemoryAccessOutOfBounds));
         };
         memory_instances.store_data(effective_address, ptr, &c);
+        if self.counts_metrics {
+            self.metrics.borrow_mut().memory_bytes_written += u64::from(width);
+        }
         Ok(())
     }
 
@@ -224,86 +306,472 @@ impl ModuleInstance {
         external_modules: ExternalModules,
         stack_height: usize,
     ) -> Result<Self> {
+        let defining_instance = Rc::new(ExternalModule::from(&store));
+        for function_instance in store.function_instances.iter() {
+            function_instance.claim_defining_instance(&defining_instance);
+        }
         Ok(ModuleInstance {
             store,
             internal_module,
             stack: Stack::new(stack_height),
             external_modules,
+            metrics: RefCell::new(Metrics::default()),
+            counts_metrics: false,
+            backtrace: RefCell::new(vec![]),
+            trap_snapshot: RefCell::new(None),
+            instruction_budget: Cell::new(None),
+            generation: Cell::new(0),
+            suspended: Cell::new(None),
+            yielded: RefCell::new(None),
+            engine_slot: None,
+            memory_grow_listener: None,
+            defining_instance,
         })
     }
 
-    pub fn get_function_instance(&self, idx: &Indice) -> Option<FunctionInstance> {
+    pub fn get_function_instance(&self, idx: &Indice) -> Option<&FunctionInstance> {
         self.store.get_function_instance(idx)
     }
 
+    /// Names of exports in the order declared in the binary.
+    pub fn export_names(&self) -> Vec<&str> {
+        self.internal_module.export_names()
+    }
+
+    /// Languages, tools and SDKs recorded in `producers` section, None if the module has no valid one.
+    pub fn producers(&self) -> Option<&Producers> {
+        self.internal_module.producers.as_ref()
+    }
+
+    pub(crate) fn has_export(&self, name: &str) -> bool {
+        self.internal_module.get_export_by_key(name).is_some()
+    }
+
     pub fn export_module(&self) -> ExternalModule {
         ExternalModule::from(&self.store)
     }
 
-    fn get_local(&self, idx: &Indice) -> Result<()> {
-        let frame_ptr = self.stack.frame_ptr();
-        let index = idx.to_usize() + frame_ptr;
-        let value = self.stack.get(index)?;
-        self.stack.push(value)?;
-        Ok(())
+    pub fn write_memory(&self, offset: u32, bytes: &[u8]) -> Result<()> {
+        self.store.write_memory(offset, bytes)
     }
 
-    fn set_local(&self, idx: &Indice) -> Result<()> {
-        let value = self.stack.pop().map(|s| s.to_owned())?;
-        let frame_ptr = self.stack.frame_ptr();
-        self.stack.set(idx.to_usize() + frame_ptr, value)?;
-        Ok(())
+    pub fn read_memory(&self, offset: u32, length: usize) -> Result<Vec<u8>> {
+        self.store.read_memory(offset, length)
+    }
+
+    /// Borrow linear memory without copying, as long as it's stored contiguously, e.g. by `MemoryStrategy::Eager`.
+    /// Guest can't run while the view lives, since it borrows this instance.
+    pub fn memory_view(&self) -> Result<MemoryView> {
+        self.store.memory_instances.view()
     }
 
-    fn tee_local(&self, idx: &Indice) -> Result<()> {
-        let value = self.stack.pop().map(|s| s.to_owned())?;
-        self.stack.push(value.clone())?;
-        let frame_ptr = self.stack.frame_ptr();
-        self.stack.set(idx.to_usize() + frame_ptr, value)?;
+    /// Persist contents of linear memory, e.g. to restore guest heap after restart of process.
+    /// See `MemoryInstance::save_image` for its format.
+    #[cfg(any(test, feature = "std"))]
+    pub fn save_memory_image<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.store.memory_instances.save_image(writer)
+    }
+
+    /// Restore contents of linear memory saved by `save_memory_image`.
+    #[cfg(any(test, feature = "std"))]
+    pub fn load_memory_image<R: io::Read>(&self, reader: &mut R) -> io::Result<()> {
+        self.store.memory_instances.load_image(reader)
+    }
+
+    // NOTE: Globals are looked up by exports of this module, so that imported ones are named as re-exported.
+    fn exported_globals(&self) -> Vec<(String, GlobalInstance)> {
+        self.internal_module
+            .exports
+            .iter()
+            .filter_map(|export| match &export.descriptor {
+                ModuleDescriptor::ExportDescriptor(ExportDescriptor::Global(idx)) => self
+                    .store
+                    .global_instances
+                    .get_global_instance(idx)
+                    .map(|global| (export.name.to_owned(), global)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Values of exported mutable globals by their export names, in order of exports.
+    /// Unexported globals are not captured, so modules have to export ones which hold their state.
+    pub fn export_globals(&self) -> Vec<(String, Values)> {
+        self.exported_globals()
+            .into_iter()
+            .filter(|(_, global)| global.is_mutable())
+            .map(|(name, global)| (name, global.get_value()))
+            .collect()
+    }
+
+    /// Restore values captured by `export_globals`, e.g. from another instance of the same module.
+    /// Nothing is restored unless every name refers to an exported mutable global of the same type.
+    pub fn import_globals(&self, globals: &[(String, Values)]) -> Result<()> {
+        let exported_globals = self.exported_globals();
+        let mut restored = vec![];
+        for (name, value) in globals.iter() {
+            let found = exported_globals
+                .iter()
+                .find(|(export_name, _)| export_name == name);
+            let global = match found {
+                Some((_, global)) => global,
+                None => {
+                    let names = exported_globals
+                        .iter()
+                        .map(|(export_name, _)| export_name.as_str())
+                        .collect::<Vec<_>>();
+                    return Err(unknown_export(name, &names, &self.store.export_suggestion));
+                }
+            };
+            if !global.is_mutable() || global.value_type() != value.value_type() {
+                return Err(WasmError::Trap(Trap::IncompatibleGlobal(name.to_owned())));
+            }
+            restored.push((global, value));
+        }
+        for (global, value) in restored.into_iter() {
+            global.set_value(value.clone());
+        }
         Ok(())
     }
 
-    fn get_global(&self, idx: &Indice) -> Result<()> {
-        let value = self.store.get_global(idx)?;
+    /// Read a byte slice which guest code passed as a pair of offset and length.
+    pub fn read_bytes(&self, offset: u32, length: u32) -> Result<Vec<u8>> {
+        self.read_memory(offset, length as usize)
+    }
+
+    /// Read an UTF-8 string which guest code passed as a pair of offset and length.
+    pub fn read_string(&self, offset: u32, length: u32) -> Result<String> {
+        let bytes = self.read_bytes(offset, length)?;
+        String::from_utf8(bytes).map_err(|_| WasmError::Trap(Trap::InvalidUTF8Encoding))
+    }
+
+    /// Allocate a region in linear memory by an allocator exported from guest
+    /// like `__wbindgen_malloc`, which takes size and returns offset, then copy bytes into it.
+    /// Returns a pair of offset and length to pass to guest code.
+    /// Bytes of 4 GiB or more never fit in linear memory, so they're rejected before allocation.
+    pub fn pass_bytes(&mut self, allocator: &str, bytes: &[u8]) -> Result<(u32, u32)> {
+        let length = u32::try_from(bytes.len())
+            .map_err(|_| WasmError::Trap(Trap::MemoryAccessOutOfBounds))?;
+        let results = self.run(allocator, vec![Values::I32(length as i32)])?;
+        let offset = match results.as_slice() {
+            [Values::I32(offset)] => *offset as u32,
+            _ => return Err(WasmError::Trap(Trap::TypeMismatch)),
+        };
+        self.write_memory(offset, bytes)?;
+        Ok((offset, length))
+    }
+
+    pub fn pass_string(&mut self, allocator: &str, string: &str) -> Result<(u32, u32)> {
+        self.pass_bytes(allocator, string.as_bytes())
+    }
+
+    pub fn metrics(&self) -> Metrics {
+        self.metrics.borrow().clone()
+    }
+
+    /// Start or stop counting `Metrics`, which are disabled by default.
+    /// Counters collected so far are kept until `reset_metrics`.
+    pub fn enable_metrics(&mut self, enabled: bool) {
+        self.counts_metrics = enabled;
+    }
+
+    /// Reset counters, e.g. to collect metrics per invocation.
+    pub fn reset_metrics(&self) {
+        self.metrics.replace(Metrics::default());
+    }
+
+    /// Call stack at the time of the last trap, innermost first.
+    pub fn backtrace(&self) -> Vec<BacktraceFrame> {
+        self.backtrace.borrow().clone()
+    }
+
+    fn backtrace_frame(&self, frame: &Frame) -> BacktraceFrame {
+        let function_instance = &frame.function_instance;
+        let offset = function_instance
+            .instruction_offsets()
+            .and_then(|offsets| offsets.offset_before(frame.ptr()));
+        // NOTE: Offsets of imported functions point code section of other modules.
+        let location = match (&self.internal_module.debug_lines, offset) {
+            (Some(debug_lines), Some(offset)) if self.is_own_function(function_instance) => {
+                debug_lines.location_of(offset)
+            }
+            _ => None,
+        };
+        BacktraceFrame {
+            function_name: function_instance.export_name().cloned(),
+            offset,
+            location,
+        }
+    }
+
+    fn capture_backtrace(&self, trapped_frame: &Frame) {
+        let mut backtrace = vec![self.backtrace_frame(trapped_frame)];
+        for frame in self.stack.frames().iter().rev() {
+            backtrace.push(self.backtrace_frame(frame));
+        }
+        self.backtrace.replace(backtrace);
+    }
+
+    /// Frames and operands which are on the stack at this moment.
+    pub fn stack_snapshot(&self) -> StackSnapshot {
+        self.snapshot_with(None)
+    }
+
+    /// Stack at the time of the last trap.
+    /// Operands of the trapped instruction are already popped.
+    pub fn trap_snapshot(&self) -> Option<StackSnapshot> {
+        self.trap_snapshot.borrow().clone()
+    }
+
+    fn frame_snapshot(&self, frame: &Frame, end_of_operands: usize) -> FrameSnapshot {
+        let function_instance = &frame.function_instance;
+        let start_of_locals = frame.activation.base_ptr;
+        let end_of_locals = start_of_locals + frame.count_of_locals();
+        FrameSnapshot {
+            function_index: self
+                .store
+                .function_instances
+                .iter()
+                .position(|f| f.is_same_instance(function_instance))
+                .map(|idx| idx as u32),
+            function_name: function_instance.export_name().cloned(),
+            ptr: frame.ptr(),
+            locals: self.stack.values_between(start_of_locals, end_of_locals),
+            // NOTE: Label of the frame lies between locals and operands.
+            operands: self
+                .stack
+                .values_between(end_of_locals + 1, end_of_operands),
+        }
+    }
+
+    // NOTE: Frame under evaluation is popped from call stack, so it has to be passed.
+    fn snapshot_with(&self, current_frame: Option<&Frame>) -> StackSnapshot {
+        let mut end_of_operands = self.stack.stack_ptr();
+        let mut frames = vec![];
+        for frame in current_frame
+            .into_iter()
+            .chain(self.stack.frames().iter().rev())
+        {
+            frames.push(self.frame_snapshot(frame, end_of_operands));
+            end_of_operands = frame.activation.base_ptr;
+        }
+        StackSnapshot { frames }
+    }
+
+    /// Cap count of pages which memory.grow can reach, regardless of the limit declared by module.
+    pub fn limit_memory_pages(&self, max_pages: u32) -> Result<()> {
+        self.store.memory_instances.limit_pages_by_host(max_pages)
+    }
+
+    /// Notify `listener` of every memory.grow executed by functions of this instance, whether it succeeded or not.
+    /// The listener runs while the instance is borrowed, so it can't call into the instance.
+    /// Embedders record the growth instead, e.g. to call a collector of guest after the invocation.
+    pub fn on_memory_grow<F>(&mut self, listener: F)
+    where
+        F: Fn(&MemoryGrowth) + 'static,
+    {
+        self.memory_grow_listener = Some(MemoryGrowListener(Rc::new(listener)));
+    }
+
+    // NOTE: Only results of float arithmetic are flushed, operands are kept as is.
+    #[cfg(feature = "float")]
+    fn flush_subnormal(&self) -> Result<()> {
+        if self.store.subnormal_mode == SubnormalMode::Strict {
+            return Ok(());
+        }
+        self.stack.flush_subnormal_on_top()
+    }
+
+    // NOTE: Validator rejects unknown locals, so an index out of the frame means inconsistency of interpreter
+    // or a module which isn't validated. It traps rather than accessing entries of other frames.
+    fn local_ptr(&self, frame: &Frame, idx: &Indice) -> Result<usize> {
+        let idx = idx.to_usize();
+        let count_of_locals = frame.count_of_locals();
+        debug_assert!(
+            idx < count_of_locals,
+            "Local {} is out of range of {} locals.",
+            idx,
+            count_of_locals
+        );
+        if idx >= count_of_locals {
+            return Err(WasmError::Trap(Trap::OutOfRangeLocal));
+        }
+        Ok(self.stack.frame_ptr() + idx)
+    }
+
+    fn get_local(&self, frame: &Frame, idx: &Indice) -> Result<()> {
+        let index = self.local_ptr(frame, idx)?;
+        let entry = self.stack.get_value(index)?;
+        self.stack.push(entry)
+    }
+
+    fn set_local(&self, frame: &Frame, idx: &Indice) -> Result<()> {
+        let index = self.local_ptr(frame, idx)?;
+        let entry = self.stack.pop_value_entry()?;
+        self.stack.set_value(index, entry)
+    }
+
+    fn tee_local(&self, frame: &Frame, idx: &Indice) -> Result<()> {
+        let index = self.local_ptr(frame, idx)?;
+        let entry = self.stack.pop_value_entry()?;
+        self.stack.push(entry.clone())?;
+        self.stack.set_value(index, entry)
+    }
+
+    fn get_global(&self, idx: &Indice, source_of_frame: &SourceOfFrame) -> Result<()> {
+        let value = self
+            .get_global_instances(source_of_frame)?
+            .get_global(idx)?;
         self.stack.push(StackEntry::new_value(value))?;
         Ok(())
     }
 
-    fn set_global(&mut self, idx: &Indice) -> Result<()> {
+    fn set_global(&mut self, idx: &Indice, source_of_frame: &SourceOfFrame) -> Result<()> {
         let value = self.stack.pop_value_ext();
-        self.store.set_global(idx, value);
+        self.get_global_instances(source_of_frame)?
+            .set_global(idx, value);
         Ok(())
     }
 
-    fn get_memory_instances(&self, source_of_frame: &ModuleName) -> Result<MemoryInstances> {
-        Ok(match source_of_frame {
-            Some(module_name) => {
-                self.external_modules
-                    .get(&Some(module_name.to_string()))?
-                    .memory_instances
+    fn source_of_frame(&self, function_instance: &FunctionInstance) -> SourceOfFrame {
+        match function_instance.defining_instance() {
+            Some(ref instance) if Rc::ptr_eq(instance, &self.defining_instance) => {
+                SourceOfFrame::Own
             }
-            None => self.store.memory_instances.clone(),
-        })
+            Some(instance) => SourceOfFrame::Defining(instance),
+            None => match function_instance.get_source_symbol() {
+                Some(module_name) => SourceOfFrame::Registered(module_name),
+                None => SourceOfFrame::Own,
+            },
+        }
+    }
+
+    fn is_own_function(&self, function_instance: &FunctionInstance) -> bool {
+        match self.source_of_frame(function_instance) {
+            SourceOfFrame::Own => true,
+            _ => false,
+        }
+    }
+
+    fn get_memory_instances(&self, source_of_frame: &SourceOfFrame) -> Result<MemoryInstances> {
+        match source_of_frame {
+            SourceOfFrame::Own => Ok(self.store.memory_instances.clone()),
+            SourceOfFrame::Defining(instance) => Ok(instance.memory_instances.clone()),
+            SourceOfFrame::Registered(module_name) => {
+                self.external_modules.get_memory_instances(module_name)
+            }
+        }
+    }
+
+    fn get_global_instances(&self, source_of_frame: &SourceOfFrame) -> Result<GlobalInstances> {
+        match source_of_frame {
+            SourceOfFrame::Own => Ok(self.store.global_instances.clone()),
+            SourceOfFrame::Defining(instance) => Ok(instance.global_instances.clone()),
+            SourceOfFrame::Registered(module_name) => {
+                self.external_modules.get_global_instances(module_name)
+            }
+        }
     }
 
     fn evaluate_instructions(&mut self, frame: &Frame) -> Result<()> {
-        use self::Isa::*;
         if let FunctionInstance::HostFn(ref f) = &frame.function_instance {
-            let arity = frame.function_instance.get_arity();
+            let arity = frame.arity();
+            // NOTE: Only a raw host function invoked from embedder runs in a frame,
+            // since calls from guest code read arguments off the operand stack.
+            if let Some(raw) = f.raw() {
+                let mut arguments = [0; MAX_RAW_HOST_FN_VALUES];
+                for (i, argument) in arguments.iter_mut().take(arity as usize).enumerate() {
+                    self.get_local(frame, &Indice::from(i as u32))?;
+                    *argument = self.stack.pop_value_ext().to_bits();
+                }
+                return self.call_raw(raw, &arguments, frame.function_instance.get_return_type());
+            }
             let mut arguments = vec![];
             for i in 0..arity {
-                self.get_local(&Indice::from(i))?;
+                self.get_local(frame, &Indice::from(i))?;
                 arguments.push(self.stack.pop_value_ext());
             }
-            let results = f.call(arguments.as_slice());
+            if frame.function_instance.is_yield() {
+                self.yielded.replace(Some(arguments));
+                return Ok(());
+            }
+            let results = f.call(arguments.as_slice())?;
             for r in results.into_iter() {
                 self.stack.push(StackEntry::new_value(r))?;
             }
             return Ok(());
         }
-        let source_of_frame = frame.function_instance.get_source_module_name();
-        while let Some(expression) = frame.pop_ref() {
-            match Isa::from(*expression) {
+        // NOTE: Budget and fuel are counted locally, and consumed when evaluation of the frame stops.
+        let fuel = match &self.store.engine {
+            Some(engine) => engine.remaining_fuel(),
+            None => None,
+        };
+        let limit = match (self.instruction_budget.get(), fuel) {
+            (Some(budget), Some(fuel)) => Some(budget.min(fuel)),
+            (budget, fuel) => budget.or(fuel),
+        };
+        let mut executed = 0;
+        let result = self.dispatch_instructions(frame, limit, &mut executed);
+        self.consume_budget(executed);
+        result
+    }
+
+    /// Call a raw host function by arguments on top of the operand stack without building a frame.
+    fn call_raw_from_stack(
+        &mut self,
+        raw: RawHostFunction,
+        function_type: &FunctionType,
+    ) -> Result<()> {
+        let mut arguments = [0; MAX_RAW_HOST_FN_VALUES];
+        let arity = function_type.parameters().len();
+        for argument in arguments[..arity].iter_mut().rev() {
+            *argument = self.stack.pop_value_ext().to_bits();
+        }
+        self.call_raw(raw, &arguments, function_type.returns())
+    }
+
+    fn call_raw(
+        &mut self,
+        raw: RawHostFunction,
+        arguments: &[u64; MAX_RAW_HOST_FN_VALUES],
+        returns: &[ValueTypes],
+    ) -> Result<()> {
+        let results = {
+            let mut memory_view = self.store.memory_instances.view().ok();
+            raw.call(
+                arguments,
+                memory_view.as_mut().map(|view| view.as_mut_slice()),
+            )
+        };
+        for (value_type, bits) in returns.iter().zip(results.iter()) {
+            let value = Values::from_bits(value_type, *bits);
+            self.stack.push(StackEntry::new_value(value))?;
+        }
+        Ok(())
+    }
+
+    fn dispatch_instructions(
+        &mut self,
+        frame: &Frame,
+        limit: Option<u64>,
+        executed: &mut u64,
+    ) -> Result<()> {
+        use self::Isa::*;
+        let source_of_frame = self.source_of_frame(&frame.function_instance);
+        let counts_metrics = self.counts_metrics;
+        let is_interruptible = self.store.engine.is_some();
+        while Some(*executed) != limit && !(is_interruptible && self.is_interrupted()) {
+            let expression = match frame.pop_ref() {
+                Some(expression) => expression,
+                None => break,
+            };
+            #[cfg(feature = "checked-interp")]
+            checked::check_operands(frame, expression, &self.stack);
+            *executed += 1;
+            if counts_metrics {
+                self.metrics.borrow_mut().count_instruction(expression);
+            }
+            match Isa::from(expression) {
                 Reserved => unreachable!(),
                 Unreachable => return Err(WasmError::Trap(Trap::Unreachable)),
                 Return => {
@@ -360,7 +828,7 @@ impl ModuleInstance {
                     let else_size = frame.pop_raw_u32()?;
                     let continuation = start_of_label + if_size + else_size;
                     let block_type = frame.pop_runtime_type()?;
-                    if cond.is_truthy() {
+                    if cond.is_truthy(&self.store.condition_mode)? {
                         let label = StackEntry::new_label(continuation, block_type, LabelKind::If);
                         self.stack.push(label)?;
                     } else {
@@ -383,112 +851,143 @@ impl ModuleInstance {
                 BrIf => {
                     let label = Indice::from(frame.pop_raw_u32()?);
                     let cond = &self.stack.pop_value_ext();
-                    if cond.is_truthy() {
+                    if cond.is_truthy(&self.store.condition_mode)? {
                         let continuation = self.stack.jump_to_label(&label)?;
                         frame.jump_to(continuation);
                     };
                 }
                 BrTable => {
                     let len = frame.pop_raw_u32()?;
-                    let mut indices = vec![];
-                    for _ in 0..len {
-                        let idx = frame.pop_raw_u32()?;
-                        indices.push(Indice::from(idx));
-                    }
-                    let idx = &Indice::from(frame.pop_raw_u32()?);
+                    self.store.execution_limits.check_br_table(len + 1)?;
+                    // NOTE: Labels are followed by the default one, so the taken label is read in place.
                     let i = self.stack.pop_value_ext_i32() as u32;
-                    let l = if i < len {
-                        indices.get(i as usize)?
-                    } else {
-                        idx
-                    };
-                    let continuation = self.stack.jump_to_label(l)?;
+                    frame.jump_to(frame.ptr() + 4 * i.min(len));
+                    let label = Indice::from(frame.pop_raw_u32()?);
+                    let continuation = self.stack.jump_to_label(&label)?;
                     frame.jump_to(continuation);
                 }
                 Call => {
+                    if counts_metrics {
+                        self.metrics.borrow_mut().calls += 1;
+                    }
                     let idx = Indice::from(frame.pop_raw_u32()?);
                     let function_instance = match &source_of_frame {
-                        Some(module_name) => self
+                        SourceOfFrame::Own => self.store.get_function_instance(&idx)?.clone(),
+                        SourceOfFrame::Defining(instance) => {
+                            instance.function_instances.get(idx.to_usize()).cloned()?
+                        }
+                        SourceOfFrame::Registered(module_name) => self
                             .external_modules
-                            // FIXME: Drop owning of name to search something.
-                            .get_function_instance(&Some(module_name.to_owned()), idx.to_usize())
-                            .map(|x| x.clone())?,
-                        None => self.store.get_function_instance(&idx)?,
+                            .get_function_instance(module_name, idx.to_usize())?,
                     };
-                    let arity = function_instance.get_arity();
-                    let mut arguments = vec![];
-                    for _ in 0..arity {
-                        arguments.push(self.stack.pop()?);
+                    if let Some(raw) = function_instance.raw() {
+                        self.call_raw_from_stack(raw, function_instance.function_type_ref())?;
+                        continue;
                     }
+                    let mut arguments =
+                        self.stack.pop_arguments(function_instance.function_type_ref())?;
                     let frame = Frame::new(
-                        self.stack.stack_ptr(),
-                        self.stack.frame_ptr(),
+                        self.stack.activation_record(),
                         function_instance,
                         &mut arguments,
-                    );
+                    )?;
                     self.stack.push_frame(frame)?;
                     break;
                 }
                 CallIndirect => {
+                    if counts_metrics {
+                        self.metrics.borrow_mut().calls += 1;
+                    }
                     let idx = Indice::from(frame.pop_raw_u32()?);
                     // NOTE: Due to only single table instance allowed, `ta` always equal to 0.
                     let ta = frame.get_table_address();
                     let table = match &source_of_frame {
-                        Some(module_name) => self
-                            .external_modules
-                            .get_table_instance(&Some(module_name.to_owned()), &ta)?,
-                        None => self.store.get_table_at(&ta)?,
+                        SourceOfFrame::Own => self.store.get_table_at(&ta)?,
+                        SourceOfFrame::Defining(instance) => {
+                            instance.table_instances.get_table_at(&ta)?
+                        }
+                        SourceOfFrame::Registered(module_name) => {
+                            self.external_modules.get_table_instance(module_name, &ta)?
+                        }
                     };
-                    let i = self.stack.pop_value_ext_i32();
-                    if i > table.len() as i32 {
-                        return Err(WasmError::Trap(Trap::UndefinedElement));
-                    }
-                    let function_instance = table.get_function_instance(i as u32)?;
+                    let i = table.index_of(&self.stack.pop_value_ext())?;
+                    let function_instance = table.get_function_instance_u64(i)?;
                     let mut arguments = {
                         let actual_fn_ty = function_instance.function_type_ref();
-                        let expect_fn_ty = &match &source_of_frame {
-                            Some(module_name) => self
-                                .external_modules
-                                .get_function_type(&Some(module_name.to_owned()), idx.to_u32())?,
-                            None => self.store.get_function_type(&idx)?.clone(),
+                        let is_expected_type = match &source_of_frame {
+                            SourceOfFrame::Own => {
+                                self.store.get_function_type(&idx)? == actual_fn_ty
+                            }
+                            SourceOfFrame::Defining(instance) => {
+                                instance.function_types.get(idx.to_usize())? == actual_fn_ty
+                            }
+                            SourceOfFrame::Registered(module_name) => {
+                                &self
+                                    .external_modules
+                                    .get_function_type(module_name, idx.to_u32())?
+                                    == actual_fn_ty
+                            }
                         };
-                        if actual_fn_ty != expect_fn_ty {
+                        if !is_expected_type {
                             return Err(WasmError::Trap(Trap::IndirectCallTypeMismatch));
                         }
-                        let mut arg = vec![];
-                        for _ in 0..actual_fn_ty.get_arity() {
-                            arg.push(self.stack.pop()?);
+                        if let Some(raw) = function_instance.raw() {
+                            self.call_raw_from_stack(raw, actual_fn_ty)?;
+                            continue;
                         }
-                        arg
+                        self.stack.pop_arguments(actual_fn_ty)?
                     };
                     let frame = Frame::new(
-                        self.stack.stack_ptr(),
-                        self.stack.frame_ptr(),
+                        self.stack.activation_record(),
                         function_instance,
                         &mut arguments,
-                    );
+                    )?;
                     self.stack.push_frame(frame)?;
                     break;
                 }
                 GetLocal => {
                     let idx = Indice::from(frame.pop_raw_u32()?);
-                    self.get_local(&idx)?;
+                    self.get_local(frame, &idx)?;
                 }
                 SetLocal => {
                     let idx = Indice::from(frame.pop_raw_u32()?);
-                    self.set_local(&idx)?;
+                    self.set_local(frame, &idx)?;
                 }
                 TeeLocal => {
                     let idx = Indice::from(frame.pop_raw_u32()?);
-                    self.tee_local(&idx)?
+                    self.tee_local(frame, &idx)?
                 }
                 GetGlobal => {
                     let idx = Indice::from(frame.pop_raw_u32()?);
-                    self.get_global(&idx)?;
+                    self.get_global(&idx, &source_of_frame)?;
                 }
                 SetGlobal => {
                     let idx = Indice::from(frame.pop_raw_u32()?);
-                    self.set_global(&idx)?;
+                    self.set_global(&idx, &source_of_frame)?;
+                }
+                GetLocalGetLocalI32Add => {
+                    let left = Indice::from(frame.pop_raw_u32()?);
+                    frame.pop_ref(); // Skip GetLocal.
+                    let right = Indice::from(frame.pop_raw_u32()?);
+                    frame.pop_ref(); // Skip I32Add.
+                    self.get_local(frame, &left)?;
+                    self.get_local(frame, &right)?;
+                    self.add()?;
+                }
+                I32ConstI32Add => {
+                    let n = frame.pop_raw_u32()? as i32;
+                    frame.pop_ref(); // Skip I32Add.
+                    let left = self.stack.pop_value_ext();
+                    self.stack
+                        .push(StackEntry::new_value(left.add(&Values::I32(n))))?;
+                }
+                GetLocalI32Load => {
+                    let idx = Indice::from(frame.pop_raw_u32()?);
+                    frame.pop_ref(); // Skip I32Load.
+                    let _align = frame.pop_raw_u32()?;
+                    let offset = frame.pop_raw_u32()?;
+                    self.get_local(frame, &idx)?;
+                    self.load_data_to_i32(offset, 32, true, &source_of_frame)?;
                 }
                 I32Const => {
                     let n = frame.pop_raw_u32()? as i32;
@@ -498,10 +997,12 @@ impl ModuleInstance {
                     let n = frame.pop_raw_u64()? as i64;
                     self.stack.push(StackEntry::new_value(Values::I64(n)))?;
                 }
+                #[cfg(feature = "float")]
                 F32Const => {
                     let n = f32::from_bits(frame.pop_raw_u32()?);
                     self.stack.push(StackEntry::new_value(Values::F32(n)))?;
                 }
+                #[cfg(feature = "float")]
                 F64Const => {
                     let n = f64::from_bits(frame.pop_raw_u64()?);
                     self.stack.push(StackEntry::new_value(Values::F64(n)))?;
@@ -512,11 +1013,32 @@ impl ModuleInstance {
                 I32RemSign | I64RemSign => self.rem_s()?,
                 I32RemUnsign | I64RemUnsign => self.rem_u()?,
 
-                I32Add | I64Add | F32Add | F64Add => self.add()?,
-                I32Sub | I64Sub | F32Sub | F64Sub => self.sub()?,
-                I32Mul | I64Mul | F32Mul | F64Mul => self.mul()?,
-                F32Div | F64Div => self.div_f()?,
+                I32Add | I64Add => self.add()?,
+                I32Sub | I64Sub => self.sub()?,
+                I32Mul | I64Mul => self.mul()?,
+                #[cfg(feature = "float")]
+                F32Add | F64Add => {
+                    self.add()?;
+                    self.flush_subnormal()?
+                }
+                #[cfg(feature = "float")]
+                F32Sub | F64Sub => {
+                    self.sub()?;
+                    self.flush_subnormal()?
+                }
+                #[cfg(feature = "float")]
+                F32Mul | F64Mul => {
+                    self.mul()?;
+                    self.flush_subnormal()?
+                }
+                #[cfg(feature = "float")]
+                F32Div | F64Div => {
+                    self.div_f()?;
+                    self.flush_subnormal()?
+                }
+                #[cfg(feature = "float")]
                 F32Min | F64Min => self.min()?,
+                #[cfg(feature = "float")]
                 F32Max | F64Max => self.max()?,
 
                 I32LessThanSign | I64LessThanSign | F32LessThan | F64LessThan => {
@@ -547,13 +1069,14 @@ impl ModuleInstance {
                 I32ShiftRightUnsign | I64ShiftRightUnsign => self.shift_right_unsign()?,
                 I32RotateLeft | I64RotateLeft => self.wasm_rotate_left()?,
                 I32RotateRight | I64RotateRight => self.wasm_rotate_right()?,
+                #[cfg(feature = "float")]
                 F32Copysign | F64Copysign => self.copy_sign()?,
 
                 Select => {
                     let cond = &self.stack.pop_value_ext();
                     let false_br = self.stack.pop_value_ext();
                     let true_br = self.stack.pop_value_ext();
-                    if cond.is_truthy() {
+                    if cond.is_truthy(&self.store.condition_mode)? {
                         self.stack.push(StackEntry::new_value(true_br))?;
                     } else {
                         self.stack.push(StackEntry::new_value(false_br))?;
@@ -573,18 +1096,29 @@ impl ModuleInstance {
                         x => unreachable!("Expected i64 value, got {:?}", x),
                     }
                 }
-                F32Sqrt | F64Sqrt => self.sqrt()?,
+                #[cfg(feature = "float")]
+                F32Sqrt | F64Sqrt => {
+                    self.sqrt()?;
+                    self.flush_subnormal()?
+                }
+                #[cfg(feature = "float")]
                 F32Ceil | F64Ceil => self.ceil()?,
+                #[cfg(feature = "float")]
                 F32Floor | F64Floor => self.floor()?,
+                #[cfg(feature = "float")]
                 F32Trunc | F64Trunc => self.trunc()?,
+                #[cfg(feature = "float")]
                 F32Nearest | F64Nearest => self.nearest()?,
 
                 I32CountLeadingZero | I64CountLeadingZero => self.count_leading_zero()?,
                 I32CountTrailingZero | I64CountTrailingZero => self.count_trailing_zero()?,
                 I32CountNonZero | I64CountNonZero => self.pop_count()?,
                 I32EqualZero | I64EqualZero => self.equal_zero()?,
+                #[cfg(feature = "float")]
                 F32Abs | F64Abs => self.abs()?,
+                #[cfg(feature = "float")]
                 F64Neg | F32Neg => self.neg()?,
+                #[cfg(feature = "float")]
                 I32ReinterpretF32 | I64ReinterpretF64 | F32ReinterpretI32 | F64ReinterpretI64 => {
                     self.reinterpret()?
                 }
@@ -651,6 +1185,7 @@ impl ModuleInstance {
                     self.load_data_to_i64(offset, 32, true, &source_of_frame)?
                 }
 
+                #[cfg(feature = "float")]
                 F32Load => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
@@ -659,6 +1194,7 @@ impl ModuleInstance {
                         .push(StackEntry::new_value(Values::F32(value as f32)))?;
                 }
 
+                #[cfg(feature = "float")]
                 F64Load => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
@@ -672,6 +1208,7 @@ impl ModuleInstance {
                     let offset = frame.pop_raw_u32()?;
                     self.store(32, offset, &source_of_frame)?;
                 }
+                #[cfg(feature = "float")]
                 F32Store => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
@@ -682,6 +1219,7 @@ impl ModuleInstance {
                     let offset = frame.pop_raw_u32()?;
                     self.store(64, offset, &source_of_frame)?;
                 }
+                #[cfg(feature = "float")]
                 F64Store => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
@@ -721,124 +1259,467 @@ impl ModuleInstance {
                 }
                 MemoryGrow => {
                     let memory_instances = self.get_memory_instances(&source_of_frame)?;
-                    let page_size = memory_instances.size_by_pages();
                     let n = self.stack.pop_value_ext_i32() as u32;
-                    let result = match memory_instances.memory_grow(n) {
-                        Ok(()) => (page_size as i32),
-                        Err(WasmError::Trap(Trap::FailToGrow)) => -1,
-                        _ => unreachable!(),
+                    // NOTE: Failure of growing is notified to guest by -1, it never traps.
+                    let can_grow = match &self.store.engine {
+                        Some(engine) => engine.can_grow_memory(n),
+                        None => true,
+                    };
+                    let grown = if can_grow {
+                        memory_instances.memory_grow(n)
+                    } else {
+                        Err(WasmError::Trap(Trap::FailToGrow))
+                    };
+                    let result = match grown {
+                        Ok(previous_pages) => previous_pages as i32,
+                        Err(_) => -1,
                     };
+                    if let Some(listener) = &self.memory_grow_listener {
+                        let current_pages = memory_instances.size_by_pages();
+                        let growth = match grown {
+                            Ok(previous_pages) => MemoryGrowth::Grown {
+                                requested_pages: n,
+                                previous_pages,
+                                current_pages,
+                            },
+                            Err(_) => MemoryGrowth::Failed {
+                                requested_pages: n,
+                                current_pages,
+                            },
+                        };
+                        (listener.0)(&growth);
+                    }
                     self.stack
                         .push(StackEntry::new_value(Values::I32(result)))?;
                 }
 
                 I64ExtendUnsignI32 => self.extend_u32_to_i64()?,
                 I64ExtendSignI32 => self.extend_i32_to_i64()?,
+                #[cfg(feature = "float")]
                 F32ConvertSignI32 => self.convert_sign_i32_to_f32()?,
+                #[cfg(feature = "float")]
                 F32ConvertUnsignI32 => self.convert_unsign_i32_to_f32()?,
+                #[cfg(feature = "float")]
                 F64ConvertSignI64 => self.convert_sign_i64_to_f64()?,
+                #[cfg(feature = "float")]
                 F64ConvertUnsignI64 => self.convert_unsign_i64_to_f64()?,
+                #[cfg(feature = "float")]
                 F64ConvertSignI32 => self.convert_sign_i32_to_f64()?,
+                #[cfg(feature = "float")]
                 F64ConvertUnsignI32 => self.convert_unsign_i32_to_f64()?,
+                #[cfg(feature = "float")]
                 F32ConvertSignI64 => self.convert_sign_i64_to_f32()?,
+                #[cfg(feature = "float")]
                 F32ConvertUnsignI64 => self.convert_unsign_i64_to_f32()?,
+                #[cfg(feature = "float")]
                 F64PromoteF32 => self.promote_f32_to_f64()?,
-                F32DemoteF64 => self.demote_f64_to_f32()?,
+                #[cfg(feature = "float")]
+                F32DemoteF64 => {
+                    self.demote_f64_to_f32()?;
+                    self.flush_subnormal()?
+                }
 
+                #[cfg(feature = "float")]
                 I32TruncSignF32 => self.trunc_f32_to_sign_i32()?,
+                #[cfg(feature = "float")]
                 I32TruncUnsignF32 => self.trunc_f32_to_unsign_i32()?,
+                #[cfg(feature = "float")]
                 I64TruncSignF64 => self.trunc_f64_to_sign_i64()?,
+                #[cfg(feature = "float")]
                 I64TruncUnsignF64 => self.trunc_f64_to_unsign_i64()?,
+                #[cfg(feature = "float")]
                 I32TruncSignF64 => self.trunc_f64_to_sign_i32()?,
+                #[cfg(feature = "float")]
                 I32TruncUnsignF64 => self.trunc_f64_to_unsign_i32()?,
+                #[cfg(feature = "float")]
                 I64TruncSignF32 => self.trunc_f32_to_sign_i64()?,
+                #[cfg(feature = "float")]
                 I64TruncUnsignF32 => self.trunc_f32_to_unsign_i64()?,
+                // NOTE: Without "float" feature, decoder rejects float instructions before they are flattened.
+                #[cfg(not(feature = "float"))]
+                x => unreachable!("Float instruction {:?} is disabled", x),
             };
         }
         Ok(())
     }
 
+    pub(crate) fn is_out_of_budget(&self) -> bool {
+        let is_out_of_fuel = match &self.store.engine {
+            Some(engine) => engine.is_out_of_fuel(),
+            None => false,
+        };
+        self.instruction_budget.get() == Some(0) || is_out_of_fuel
+    }
+
+    pub(crate) fn is_interrupted(&self) -> bool {
+        match &self.store.engine {
+            Some(engine) => engine.is_interrupted(),
+            None => false,
+        }
+    }
+
+    fn consume_budget(&self, executed: u64) {
+        if let Some(budget) = self.instruction_budget.get() {
+            self.instruction_budget.set(Some(budget - executed));
+        }
+        if let Some(engine) = &self.store.engine {
+            engine.consume_fuel(executed);
+        }
+    }
+
+    // NOTE: Frames are left on call stack when the budget runs out.
     pub(crate) fn evaluate(&mut self) -> Result<()> {
         while !self.stack.call_stack_is_empty() {
+            if self.is_out_of_budget() {
+                return Ok(());
+            }
             let frame = self.stack.pop_frame()?;
             // NOTE: Only fresh frame should be initialization.
             if frame.is_fresh() {
-                let return_type = frame
-                    .get_return_type()
-                    .first()
-                    .map_or(TYPE_UNIT, |x| x.to_owned());
-                let label = StackEntry::new_label(frame.last_ptr, return_type, LabelKind::Frame);
-                self.stack.frame_ptr.set(frame.return_ptr);
-                self.stack.push_entries(&mut frame.get_local_variables())?;
-                self.stack.push(label)?;
+                self.stack.enter_frame(&frame)?;
+            }
+            let count_of_frames = self.stack.frames().len();
+            if let Err(err) = self.evaluate_instructions(&frame) {
+                self.capture_backtrace(&frame);
+                self.trap_snapshot
+                    .replace(Some(self.snapshot_with(Some(&frame))));
+                return Err(err);
             }
-            self.evaluate_instructions(&frame)?;
 
+            // NOTE: Frame of yield function is left to receive results on resuming.
+            if self.yielded.borrow().is_some() {
+                self.stack.push_frame(frame)?;
+                return Ok(());
+            }
             let is_completed = frame.is_completed();
             if !is_completed {
-                self.stack.push_back_frame(frame);
+                // NOTE: Frame suspended by the budget is kept on top, otherwise it is under the callee.
+                if self.stack.frames().len() > count_of_frames {
+                    self.stack.push_back_frame(frame);
+                } else {
+                    self.stack.push_frame(frame)?;
+                }
                 continue;
             }
-            let count_of_returns = frame.get_return_count();
-            let mut returns = vec![];
-            for _ in 0..count_of_returns {
-                returns.push(StackEntry::new_value(self.stack.pop_value()?));
-            }
-            self.stack.update_frame_ptr(&frame);
-            self.stack.push_entries(&mut returns)?;
+            self.stack.leave_frame(&frame)?;
         }
         Ok(())
     }
 
-    fn run_internal(&mut self, invoke: &str, mut arguments: Vec<Values>) -> Result<Values> {
+    fn check_argument_types(
+        &self,
+        parameters: &[ValueTypes],
+        arguments: Vec<ValueTypes>,
+    ) -> Result<()> {
+        let coercion = &self.store.argument_coercion;
+        if parameters.len() == arguments.len()
+            && arguments
+                .iter()
+                .zip(parameters.iter())
+                .all(|(argument, parameter)| coercion.accepts(argument, parameter))
+        {
+            return Ok(());
+        }
+        Err(WasmError::Trap(Trap::ArgumentTypeMismatch {
+            expected: parameters.to_vec(),
+            actual: arguments,
+        }))
+    }
+
+    // NOTE: Arguments become locals of the frame as they are, so they must be checked before calling.
+    fn coerce_arguments(
+        &self,
+        function_type: &FunctionType,
+        arguments: Vec<Values>,
+    ) -> Result<Vec<Values>> {
+        function_type.check_arity(arguments.len())?;
+        let parameters = function_type.parameters();
+        self.check_argument_types(
+            parameters,
+            arguments.iter().map(Values::value_type).collect(),
+        )?;
+        Ok(arguments
+            .iter()
+            .zip(parameters.iter())
+            .map(|(argument, parameter)| argument.coerce_into(parameter))
+            .collect())
+    }
+
+    /// Signature of a function which embedder sees under `I64Boundary` of the store.
+    fn legalize_signature(&self, function_type: &FunctionType) -> Result<FunctionType> {
+        let boundary = &self.store.i64_boundary;
+        boundary.check(function_type.parameters())?;
+        boundary.check(function_type.returns())?;
+        Ok(boundary.legalize(function_type))
+    }
+
+    // NOTE: Under `I64Boundary::Split`, pairs of i32 are joined into i64 before arguments are coerced.
+    fn join_i64_arguments(
+        &self,
+        function_type: &FunctionType,
+        arguments: Vec<Values>,
+    ) -> Result<Vec<Values>> {
+        let legalized = self.legalize_signature(function_type)?;
+        let boundary = &self.store.i64_boundary;
+        if *boundary != I64Boundary::Split {
+            return Ok(arguments);
+        }
+        legalized.check_arity(arguments.len())?;
+        self.check_argument_types(
+            legalized.parameters(),
+            arguments.iter().map(Values::value_type).collect(),
+        )?;
+        Ok(boundary.join_arguments(function_type.parameters(), arguments))
+    }
+
+    /// Types of results if an export is invoked with arguments of `argument_types`, without executing it.
+    /// Both of them are legalized as `run` does under `I64Boundary` of the store.
+    pub fn check_call(
+        &self,
+        invoke: &str,
+        argument_types: &[ValueTypes],
+    ) -> Result<Vec<ValueTypes>> {
+        match &self
+            .internal_module
+            .find_export(invoke, &self.store.export_suggestion)?
+            .descriptor
+        {
+            ModuleDescriptor::ExportDescriptor(ExportDescriptor::Function(idx)) => {
+                let function_instance = self.store.get_function_instance(idx)?;
+                let function_type =
+                    self.legalize_signature(function_instance.function_type_ref())?;
+                function_type.check_arity(argument_types.len())?;
+                self.check_argument_types(function_type.parameters(), argument_types.to_vec())?;
+                Ok(function_type.returns().to_owned())
+            }
+            ModuleDescriptor::ExportDescriptor(ExportDescriptor::Global(idx)) => {
+                self.check_argument_types(&[], argument_types.to_vec())?;
+                let value_type = self.store.get_global(idx)?.value_type();
+                let function_type = FunctionType::new(vec![], vec![value_type]);
+                let function_type = self.legalize_signature(&function_type)?;
+                Ok(function_type.returns().to_owned())
+            }
+            _ => Err(WasmError::Trap(Trap::Notfound)),
+        }
+    }
+
+    fn run_internal(&mut self, invoke: &str, arguments: Vec<Values>) -> Result<BoundedRun> {
+        // FIXME: Remove to owning.
         match self
             .internal_module
-            .get_export_by_key(invoke)
-            // FIXME: Remove to owning.
-            .map(|x| x.to_owned())
+            .find_export(invoke, &self.store.export_suggestion)?
+            .to_owned()
         {
-            Some(ExternalInterface {
+            ExternalInterface {
                 descriptor: ModuleDescriptor::ExportDescriptor(ExportDescriptor::Function(idx)),
                 ..
-            }) => {
+            } => {
+                let function_instance = self.store.get_function_instance(&idx).unwrap().clone();
+                let arguments =
+                    self.join_i64_arguments(function_instance.function_type_ref(), arguments)?;
+                let mut arguments =
+                    self.coerce_arguments(function_instance.function_type_ref(), arguments)?;
                 let mut argument_entries = vec![];
                 while let Some(argument) = arguments.pop() {
                     argument_entries.push(StackEntry::new_value(argument));
                 }
-                let function_instance = self.store.get_function_instance(&idx).unwrap();
+                let count_of_returns = function_instance.get_return_count();
                 let frame = Frame::new(
-                    self.stack.stack_ptr(),
-                    self.stack.frame_ptr(),
+                    self.stack.activation_record(),
                     function_instance,
                     &mut argument_entries,
-                );
+                )?;
                 let _ = self.stack.push_frame(frame);
-                match self.evaluate() {
-                    Ok(_) => match self.stack.pop_value() {
-                        Ok(v) => Ok(v),
-                        Err(_) => Ok(Values::I32(0)),
-                    },
-                    // Err(WasmError::Trap(Trap::StackUnderflow)) => Ok(Values::I32(0)),
-                    Err(err) => Err(err),
-                }
+                self.continue_invocation(count_of_returns)
             }
-            Some(ExternalInterface {
+            ExternalInterface {
                 descriptor: ModuleDescriptor::ExportDescriptor(ExportDescriptor::Global(idx)),
                 ..
-            }) => self.store.get_global(&idx),
-            None => Err(WasmError::Trap(Trap::Notfound)),
+            } => {
+                let global = self.store.get_global(&idx)?;
+                let boundary = &self.store.i64_boundary;
+                boundary.check(&[global.value_type()])?;
+                Ok(BoundedRun::Finished(boundary.split_results(vec![global])))
+            }
             x => unimplemented!("{:?}", x),
         }
     }
 
-    #[cfg(not(debug_assertions))]
-    pub fn run(&mut self, invoke: &str, arguments: Vec<Values>) -> Result<Values> {
-        self.run_internal(invoke, arguments)
+    fn continue_invocation(&mut self, count_of_returns: u32) -> Result<BoundedRun> {
+        self.evaluate()?;
+        if !self.stack.call_stack_is_empty() {
+            // NOTE: Interrupted invocation can't be resumed, so its frames are discarded.
+            if self.is_interrupted() {
+                self.stack = Stack::new(self.stack.stack_size);
+                return Err(WasmError::Trap(Trap::Interrupted));
+            }
+            let generation = self.generation.get();
+            self.suspended.set(Some(generation));
+            let token = ResumeToken {
+                generation,
+                count_of_returns,
+            };
+            return Ok(match self.yielded.replace(None) {
+                Some(values) => BoundedRun::Suspended(values, token),
+                None => BoundedRun::Yielded(token),
+            });
+        }
+        let mut returns = vec![];
+        for _ in 0..count_of_returns {
+            returns.push(self.stack.pop_value()?);
+        }
+        returns.reverse();
+        Ok(BoundedRun::Finished(
+            self.store.i64_boundary.split_results(returns),
+        ))
+    }
+
+    fn count_trap(&self, result: &Result<BoundedRun>) {
+        match result {
+            Err(WasmError::Trap(_)) if self.counts_metrics => self.metrics.borrow_mut().traps += 1,
+            _ => {}
+        }
+    }
+
+    // NOTE: Failed invocation leaves frames of its callers on the stack,
+    // which would be continued after frames of the next invocation otherwise.
+    fn discard_failed_invocation(&mut self, result: &Result<BoundedRun>) {
+        if result.is_err() {
+            self.stack = Stack::new(self.stack.stack_size);
+        }
+    }
+
+    fn run_and_count_trap(
+        &mut self,
+        invoke: &str,
+        arguments: Vec<Values>,
+        instruction_budget: Option<u64>,
+    ) -> Result<BoundedRun> {
+        // NOTE: Frames of suspended invocation are discarded with the stack.
+        if self.suspended.replace(None).is_some() {
+            self.stack = Stack::new(self.stack.stack_size);
+        }
+        self.generation.set(self.generation.get() + 1);
+        self.instruction_budget.set(instruction_budget);
+        self.backtrace.borrow_mut().clear();
+        self.trap_snapshot.replace(None);
+        self.yielded.replace(None);
+        let result = self.run_internal(invoke, arguments);
+        self.count_trap(&result);
+        self.discard_failed_invocation(&result);
+        result
+    }
+
+    fn run_to_completion(&mut self, invoke: &str, arguments: Vec<Values>) -> Result<Vec<Values>> {
+        match self.run_and_count_trap(invoke, arguments, None)? {
+            BoundedRun::Finished(returns) => Ok(returns),
+            BoundedRun::Suspended(_, _) => Err(WasmError::Trap(Trap::UnexpectedYield)),
+            // NOTE: Only fuel of engine runs out without instruction budget.
+            BoundedRun::Yielded(_) => {
+                if self.counts_metrics {
+                    let mut metrics = self.metrics.borrow_mut();
+                    metrics.traps += 1;
+                    metrics.fuel_exhausted += 1;
+                }
+                Err(WasmError::Trap(Trap::ResourceLimitExceeded))
+            }
+        }
     }
 
-    #[cfg(debug_assertions)]
-    pub fn run(&mut self, invoke: &str, arguments: Vec<Values>) -> Result<Values> {
-        self.stack = Stack::new(self.stack.stack_size);
-        self.run_internal(invoke, arguments)
+    pub fn run(&mut self, invoke: &str, arguments: Vec<Values>) -> Result<Vec<Values>> {
+        self.run_to_completion(invoke, arguments)
+    }
+
+    /// Invoke a function as same as `run`, but traps are returned as `RunOutcome::Trap` with its details.
+    /// Errors other than traps, e.g. type errors, are still returned as `Err`.
+    pub fn invoke(&mut self, invoke: &str, arguments: Vec<Values>) -> Result<RunOutcome> {
+        match self.run(invoke, arguments) {
+            Ok(values) => Ok(RunOutcome::Values(values)),
+            Err(WasmError::Trap(trap)) => {
+                Ok(RunOutcome::Trap(TrapDetails::new(trap, self.backtrace())))
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Invoke a function which yields after executing `max_instructions` instructions,
+    /// so that an embedder can interleave invocations of many instances cooperatively.
+    /// A fused superinstruction is counted once as same as `Metrics`.
+    /// Invocation suspended before, if exists, is discarded.
+    pub fn run_bounded(
+        &mut self,
+        invoke: &str,
+        arguments: Vec<Values>,
+        max_instructions: u64,
+    ) -> Result<BoundedRun> {
+        self.run_and_count_trap(invoke, arguments, Some(max_instructions))
+    }
+
+    /// Invoke a function which may call yield function, without instruction budget.
+    pub fn run_resumable(&mut self, invoke: &str, arguments: Vec<Values>) -> Result<BoundedRun> {
+        self.run_and_count_trap(invoke, arguments, None)
+    }
+
+    /// Continue the invocation exactly where it yielded, with another budget.
+    pub fn resume(&mut self, token: ResumeToken, max_instructions: u64) -> Result<BoundedRun> {
+        self.resume_with(token, vec![], Some(max_instructions))
+    }
+
+    /// Continue the invocation with `values` as results of yield function which suspended it.
+    /// `values` must be empty for the invocation which ran out of its budget.
+    pub fn resume_with(
+        &mut self,
+        token: ResumeToken,
+        values: Vec<Values>,
+        max_instructions: Option<u64>,
+    ) -> Result<BoundedRun> {
+        if self.suspended.get() != Some(token.generation) {
+            return Err(WasmError::Trap(Trap::StaleResumeToken));
+        }
+        // NOTE: Invocation stays suspended when `values` are rejected, so that the embedder can retry.
+        self.check_results_of_yield(&values)?;
+        self.suspended.set(None);
+        self.instruction_budget.set(max_instructions);
+        let result = self
+            .receive_results_of_yield(values)
+            .and_then(|_| self.continue_invocation(token.count_of_returns));
+        self.count_trap(&result);
+        self.discard_failed_invocation(&result);
+        result
+    }
+
+    fn is_suspended_by_yield(&self) -> bool {
+        self.stack.get_frame(0).ok().map_or(false, |frame| {
+            frame.function_instance.is_yield() && !frame.is_fresh()
+        })
+    }
+
+    fn check_results_of_yield(&self, values: &[Values]) -> Result<()> {
+        let is_matched = if self.is_suspended_by_yield() {
+            let types = values.iter().map(Values::value_type).collect::<Vec<_>>();
+            self.stack
+                .get_frame(0)
+                .ok()
+                .map_or(false, |frame| &types == frame.get_return_type())
+        } else {
+            values.is_empty()
+        };
+        if is_matched {
+            Ok(())
+        } else {
+            Err(WasmError::Trap(Trap::TypeMismatch))
+        }
+    }
+
+    // NOTE: `values` must be checked by `check_results_of_yield` before.
+    fn receive_results_of_yield(&mut self, values: Vec<Values>) -> Result<()> {
+        if !self.is_suspended_by_yield() {
+            return Ok(());
+        }
+        let frame = self.stack.pop_frame()?;
+        for value in values.into_iter() {
+            self.stack.push(StackEntry::new_value(value))?;
+        }
+        self.stack.leave_frame(&frame)
     }
 }
@@ -0,0 +1,432 @@
+#[cfg(not(any(test, feature = "std")))]
+use alloc::prelude::*;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use error::{Result, Trap};
+use function::{FunctionInstance, FunctionType};
+use memory::{MemoryInstances, PAGE_SIZE};
+use module::ExternalModule;
+use value::Values;
+use value_type::{ValueTypes, TYPE_I32, TYPE_I64};
+use vm::ModuleInstance;
+
+/// Name of the module which modules targeting WASI import from.
+pub const WASI_MODULE_NAME: &str = "wasi_snapshot_preview1";
+
+// NOTE: Errno of WASI.
+const ESUCCESS: i32 = 0;
+const EBADF: i32 = 8;
+const EFAULT: i32 = 21;
+const EINVAL: i32 = 28;
+const ENOSYS: i32 = 52;
+
+// NOTE: Filetype of character devices and rights of fd_read and fd_write, which fd_fdstat_get reports for stdio.
+const FILETYPE_CHARACTER_DEVICE: u8 = 2;
+const RIGHTS_OF_STDIO: u64 = 1 << 1 | 1 << 6;
+const SIZE_OF_FDSTAT: usize = 24;
+
+// NOTE: random_get fills memory by this size, so that a large buffer doesn't allocate as large one on host.
+const SIZE_OF_RANDOM_CHUNK: usize = 256;
+
+// NOTE: realtime, monotonic, process_cputime_id and thread_cputime_id.
+const COUNT_OF_CLOCKS: u32 = 4;
+
+type Errno = core::result::Result<(), i32>;
+
+/// Time which clock_time_get returns.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WasiClock {
+  /// Nanoseconds since the epoch, which every clock returns however long guest runs.
+  Fixed(u64),
+  /// Time of the host, which every clock returns as nanoseconds since the epoch.
+  #[cfg(any(test, feature = "std"))]
+  Real,
+}
+
+/// Destination of stdout or stderr of guest.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WasiOutput {
+  /// Kept in a buffer, which `Wasi::stdout` or `Wasi::stderr` returns.
+  Capture,
+  Discard,
+  /// Written to the same stream of the host.
+  #[cfg(any(test, feature = "std"))]
+  Inherit,
+}
+
+/// Configuration of WASI shims, whose default is hermetic: neither arguments nor environment variables,
+/// a clock fixed at the epoch, random bytes seeded by 0, empty stdin and captured stdout and stderr.
+#[derive(Debug, Clone)]
+pub struct WasiConfig {
+  /// Arguments including the program name, which guest usually expects to be the first one.
+  pub args: Vec<String>,
+  pub env: Vec<(String, String)>,
+  pub clock: WasiClock,
+  /// Seed of pseudo random bytes which random_get returns, so that runs of the same seed get the same bytes.
+  pub random_seed: u64,
+  pub stdin: Vec<u8>,
+  pub stdout: WasiOutput,
+  pub stderr: WasiOutput,
+}
+
+impl Default for WasiConfig {
+  fn default() -> Self {
+    WasiConfig {
+      args: vec![],
+      env: vec![],
+      clock: WasiClock::Fixed(0),
+      random_seed: 0,
+      stdin: vec![],
+      stdout: WasiOutput::Capture,
+      stderr: WasiOutput::Capture,
+    }
+  }
+}
+
+struct WasiState {
+  config: WasiConfig,
+  memory_instances: Option<MemoryInstances>,
+  random_state: u64,
+  stdin_ptr: usize,
+  stdout: Vec<u8>,
+  stderr: Vec<u8>,
+}
+
+fn argument(arguments: &[Values], idx: usize) -> core::result::Result<u32, i32> {
+  match arguments.get(idx) {
+    Some(Values::I32(v)) => Ok(*v as u32),
+    _ => Err(EINVAL),
+  }
+}
+
+fn offset(base: u32, delta: usize) -> core::result::Result<u32, i32> {
+  if delta > u32::max_value() as usize {
+    return Err(EFAULT);
+  }
+  base.checked_add(delta as u32).ok_or(EFAULT)
+}
+
+impl WasiState {
+  fn memory(&self) -> core::result::Result<&MemoryInstances, i32> {
+    self.memory_instances.as_ref().ok_or(EFAULT)
+  }
+
+  fn read(&self, ptr: u32, length: usize) -> core::result::Result<Vec<u8>, i32> {
+    self.memory()?.read_data(ptr, length).map_err(|_| EFAULT)
+  }
+
+  fn write(&self, ptr: u32, bytes: &[u8]) -> Errno {
+    self.memory()?.write_data(ptr, bytes).map_err(|_| EFAULT)
+  }
+
+  fn read_u32(&self, ptr: u32) -> core::result::Result<u32, i32> {
+    let bytes = self.read(ptr, 4)?;
+    Ok(
+      bytes
+        .iter()
+        .rev()
+        .fold(0, |acc, byte| (acc << 8) | u32::from(*byte)),
+    )
+  }
+
+  fn write_u32(&self, ptr: u32, value: u32) -> Errno {
+    let bytes = (0..4).map(|i| (value >> (i * 8)) as u8).collect::<Vec<_>>();
+    self.write(ptr, &bytes)
+  }
+
+  fn write_u64(&self, ptr: u32, value: u64) -> Errno {
+    let bytes = (0..8).map(|i| (value >> (i * 8)) as u8).collect::<Vec<_>>();
+    self.write(ptr, &bytes)
+  }
+
+  // NOTE: Null terminated strings are written contiguously from `buf_ptr`,
+  // and pointers to each of them from `ptrs_ptr`.
+  fn write_strings(&self, strings: &[String], ptrs_ptr: u32, buf_ptr: u32) -> Errno {
+    let mut written = 0;
+    for (idx, string) in strings.iter().enumerate() {
+      let ptr = offset(buf_ptr, written)?;
+      self.write_u32(offset(ptrs_ptr, idx * 4)?, ptr)?;
+      self.write(ptr, string.as_bytes())?;
+      self.write(offset(ptr, string.len())?, &[0])?;
+      written += string.len() + 1;
+    }
+    Ok(())
+  }
+
+  fn write_sizes(&self, strings: &[String], count_ptr: u32, buf_size_ptr: u32) -> Errno {
+    let buf_size = strings.iter().map(|string| string.len() + 1).sum::<usize>();
+    self.write_u32(count_ptr, strings.len() as u32)?;
+    self.write_u32(buf_size_ptr, buf_size as u32)
+  }
+
+  fn environ(&self) -> Vec<String> {
+    self
+      .config
+      .env
+      .iter()
+      .map(|(key, value)| format!("{}={}", key, value))
+      .collect()
+  }
+
+  fn now(&self) -> u64 {
+    match self.config.clock {
+      WasiClock::Fixed(nanos) => nanos,
+      #[cfg(any(test, feature = "std"))]
+      WasiClock::Real => std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() * 1_000_000_000 + u64::from(elapsed.subsec_nanos()))
+        .unwrap_or(0),
+    }
+  }
+
+  // NOTE: splitmix64, which is enough for hermetic tests but not for cryptography.
+  fn next_random(&mut self) -> u64 {
+    self.random_state = self.random_state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = self.random_state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+  }
+
+  // NOTE: Pairs of a pointer and a length, each of them are u32.
+  fn iovecs(&self, iovs_ptr: u32, iovs_len: u32) -> core::result::Result<Vec<(u32, u32)>, i32> {
+    (0..iovs_len as usize)
+      .map(|idx| {
+        let ptr = offset(iovs_ptr, idx * 8)?;
+        Ok((self.read_u32(ptr)?, self.read_u32(offset(ptr, 4)?)?))
+      })
+      .collect()
+  }
+
+  fn output(&mut self, fd: u32, bytes: &[u8]) -> Errno {
+    let (output, buffer) = match fd {
+      1 => (&self.config.stdout, &mut self.stdout),
+      2 => (&self.config.stderr, &mut self.stderr),
+      _ => return Err(EBADF),
+    };
+    match output {
+      WasiOutput::Capture => buffer.extend_from_slice(bytes),
+      WasiOutput::Discard => {}
+      #[cfg(any(test, feature = "std"))]
+      WasiOutput::Inherit => {
+        use std::io::Write;
+        let written = if fd == 1 {
+          std::io::stdout().write_all(bytes)
+        } else {
+          std::io::stderr().write_all(bytes)
+        };
+        written.map_err(|_| EBADF)?;
+      }
+    }
+    Ok(())
+  }
+
+  fn args_get(&mut self, arguments: &[Values]) -> Errno {
+    let (argv_ptr, buf_ptr) = (argument(arguments, 0)?, argument(arguments, 1)?);
+    self.write_strings(&self.config.args, argv_ptr, buf_ptr)
+  }
+
+  fn args_sizes_get(&mut self, arguments: &[Values]) -> Errno {
+    let (argc_ptr, buf_size_ptr) = (argument(arguments, 0)?, argument(arguments, 1)?);
+    self.write_sizes(&self.config.args, argc_ptr, buf_size_ptr)
+  }
+
+  fn environ_get(&mut self, arguments: &[Values]) -> Errno {
+    let (environ_ptr, buf_ptr) = (argument(arguments, 0)?, argument(arguments, 1)?);
+    self.write_strings(&self.environ(), environ_ptr, buf_ptr)
+  }
+
+  fn environ_sizes_get(&mut self, arguments: &[Values]) -> Errno {
+    let (count_ptr, buf_size_ptr) = (argument(arguments, 0)?, argument(arguments, 1)?);
+    self.write_sizes(&self.environ(), count_ptr, buf_size_ptr)
+  }
+
+  fn clock_time_get(&mut self, arguments: &[Values]) -> Errno {
+    let (clock_id, time_ptr) = (argument(arguments, 0)?, argument(arguments, 2)?);
+    if clock_id >= COUNT_OF_CLOCKS {
+      return Err(EINVAL);
+    }
+    self.write_u64(time_ptr, self.now())
+  }
+
+  // NOTE: Bounds are checked before filling, so that random state doesn't advance for a faulty buffer.
+  fn random_get(&mut self, arguments: &[Values]) -> Errno {
+    let (buf_ptr, buf_len) = (argument(arguments, 0)?, argument(arguments, 1)?);
+    let size_of_memory = u64::from(self.memory()?.size_by_pages()) * u64::from(PAGE_SIZE);
+    if u64::from(buf_ptr) + u64::from(buf_len) > size_of_memory {
+      return Err(EFAULT);
+    }
+    let mut filled = 0;
+    while filled < buf_len as usize {
+      let length = core::cmp::min(SIZE_OF_RANDOM_CHUNK, buf_len as usize - filled);
+      let mut chunk = [0; SIZE_OF_RANDOM_CHUNK];
+      for bytes in chunk[..length].chunks_mut(8) {
+        let random = self.next_random();
+        for (i, byte) in bytes.iter_mut().enumerate() {
+          *byte = (random >> (i * 8)) as u8;
+        }
+      }
+      self.write(offset(buf_ptr, filled)?, &chunk[..length])?;
+      filled += length;
+    }
+    Ok(())
+  }
+
+  fn fd_write(&mut self, arguments: &[Values]) -> Errno {
+    let fd = argument(arguments, 0)?;
+    let iovecs = self.iovecs(argument(arguments, 1)?, argument(arguments, 2)?)?;
+    let mut written = 0u32;
+    for (ptr, len) in iovecs.into_iter() {
+      let bytes = self.read(ptr, len as usize)?;
+      self.output(fd, &bytes)?;
+      written = written.wrapping_add(len);
+    }
+    self.write_u32(argument(arguments, 3)?, written)
+  }
+
+  fn fd_read(&mut self, arguments: &[Values]) -> Errno {
+    if argument(arguments, 0)? != 0 {
+      return Err(EBADF);
+    }
+    let iovecs = self.iovecs(argument(arguments, 1)?, argument(arguments, 2)?)?;
+    let mut read = 0usize;
+    for (ptr, len) in iovecs.into_iter() {
+      let rest = self.config.stdin.len() - self.stdin_ptr;
+      let len = core::cmp::min(len as usize, rest);
+      let end = self.stdin_ptr + len;
+      self.write(ptr, &self.config.stdin[self.stdin_ptr..end])?;
+      self.stdin_ptr = end;
+      read += len;
+    }
+    self.write_u32(argument(arguments, 3)?, read as u32)
+  }
+
+  fn fd_fdstat_get(&mut self, arguments: &[Values]) -> Errno {
+    let (fd, stat_ptr) = (argument(arguments, 0)?, argument(arguments, 1)?);
+    if fd > 2 {
+      return Err(EBADF);
+    }
+    // NOTE: Filetype, flags, base rights and inheriting rights, whose rest is padding.
+    let mut stat = [0; SIZE_OF_FDSTAT];
+    stat[0] = FILETYPE_CHARACTER_DEVICE;
+    for (i, byte) in stat[8..16].iter_mut().enumerate() {
+      *byte = (RIGHTS_OF_STDIO >> (i * 8)) as u8;
+    }
+    self.write(stat_ptr, &stat)
+  }
+
+  // NOTE: Stdio can be neither closed nor seeked, and no other file exists.
+  fn fd_close(&mut self, _arguments: &[Values]) -> Errno {
+    Err(ENOSYS)
+  }
+
+  fn fd_seek(&mut self, _arguments: &[Values]) -> Errno {
+    Err(ENOSYS)
+  }
+}
+
+fn proc_exit(arguments: &[Values]) -> Result<Vec<Values>> {
+  let code = argument(arguments, 0).unwrap_or(0);
+  Err(Trap::ProcessExit(code).into())
+}
+
+/// Shims of WASI configured by `WasiConfig`, e.g. to run command-style modules hermetically in tests.
+/// Clones share the same state.
+///
+/// Functions of `module` access memory of the instance passed to `attach`, so guest must not call them
+/// before it's attached, e.g. by a start function. They return EFAULT otherwise.
+/// Only args, environ, clock_time_get, random_get, fd_write to stdout and stderr, fd_read from stdin
+/// and fd_fdstat_get of stdio are provided, and fd_close and fd_seek return ENOSYS.
+/// proc_exit unwinds the invocation by `Trap::ProcessExit` of the exit code.
+#[derive(Clone)]
+pub struct Wasi(Rc<RefCell<WasiState>>);
+
+impl Wasi {
+  pub fn new(config: WasiConfig) -> Self {
+    let random_state = config.random_seed;
+    Wasi(Rc::new(RefCell::new(WasiState {
+      config,
+      memory_instances: None,
+      random_state,
+      stdin_ptr: 0,
+      stdout: vec![],
+      stderr: vec![],
+    })))
+  }
+
+  /// Module of WASI functions, which should be registered by `WASI_MODULE_NAME`.
+  pub fn module(&self) -> ExternalModule {
+    let function =
+      |name: &str, parameters: Vec<ValueTypes>, call: fn(&mut WasiState, &[Values]) -> Errno| {
+        let state = self.0.clone();
+        FunctionInstance::new_host_closure(
+          Some(name.to_owned()),
+          FunctionType::new(parameters, vec![TYPE_I32]),
+          move |arguments| {
+            let errno = match call(&mut state.borrow_mut(), arguments) {
+              Ok(()) => ESUCCESS,
+              Err(errno) => errno,
+            };
+            vec![Values::I32(errno)]
+          },
+        )
+      };
+    ExternalModule::new(
+      vec![
+        function("args_get", vec![TYPE_I32; 2], WasiState::args_get),
+        function(
+          "args_sizes_get",
+          vec![TYPE_I32; 2],
+          WasiState::args_sizes_get,
+        ),
+        function("environ_get", vec![TYPE_I32; 2], WasiState::environ_get),
+        function(
+          "environ_sizes_get",
+          vec![TYPE_I32; 2],
+          WasiState::environ_sizes_get,
+        ),
+        function(
+          "clock_time_get",
+          vec![TYPE_I32, TYPE_I64, TYPE_I32],
+          WasiState::clock_time_get,
+        ),
+        function("random_get", vec![TYPE_I32; 2], WasiState::random_get),
+        function("fd_write", vec![TYPE_I32; 4], WasiState::fd_write),
+        function("fd_read", vec![TYPE_I32; 4], WasiState::fd_read),
+        function("fd_fdstat_get", vec![TYPE_I32; 2], WasiState::fd_fdstat_get),
+        function("fd_close", vec![TYPE_I32], WasiState::fd_close),
+        function(
+          "fd_seek",
+          vec![TYPE_I32, TYPE_I64, TYPE_I32, TYPE_I32],
+          WasiState::fd_seek,
+        ),
+        FunctionInstance::new_fallible_host_closure(
+          Some("proc_exit".to_owned()),
+          FunctionType::new(vec![TYPE_I32], vec![]),
+          proc_exit,
+        ),
+      ],
+      vec![],
+      vec![],
+      vec![],
+      vec![],
+    )
+  }
+
+  /// Let WASI functions access memory of `instance`, which replaces formerly attached one.
+  pub fn attach(&self, instance: &ModuleInstance) {
+    self.0.borrow_mut().memory_instances = Some(instance.export_module().memory_instances);
+  }
+
+  /// Bytes written to stdout so far, when it's captured.
+  pub fn stdout(&self) -> Vec<u8> {
+    self.0.borrow().stdout.clone()
+  }
+
+  /// Bytes written to stderr so far, when it's captured.
+  pub fn stderr(&self) -> Vec<u8> {
+    self.0.borrow().stderr.clone()
+  }
+}
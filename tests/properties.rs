@@ -0,0 +1,131 @@
+//! Properties of integer arithmetic of `Values` against reference semantics of the specification,
+//! which is computed in 128 bits integers not to overflow.
+#[macro_use]
+extern crate quickcheck;
+extern crate wasvm;
+
+use quickcheck::{Arbitrary, Gen};
+use wasvm::{Trap, Values, WasmError};
+
+fn trap<T>(trap: Trap) -> Result<T, WasmError> {
+  Err(WasmError::Trap(trap))
+}
+
+fn logical(condition: bool) -> Values {
+  Values::I32(condition as i32)
+}
+
+macro_rules! properties_of {
+  ($module: ident, $ty: ty, $variant: path, $bits: expr) => {
+    mod $module {
+      use super::*;
+
+      // NOTE: Values around boundaries are more likely to break than uniformly distributed ones.
+      #[derive(Clone, Debug)]
+      struct Edge($ty);
+
+      impl Arbitrary for Edge {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+          let edges: [$ty; 6] = [
+            0,
+            1,
+            -1,
+            <$ty>::min_value(),
+            <$ty>::max_value(),
+            1 << ($bits / 2),
+          ];
+          let base = edges[usize::arbitrary(g) % edges.len()];
+          Edge(base.wrapping_add(<$ty>::from(i8::arbitrary(g))))
+        }
+
+        fn shrink(&self) -> Box<Iterator<Item = Self>> {
+          Box::new(self.0.shrink().map(Edge))
+        }
+      }
+
+      fn unsign(x: $ty) -> u128 {
+        if x < 0 {
+          (x as i128 + (1i128 << $bits)) as u128
+        } else {
+          x as u128
+        }
+      }
+
+      fn count_of_shift(x: $ty) -> u32 {
+        (unsign(x) % $bits) as u32
+      }
+
+      quickcheck! {
+        fn wrap_arithmetic(l: Edge, r: Edge) -> bool {
+          let (vl, vr) = ($variant(l.0), $variant(r.0));
+          let (wl, wr) = (l.0 as i128, r.0 as i128);
+          vl.add(&vr) == $variant((wl + wr) as $ty)
+            && vl.sub(&vr) == $variant((wl - wr) as $ty)
+            && vl.mul(&vr) == $variant((wl * wr) as $ty)
+        }
+
+        fn divide_signed(l: Edge, r: Edge) -> bool {
+          let expected = if r.0 == 0 {
+            trap(Trap::DivisionByZero)
+          } else {
+            let quotient = l.0 as i128 / r.0 as i128;
+            if quotient > <$ty>::max_value() as i128 {
+              trap(Trap::IntegerOverflow)
+            } else {
+              Ok($variant(quotient as $ty))
+            }
+          };
+          $variant(l.0).div_s(&$variant(r.0)) == expected
+        }
+
+        fn remainder_signed(l: Edge, r: Edge) -> bool {
+          let expected = if r.0 == 0 {
+            trap(Trap::DivisionByZero)
+          } else {
+            Ok($variant((l.0 as i128 % r.0 as i128) as $ty))
+          };
+          $variant(l.0).rem_s(&$variant(r.0)) == expected
+        }
+
+        fn divide_and_remainder_unsigned(l: Edge, r: Edge) -> bool {
+          let (vl, vr) = ($variant(l.0), $variant(r.0));
+          if r.0 == 0 {
+            return vl.div_u(&vr) == trap(Trap::DivisionByZero)
+              && vl.rem_u(&vr) == trap(Trap::DivisionByZero);
+          }
+          let (ul, ur) = (unsign(l.0), unsign(r.0));
+          vl.div_u(&vr) == Ok($variant((ul / ur) as $ty))
+            && vl.rem_u(&vr) == Ok($variant((ul % ur) as $ty))
+        }
+
+        fn compare_unsigned(l: Edge, r: Edge) -> bool {
+          let (vl, vr) = ($variant(l.0), $variant(r.0));
+          let (ul, ur) = (unsign(l.0), unsign(r.0));
+          vl.less_than_unsign(&vr) == logical(ul < ur)
+            && vl.less_than_equal_unsign(&vr) == logical(ul <= ur)
+            && vl.greater_than_unsign(&vr) == logical(ul > ur)
+            && vl.greater_than_equal_unsign(&vr) == logical(ul >= ur)
+        }
+
+        fn shift_by_count_modulo_width(l: Edge, r: Edge) -> bool {
+          let (vl, vr) = ($variant(l.0), $variant(r.0));
+          let k = count_of_shift(r.0);
+          vl.shift_left(&vr) == $variant((unsign(l.0) << k) as $ty)
+            && vl.shift_right_sign(&vr) == $variant((l.0 as i128 >> k) as $ty)
+            && vl.shift_right_unsign(&vr) == $variant((unsign(l.0) >> k) as $ty)
+        }
+
+        fn rotate_by_count_modulo_width(l: Edge, r: Edge) -> bool {
+          let (vl, vr) = ($variant(l.0), $variant(r.0));
+          let (x, k) = (unsign(l.0), count_of_shift(r.0));
+          let rest = ($bits - k) % $bits;
+          vl.wasm_rotate_left(&vr) == $variant(((x << k) | (x >> rest)) as $ty)
+            && vl.wasm_rotate_right(&vr) == $variant(((x >> k) | (x << rest)) as $ty)
+        }
+      }
+    }
+  };
+}
+
+properties_of!(i32_properties, i32, Values::I32, 32);
+properties_of!(i64_properties, i64, Values::I64, 64);
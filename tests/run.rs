@@ -3,16 +3,12 @@ extern crate wabt;
 #[cfg(test)]
 extern crate wasvm;
 use std::cell::RefCell;
-use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
 use std::rc::Rc;
 use std::{f32, f64};
 use wabt::script::{Action, Command, CommandKind, ModuleBinary, ScriptParser, Value};
-use wasvm::{
-  create_spectest, decode_module, init_store, instantiate_module, validate_module, ExternalModules,
-  ModuleInstance, Trap, Values, WasmError,
-};
+use wasvm::prelude::*;
 
 fn get_args(args: &[Value<f32, f64>]) -> Vec<Values> {
   args
@@ -26,31 +22,46 @@ fn get_args(args: &[Value<f32, f64>]) -> Vec<Values> {
     .collect()
 }
 
-fn get_expectation(expected: &[Value]) -> Values {
-  match expected.get(0) {
-    Some(Value::I32(v)) => Values::I32(*v),
-    Some(Value::I64(v)) => Values::I64(*v),
-    Some(Value::F32(v)) => Values::F32(*v),
-    Some(Value::F64(v)) => Values::F64(*v),
-    None => Values::I32(0),
+// NOTE: Modules of `module quote` remain in text format, so they are assembled by wabt.
+fn into_binary(module: &ModuleBinary) -> Result<Vec<u8>, wabt::Error> {
+  let bytes = module.clone().into_vec();
+  if bytes.starts_with(&[0, 97, 115, 109]) {
+    Ok(bytes)
+  } else {
+    wabt::wat2wasm(bytes)
   }
 }
 
-struct E2ETest<'a> {
+// NOTE: Reference interpreter appends details to some messages, so ones of traps and type errors are compared as prefixes.
+// Other errors have no counterpart of the reference, so only their presence is asserted.
+fn assert_error_message(error: WasmError, message: &str, line: u64) {
+  let actual = match error {
+    WasmError::Trap(ref trap) => trap.message(),
+    WasmError::TypeError(ref type_error) => type_error.message(),
+    _ => return,
+  };
+  assert!(
+    message.starts_with(actual),
+    "Expected error '{}', got {:?} at line {}.",
+    message,
+    error,
+    line
+  );
+}
+
+struct E2ETest {
   parser: ScriptParser<f32, f64>,
-  modules: HashMap<Option<String>, Rc<RefCell<ModuleInstance>>>,
-  external_modules: ExternalModules,
-  file_name: &'a str,
+  environment: ScriptEnvironment,
+  configure: fn(&mut Store),
 }
 
-impl<'a> E2ETest<'a> {
-  fn new(file_name: &'a str) -> Self {
+impl E2ETest {
+  fn new(file_name: &str, configure: fn(&mut Store)) -> Self {
     let mut buf = String::new();
     let test_filename = format!("./testsuite/{}.wast", file_name);
     let mut json = File::open(&test_filename).unwrap();
     json.read_to_string(&mut buf).unwrap();
     let parser = ScriptParser::from_str(&buf).unwrap();
-    let modules = HashMap::new();
     let mut external_modules = ExternalModules::default();
     external_modules
       .register_module(Some("spectest".to_owned()), create_spectest())
@@ -58,26 +69,42 @@ impl<'a> E2ETest<'a> {
 
     E2ETest {
       parser,
-      modules,
-      external_modules,
-      file_name,
+      environment: ScriptEnvironment::new(external_modules),
+      configure,
     }
   }
 
+  fn init_store(&self) -> Store {
+    let mut store = init_store();
+    (self.configure)(&mut store);
+    store
+  }
+
+  fn module_of(&self, name: &Option<String>) -> Rc<RefCell<ModuleInstance>> {
+    self
+      .environment
+      .get(name.as_ref().map(|name| name.as_str()))
+      .unwrap()
+  }
+
   fn do_instantiate(&mut self, module: &ModuleBinary, name: &Option<String>) {
     let bytes = module.clone().into_vec();
-    let store = init_store();
-    let section = decode_module(&bytes);
-    let vm_ref = Rc::new(RefCell::new(
-      instantiate_module(store, section, self.external_modules.clone(), 65536).unwrap(),
-    ));
-    self.modules.insert(None, vm_ref.clone());
-    self.modules.insert(name.clone(), vm_ref.clone());
+    let external_modules = self.environment.external_modules().unwrap();
+    let instance = instantiate_module(
+      self.init_store(),
+      decode_module(&bytes),
+      external_modules,
+      65536,
+    )
+    .unwrap();
+    self
+      .environment
+      .define(name.as_ref().map(|name| name.as_str()), instance);
   }
 
   fn do_action(&mut self, field: &str, args: &[Value], module: &Option<String>, line: u64) {
     println!("Perform action at {}:{}.", field, line);
-    let vm_ref: Rc<RefCell<ModuleInstance>> = self.modules[module].clone();
+    let vm_ref = self.module_of(module);
     let mut vm = vm_ref.borrow_mut();
     vm.run(field, get_args(args)).unwrap();
   }
@@ -87,12 +114,9 @@ impl<'a> E2ETest<'a> {
       "Register importable module, key={:?} import_name={}.",
       name, as_name
     );
-    let vm_ref: Rc<RefCell<ModuleInstance>> = self.modules[name].clone();
-    let vm = vm_ref.borrow();
-    let importable_module = vm.export_module();
     self
-      .external_modules
-      .register_module(Some(as_name.to_owned()), importable_module)
+      .environment
+      .register(name.as_ref().map(|name| name.as_str()), as_name)
       .unwrap();
   }
 
@@ -109,23 +133,26 @@ impl<'a> E2ETest<'a> {
       } => (field, vec![], module),
     };
     println!("Assert return at {}:{}.", field, line);
-    let vm_ref: Rc<RefCell<ModuleInstance>> = self.modules[module].clone();
+    let vm_ref = self.module_of(module);
     let mut vm = vm_ref.borrow_mut();
-    let actual = vm.run(field.as_ref(), args).unwrap();
-    let expectation = get_expectation(expected);
-    match actual {
-      Values::F32(n) if n.is_nan() => match expectation {
-        Values::F32(m) => assert!(m.is_nan()),
-        _ => unreachable!(),
-      },
-      Values::F64(n) if n.is_nan() => match expectation {
-        Values::F64(m) => assert!(m.is_nan()),
-        _ => unreachable!(),
-      },
-      _ => assert_eq!(actual, expectation),
-    };
+    let actuals = vm.run(field.as_ref(), args).unwrap();
+    let expectations = get_args(expected);
+    assert_eq!(actuals.len(), expectations.len());
+    for (actual, expectation) in actuals.into_iter().zip(expectations) {
+      match actual {
+        Values::F32(n) if n.is_nan() => match expectation {
+          Values::F32(m) => assert!(m.is_nan()),
+          _ => unreachable!(),
+        },
+        Values::F64(n) if n.is_nan() => match expectation {
+          Values::F64(m) => assert!(m.is_nan()),
+          _ => unreachable!(),
+        },
+        _ => assert_eq!(actual, expectation),
+      };
+    }
   }
-  fn assert_trap(&mut self, action: &Action, _message: &str, line: u64) {
+  fn assert_trap(&mut self, action: &Action, message: &str, line: u64) {
     match action {
       Action::Invoke {
         ref field,
@@ -133,46 +160,53 @@ impl<'a> E2ETest<'a> {
         ref module,
       } => {
         println!("Assert trap at {}:{}.", field, line,);
-        let vm_ref: Rc<RefCell<ModuleInstance>> = self.modules[module].clone();
+        let vm_ref = self.module_of(module);
         let mut vm = vm_ref.borrow_mut();
-        vm.run(field, get_args(args)).unwrap_err();
+        match vm.invoke(field, get_args(args)).unwrap() {
+          RunOutcome::Trap(details) => assert!(
+            message.starts_with(details.message),
+            "Expected trap '{}', got {:?} at line {}.",
+            message,
+            details.trap,
+            line
+          ),
+          RunOutcome::Values(values) => panic!("Expected trap, got {:?}", values),
+        }
       }
       x => unreachable!("{:?}", x),
     }
   }
 
-  fn assert_uninstantiable(&mut self, module: &ModuleBinary, _message: &str, line: u64) {
+  fn assert_uninstantiable(&mut self, module: &ModuleBinary, message: &str, line: u64) {
     println!("Assert uninstantiable at line:{}.", line);
     let bytes = module.clone().into_vec();
-    let store = init_store();
+    let store = self.init_store();
     let module = decode_module(&bytes);
-    instantiate_module(store, module, Default::default(), 65536).unwrap_err();
+    let error = instantiate_module(store, module, Default::default(), 65536).unwrap_err();
+    assert_error_message(error, message, line);
   }
 
-  fn assert_malformed(&self, module: &ModuleBinary, _message: &str, line: u64) {
-    if (self.file_name == "custom_section" && line == 77)
-      || (self.file_name == "custom_section" && line == 94)
-      || (self.file_name == "custom" && line == 85)
-    {
-      println!("Skip {}, it seems can't resolvable yet...", line);
-      return;
-    };
-    let bytes = module.clone().into_vec();
-    let store = init_store();
-    let module = decode_module(&bytes);
-    let err = instantiate_module(store, module, Default::default(), 65536).unwrap_err();
-    if let WasmError::Trap(Trap::UnsupportedTextform) = err {
-      println!("Skip malformed text form at line:{}.", line);
-      return;
+  fn assert_malformed(&self, module: &ModuleBinary, message: &str, line: u64) {
+    let bytes = match into_binary(module) {
+      Ok(bytes) => bytes,
+      Err(err) => {
+        println!("Assert malformed text form at {}: {:?}.", line, err);
+        return;
+      }
     };
     println!("Assert malformed at {}.", line,);
+    let store = self.init_store();
+    let module = decode_module(&bytes);
+    let error = instantiate_module(store, module, Default::default(), 65536).unwrap_err();
+    assert_error_message(error, message, line);
   }
 
   fn assert_invalid(&self, message: &str, module: &ModuleBinary, line: u64) {
     println!("Assert invalid at {}:{}.", message, line);
     let bytes = module.clone().into_vec();
     let section = decode_module(&bytes);
-    validate_module(&section).unwrap_err();
+    let error = validate_module(&section).unwrap_err();
+    assert_error_message(error, message, line);
   }
 
   fn assert_nan(&self, action: &Action, line: u64) {
@@ -183,10 +217,10 @@ impl<'a> E2ETest<'a> {
         ref module,
       } => {
         println!("Assert NaN at '{}:{}'.", field, line);
-        let vm_ref: Rc<RefCell<ModuleInstance>> = self.modules[module].clone();
+        let vm_ref = self.module_of(module);
         let mut vm = vm_ref.borrow_mut();
         let actual = vm.run(field.as_ref(), get_args(args)).unwrap();
-        match actual {
+        match actual[0] {
           Values::F32(n) => assert!(n.is_nan()),
           Values::F64(n) => assert!(n.is_nan()),
           _ => unreachable!(),
@@ -196,18 +230,14 @@ impl<'a> E2ETest<'a> {
     }
   }
 
-  fn assert_unlinkable(&self, module: &ModuleBinary, _message: &str, line: u64) {
-    let bytes = module.clone().into_vec();
-    let tmp_bytes = bytes.clone();
-    let (magic_numbers, _) = tmp_bytes.split_at(8);
-    if magic_numbers == [0u8, 97, 115, 109, 1, 0, 0, 0] {
-      println!("Assert unlinkable at {}.", line,);
-      let store = init_store();
-      let section = decode_module(&bytes);
-      instantiate_module(store, section, self.external_modules.clone(), 65536).unwrap_err();
-    } else {
-      println!("Skip unlinkable text form at line:{}.", line);
-    };
+  fn assert_unlinkable(&self, module: &ModuleBinary, message: &str, line: u64) {
+    println!("Assert unlinkable at {}.", line,);
+    let bytes = into_binary(module).unwrap();
+    let store = self.init_store();
+    let section = decode_module(&bytes);
+    let external_modules = self.environment.external_modules().unwrap();
+    let error = instantiate_module(store, section, external_modules, 65536).unwrap_err();
+    assert_error_message(error, message, line);
   }
 
   fn do_test(&mut self) {
@@ -268,9 +298,12 @@ impl<'a> E2ETest<'a> {
 
 macro_rules! impl_e2e {
   ($test_name: ident, $file_name: expr) => {
+    impl_e2e!($test_name, $file_name, |_| {});
+  };
+  ($test_name: ident, $file_name: expr, $configure: expr) => {
     #[test]
     fn $test_name() {
-      let mut t = E2ETest::new($file_name);
+      let mut t = E2ETest::new($file_name, $configure);
       t.do_test()
     }
   };
@@ -284,6 +317,9 @@ impl_e2e!(test_align, "align");
 // impl_e2e!(test_binary, "binary");
 impl_e2e!(test_block, "block");
 impl_e2e!(test_br_if, "br_if");
+impl_e2e!(test_br_if_coercing_conditions, "br_if", |store| {
+  store.condition_mode = ConditionMode::Coerce
+});
 impl_e2e!(test_br_table, "br_table");
 impl_e2e!(test_br_only, "br");
 impl_e2e!(test_break_drop, "break-drop");
@@ -296,6 +332,9 @@ impl_e2e!(test_custom_section, "custom_section");
 impl_e2e!(test_custom_simple, "custom");
 impl_e2e!(test_data, "data");
 impl_e2e!(test_elem, "elem");
+impl_e2e!(test_elem_partially, "elem", |store| {
+  store.segment_initialization = SegmentInitialization::Partial
+});
 impl_e2e!(test_endianness, "endianness");
 impl_e2e!(test_exports, "exports");
 impl_e2e!(test_f32_bitwise, "f32_bitwise");
@@ -317,6 +356,9 @@ impl_e2e!(test_globals, "globals");
 impl_e2e!(test_i32, "i32");
 impl_e2e!(test_i64, "i64");
 impl_e2e!(test_if, "if");
+impl_e2e!(test_if_coercing_conditions, "if", |store| {
+  store.condition_mode = ConditionMode::Coerce
+});
 impl_e2e!(test_imports, "imports");
 impl_e2e!(test_inline_module, "inline-module");
 impl_e2e!(test_int_exprs, "int_exprs");
@@ -324,6 +366,9 @@ impl_e2e!(test_int_literals, "int_literals");
 impl_e2e!(test_labels, "labels");
 impl_e2e!(test_left_to_right, "left-to-right");
 impl_e2e!(test_linking, "linking");
+impl_e2e!(test_linking_partially, "linking", |store| {
+  store.segment_initialization = SegmentInitialization::Partial
+});
 impl_e2e!(test_loop, "loop");
 impl_e2e!(test_memory_grow, "memory_grow");
 impl_e2e!(test_memory_redundancy, "memory_redundancy");
@@ -334,6 +379,9 @@ impl_e2e!(test_nop, "nop");
 impl_e2e!(test_resizing, "resizing");
 impl_e2e!(test_return, "return");
 impl_e2e!(test_select, "select");
+impl_e2e!(test_select_coercing_conditions, "select", |store| {
+  store.condition_mode = ConditionMode::Coerce
+});
 impl_e2e!(test_set_local, "set_local");
 impl_e2e!(test_skip_stack_guard_page, "skip-stack-guard-page");
 impl_e2e!(test_stack, "stack");